@@ -0,0 +1,33 @@
+/*
+ * C API of the XCM minimal indexer, available when the library is built with the `ffi` feature:
+ * `cargo build --release --features ffi` produces `libxcm_minimal_indexer.so` (`.dylib` on macOS,
+ * `.dll` on Windows).
+ */
+#ifndef XCM_INDEXER_H
+#define XCM_INDEXER_H
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/*
+ * Parses the XCM transfers of a block from its events, without connecting to a node.
+ *
+ * `input` is a NUL terminated JSON object:
+ * `{ "block_number": <NUMBER>, "events": "0x<HEX>", "assets": { ... } }`, where `events` are the
+ * SCALE encoded `System::Events` of the block and `assets` optionally gives the symbol and
+ * decimals of the assets involved, in the format of `--asset-overrides`.
+ *
+ * Returns a JSON object, either `{ "transfers": [...] }` or `{ "error": "<MESSAGE>" }`, which must
+ * be released with `xcm_indexer_free_string`.
+ */
+char *xcm_indexer_parse_block(const char *input);
+
+/* Releases a string returned by `xcm_indexer_parse_block`. */
+void xcm_indexer_free_string(char *output);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* XCM_INDEXER_H */
@@ -0,0 +1,137 @@
+// Conversions of the raw amounts of the chain to the decimal amounts of the output. Asset metadata
+// is set by the asset owners, so the decimals may be absurd (u128 factors overflow above 38), and
+// balances may be close to u128::MAX. Dividing the amount by the factor as floats rounds twice,
+// so the amounts are rendered as exact decimal strings first, and only rounded once when parsed.
+
+// The exact decimal rendering of an amount, without trailing zeros, eg `1.5` or `0.00001`.
+pub(crate) fn to_decimal_string(value: u128, decimals: u8) -> String {
+	let digits = value.to_string();
+	let decimals = usize::from(decimals);
+	let (integer, fraction) = if digits.len() > decimals {
+		digits.split_at(digits.len() - decimals)
+	} else {
+		("", digits.as_str())
+	};
+	let fraction = format!("{:0>width$}", fraction, width = decimals);
+	let fraction = fraction.trim_end_matches('0');
+	let integer = if integer.is_empty() { "0" } else { integer };
+	if fraction.is_empty() { integer.to_owned() } else { format!("{}.{}", integer, fraction) }
+}
+
+// The raw amount of a decimal rendering, if it's exact with the given decimals and fits a u128.
+pub(crate) fn parse_decimal(amount: &str, decimals: u8) -> Option<u128> {
+	let (integer, fraction) = amount.split_once('.').unwrap_or((amount, ""));
+	let is_digits = |part: &str| part.chars().all(|c| c.is_ascii_digit());
+	if integer.is_empty() || !is_digits(integer) || !is_digits(fraction) {
+		return None;
+	}
+	let decimals = usize::from(decimals);
+	let fraction = fraction.trim_end_matches('0');
+	if fraction.len() > decimals {
+		return None;
+	}
+	// Leading zeros don't count towards the size of the amount, however many decimals it has
+	let digits =
+		format!("{}{}{:0<width$}", integer, fraction, "", width = decimals - fraction.len());
+	let digits = digits.trim_start_matches('0');
+	if digits.is_empty() { Some(0) } else { digits.parse().ok() }
+}
+
+// The raw amount of an amount read back from a record, given the decimals of its asset. Records
+// output by previous versions only have the float, which is rounded to the decimals of the asset.
+pub(crate) fn read_raw_amount(
+	amount: f64,
+	exact_amount: Option<&str>,
+	decimals: u8,
+) -> Option<u128> {
+	match exact_amount {
+		Some(exact_amount) => parse_decimal(exact_amount, decimals),
+		None => parse_decimal(&format!("{:.*}", usize::from(decimals), amount), decimals),
+	}
+}
+
+// Whether an amount read back from a record is the exact decimal rendering of a raw amount.
+pub(crate) fn is_exact_decimal(amount: &str) -> bool {
+	let decimals = amount.split_once('.').map_or(0, |(_, fraction)| fraction.len());
+	u8::try_from(decimals).is_ok_and(|decimals| parse_decimal(amount, decimals).is_some())
+}
+
+// The decimal rendering of an amount of a record: the exact one if present, the float otherwise.
+pub(crate) fn decimal_string(amount: f64, exact_amount: Option<&str>) -> String {
+	exact_amount.map_or_else(|| amount.to_string(), str::to_owned)
+}
+
+// The amount as the closest float to its exact value.
+pub(crate) fn to_decimal_f64(value: u128, decimals: u8) -> f64 {
+	// Parsing a float from a valid decimal rendering can't fail
+	to_decimal_string(value, decimals).parse().unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use proptest::prelude::*;
+
+	#[test]
+	fn amounts_are_rendered_exactly() {
+		assert_eq!(to_decimal_string(15_000_000_000, 10), "1.5");
+		assert_eq!(to_decimal_string(10_000_000_000_000, 18), "0.00001");
+		assert_eq!(to_decimal_string(123, 0), "123");
+		assert_eq!(to_decimal_string(0, 6), "0");
+		assert_eq!(to_decimal_string(u128::MAX, 18), "340282366920938463463.374607431768211455");
+		assert_eq!(to_decimal_string(1, u8::MAX), format!("0.{}1", "0".repeat(254)));
+
+		assert_eq!(to_decimal_f64(10_000_000_000_000, 18), 0.00001);
+		assert_eq!(to_decimal_f64(123_456_789, 6), 123.456789);
+		assert_eq!(to_decimal_f64(123, 0), 123f64);
+		assert_eq!(to_decimal_f64(1, u8::MAX), 1e-255);
+
+		assert_eq!(parse_decimal("1.50", 10), Some(15_000_000_000));
+		assert_eq!(parse_decimal("0.0000001", 6), None);
+		assert_eq!(parse_decimal("340282366920938463463.374607431768211456", 18), None);
+		assert_eq!(parse_decimal("1e5", 0), None);
+		assert_eq!(parse_decimal(".5", 1), None);
+
+		assert_eq!(read_raw_amount(0.1, Some("0.1"), 10), Some(1_000_000_000));
+		assert_eq!(read_raw_amount(0.1 + 0.2, None, 10), Some(3_000_000_000));
+		assert_eq!(read_raw_amount(1.0, Some("0.00001"), 2), None);
+		assert_eq!(read_raw_amount(-1.0, None, 2), None);
+
+		assert!(is_exact_decimal("340282366920938463463.374607431768211455"));
+		assert!(!is_exact_decimal("340282366920938463463.374607431768211456"));
+		assert!(!is_exact_decimal("1e5"));
+
+		assert_eq!(
+			decimal_string(0.1, Some("0.1000000000000000000001")),
+			"0.1000000000000000000001"
+		);
+		assert_eq!(decimal_string(2.0, None), "2");
+	}
+
+	proptest! {
+		#[test]
+		fn decimal_strings_round_trip(value: u128, decimals: u8) {
+			let rendered = to_decimal_string(value, decimals);
+			prop_assert_eq!(parse_decimal(&rendered, decimals), Some(value));
+		}
+
+		#[test]
+		fn floats_are_the_closest_to_the_exact_amount(value: u128) {
+			// Converting an integer to a float rounds to the nearest, as parsing does
+			prop_assert_eq!(to_decimal_f64(value, 0), value as f64);
+		}
+
+		#[test]
+		fn floats_round_trip_through_the_json_output(value: u128, decimals: u8) {
+			let amount = to_decimal_f64(value, decimals);
+			let json = serde_json::to_string(&amount).unwrap();
+			prop_assert_eq!(serde_json::from_str::<f64>(&json).unwrap(), amount);
+		}
+
+		#[test]
+		fn floats_keep_the_order_of_the_amounts(a: u128, b: u128, decimals: u8) {
+			let (low, high) = if a <= b { (a, b) } else { (b, a) };
+			prop_assert!(to_decimal_f64(low, decimals) <= to_decimal_f64(high, decimals));
+		}
+	}
+}
@@ -0,0 +1,163 @@
+use crate::{Error, sink::TransferSink, types::XcmTransfer};
+use lapin::{
+	BasicProperties, Channel, Connection, ConnectionProperties, ExchangeKind,
+	options::{BasicPublishOptions, ConfirmSelectOptions, ExchangeDeclareOptions},
+	types::FieldTable,
+};
+use std::time::Duration;
+
+// The name of the sink in the routing rules
+pub(crate) const AMQP_SINK: &str = "amqp";
+// The attempts to publish a message, reconnecting to the broker in between, before the delivery
+// fails
+const PUBLISH_ATTEMPTS: u32 = 4;
+// The wait before reconnecting, doubled after every failed attempt
+const RECONNECT_BACKOFF: Duration = Duration::from_secs(1);
+
+// Publishes a message per transfer to an AMQP exchange (eg, RabbitMQ), with routing key
+// `<routing_key>.<kind of record>`. Publisher confirms are enabled, so a transfer is only
+// considered published once the broker takes responsibility for it. Once the connection or the
+// channel fails, the broker is connected to again, backing off between the attempts.
+pub(crate) struct AmqpSink {
+	url: String,
+	exchange: String,
+	routing_key: String,
+	// Only locked to get the channel, replaced once it's no longer connected
+	connection: tokio::sync::Mutex<Option<AmqpConnection>>,
+}
+
+struct AmqpConnection {
+	// Kept alive while publishing, dropping it closes the channel
+	_connection: Connection,
+	channel: Channel,
+}
+
+// A message ready to be published: its routing key and its JSON payload
+struct AmqpMessage {
+	routing_key: String,
+	payload: Vec<u8>,
+}
+
+impl AmqpSink {
+	// Connects to the broker, so a misconfigured broker is reported on startup.
+	pub(crate) async fn connect(
+		url: &str,
+		exchange: String,
+		routing_key: String,
+	) -> Result<Self, Error> {
+		let connection = open_connection(url, &exchange).await?;
+		Ok(Self {
+			url: url.to_owned(),
+			exchange,
+			routing_key,
+			connection: tokio::sync::Mutex::new(Some(connection)),
+		})
+	}
+
+	pub(crate) async fn publish_transfers(&self, transfers: &[XcmTransfer]) -> Result<(), Error> {
+		for transfer in transfers {
+			let Some(message) = message_from_transfer(&self.routing_key, transfer) else {
+				continue;
+			};
+			let mut backoff = RECONNECT_BACKOFF;
+			for attempt in 1..=PUBLISH_ATTEMPTS {
+				match self.publish(&message).await {
+					Ok(()) => break,
+					Err(e) if attempt == PUBLISH_ATTEMPTS => return Err(e),
+					Err(_) => {
+						// The connection is opened again, in case it's the one failing
+						*self.connection.lock().await = None;
+						tokio::time::sleep(backoff).await;
+						backoff *= 2;
+					},
+				}
+			}
+		}
+		Ok(())
+	}
+
+	async fn publish(&self, message: &AmqpMessage) -> Result<(), Error> {
+		let confirmation = self
+			.channel()
+			.await?
+			.basic_publish(
+				&self.exchange,
+				&message.routing_key,
+				BasicPublishOptions::default(),
+				&message.payload,
+				BasicProperties::default().with_content_type("application/json".into()),
+			)
+			.await?
+			.await?;
+		if confirmation.is_nack() {
+			return Err(Error::UnconfirmedMessage);
+		}
+		Ok(())
+	}
+
+	// The channel to publish to, connecting to the broker again if it's no longer connected.
+	async fn channel(&self) -> Result<Channel, Error> {
+		let mut connection = self.connection.lock().await;
+		if let Some(connection) = connection.as_ref() {
+			if connection.channel.status().connected() {
+				return Ok(connection.channel.clone());
+			}
+		}
+		let new_connection = open_connection(&self.url, &self.exchange).await?;
+		let channel = new_connection.channel.clone();
+		*connection = Some(new_connection);
+		Ok(channel)
+	}
+}
+
+impl TransferSink for AmqpSink {
+	fn name(&self) -> String {
+		AMQP_SINK.to_owned()
+	}
+
+	async fn deliver(&self, transfers: &[XcmTransfer]) -> Result<(), String> {
+		self.publish_transfers(transfers).await.map_err(|e| e.to_string())
+	}
+}
+
+// Opens a connection with publisher confirms, declaring the exchange as a durable topic exchange if
+// it doesn't exist yet.
+async fn open_connection(url: &str, exchange: &str) -> Result<AmqpConnection, Error> {
+	let connection = Connection::connect(url, ConnectionProperties::default()).await?;
+	let channel = connection.create_channel().await?;
+	channel.confirm_select(ConfirmSelectOptions::default()).await?;
+	channel
+		.exchange_declare(
+			exchange,
+			ExchangeKind::Topic,
+			ExchangeDeclareOptions { durable: true, ..Default::default() },
+			FieldTable::default(),
+		)
+		.await?;
+	Ok(AmqpConnection { _connection: connection, channel })
+}
+
+fn message_from_transfer(routing_key: &str, transfer: &XcmTransfer) -> Option<AmqpMessage> {
+	Some(AmqpMessage {
+		routing_key: format!("{}.{}", routing_key, transfer.kind()),
+		payload: serde_json::to_vec(transfer).ok()?,
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::analytics::{TransferStatus, TransferStatusUpdate};
+
+	#[test]
+	fn message_from_transfer_routes_by_kind() {
+		let transfer = XcmTransfer::StatusUpdate(TransferStatusUpdate {
+			transfer_group_id: "8000000-2".to_owned(),
+			message_id: None,
+			status: TransferStatus::Delivered,
+		});
+		let message = message_from_transfer("transfers", &transfer).unwrap();
+		assert_eq!(message.routing_key, "transfers.status_update");
+		assert_eq!(serde_json::from_slice::<XcmTransfer>(&message.payload).unwrap(), transfer);
+	}
+}
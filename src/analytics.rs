@@ -0,0 +1,889 @@
+use crate::{
+	bridge::ProcessedMessage,
+	incoming_parser::XcmIncomingTransfer,
+	outgoing_parser::{DestinationChain, ExecutionOutcome, XcmOutgoingTransfer},
+	types::{BlockHash, BlockNumber, MessageError, XcmTransfer},
+};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet, VecDeque};
+
+// The amount coming back is lower than the amount sent, as fees are paid on both legs. An incoming
+// transfer is considered to close a round trip if it returns at least this fraction of the amount
+// sent.
+const ROUND_TRIP_MIN_RETURNED_FRACTION: f64 = 0.95;
+// AssetHub produces a block every 6 seconds, so the volumes are bucketed by this amount of blocks.
+const BLOCKS_PER_HOUR: BlockNumber = 600;
+// The hours of history the baseline volumes are learned from, and how many of them are needed
+// before reporting anomalies.
+const BASELINE_HOURS: usize = 7 * 24;
+const MIN_BASELINE_HOURS: usize = 24;
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+#[cfg_attr(feature = "typescript", derive(ts_rs::TS))]
+pub struct RoundTrip {
+	pub outgoing: XcmOutgoingTransfer,
+	pub incoming: XcmIncomingTransfer,
+	pub blocks_elapsed: BlockNumber,
+}
+
+// Matches outgoing transfers with later incoming transfers of the same asset and a similar amount
+// coming back to the sender within `window` blocks. The blocks must be fed in order, so the blocks
+// processed concurrently are expected in the order they're queued, and buffered until the blocks
+// before them are observed. A block still not observed once `window` blocks were expected after it
+// is given up on, as no round trip could span it anymore.
+pub(crate) struct RoundTripDetector {
+	window: BlockNumber,
+	outgoing_transfers: VecDeque<XcmOutgoingTransfer>,
+	// The blocks expected, in order, with their hash and transfers once observed
+	expected_blocks: VecDeque<(BlockNumber, Option<(BlockHash, Vec<XcmTransfer>)>)>,
+}
+
+// The round trips closed by a block, which may be a block observed before the one fed
+pub(crate) type BlockRoundTrips = (BlockHash, BlockNumber, Vec<RoundTrip>);
+
+impl RoundTripDetector {
+	pub(crate) fn new(window: BlockNumber) -> Self {
+		Self { window, outgoing_transfers: VecDeque::new(), expected_blocks: VecDeque::new() }
+	}
+
+	// Expects a block to be observed before the blocks expected after it.
+	pub(crate) fn expect(&mut self, block_number: BlockNumber) {
+		self.expected_blocks.push_back((block_number, None));
+	}
+
+	// Gives up on an expected block, eg if its transfers couldn't be parsed, so the blocks after it
+	// aren't held back. They're fed when the next block is observed.
+	pub(crate) fn release(&mut self, block_number: BlockNumber) {
+		if let Some(position) = self.expected_block(block_number) {
+			self.expected_blocks.remove(position);
+		}
+	}
+
+	// Feeds the detector with the transfers of a block, returning the round trips closed by them
+	// and by the blocks buffered behind it, grouped by the block closing them. Blocks which aren't
+	// expected, eg those read in order or retried after being released, are fed right away.
+	pub(crate) fn observe(
+		&mut self,
+		block_hash: BlockHash,
+		block_number: BlockNumber,
+		transfers: &[XcmTransfer],
+	) -> Vec<BlockRoundTrips> {
+		let mut round_trips = vec![];
+		match self.expected_block(block_number) {
+			Some(position) =>
+				self.expected_blocks[position].1 = Some((block_hash, transfers.to_vec())),
+			None => {
+				let block_round_trips = self.feed(transfers);
+				if !block_round_trips.is_empty() {
+					round_trips.push((block_hash, block_number, block_round_trips));
+				}
+			},
+		}
+		let newest_block = self.expected_blocks.back().map(|(block_number, _)| *block_number);
+		while let Some((expected_number, observed)) = self.expected_blocks.pop_front() {
+			match observed {
+				Some((expected_hash, transfers)) => {
+					let block_round_trips = self.feed(&transfers);
+					if !block_round_trips.is_empty() {
+						round_trips.push((expected_hash, expected_number, block_round_trips));
+					}
+				},
+				// Given up on once it's out of the window of the newest block expected
+				None if newest_block
+					.is_some_and(|newest| expected_number.saturating_add(self.window) < newest) =>
+					(),
+				None => {
+					self.expected_blocks.push_front((expected_number, None));
+					break;
+				},
+			}
+		}
+		round_trips
+	}
+
+	fn expected_block(&self, block_number: BlockNumber) -> Option<usize> {
+		self.expected_blocks
+			.iter()
+			.position(|(expected, observed)| *expected == block_number && observed.is_none())
+	}
+
+	fn feed(&mut self, transfers: &[XcmTransfer]) -> Vec<RoundTrip> {
+		let mut round_trips = vec![];
+		for transfer in transfers {
+			match transfer {
+				XcmTransfer::SentTransfer(outgoing) =>
+					self.outgoing_transfers.push_back(outgoing.clone()),
+				XcmTransfer::ReceivedTransfer(incoming) => {
+					let window = self.window;
+					self.outgoing_transfers.retain(|outgoing| {
+						outgoing.block_number.saturating_add(window) >= incoming.block_number
+					});
+					if let Some(outgoing) = self
+						.outgoing_transfers
+						.iter()
+						.position(|outgoing| is_round_trip(outgoing, incoming))
+						.and_then(|position| self.outgoing_transfers.remove(position))
+					{
+						round_trips.push(RoundTrip {
+							blocks_elapsed: incoming.block_number - outgoing.block_number,
+							outgoing,
+							incoming: incoming.clone(),
+						});
+					}
+				},
+				_ => (),
+			}
+		}
+		round_trips
+	}
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+#[cfg_attr(feature = "typescript", derive(ts_rs::TS))]
+pub enum TransferStatus {
+	// The transfer left AssetHub and no evidence of its fate arrived yet
+	Pending,
+	// The message was successfully processed at destination
+	Delivered,
+	// The message failed, either locally or in some hop of its route, with the error reported
+	// there if any
+	Failed {
+		reason: String,
+		#[serde(default, skip_serializing_if = "Option::is_none")]
+		#[cfg_attr(feature = "typescript", ts(optional))]
+		error: Option<MessageError>,
+	},
+	// The assets in holding were trapped (`PolkadotXcm::AssetsTrapped`), either by the local
+	// execution in AssetHub or in some hop of the route
+	Trapped,
+	// No evidence arrived within the timeout
+	TimedOut {
+		blocks: BlockNumber,
+	},
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+#[cfg_attr(feature = "typescript", derive(ts_rs::TS))]
+pub struct TransferStatusUpdate {
+	pub transfer_group_id: String,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	#[cfg_attr(feature = "typescript", ts(optional))]
+	pub message_id: Option<String>,
+	pub status: TransferStatus,
+}
+
+// Keeps the outgoing transfers in the `Pending` state until evidence of their fate arrives,
+// emitting a status update every time a transfer changes its state. Transfers are tracked by
+// transfer group, as all the assets of an extrinsic travel in the same message.
+pub(crate) struct TransferTracker {
+	timeout: BlockNumber,
+	// The block where each pending transfer was sent, together with its message id and destination
+	pending_transfers: HashMap<String, (BlockNumber, Option<String>, DestinationChain)>,
+}
+
+impl TransferTracker {
+	pub(crate) fn new(timeout: BlockNumber) -> Self {
+		Self { timeout, pending_transfers: HashMap::new() }
+	}
+
+	// Feeds the tracker with the transfers of a block, returning the updates of the new transfers.
+	// Transfers whose local execution didn't complete are resolved right away, as trapped if their
+	// assets were trapped in AssetHub.
+	pub(crate) fn track(&mut self, transfers: &[XcmTransfer]) -> Vec<TransferStatusUpdate> {
+		let mut updates = vec![];
+		for transfer in transfers {
+			let XcmTransfer::SentTransfer(outgoing) = transfer else {
+				continue;
+			};
+			if self.pending_transfers.contains_key(&outgoing.transfer_group_id) ||
+				updates.iter().any(|update: &TransferStatusUpdate| {
+					update.transfer_group_id == outgoing.transfer_group_id
+				}) {
+				continue;
+			}
+
+			let status = match &outgoing.outcome {
+				Some(ExecutionOutcome::Incomplete { .. } | ExecutionOutcome::Error { .. })
+					if outgoing.trapped_assets_hash.is_some() =>
+					TransferStatus::Trapped,
+				Some(
+					ExecutionOutcome::Incomplete { error, .. } | ExecutionOutcome::Error { error },
+				) => TransferStatus::Failed {
+					reason: error.clone(),
+					error: Some(MessageError::Xcm(error.clone())),
+				},
+				_ => {
+					self.pending_transfers.insert(
+						outgoing.transfer_group_id.clone(),
+						(
+							outgoing.block_number,
+							outgoing.message_id.clone(),
+							outgoing.destination_chain.clone(),
+						),
+					);
+					TransferStatus::Pending
+				},
+			};
+			updates.push(TransferStatusUpdate {
+				transfer_group_id: outgoing.transfer_group_id.clone(),
+				message_id: outgoing.message_id.clone(),
+				status,
+			});
+		}
+		updates
+	}
+
+	// Resolves the pending transfer sent in a message given the evidence of its fate.
+	pub(crate) fn resolve(
+		&mut self,
+		message_id: &str,
+		status: TransferStatus,
+	) -> Option<TransferStatusUpdate> {
+		let transfer_group_id = self
+			.pending_transfers
+			.iter()
+			.find(|(_, (_, pending_message_id, _))| {
+				pending_message_id.as_deref() == Some(message_id)
+			})
+			.map(|(transfer_group_id, _)| transfer_group_id.clone())?;
+		self.pending_transfers.remove(&transfer_group_id);
+		Some(TransferStatusUpdate {
+			transfer_group_id,
+			message_id: Some(message_id.to_owned()),
+			status,
+		})
+	}
+
+	// Resolves the pending transfer sent in a message given how a chain of its route processed it,
+	// `destination` being that chain as a destination of transfers, if it can be one. Transfers are
+	// trapped or failed wherever that happens, but only delivered once processed at destination.
+	pub(crate) fn resolve_processed(
+		&mut self,
+		chain: &str,
+		destination: Option<&DestinationChain>,
+		message: &ProcessedMessage,
+	) -> Option<TransferStatusUpdate> {
+		let status = match (&message.trapped_assets_hash, &message.outcome) {
+			(Some(_), _) => TransferStatus::Trapped,
+			(None, Err(error)) => TransferStatus::Failed {
+				reason: format!("{} failed", chain),
+				error: Some(error.clone()),
+			},
+			(None, Ok(())) => {
+				let delivered =
+					self.pending_transfers.values().any(|(_, message_id, transfer_destination)| {
+						message_id.as_deref() == Some(message.message_id.as_str()) &&
+							Some(transfer_destination) == destination
+					});
+				if !delivered {
+					return None;
+				}
+				TransferStatus::Delivered
+			},
+		};
+		self.resolve(&message.message_id, status)
+	}
+
+	// Times out the transfers pending for longer than the timeout at a given block.
+	pub(crate) fn expire(&mut self, block_number: BlockNumber) -> Vec<TransferStatusUpdate> {
+		let timeout = self.timeout;
+		let mut updates = vec![];
+		self.pending_transfers
+			.retain(|transfer_group_id, (sent_block_number, message_id, _)| {
+				let blocks = block_number.saturating_sub(*sent_block_number);
+				if blocks <= timeout {
+					return true;
+				}
+				updates.push(TransferStatusUpdate {
+					transfer_group_id: transfer_group_id.clone(),
+					message_id: message_id.clone(),
+					status: TransferStatus::TimedOut { blocks },
+				});
+				false
+			});
+		updates
+	}
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "typescript", derive(ts_rs::TS))]
+pub enum TransferDirection {
+	Received,
+	Sent,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+#[cfg_attr(feature = "typescript", derive(ts_rs::TS))]
+pub enum VolumeDeviation {
+	// The volume of the current hour exceeds the baseline by more than the multiple
+	Spike,
+	// The volume of a closed hour fell below the baseline divided by the multiple
+	Drop,
+}
+
+// An hourly volume of an asset to or from a chain deviating from its baseline, which may be the
+// early sign of an exploit or a bridge incident.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+#[cfg_attr(feature = "typescript", derive(ts_rs::TS))]
+pub struct VolumeAnomaly {
+	// The block where the deviation was noticed
+	pub block_number: BlockNumber,
+	pub direction: TransferDirection,
+	pub asset: String,
+	// The origin or destination chain, as rendered in the debug output, eg
+	// `PolkadotParachain(2034)`
+	pub counterparty: String,
+	pub deviation: VolumeDeviation,
+	pub hourly_volume: f64,
+	// The mean hourly volume of the last week
+	pub baseline: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct VolumeKey {
+	direction: TransferDirection,
+	asset: String,
+	counterparty: String,
+}
+
+// Learns the baseline hourly volume of every asset and chain, in both directions, from the
+// transfers observed, and reports the hours deviating from it beyond a multiple. Spikes are
+// reported as soon as the running volume of the hour exceeds the baseline, once per hour, while
+// drops are reported when an hour closes, only for the flows active during every hour of the
+// history, so sporadic flows don't raise an alert every quiet hour.
+pub(crate) struct AnomalyDetector {
+	multiple: f64,
+	current_hour: Option<BlockNumber>,
+	current_volumes: HashMap<VolumeKey, f64>,
+	// The volumes of the closed hours, oldest first
+	history: VecDeque<HashMap<VolumeKey, f64>>,
+	reported_spikes: HashSet<VolumeKey>,
+}
+
+impl AnomalyDetector {
+	pub(crate) fn new(multiple: f64) -> Self {
+		Self {
+			multiple,
+			current_hour: None,
+			current_volumes: HashMap::new(),
+			history: VecDeque::new(),
+			reported_spikes: HashSet::new(),
+		}
+	}
+
+	// Feeds the detector with the transfers of a block, returning the anomalies noticed.
+	pub(crate) fn observe(
+		&mut self,
+		block_number: BlockNumber,
+		transfers: &[XcmTransfer],
+	) -> Vec<VolumeAnomaly> {
+		let hour = block_number / BLOCKS_PER_HOUR;
+		let mut anomalies = vec![];
+		let volumes = match self.current_hour {
+			// Blocks are processed concurrently, so a block of a closed hour may arrive late. It
+			// only counts towards the history
+			Some(current_hour) if hour < current_hour => {
+				let age = (current_hour - hour) as usize;
+				match self.history.len().checked_sub(age) {
+					Some(index) => &mut self.history[index],
+					None => return anomalies,
+				}
+			},
+			Some(current_hour) if hour > current_hour => {
+				anomalies.extend(self.close_hours(block_number, hour - current_hour));
+				self.current_hour = Some(hour);
+				&mut self.current_volumes
+			},
+			Some(_) => &mut self.current_volumes,
+			None => {
+				self.current_hour = Some(hour);
+				&mut self.current_volumes
+			},
+		};
+		for (key, amount) in transfers.iter().filter_map(volume_of) {
+			*volumes.entry(key).or_default() += amount;
+		}
+		if self.current_hour != Some(hour) {
+			return anomalies;
+		}
+
+		for (key, volume) in &self.current_volumes {
+			let Some(baseline) = self.baseline(key) else {
+				continue;
+			};
+			if *volume > baseline * self.multiple && self.reported_spikes.insert(key.clone()) {
+				anomalies.push(anomaly(
+					block_number,
+					key,
+					VolumeDeviation::Spike,
+					*volume,
+					baseline,
+				));
+			}
+		}
+		anomalies
+	}
+
+	// Closes the current hour, followed by `hours - 1` hours without transfers.
+	fn close_hours(&mut self, block_number: BlockNumber, hours: BlockNumber) -> Vec<VolumeAnomaly> {
+		let mut anomalies = vec![];
+		let closed_volumes = std::mem::take(&mut self.current_volumes);
+		let steady_flows = self
+			.history
+			.iter()
+			.flat_map(HashMap::keys)
+			.filter(|key| self.history.iter().all(|volumes| volumes.contains_key(*key)))
+			.collect::<HashSet<_>>();
+		for key in steady_flows {
+			let volume = closed_volumes.get(key).copied().unwrap_or_default();
+			if let Some(baseline) = self.baseline(key) {
+				if volume < baseline / self.multiple {
+					anomalies.push(anomaly(
+						block_number,
+						key,
+						VolumeDeviation::Drop,
+						volume,
+						baseline,
+					));
+				}
+			}
+		}
+
+		self.history.push_back(closed_volumes);
+		for _ in 1..hours.min(BASELINE_HOURS as BlockNumber) {
+			self.history.push_back(HashMap::new());
+		}
+		while self.history.len() > BASELINE_HOURS {
+			self.history.pop_front();
+		}
+		self.reported_spikes.clear();
+		anomalies
+	}
+
+	// The mean hourly volume of a flow over the history, once enough of it was observed.
+	fn baseline(&self, key: &VolumeKey) -> Option<f64> {
+		if self.history.len() < MIN_BASELINE_HOURS {
+			return None;
+		}
+		let total = self.history.iter().filter_map(|volumes| volumes.get(key)).sum::<f64>();
+		Some(total / self.history.len() as f64).filter(|baseline| *baseline > 0.0)
+	}
+}
+
+fn volume_of(transfer: &XcmTransfer) -> Option<(VolumeKey, f64)> {
+	match transfer {
+		XcmTransfer::ReceivedTransfer(incoming) => Some((
+			VolumeKey {
+				direction: TransferDirection::Received,
+				asset: incoming.asset.clone(),
+				counterparty: format!("{:?}", incoming.origin_chain),
+			},
+			incoming.amount,
+		)),
+		XcmTransfer::SentTransfer(outgoing) => Some((
+			VolumeKey {
+				direction: TransferDirection::Sent,
+				asset: outgoing.asset.clone(),
+				counterparty: format!("{:?}", outgoing.destination_chain),
+			},
+			outgoing.amount,
+		)),
+		_ => None,
+	}
+}
+
+fn anomaly(
+	block_number: BlockNumber,
+	key: &VolumeKey,
+	deviation: VolumeDeviation,
+	hourly_volume: f64,
+	baseline: f64,
+) -> VolumeAnomaly {
+	VolumeAnomaly {
+		block_number,
+		direction: key.direction.clone(),
+		asset: key.asset.clone(),
+		counterparty: key.counterparty.clone(),
+		deviation,
+		hourly_volume,
+		baseline,
+	}
+}
+
+fn is_round_trip(outgoing: &XcmOutgoingTransfer, incoming: &XcmIncomingTransfer) -> bool {
+	outgoing.sender == incoming.beneficiary &&
+		outgoing.asset == incoming.asset &&
+		outgoing.block_number <= incoming.block_number &&
+		incoming.amount <= outgoing.amount &&
+		incoming.amount >= outgoing.amount * ROUND_TRIP_MIN_RETURNED_FRACTION
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{incoming_parser::OriginChain, outgoing_parser::WeightLimit, types::TransferType};
+
+	const ACCOUNT: &str = "13KsaHFcQKSTd4m73Ub9yVwM1JGCZvipMyTZonHEXEceFYwS";
+	const ACCOUNT_PUBLIC_KEY: &str =
+		"0x66dd21797b3ab4b27ff723196324a33f7a80683bda78e0fcebf8e7cebb09a358";
+
+	fn outgoing(block_number: BlockNumber, amount: f64) -> XcmOutgoingTransfer {
+		XcmOutgoingTransfer {
+			block_number,
+			transfer_group_id: format!("{}-2", block_number),
+			origin: None,
+			destination_chain: DestinationChain::PolkadotParachain(2034),
+			sender: ACCOUNT.to_owned(),
+			sender_public_key: Some(ACCOUNT_PUBLIC_KEY.to_owned()),
+			beneficiary: "16hiHzdGAR7wi29PjCyUkpFCbjTe9Ri6PrnumbEeyhqg75wy".to_owned(),
+			beneficiary_public_key:
+				"0xfc39fcf04a8071b7409823b7c82427ce67910c6ed80aa0e5093aff234624c820".to_owned(),
+			asset: "DOT".to_owned(),
+			asset_id: None,
+			amount,
+			exact_amount: None,
+			transfer_type: TransferType::Reserve,
+			fee_asset_item: 0,
+			pays_fees: true,
+			weight_limit: WeightLimit::Unlimited,
+			burned_amount: None,
+			outcome: None,
+			message_id: None,
+			trapped_assets_hash: None,
+			local_fee_dot: None,
+			nonce: None,
+			tip_dot: None,
+			controllers: None,
+			raw_xcm: None,
+			entities: vec![],
+			xc20_address: None,
+		}
+	}
+
+	fn incoming(block_number: BlockNumber, amount: f64) -> XcmIncomingTransfer {
+		XcmIncomingTransfer {
+			block_number,
+			origin_chain: OriginChain::PolkadotParachain(2034),
+			message_id: None,
+			beneficiary: ACCOUNT.to_owned(),
+			beneficiary_public_key: ACCOUNT_PUBLIC_KEY.to_owned(),
+			asset: "DOT".to_owned(),
+			asset_id: None,
+			amount,
+			exact_amount: None,
+			transfer_type: TransferType::Reserve,
+			fee_swap: None,
+			entities: vec![],
+			dust_lost: None,
+			trapped_assets_hash: None,
+			new_account: false,
+			xc20_address: None,
+		}
+	}
+
+	#[test]
+	fn round_trip_detector_matches_returning_transfer() {
+		let mut detector = RoundTripDetector::new(100);
+		let hash = |block_number: u8| BlockHash::repeat_byte(block_number);
+		assert!(
+			detector
+				.observe(hash(10), 10, &[XcmTransfer::SentTransfer(outgoing(10, 100.0))])
+				.is_empty()
+		);
+		assert_eq!(
+			detector.observe(hash(50), 50, &[XcmTransfer::ReceivedTransfer(incoming(50, 99.0))]),
+			vec![(
+				hash(50),
+				50,
+				vec![RoundTrip {
+					outgoing: outgoing(10, 100.0),
+					incoming: incoming(50, 99.0),
+					blocks_elapsed: 40
+				}]
+			)]
+		);
+		// The outgoing transfer can only close one round trip
+		assert!(
+			detector
+				.observe(hash(60), 60, &[XcmTransfer::ReceivedTransfer(incoming(60, 99.0))])
+				.is_empty()
+		);
+	}
+
+	#[test]
+	fn round_trip_detector_feeds_the_expected_blocks_in_order() {
+		let mut detector = RoundTripDetector::new(100);
+		let hash = |block_number: u8| BlockHash::repeat_byte(block_number);
+		for block_number in [10, 11, 50] {
+			detector.expect(block_number);
+		}
+		// The transfer coming back is held until the transfer sent is observed
+		assert!(
+			detector
+				.observe(hash(50), 50, &[XcmTransfer::ReceivedTransfer(incoming(50, 99.0))])
+				.is_empty()
+		);
+		detector.release(11);
+		// The round trip is closed by the block of the transfer coming back
+		assert_eq!(
+			detector.observe(hash(10), 10, &[XcmTransfer::SentTransfer(outgoing(10, 100.0))]),
+			vec![(
+				hash(50),
+				50,
+				vec![RoundTrip {
+					outgoing: outgoing(10, 100.0),
+					incoming: incoming(50, 99.0),
+					blocks_elapsed: 40
+				}]
+			)]
+		);
+	}
+
+	#[test]
+	fn round_trip_detector_gives_up_on_blocks_out_of_the_window() {
+		let mut detector = RoundTripDetector::new(100);
+		let hash = |block_number: u8| BlockHash::repeat_byte(block_number);
+		// Block 10 is never observed nor released, holding back the blocks after it
+		detector.expect(10);
+		detector.expect(20);
+		assert!(
+			detector
+				.observe(hash(20), 20, &[XcmTransfer::SentTransfer(outgoing(20, 100.0))])
+				.is_empty()
+		);
+		assert_eq!(detector.expected_blocks.len(), 2);
+		// Until a block out of its window is expected
+		detector.expect(111);
+		assert_eq!(
+			detector.observe(hash(111), 111, &[XcmTransfer::ReceivedTransfer(incoming(111, 99.0))]),
+			vec![(
+				hash(111),
+				111,
+				vec![RoundTrip {
+					outgoing: outgoing(20, 100.0),
+					incoming: incoming(111, 99.0),
+					blocks_elapsed: 91
+				}]
+			)]
+		);
+		assert!(detector.expected_blocks.is_empty());
+	}
+
+	#[test]
+	fn round_trip_detector_ignores_transfers_out_of_window() {
+		let mut detector = RoundTripDetector::new(100);
+		detector.observe(BlockHash::zero(), 10, &[XcmTransfer::SentTransfer(outgoing(10, 100.0))]);
+		assert!(
+			detector
+				.observe(
+					BlockHash::zero(),
+					111,
+					&[XcmTransfer::ReceivedTransfer(incoming(111, 99.0))]
+				)
+				.is_empty()
+		);
+	}
+
+	#[test]
+	fn transfer_tracker_resolves_pending_transfers() {
+		let mut tracker = TransferTracker::new(100);
+		let mut sent = outgoing(10, 100.0);
+		sent.message_id = Some("0x01".to_owned());
+		// The assets of the same extrinsic are tracked once
+		assert_eq!(
+			tracker.track(&[
+				XcmTransfer::SentTransfer(sent.clone()),
+				XcmTransfer::SentTransfer(sent.clone())
+			]),
+			vec![TransferStatusUpdate {
+				transfer_group_id: "10-2".to_owned(),
+				message_id: Some("0x01".to_owned()),
+				status: TransferStatus::Pending
+			}]
+		);
+		assert_eq!(
+			tracker.resolve("0x01", TransferStatus::Delivered).map(|update| update.status),
+			Some(TransferStatus::Delivered)
+		);
+		// Resolved transfers aren't pending anymore
+		assert_eq!(tracker.resolve("0x01", TransferStatus::Delivered), None);
+		assert!(tracker.expire(200).is_empty());
+	}
+
+	#[test]
+	fn transfer_tracker_resolves_failed_local_executions() {
+		let mut tracker = TransferTracker::new(100);
+		let incomplete = Some(ExecutionOutcome::Incomplete {
+			ref_time: 1,
+			proof_size: 1,
+			error: "FailedToTransactAsset".to_owned(),
+		});
+		let mut trapped = outgoing(10, 100.0);
+		trapped.outcome = incomplete.clone();
+		trapped.trapped_assets_hash = Some("0xaa".to_owned());
+		// Incomplete executions which didn't trap anything just failed
+		let mut incomplete_without_trap = outgoing(11, 100.0);
+		incomplete_without_trap.outcome = incomplete;
+		let mut failed = outgoing(12, 100.0);
+		failed.outcome = Some(ExecutionOutcome::Error { error: "Barrier".to_owned() });
+		let status = |updates: Vec<TransferStatusUpdate>| {
+			updates.into_iter().map(|update| update.status).collect::<Vec<_>>()
+		};
+		assert_eq!(
+			status(tracker.track(&[
+				XcmTransfer::SentTransfer(trapped),
+				XcmTransfer::SentTransfer(incomplete_without_trap),
+				XcmTransfer::SentTransfer(failed)
+			])),
+			vec![
+				TransferStatus::Trapped,
+				TransferStatus::Failed {
+					reason: "FailedToTransactAsset".to_owned(),
+					error: Some(MessageError::Xcm("FailedToTransactAsset".to_owned()))
+				},
+				TransferStatus::Failed {
+					reason: "Barrier".to_owned(),
+					error: Some(MessageError::Xcm("Barrier".to_owned()))
+				}
+			]
+		);
+		assert!(tracker.expire(200).is_empty());
+	}
+
+	#[test]
+	fn transfer_tracker_resolves_the_messages_processed_in_the_route() {
+		let mut tracker = TransferTracker::new(100);
+		let sent = |block_number, message_id: &str| {
+			let mut transfer = outgoing(block_number, 100.0);
+			transfer.message_id = Some(message_id.to_owned());
+			XcmTransfer::SentTransfer(transfer)
+		};
+		tracker.track(&[sent(10, "0x01"), sent(11, "0x02"), sent(12, "0x03")]);
+		let processed =
+			|message_id: &str, outcome, trapped_assets_hash: Option<&str>| ProcessedMessage {
+				message_id: message_id.to_owned(),
+				outcome,
+				trapped_assets_hash: trapped_assets_hash.map(str::to_owned),
+			};
+		let destination = DestinationChain::PolkadotParachain(2034);
+		let status = |update: Option<TransferStatusUpdate>| update.map(|update| update.status);
+
+		// Processing the message in a hop of the route doesn't deliver it
+		assert_eq!(
+			tracker.resolve_processed("BridgeHub", None, &processed("0x01", Ok(()), None)),
+			None
+		);
+		assert_eq!(
+			status(tracker.resolve_processed(
+				"PolkadotParachain(2034)",
+				Some(&DestinationChain::PolkadotParachain(2000)),
+				&processed("0x01", Ok(()), None)
+			)),
+			None
+		);
+		assert_eq!(
+			status(tracker.resolve_processed(
+				"PolkadotParachain(2034)",
+				Some(&destination),
+				&processed("0x01", Ok(()), None)
+			)),
+			Some(TransferStatus::Delivered)
+		);
+		// Messages failing or trapping their assets anywhere settle their fate
+		assert_eq!(
+			status(tracker.resolve_processed(
+				"BridgeHub",
+				None,
+				&processed("0x02", Err(MessageError::Unsupported), None)
+			)),
+			Some(TransferStatus::Failed {
+				reason: "BridgeHub failed".to_owned(),
+				error: Some(MessageError::Unsupported)
+			})
+		);
+		assert_eq!(
+			status(tracker.resolve_processed(
+				"PolkadotParachain(2034)",
+				Some(&destination),
+				&processed("0x03", Ok(()), Some("0xaa"))
+			)),
+			Some(TransferStatus::Trapped)
+		);
+		assert!(tracker.expire(200).is_empty());
+	}
+
+	#[test]
+	fn transfer_tracker_times_out_pending_transfers() {
+		let mut tracker = TransferTracker::new(100);
+		tracker.track(&[XcmTransfer::SentTransfer(outgoing(10, 100.0))]);
+		assert!(tracker.expire(110).is_empty());
+		assert_eq!(
+			tracker.expire(111),
+			vec![TransferStatusUpdate {
+				transfer_group_id: "10-2".to_owned(),
+				message_id: None,
+				status: TransferStatus::TimedOut { blocks: 101 }
+			}]
+		);
+		assert!(tracker.expire(112).is_empty());
+	}
+
+	#[test]
+	fn round_trip_detector_ignores_different_amounts() {
+		let mut detector = RoundTripDetector::new(100);
+		detector.observe(BlockHash::zero(), 10, &[XcmTransfer::SentTransfer(outgoing(10, 100.0))]);
+		assert!(
+			detector
+				.observe(
+					BlockHash::zero(),
+					20,
+					&[XcmTransfer::ReceivedTransfer(incoming(20, 50.0))]
+				)
+				.is_empty()
+		);
+		assert!(
+			detector
+				.observe(
+					BlockHash::zero(),
+					20,
+					&[XcmTransfer::ReceivedTransfer(incoming(20, 101.0))]
+				)
+				.is_empty()
+		);
+	}
+
+	#[test]
+	fn anomaly_detector_reports_deviations_from_the_baseline() {
+		let mut detector = AnomalyDetector::new(3.0);
+		let received = |block_number, amount| {
+			vec![XcmTransfer::ReceivedTransfer(incoming(block_number, amount))]
+		};
+		// A day of 10 DOT per hour sets the baseline
+		for hour in 0..MIN_BASELINE_HOURS as BlockNumber {
+			let block_number = hour * BLOCKS_PER_HOUR;
+			assert!(detector.observe(block_number, &received(block_number, 10.0)).is_empty());
+		}
+
+		let block_number = MIN_BASELINE_HOURS as BlockNumber * BLOCKS_PER_HOUR;
+		assert!(detector.observe(block_number, &received(block_number, 25.0)).is_empty());
+		let spike = VolumeAnomaly {
+			block_number: block_number + 1,
+			direction: TransferDirection::Received,
+			asset: "DOT".to_owned(),
+			counterparty: "PolkadotParachain(2034)".to_owned(),
+			deviation: VolumeDeviation::Spike,
+			hourly_volume: 35.0,
+			baseline: 10.0,
+		};
+		assert_eq!(detector.observe(block_number + 1, &received(block_number, 10.0)), vec![spike]);
+		// Spikes are reported once per hour
+		assert!(detector.observe(block_number + 2, &received(block_number, 10.0)).is_empty());
+
+		// The next hour is quiet, which is noticed once it closes
+		assert!(detector.observe(block_number + BLOCKS_PER_HOUR, &[]).is_empty());
+		let anomalies = detector.observe(block_number + 2 * BLOCKS_PER_HOUR, &[]);
+		assert_eq!(anomalies.len(), 1);
+		assert_eq!(anomalies[0].deviation, VolumeDeviation::Drop);
+		assert_eq!(anomalies[0].hourly_volume, 0.0);
+	}
+}
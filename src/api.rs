@@ -0,0 +1,155 @@
+use crate::{
+	flows::FlowMetrics, recent_transfers::RecentTransfers, totals::TransferTotals,
+	types::BlockNumber,
+};
+use std::sync::{Arc, Mutex};
+use tokio::{
+	io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+	net::{TcpListener, TcpStream},
+};
+
+// A minimal read-only HTTP API. It only needs to answer a handful of GET requests, so instead of
+// pulling a whole web framework we parse the request line by hand.
+#[derive(Debug, PartialEq)]
+pub(crate) enum Route {
+	RecentTransfers { from_block: BlockNumber },
+	Metrics,
+	Totals { asset: Option<String> },
+}
+
+impl Route {
+	pub(crate) fn parse(request_line: &str) -> Option<Self> {
+		let mut parts = request_line.split_whitespace();
+		if parts.next()? != "GET" {
+			return None;
+		}
+		let target = parts.next()?;
+		let (path, query) = match target.split_once('?') {
+			Some((path, query)) => (path, Some(query)),
+			None => (target, None),
+		};
+		match path {
+			"/transfers" => {
+				let from_block = match query {
+					Some(query) => query
+						.split('&')
+						.find_map(|param| param.strip_prefix("from_block="))?
+						.parse()
+						.ok()?,
+					None => 0,
+				};
+				Some(Self::RecentTransfers { from_block })
+			},
+			"/metrics" => Some(Self::Metrics),
+			"/totals" => Some(Self::Totals {
+				asset: query.and_then(|query| {
+					query
+						.split('&')
+						.find_map(|param| param.strip_prefix("asset="))
+						.map(str::to_owned)
+				}),
+			}),
+			_ => None,
+		}
+	}
+}
+
+// The flow metrics and the transfer totals are only served if they're collected.
+pub(crate) async fn serve(
+	listener: TcpListener,
+	recent_transfers: Arc<Mutex<RecentTransfers>>,
+	flow_metrics: Option<Arc<Mutex<FlowMetrics>>>,
+	transfer_totals: Option<Arc<Mutex<TransferTotals>>>,
+) {
+	while let Ok((stream, _)) = listener.accept().await {
+		let recent_transfers = recent_transfers.clone();
+		let flow_metrics = flow_metrics.clone();
+		let transfer_totals = transfer_totals.clone();
+		tokio::spawn(async move {
+			let _ =
+				handle_connection(stream, recent_transfers, flow_metrics, transfer_totals).await;
+		});
+	}
+}
+
+async fn handle_connection(
+	mut stream: TcpStream,
+	recent_transfers: Arc<Mutex<RecentTransfers>>,
+	flow_metrics: Option<Arc<Mutex<FlowMetrics>>>,
+	transfer_totals: Option<Arc<Mutex<TransferTotals>>>,
+) -> std::io::Result<()> {
+	let (reader, mut writer) = stream.split();
+	let mut request_line = String::new();
+	BufReader::new(reader).read_line(&mut request_line).await?;
+
+	let mut content_type = "application/json";
+	let (status, body) = match Route::parse(&request_line) {
+		Some(Route::RecentTransfers { from_block }) => match recent_transfers.lock() {
+			Ok(recent_transfers) =>
+				match serde_json::to_string(&recent_transfers.from_block(from_block)) {
+					Ok(json) => ("200 OK", json),
+					Err(_) => ("500 Internal Server Error", "{}".to_owned()),
+				},
+			Err(_) => ("500 Internal Server Error", "{}".to_owned()),
+		},
+		// The Prometheus text format
+		Some(Route::Metrics) => match flow_metrics.as_ref().map(|metrics| metrics.lock()) {
+			Some(Ok(flow_metrics)) => {
+				content_type = "text/plain; version=0.0.4";
+				("200 OK", flow_metrics.render() + &crate::telemetry::render_counters())
+			},
+			Some(Err(_)) => ("500 Internal Server Error", "{}".to_owned()),
+			None => ("404 Not Found", "{}".to_owned()),
+		},
+		Some(Route::Totals { asset }) => match transfer_totals.as_ref().map(|totals| totals.lock())
+		{
+			Some(Ok(transfer_totals)) =>
+				match serde_json::to_string(&transfer_totals.snapshot(asset.as_deref())) {
+					Ok(json) => ("200 OK", json),
+					Err(_) => ("500 Internal Server Error", "{}".to_owned()),
+				},
+			Some(Err(_)) => ("500 Internal Server Error", "{}".to_owned()),
+			None => ("404 Not Found", "{}".to_owned()),
+		},
+		None => ("404 Not Found", "{}".to_owned()),
+	};
+
+	let response = format!(
+		"HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+		status,
+		content_type,
+		body.len(),
+		body
+	);
+	writer.write_all(response.as_bytes()).await
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn parse_recent_transfers_route() {
+		assert_eq!(
+			Route::parse("GET /transfers HTTP/1.1\r\n"),
+			Some(Route::RecentTransfers { from_block: 0 })
+		);
+		assert_eq!(
+			Route::parse("GET /transfers?from_block=8898898 HTTP/1.1\r\n"),
+			Some(Route::RecentTransfers { from_block: 8_898_898 })
+		);
+		assert_eq!(Route::parse("GET /metrics HTTP/1.1\r\n"), Some(Route::Metrics));
+		assert_eq!(Route::parse("GET /totals HTTP/1.1\r\n"), Some(Route::Totals { asset: None }));
+		assert_eq!(
+			Route::parse("GET /totals?asset=USDT HTTP/1.1\r\n"),
+			Some(Route::Totals { asset: Some("USDT".to_owned()) })
+		);
+	}
+
+	#[test]
+	fn parse_unknown_route() {
+		assert_eq!(Route::parse("POST /transfers HTTP/1.1\r\n"), None);
+		assert_eq!(Route::parse("GET /unknown HTTP/1.1\r\n"), None);
+		assert_eq!(Route::parse("GET /transfers?from_block=latest HTTP/1.1\r\n"), None);
+	}
+}
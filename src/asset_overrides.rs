@@ -0,0 +1,157 @@
+use crate::{
+	asset_hub::runtime_types::staging_xcm::v4::location::Location, types::AssetMetadataValues,
+};
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, fs, path::Path, sync::OnceLock};
+
+// The symbol and decimals forced for an asset. Fields that aren't given keep the on-chain value.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct AssetOverride {
+	pub(crate) symbol: Option<String>,
+	pub(crate) decimals: Option<u8>,
+}
+
+// Some projects set wrong metadata for their assets, which would corrupt the amounts downstream.
+// These overrides take precedence over the on-chain metadata.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct AssetOverrides {
+	// The assets of pallet_assets, keyed by their id
+	#[serde(default)]
+	pub(crate) assets: HashMap<u32, AssetOverride>,
+	// The foreign assets, keyed by their location in its canonical string form, eg
+	// `parents=2/GlobalConsensus(Kusama)`
+	#[serde(default)]
+	pub(crate) foreign_assets: HashMap<String, AssetOverride>,
+}
+
+// The overrides are set once when the CLI starts, so the parsers don't need to carry them around.
+static ASSET_OVERRIDES: OnceLock<AssetOverrides> = OnceLock::new();
+
+pub(crate) fn set_asset_overrides(overrides: AssetOverrides) {
+	let _ = ASSET_OVERRIDES.set(overrides);
+}
+
+impl AssetOverrides {
+	// Loads the overrides from a JSON file like `{ "assets": { "1984": { "symbol": "USDT",
+	// "decimals": 6 } } }`.
+	pub(crate) fn from_file(path: &Path) -> Result<Self, String> {
+		let content = fs::read_to_string(path)
+			.map_err(|e| format!("Failed to read the asset overrides file: {}", e))?;
+		let overrides: Self = serde_json::from_str(&content)
+			.map_err(|e| format!("Failed to parse the asset overrides file: {}", e))?;
+		Ok(overrides.canonicalized())
+	}
+
+	// Rewrites the locations keying the foreign assets in their canonical form, so they match
+	// however they're spaced. Keys that aren't canonical locations are kept as they are, as they
+	// may be locations rendered by previous versions of the indexer.
+	fn canonicalized(mut self) -> Self {
+		self.foreign_assets = self
+			.foreign_assets
+			.into_iter()
+			.map(|(location, asset_override)| match crate::locations::parse_location(&location) {
+				Ok(parsed) => (crate::locations::encode_location(&parsed), asset_override),
+				Err(_) => (location, asset_override),
+			})
+			.collect();
+		self
+	}
+
+	// The override of a foreign asset, falling back to its location as rendered by the previous
+	// versions of the indexer, eg in the raw blocks they archived.
+	pub(crate) fn foreign_asset(&self, location: &Location) -> Option<&AssetOverride> {
+		self.foreign_assets
+			.get(&crate::locations::encode_location(location))
+			.or_else(|| self.foreign_assets.get(&format!("{:?}", location)))
+	}
+}
+
+impl AssetOverride {
+	fn apply(&self, values: AssetMetadataValues) -> AssetMetadataValues {
+		AssetMetadataValues {
+			asset_name: self.symbol.clone().unwrap_or(values.asset_name),
+			decimals: self.decimals.unwrap_or(values.decimals),
+		}
+	}
+}
+
+pub(crate) fn override_asset(asset_id: u32, values: AssetMetadataValues) -> AssetMetadataValues {
+	match ASSET_OVERRIDES.get().and_then(|overrides| overrides.assets.get(&asset_id)) {
+		Some(asset_override) => asset_override.apply(values),
+		None => values,
+	}
+}
+
+pub(crate) fn override_foreign_asset(
+	location: &Location,
+	values: AssetMetadataValues,
+) -> AssetMetadataValues {
+	match ASSET_OVERRIDES.get().and_then(|overrides| overrides.foreign_asset(location)) {
+		Some(asset_override) => asset_override.apply(values),
+		None => values,
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn asset_overrides_deserialize() {
+		let overrides: AssetOverrides = serde_json::from_str(
+			r#"{
+				"assets": { "1984": { "symbol": "USDT", "decimals": 6 } },
+				"foreign_assets": { "parents=1": { "decimals": 12 } }
+			}"#,
+		)
+		.unwrap();
+		assert_eq!(
+			overrides.assets[&1984],
+			AssetOverride { symbol: Some("USDT".to_owned()), decimals: Some(6) }
+		);
+		assert_eq!(
+			overrides.foreign_assets["parents=1"],
+			AssetOverride { symbol: None, decimals: Some(12) }
+		);
+		assert!(serde_json::from_str::<AssetOverrides>(r#"{ "asset": {} }"#).is_err());
+	}
+
+	#[test]
+	fn asset_override_takes_precedence() {
+		let values = || AssetMetadataValues { asset_name: "Wrong".to_owned(), decimals: 18 };
+		assert_eq!(
+			AssetOverride { symbol: Some("USDT".to_owned()), decimals: Some(6) }.apply(values()),
+			AssetMetadataValues { asset_name: "USDT".to_owned(), decimals: 6 }
+		);
+		assert_eq!(
+			AssetOverride { symbol: None, decimals: Some(6) }.apply(values()),
+			AssetMetadataValues { asset_name: "Wrong".to_owned(), decimals: 6 }
+		);
+		assert_eq!(AssetOverride::default().apply(values()), values());
+	}
+
+	#[test]
+	fn foreign_asset_overrides_are_keyed_by_location() {
+		use crate::locations::parse_location;
+		let ksm = AssetOverride { symbol: Some("KSM".to_owned()), decimals: Some(12) };
+		let laos = AssetOverride { symbol: Some("LAOS".to_owned()), decimals: None };
+		let laos_location = parse_location("parents=1/Parachain(3370)").unwrap();
+		let overrides = AssetOverrides {
+			assets: HashMap::new(),
+			foreign_assets: HashMap::from([
+				(" parents=2 / GlobalConsensus(Kusama) ".to_owned(), ksm.clone()),
+				// Keyed as previous versions of the indexer did
+				(format!("{:?}", laos_location), laos.clone()),
+			]),
+		}
+		.canonicalized();
+		assert_eq!(
+			overrides.foreign_asset(&parse_location("parents=2/GlobalConsensus(Kusama)").unwrap()),
+			Some(&ksm)
+		);
+		assert_eq!(overrides.foreign_asset(&laos_location), Some(&laos));
+		assert_eq!(overrides.foreign_asset(&parse_location("parents=1").unwrap()), None);
+	}
+}
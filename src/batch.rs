@@ -0,0 +1,59 @@
+use std::time::{Duration, Instant};
+
+// The records appended to the output (its bytes) or delivered to a sink (the transfers), buffered
+// until enough of them are pending or enough time elapsed since the last flush. Writing them
+// together reduces the I/O overhead during backfills and high-activity periods.
+#[derive(Debug)]
+pub(crate) struct OutputBatch<T = u8> {
+	size: usize,
+	interval: Duration,
+	buffer: Vec<T>,
+	records: usize,
+	last_flush: Instant,
+}
+
+impl<T: Clone> OutputBatch<T> {
+	pub(crate) fn new(size: usize, interval: Duration) -> Self {
+		Self { size, interval, buffer: vec![], records: 0, last_flush: Instant::now() }
+	}
+
+	// Buffers the output, returning the whole batch if it's due to be written.
+	pub(crate) fn push(&mut self, output: &[T], records: usize) -> Option<Vec<T>> {
+		self.buffer.extend_from_slice(output);
+		self.records += records;
+		if self.records >= self.size || self.is_due() { Some(self.take()) } else { None }
+	}
+
+	// Whether the interval elapsed since the last flush
+	pub(crate) fn is_due(&self) -> bool {
+		self.last_flush.elapsed() >= self.interval
+	}
+
+	pub(crate) fn take(&mut self) -> Vec<T> {
+		self.records = 0;
+		self.last_flush = Instant::now();
+		std::mem::take(&mut self.buffer)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn output_batch_is_due_after_size_records() {
+		let mut batch: OutputBatch = OutputBatch::new(3, Duration::from_secs(3600));
+		assert_eq!(batch.push(b"a\n", 1), None);
+		assert_eq!(batch.push(b"b\nc\n", 2), Some(b"a\nb\nc\n".to_vec()));
+		assert_eq!(batch.push(b"d\n", 1), None);
+		assert_eq!(batch.take(), b"d\n".to_vec());
+		assert_eq!(batch.take(), vec![]);
+	}
+
+	#[test]
+	fn output_batch_is_due_after_interval() {
+		let mut batch: OutputBatch = OutputBatch::new(usize::MAX, Duration::ZERO);
+		assert!(batch.is_due());
+		assert_eq!(batch.push(b"a\n", 1), Some(b"a\n".to_vec()));
+	}
+}
@@ -0,0 +1,296 @@
+use crate::{Error, sink::TransferSink, types::XcmTransfer};
+use reqwest::{RequestBuilder, Response, StatusCode};
+use serde_json::{Value, json};
+use std::{
+	sync::Mutex,
+	time::{Duration, Instant},
+};
+
+// The name of the sink in the routing rules
+pub(crate) const BIGQUERY_SINK: &str = "bigquery";
+const BIGQUERY_API: &str = "https://bigquery.googleapis.com/bigquery/v2";
+// BigQuery answers with a conflict when creating a table that already exists
+const ALREADY_EXISTS: u16 = 409;
+// The access tokens of Google Cloud expire after an hour, so the ones obtained from a command are
+// refreshed ahead of that
+const TOKEN_REFRESH_INTERVAL: Duration = Duration::from_secs(45 * 60);
+
+// The columns of the table. The most queried fields are promoted to their own columns, while the
+// whole record is kept as JSON so no information is lost. Fields that don't apply to a kind of
+// record are null.
+const SCHEMA: [(&str, &str, &str); 9] = [
+	("block_number", "INTEGER", "NULLABLE"),
+	("kind", "STRING", "REQUIRED"),
+	("transfer_group_id", "STRING", "NULLABLE"),
+	("asset", "STRING", "NULLABLE"),
+	("amount", "FLOAT", "NULLABLE"),
+	("sender", "STRING", "NULLABLE"),
+	("beneficiary", "STRING", "NULLABLE"),
+	("counterparty_chain", "STRING", "NULLABLE"),
+	("record", "JSON", "REQUIRED"),
+];
+
+// Where the OAuth access tokens authorizing the requests come from.
+pub(crate) enum AccessTokenSource {
+	// A fixed token, valid until it expires
+	Token(String),
+	// A shell command printing a fresh token, eg `gcloud auth print-access-token`
+	Command(String),
+}
+
+// Streams the transfers into a BigQuery table through the `insertAll` API, a request per batch of
+// transfers delivered. The table is created with the schema above if it doesn't exist.
+// Requests are authorized with an OAuth access token. Tokens obtained from a command are refreshed
+// periodically and whenever BigQuery rejects them, so long running subscriptions keep streaming.
+pub(crate) struct BigQuerySink {
+	client: reqwest::Client,
+	token_source: AccessTokenSource,
+	// The current access token and when it was obtained
+	access_token: Mutex<(String, Instant)>,
+	project: String,
+	dataset: String,
+	table: String,
+}
+
+impl BigQuerySink {
+	pub(crate) async fn connect(
+		token_source: AccessTokenSource,
+		project: String,
+		dataset: String,
+		table: String,
+	) -> Result<Self, Error> {
+		let access_token = match &token_source {
+			AccessTokenSource::Token(token) => token.clone(),
+			AccessTokenSource::Command(command) => run_token_command(command).await?,
+		};
+		let sink = Self {
+			client: reqwest::Client::new(),
+			token_source,
+			access_token: Mutex::new((access_token, Instant::now())),
+			project,
+			dataset,
+			table,
+		};
+		sink.create_table().await?;
+		Ok(sink)
+	}
+
+	// Sends a request authorized with the current access token. If it's rejected as unauthorized
+	// and the token comes from a command, the request is sent again with a fresh token.
+	async fn send(&self, request: impl Fn(&str) -> RequestBuilder) -> Result<Response, Error> {
+		let request = &request;
+		let send = |access_token: String| async move {
+			request(&access_token).send().await.map_err(|e| Error::BigQuery(e.to_string()))
+		};
+		let response = send(self.access_token(false).await?).await?;
+		match (&self.token_source, response.status()) {
+			(AccessTokenSource::Command(_), StatusCode::UNAUTHORIZED) =>
+				send(self.access_token(true).await?).await,
+			_ => Ok(response),
+		}
+	}
+
+	// The current access token, refreshed if forced or due to be refreshed.
+	async fn access_token(&self, refresh: bool) -> Result<String, Error> {
+		let AccessTokenSource::Command(command) = &self.token_source else {
+			return Ok(self.cached_token().0);
+		};
+		let (access_token, obtained_at) = self.cached_token();
+		if !refresh && obtained_at.elapsed() < TOKEN_REFRESH_INTERVAL {
+			return Ok(access_token);
+		}
+		let access_token = run_token_command(command).await?;
+		*self.access_token.lock().unwrap_or_else(|e| e.into_inner()) =
+			(access_token.clone(), Instant::now());
+		Ok(access_token)
+	}
+
+	fn cached_token(&self) -> (String, Instant) {
+		self.access_token.lock().unwrap_or_else(|e| e.into_inner()).clone()
+	}
+
+	pub(crate) async fn insert_transfers(&self, transfers: &[XcmTransfer]) -> Result<(), Error> {
+		let rows = transfers
+			.iter()
+			.enumerate()
+			.filter_map(|(index, transfer)| row_from_transfer(index, transfer))
+			.collect::<Vec<_>>();
+		if rows.is_empty() {
+			return Ok(());
+		}
+		let url = format!(
+			"{}/projects/{}/datasets/{}/tables/{}/insertAll",
+			BIGQUERY_API, self.project, self.dataset, self.table
+		);
+		let body = json!({ "rows": rows });
+		let response: Value = self
+			.send(|access_token| self.client.post(&url).bearer_auth(access_token).json(&body))
+			.await?
+			.error_for_status()
+			.map_err(|e| Error::BigQuery(e.to_string()))?
+			.json()
+			.await
+			.map_err(|e| Error::BigQuery(e.to_string()))?;
+		// Rows are inserted independently, so some of them may be rejected while the rest succeed.
+		// The rejected rows are dropped, as retrying them would fail again.
+		match response.get("insertErrors") {
+			Some(errors) => Err(Error::BigQuery(format!("Some rows were rejected: {}", errors))),
+			None => Ok(()),
+		}
+	}
+
+	async fn create_table(&self) -> Result<(), Error> {
+		let url =
+			format!("{}/projects/{}/datasets/{}/tables", BIGQUERY_API, self.project, self.dataset);
+		let fields = SCHEMA
+			.iter()
+			.map(
+				|(name, field_type, mode)| json!({ "name": name, "type": field_type, "mode": mode }),
+			)
+			.collect::<Vec<_>>();
+		let body = json!({
+			"tableReference": {
+				"projectId": self.project,
+				"datasetId": self.dataset,
+				"tableId": self.table,
+			},
+			"schema": { "fields": fields },
+		});
+		let response = self
+			.send(|access_token| self.client.post(&url).bearer_auth(access_token).json(&body))
+			.await?;
+		if response.status().is_success() || response.status().as_u16() == ALREADY_EXISTS {
+			Ok(())
+		} else {
+			Err(Error::BigQuery(format!("Failed to create the table: {}", response.status())))
+		}
+	}
+}
+
+impl TransferSink for BigQuerySink {
+	fn name(&self) -> String {
+		BIGQUERY_SINK.to_owned()
+	}
+
+	async fn deliver(&self, transfers: &[XcmTransfer]) -> Result<(), String> {
+		self.insert_transfers(transfers).await.map_err(|e| e.to_string())
+	}
+}
+
+// Runs the command printing an access token, through the shell so it can take arguments.
+async fn run_token_command(command: &str) -> Result<String, Error> {
+	let output = tokio::process::Command::new("sh")
+		.args(["-c", command])
+		.output()
+		.await
+		.map_err(|e| Error::BigQuery(format!("Failed to run the token command: {}", e)))?;
+	let access_token = String::from_utf8_lossy(&output.stdout).trim().to_owned();
+	if !output.status.success() || access_token.is_empty() {
+		return Err(Error::BigQuery(format!(
+			"The token command didn't print an access token: {}",
+			String::from_utf8_lossy(&output.stderr).trim()
+		)));
+	}
+	Ok(access_token)
+}
+
+// Builds the row of a transfer given its position among the transfers of its batch. The insert id,
+// derived from both, lets BigQuery deduplicate the rows sent twice.
+fn row_from_transfer(index: usize, transfer: &XcmTransfer) -> Option<Value> {
+	let record = serde_json::to_value(transfer).ok()?;
+	// The records are serialized as `{ "<Kind>": { ..fields } }`
+	let fields = record.as_object()?.values().next()?.clone();
+	let field = |name: &str| fields.get(name).cloned().unwrap_or(Value::Null);
+	let block_number = match transfer {
+		XcmTransfer::RoundTrip(round_trip) => json!(round_trip.incoming.block_number),
+		_ => field("block_number"),
+	};
+	// Chains without data are serialized as their name, the remaining ones as JSON
+	let counterparty_chain = match fields.get("destination_chain").or(fields.get("origin_chain")) {
+		Some(Value::String(chain)) => Value::String(chain.clone()),
+		Some(chain) => Value::String(chain.to_string()),
+		None => Value::Null,
+	};
+	let insert_id = crate::helpers::to_hex_string(&sp_core::hashing::blake2_128(
+		format!("{}-{}", index, record).as_bytes(),
+	));
+
+	Some(json!({
+		"insertId": insert_id,
+		"json": {
+			"block_number": block_number,
+			"kind": transfer.kind(),
+			"transfer_group_id": field("transfer_group_id"),
+			"asset": field("asset"),
+			"amount": field("amount"),
+			"sender": field("sender"),
+			"beneficiary": field("beneficiary"),
+			"counterparty_chain": counterparty_chain,
+			"record": record.to_string(),
+		},
+	}))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		analytics::{TransferStatus, TransferStatusUpdate},
+		incoming_parser::{OriginChain, XcmIncomingTransfer},
+		types::TransferType,
+	};
+
+	#[test]
+	fn row_from_transfer_promotes_the_common_fields() {
+		let transfer = XcmTransfer::ReceivedTransfer(XcmIncomingTransfer {
+			block_number: 8_935_124,
+			origin_chain: OriginChain::PolkadotParachain(2034),
+			message_id: None,
+			beneficiary: "13KsaHFcQKSTd4m73Ub9yVwM1JGCZvipMyTZonHEXEceFYwS".to_owned(),
+			beneficiary_public_key:
+				"0x66dd21797b3ab4b27ff723196324a33f7a80683bda78e0fcebf8e7cebb09a358".to_owned(),
+			asset: "DOT".to_owned(),
+			asset_id: None,
+			amount: 1.5,
+			exact_amount: None,
+			transfer_type: TransferType::Reserve,
+			fee_swap: None,
+			entities: vec![],
+			dust_lost: None,
+			trapped_assets_hash: None,
+			new_account: false,
+			xc20_address: None,
+		});
+		let row = row_from_transfer(0, &transfer).unwrap();
+		assert_eq!(row["json"]["block_number"], 8_935_124);
+		assert_eq!(row["json"]["kind"], "received");
+		assert_eq!(row["json"]["transfer_group_id"], Value::Null);
+		assert_eq!(row["json"]["asset"], "DOT");
+		assert_eq!(row["json"]["amount"], 1.5);
+		assert_eq!(row["json"]["sender"], Value::Null);
+		assert_eq!(row["json"]["counterparty_chain"], r#"{"PolkadotParachain":2034}"#);
+		// The same record always gets the same insert id, unless it's repeated in the batch
+		assert_eq!(row["insertId"], row_from_transfer(0, &transfer).unwrap()["insertId"]);
+		assert_ne!(row["insertId"], row_from_transfer(1, &transfer).unwrap()["insertId"]);
+	}
+
+	#[test]
+	fn row_from_transfer_handles_records_without_block() {
+		let transfer = XcmTransfer::StatusUpdate(TransferStatusUpdate {
+			transfer_group_id: "8935124-2".to_owned(),
+			message_id: None,
+			status: TransferStatus::Delivered,
+		});
+		let row = row_from_transfer(0, &transfer).unwrap();
+		assert_eq!(row["json"]["block_number"], Value::Null);
+		assert_eq!(row["json"]["kind"], "status_update");
+		assert_eq!(row["json"]["transfer_group_id"], "8935124-2");
+	}
+
+	#[tokio::test]
+	async fn run_token_command_reads_the_printed_token() {
+		assert_eq!(run_token_command("echo ya29.token").await.unwrap(), "ya29.token");
+		assert!(run_token_command("echo 'Reauthentication required' >&2; false").await.is_err());
+		assert!(run_token_command("true").await.is_err());
+	}
+}
@@ -0,0 +1,140 @@
+use crate::{
+	Error,
+	state_proofs::BlockStorage,
+	storage_lookup::StorageLookup,
+	types::{BlockHash, BlockNumber},
+};
+use subxt::{
+	OnlineClient, PolkadotConfig,
+	blocks::{BlockRef, Extrinsics},
+	client::OfflineClientT,
+	events::Events,
+};
+
+// The data of a block the parsers decode the transfers from.
+pub(crate) struct SourceBlock<C: OfflineClientT<PolkadotConfig>, S: StorageLookup> {
+	pub(crate) number: BlockNumber,
+	pub(crate) events: Events<PolkadotConfig>,
+	// Only sources backed by a client can decode extrinsics. Without them, the outgoing transfers
+	// are rebuilt from the messages sent, as done for the extrinsics whose call isn't known
+	pub(crate) extrinsics: Option<Extrinsics<PolkadotConfig, C>>,
+	pub(crate) storage: S,
+}
+
+// Where the blocks are read from. Abstracting it from the `OnlineClient` allows feeding the
+// parsers canned events and storage values, so every event branch can be tested offline.
+pub(crate) trait BlockSource {
+	type Client: OfflineClientT<PolkadotConfig>;
+	type Storage: StorageLookup;
+
+	async fn block(
+		&self,
+		block_hash: BlockHash,
+	) -> Result<SourceBlock<Self::Client, Self::Storage>, Error>;
+}
+
+impl BlockSource for OnlineClient<PolkadotConfig> {
+	type Client = Self;
+	type Storage = BlockStorage;
+
+	async fn block(&self, block_hash: BlockHash) -> Result<SourceBlock<Self, BlockStorage>, Error> {
+		let block = self.blocks().at(BlockRef::from_hash(block_hash)).await?;
+		Ok(SourceBlock {
+			number: block.number(),
+			events: block.events().await?,
+			extrinsics: Some(block.extrinsics().await?),
+			storage: BlockStorage::at(&block).await?,
+		})
+	}
+}
+
+#[cfg(all(test, feature = "incoming"))]
+mod tests {
+	use super::*;
+	use crate::{
+		asset_hub::runtime_types::{
+			asset_hub_polkadot_runtime::RuntimeEvent,
+			frame_system::{EventRecord, Phase},
+			pallet_balances, pallet_message_queue,
+			sp_weights::weight_v2::Weight,
+		},
+		asset_overrides::AssetOverrides,
+		helpers::XcmAggregatedOrigin,
+		incoming_parser::OriginChain,
+		raw_blocks::InjectedMetadata,
+		types::{TransferType, XcmTransfer},
+	};
+	use std::collections::HashMap;
+	use subxt::{OfflineClient, config::polkadot::AccountId32, ext::codec::Encode, utils::H256};
+
+	// Blocks made of canned events, with the asset metadata injected as storage.
+	struct CannedBlocks(HashMap<BlockHash, (BlockNumber, Vec<EventRecord<RuntimeEvent, H256>>)>);
+
+	impl BlockSource for CannedBlocks {
+		type Client = OfflineClient<PolkadotConfig>;
+		type Storage = InjectedMetadata;
+
+		async fn block(
+			&self,
+			block_hash: BlockHash,
+		) -> Result<SourceBlock<Self::Client, Self::Storage>, Error> {
+			let (number, records) = self.0.get(&block_hash).ok_or(Error::GeneratePayloadFailed)?;
+			let metadata = crate::raw_blocks::metadata().map_err(Error::RuntimeApiFailed)?;
+			Ok(SourceBlock {
+				number: *number,
+				events: Events::decode_from(records.encode(), metadata),
+				extrinsics: None,
+				storage: InjectedMetadata(AssetOverrides::default()),
+			})
+		}
+	}
+
+	fn finalization(event: RuntimeEvent) -> EventRecord<RuntimeEvent, H256> {
+		EventRecord { phase: Phase::Finalization, event, topics: vec![] }
+	}
+
+	#[tokio::test]
+	async fn transfers_are_parsed_from_canned_blocks() {
+		let beneficiary = AccountId32([1; 32]);
+		let block_hash = BlockHash::repeat_byte(1);
+		let blocks = CannedBlocks(HashMap::from([(
+			block_hash,
+			(
+				10,
+				vec![
+					finalization(RuntimeEvent::Balances(pallet_balances::pallet::Event::Minted {
+						who: beneficiary.clone(),
+						amount: 15_000_000_000,
+					})),
+					finalization(RuntimeEvent::MessageQueue(
+						pallet_message_queue::pallet::Event::Processed {
+							id: H256::repeat_byte(2),
+							origin: XcmAggregatedOrigin::Parent,
+							weight_used: Weight { ref_time: 0, proof_size: 0 },
+							success: true,
+						},
+					)),
+				],
+			),
+		)]));
+
+		let transfers = crate::helpers::get_all_transfers_at_block_hash(&blocks, block_hash)
+			.await
+			.unwrap();
+		let [XcmTransfer::ReceivedTransfer(transfer)] = transfers.as_slice() else {
+			panic!("Expected a single received transfer, got {:?}", transfers);
+		};
+		assert_eq!(transfer.block_number, 10);
+		assert_eq!(transfer.origin_chain, OriginChain::Polkadot);
+		assert_eq!(transfer.beneficiary_public_key, crate::helpers::to_hex_string(&beneficiary.0));
+		assert_eq!(transfer.asset, "DOT");
+		assert_eq!(transfer.amount, 1.5);
+		assert_eq!(transfer.transfer_type, TransferType::Teleport);
+
+		assert!(
+			crate::helpers::get_all_transfers_at_block_hash(&blocks, BlockHash::repeat_byte(3))
+				.await
+				.is_err()
+		);
+	}
+}
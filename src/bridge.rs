@@ -0,0 +1,640 @@
+use crate::{
+	Error,
+	asset_hub::runtime_types::{
+		frame_support::traits::messages::ProcessMessageError,
+		staging_xcm::{v4::Instruction as V4Instruction, v5::Instruction as V5Instruction},
+		xcm::{VersionedXcm, v3::Instruction as V3Instruction},
+	},
+	congestion::CONCATENATED_VERSIONED_XCM_FORMAT,
+	outgoing_parser::{DestinationChain, XcmOutgoingTransfer},
+	types::{BlockNumber, MessageError, XcmTransfer},
+};
+use serde::Serialize;
+use std::{collections::HashMap, str::FromStr};
+use subxt::{
+	OnlineClient, PolkadotConfig,
+	blocks::Block,
+	events::StaticEvent,
+	ext::{codec::Decode, scale_decode::DecodeAsType},
+	utils::H256,
+};
+
+// BridgeHub relays the messages sent from AssetHub to other consensus systems.
+pub(crate) const BRIDGE_HUB_RPC_ENDPOINT: &str = "wss://polkadot-bridge-hub-rpc.polkadot.io";
+// The chains traversed by the messages sent to Kusama AssetHub after leaving Polkadot BridgeHub.
+pub(crate) const KUSAMA_BRIDGE_HUB_RPC_ENDPOINT: &str = "wss://kusama-bridge-hub-rpc.polkadot.io";
+pub(crate) const KUSAMA_ASSET_HUB_RPC_ENDPOINT: &str = "wss://kusama-asset-hub-rpc.polkadot.io";
+pub(crate) const KUSAMA_ASSET_HUB_PARA_ID: u32 = 1000;
+
+// A parachain followed to resolve the transfers sent to it, given as `<PARA_ID>=<URL>`, eg
+// `2034=wss://hydration-rpc.n.dwellir.com`.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct ParachainEndpoint {
+	pub(crate) para_id: u32,
+	pub(crate) url: String,
+}
+
+impl FromStr for ParachainEndpoint {
+	type Err = String;
+
+	fn from_str(endpoint: &str) -> Result<Self, Self::Err> {
+		let (para_id, url) = endpoint
+			.split_once('=')
+			.ok_or_else(|| format!("{} isn't a valid <PARA_ID>=<URL> endpoint", endpoint))?;
+		let para_id =
+			para_id.parse().map_err(|_| format!("{} isn't a valid parachain id", para_id))?;
+		Ok(Self { para_id, url: url.to_owned() })
+	}
+}
+
+// The events of the Snowbridge outbound queue in BridgeHub. The indexer only embeds the metadata of
+// AssetHub, so these events are defined here and decoded against the metadata of the node. The id
+// of the messages is the id of the message sent from AssetHub.
+#[derive(Debug, Decode, DecodeAsType)]
+#[codec(crate = subxt::ext::codec)]
+#[decode_as_type(crate_path = "subxt::ext::scale_decode")]
+struct MessageQueued {
+	id: H256,
+}
+
+impl StaticEvent for MessageQueued {
+	const PALLET: &'static str = "EthereumOutboundQueue";
+	const EVENT: &'static str = "MessageQueued";
+}
+
+#[derive(Debug, Decode, DecodeAsType)]
+#[codec(crate = subxt::ext::codec)]
+#[decode_as_type(crate_path = "subxt::ext::scale_decode")]
+struct MessageAccepted {
+	id: H256,
+	nonce: u64,
+}
+
+impl StaticEvent for MessageAccepted {
+	const PALLET: &'static str = "EthereumOutboundQueue";
+	const EVENT: &'static str = "MessageAccepted";
+}
+
+// Only the commitment is decoded, the count of messages committed is skipped.
+#[derive(Debug, DecodeAsType)]
+#[decode_as_type(crate_path = "subxt::ext::scale_decode")]
+struct MessagesCommitted {
+	root: H256,
+}
+
+impl StaticEvent for MessagesCommitted {
+	const PALLET: &'static str = "EthereumOutboundQueue";
+	const EVENT: &'static str = "MessagesCommitted";
+}
+
+// The messages processed by the message queue of a chain. Only the fields needed to follow the
+// messages are decoded, the remaining ones are skipped.
+#[derive(Debug, DecodeAsType)]
+#[decode_as_type(crate_path = "subxt::ext::scale_decode")]
+struct Processed {
+	id: H256,
+	success: bool,
+}
+
+impl StaticEvent for Processed {
+	const PALLET: &'static str = "MessageQueue";
+	const EVENT: &'static str = "Processed";
+}
+
+// The messages discarded by the message queue of a chain, along with the reason. The error is
+// shared by every chain using the message queue, so it's decoded as AssetHub defines it.
+#[derive(Debug, DecodeAsType)]
+#[decode_as_type(crate_path = "subxt::ext::scale_decode")]
+struct ProcessingFailed {
+	id: H256,
+	error: ProcessMessageError,
+}
+
+impl StaticEvent for ProcessingFailed {
+	const PALLET: &'static str = "MessageQueue";
+	const EVENT: &'static str = "ProcessingFailed";
+}
+
+// The assets trapped by the execution of a message, identified by their hash.
+#[derive(Debug, DecodeAsType)]
+#[decode_as_type(crate_path = "subxt::ext::scale_decode")]
+struct AssetsTrapped {
+	hash: H256,
+}
+
+impl StaticEvent for AssetsTrapped {
+	const PALLET: &'static str = "PolkadotXcm";
+	const EVENT: &'static str = "AssetsTrapped";
+}
+
+// The XCMP pages sent by a parachain in a block, kept by `ParachainSystem` until the next block.
+// The hash reported by `XcmpQueue::XcmpMessageSent` is the hash of the message, not its topic, so
+// the messages forwarded by Kusama BridgeHub are read from here instead.
+#[derive(Debug, Decode)]
+#[codec(crate = subxt::ext::codec)]
+struct OutboundHrmpMessage {
+	recipient: u32,
+	data: Vec<u8>,
+}
+
+// A message processed by the message queue of a chain, with the error if it failed and the hash of
+// the assets it trapped, if any.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct ProcessedMessage {
+	pub(crate) message_id: String,
+	pub(crate) outcome: Result<(), MessageError>,
+	pub(crate) trapped_assets_hash: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum OutboundQueueEvent {
+	Queued { id: String },
+	Accepted { id: String, nonce: u64 },
+	// All the messages accepted in a block are committed at the end of it
+	Committed { root: String },
+}
+
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub(crate) enum BridgeStatus {
+	// The message reached BridgeHub and waits in the outbound queue
+	Queued,
+	// The message was accepted by the outbound queue, which assigned it a nonce
+	Accepted { nonce: u64 },
+	// The message is part of a commitment sent to Ethereum, so relayers can dispatch it there
+	Committed { nonce: u64, commitment: String },
+}
+
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub(crate) struct BridgeStatusUpdate {
+	pub(crate) transfer_group_id: String,
+	pub(crate) message_id: String,
+	pub(crate) bridge_hub_block_number: BlockNumber,
+	pub(crate) status: BridgeStatus,
+}
+
+// Follows the transfers to Ethereum through the outbound queue of BridgeHub, reporting their status
+// as their message advances towards Ethereum.
+#[derive(Default)]
+pub(crate) struct EthereumBridgeFollower {
+	// The transfer group id of the tracked transfers by message id, together with the nonce
+	// assigned to them once accepted.
+	pending_transfers: HashMap<String, (String, Option<u64>)>,
+}
+
+impl EthereumBridgeFollower {
+	// Starts tracking the transfers to Ethereum among the transfers of an AssetHub block.
+	pub(crate) fn track(&mut self, transfers: &[XcmTransfer]) {
+		for transfer in transfers {
+			if let XcmTransfer::SentTransfer(XcmOutgoingTransfer {
+				destination_chain: DestinationChain::Ethereum { .. },
+				message_id: Some(message_id),
+				transfer_group_id,
+				..
+			}) = transfer
+			{
+				self.pending_transfers
+					.entry(message_id.clone())
+					.or_insert_with(|| (transfer_group_id.clone(), None));
+			}
+		}
+	}
+
+	// Feeds the follower with the outbound queue events of a BridgeHub block, returning the status
+	// updates of the tracked transfers. Committed transfers aren't tracked anymore.
+	pub(crate) fn observe(
+		&mut self,
+		bridge_hub_block_number: BlockNumber,
+		events: &[OutboundQueueEvent],
+	) -> Vec<BridgeStatusUpdate> {
+		let mut updates = vec![];
+		let mut accepted_messages = vec![];
+		for event in events {
+			match event {
+				OutboundQueueEvent::Queued { id } => {
+					if let Some((transfer_group_id, _)) = self.pending_transfers.get(id) {
+						updates.push(BridgeStatusUpdate {
+							transfer_group_id: transfer_group_id.clone(),
+							message_id: id.clone(),
+							bridge_hub_block_number,
+							status: BridgeStatus::Queued,
+						});
+					}
+				},
+				OutboundQueueEvent::Accepted { id, nonce } => {
+					if let Some((transfer_group_id, accepted_nonce)) =
+						self.pending_transfers.get_mut(id)
+					{
+						*accepted_nonce = Some(*nonce);
+						accepted_messages.push(id.clone());
+						updates.push(BridgeStatusUpdate {
+							transfer_group_id: transfer_group_id.clone(),
+							message_id: id.clone(),
+							bridge_hub_block_number,
+							status: BridgeStatus::Accepted { nonce: *nonce },
+						});
+					}
+				},
+				OutboundQueueEvent::Committed { root } =>
+					for id in accepted_messages.drain(..) {
+						if let Some((transfer_group_id, Some(nonce))) =
+							self.pending_transfers.remove(&id)
+						{
+							updates.push(BridgeStatusUpdate {
+								transfer_group_id,
+								message_id: id,
+								bridge_hub_block_number,
+								status: BridgeStatus::Committed { nonce, commitment: root.clone() },
+							});
+						}
+					},
+			}
+		}
+		updates
+	}
+}
+
+// The legs of the route followed by the messages sent to Kusama AssetHub, each one observed in a
+// different chain.
+#[derive(Debug, Serialize, Clone, Copy, PartialEq)]
+pub(crate) enum KusamaBridgeLeg {
+	// Polkadot BridgeHub processed the message and exported it over the bridge
+	PolkadotBridgeHub,
+	// Kusama BridgeHub received the message from the bridge and forwarded it to Kusama AssetHub
+	KusamaBridgeHub,
+	// Kusama AssetHub processed the message
+	KusamaAssetHub,
+}
+
+impl KusamaBridgeLeg {
+	pub(crate) const ALL: [Self; 3] =
+		[Self::PolkadotBridgeHub, Self::KusamaBridgeHub, Self::KusamaAssetHub];
+}
+
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub(crate) struct BridgeLegUpdate {
+	pub(crate) transfer_group_id: String,
+	pub(crate) message_id: String,
+	pub(crate) leg: KusamaBridgeLeg,
+	pub(crate) block_number: BlockNumber,
+	pub(crate) success: bool,
+	// Why the message failed in the chain of the leg, if it did
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub(crate) error: Option<MessageError>,
+	// The hash of the assets trapped in the chain of the leg, if any
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub(crate) trapped_assets_hash: Option<String>,
+}
+
+// Follows the transfers to Kusama AssetHub through the Polkadot-Kusama bridge. The messages keep
+// their id (the topic set by AssetHub) in every hop, so they're recognized in every chain.
+#[derive(Default)]
+pub(crate) struct KusamaBridgeFollower {
+	// The transfer group id of the tracked transfers by message id
+	pending_transfers: HashMap<String, String>,
+}
+
+impl KusamaBridgeFollower {
+	// Starts tracking the transfers to Kusama AssetHub among the transfers of an AssetHub block.
+	pub(crate) fn track(&mut self, transfers: &[XcmTransfer]) {
+		for transfer in transfers {
+			if let XcmTransfer::SentTransfer(XcmOutgoingTransfer {
+				destination_chain: DestinationChain::KusamaParachain(KUSAMA_ASSET_HUB_PARA_ID),
+				message_id: Some(message_id),
+				transfer_group_id,
+				..
+			}) = transfer
+			{
+				self.pending_transfers
+					.entry(message_id.clone())
+					.or_insert_with(|| transfer_group_id.clone());
+			}
+		}
+	}
+
+	// Feeds the follower with the messages seen in a block of the chain observed by a leg, together
+	// with whether they succeeded, returning the updates of the tracked transfers. Transfers aren't
+	// tracked anymore once they reach Kusama AssetHub or fail.
+	pub(crate) fn observe(
+		&mut self,
+		leg: KusamaBridgeLeg,
+		block_number: BlockNumber,
+		messages: &[ProcessedMessage],
+	) -> Vec<BridgeLegUpdate> {
+		let mut updates = vec![];
+		for message in messages {
+			let Some(transfer_group_id) = self.pending_transfers.get(&message.message_id) else {
+				continue;
+			};
+			updates.push(BridgeLegUpdate {
+				transfer_group_id: transfer_group_id.clone(),
+				message_id: message.message_id.clone(),
+				leg,
+				block_number,
+				success: message.outcome.is_ok(),
+				error: message.outcome.clone().err(),
+				trapped_assets_hash: message.trapped_assets_hash.clone(),
+			});
+			if leg == KusamaBridgeLeg::KusamaAssetHub || message.outcome.is_err() {
+				self.pending_transfers.remove(&message.message_id);
+			}
+		}
+		updates
+	}
+}
+
+// Reads the messages processed by the message queue of a chain in a block. The assets trapped while
+// executing a message are reported before the message is processed, so they're attributed to the
+// next message processed.
+pub(crate) async fn get_processed_messages(
+	block: &Block<PolkadotConfig, OnlineClient<PolkadotConfig>>,
+) -> Result<Vec<ProcessedMessage>, Error> {
+	let events = block.events().await?;
+	let mut output = vec![];
+	let mut trapped_assets_hash = None;
+	for event in events.iter() {
+		let event = event?;
+		if let Some(trapped_event) = event.as_event::<AssetsTrapped>()? {
+			trapped_assets_hash =
+				Some(crate::helpers::to_hex_string(trapped_event.hash.as_bytes()));
+		} else if let Some(processed_event) = event.as_event::<Processed>()? {
+			output.push(ProcessedMessage {
+				message_id: crate::helpers::to_hex_string(processed_event.id.as_bytes()),
+				outcome: if processed_event.success {
+					Ok(())
+				} else {
+					Err(MessageError::ExecutionFailed)
+				},
+				trapped_assets_hash: trapped_assets_hash.take(),
+			});
+		} else if let Some(failed_event) = event.as_event::<ProcessingFailed>()? {
+			output.push(ProcessedMessage {
+				message_id: crate::helpers::to_hex_string(failed_event.id.as_bytes()),
+				outcome: Err(MessageError::from(&failed_event.error)),
+				trapped_assets_hash: trapped_assets_hash.take(),
+			});
+		}
+	}
+	Ok(output)
+}
+
+// Reads the messages of a leg seen in a block of the chain observed by it, together with the error
+// of the failed ones.
+pub(crate) async fn get_leg_messages(
+	leg: KusamaBridgeLeg,
+	block: &Block<PolkadotConfig, OnlineClient<PolkadotConfig>>,
+) -> Result<Vec<ProcessedMessage>, Error> {
+	match leg {
+		KusamaBridgeLeg::PolkadotBridgeHub | KusamaBridgeLeg::KusamaAssetHub =>
+			get_processed_messages(block).await,
+		KusamaBridgeLeg::KusamaBridgeHub => {
+			let key = [
+				sp_core::hashing::twox_128(b"ParachainSystem"),
+				sp_core::hashing::twox_128(b"HrmpOutboundMessages"),
+			]
+			.concat();
+			let Some(messages) = block.storage().fetch_raw(key).await? else {
+				return Ok(vec![]);
+			};
+			let messages = Vec::<OutboundHrmpMessage>::decode(&mut &messages[..])
+				.map_err(subxt::error::Error::from)?;
+			Ok(messages
+				.iter()
+				.filter(|message| message.recipient == KUSAMA_ASSET_HUB_PARA_ID)
+				.flat_map(|message| page_topics(&message.data))
+				.map(|message_id| ProcessedMessage {
+					message_id,
+					outcome: Ok(()),
+					trapped_assets_hash: None,
+				})
+				.collect())
+		},
+	}
+}
+
+// The topics of the messages of an XCMP page, which are the ids kept by the messages in every hop.
+// Messages without topic can't be followed, so they're left out.
+fn page_topics(page: &[u8]) -> Vec<String> {
+	let Some((&CONCATENATED_VERSIONED_XCM_FORMAT, mut messages)) = page.split_first() else {
+		return vec![];
+	};
+	let mut topics = vec![];
+	while !messages.is_empty() {
+		let Ok(message) = VersionedXcm::decode(&mut messages) else {
+			break;
+		};
+		let topic = match message {
+			VersionedXcm::V3(xcm) =>
+				xcm.0.into_iter().rev().find_map(|instruction| match instruction {
+					V3Instruction::SetTopic(topic) => Some(topic),
+					_ => None,
+				}),
+			VersionedXcm::V4(xcm) =>
+				xcm.0.into_iter().rev().find_map(|instruction| match instruction {
+					V4Instruction::SetTopic(topic) => Some(topic),
+					_ => None,
+				}),
+			VersionedXcm::V5(xcm) =>
+				xcm.0.into_iter().rev().find_map(|instruction| match instruction {
+					V5Instruction::SetTopic(topic) => Some(topic),
+					_ => None,
+				}),
+		};
+		topics.extend(topic.map(|topic| crate::helpers::to_hex_string(&topic)));
+	}
+	topics
+}
+
+// Reads the events of the Snowbridge outbound queue emitted in a BridgeHub block.
+pub(crate) async fn get_outbound_queue_events(
+	block: &Block<PolkadotConfig, OnlineClient<PolkadotConfig>>,
+) -> Result<Vec<OutboundQueueEvent>, Error> {
+	let events = block.events().await?;
+	let mut output = vec![];
+	for event in events.iter() {
+		let event = event?;
+		if let Some(queued_event) = event.as_event::<MessageQueued>()? {
+			output.push(OutboundQueueEvent::Queued {
+				id: crate::helpers::to_hex_string(queued_event.id.as_bytes()),
+			});
+		} else if let Some(accepted_event) = event.as_event::<MessageAccepted>()? {
+			output.push(OutboundQueueEvent::Accepted {
+				id: crate::helpers::to_hex_string(accepted_event.id.as_bytes()),
+				nonce: accepted_event.nonce,
+			});
+		} else if let Some(committed_event) = event.as_event::<MessagesCommitted>()? {
+			output.push(OutboundQueueEvent::Committed {
+				root: crate::helpers::to_hex_string(committed_event.root.as_bytes()),
+			});
+		}
+	}
+	Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{outgoing_parser::WeightLimit, types::TransferType};
+
+	const MESSAGE_ID: &str = "0x2b7ce7bc7e87e4d6619da21487c7a53f0f1ea8c5a2ec6e5dc3d4b8d4a4e5e6f7";
+
+	fn transfer_to(destination_chain: DestinationChain) -> XcmTransfer {
+		XcmTransfer::SentTransfer(XcmOutgoingTransfer {
+			block_number: 8_935_124,
+			transfer_group_id: "8935124-2".to_owned(),
+			origin: None,
+			destination_chain,
+			sender: "13KsaHFcQKSTd4m73Ub9yVwM1JGCZvipMyTZonHEXEceFYwS".to_owned(),
+			sender_public_key: None,
+			beneficiary: "0xda3985513642D591aE95EF6dEc4Ff6D725373004".to_owned(),
+			beneficiary_public_key: "0xda3985513642d591ae95ef6dec4ff6d725373004".to_owned(),
+			asset: "Wrapped Ether".to_owned(),
+			asset_id: None,
+			amount: 1.0,
+			exact_amount: None,
+			transfer_type: TransferType::Reserve,
+			fee_asset_item: 0,
+			pays_fees: true,
+			weight_limit: WeightLimit::Unlimited,
+			burned_amount: Some(1.0),
+			outcome: None,
+			message_id: Some(MESSAGE_ID.to_owned()),
+			trapped_assets_hash: None,
+			local_fee_dot: None,
+			nonce: None,
+			tip_dot: None,
+			controllers: None,
+			raw_xcm: None,
+			entities: vec![],
+			xc20_address: None,
+		})
+	}
+
+	#[test]
+	fn parachain_endpoints_are_parsed() {
+		assert_eq!(
+			"2034=wss://hydration-rpc.n.dwellir.com".parse(),
+			Ok(ParachainEndpoint {
+				para_id: 2034,
+				url: "wss://hydration-rpc.n.dwellir.com".to_owned()
+			})
+		);
+		assert!("wss://hydration-rpc.n.dwellir.com".parse::<ParachainEndpoint>().is_err());
+		assert!(
+			"hydration=wss://hydration-rpc.n.dwellir.com"
+				.parse::<ParachainEndpoint>()
+				.is_err()
+		);
+	}
+
+	#[test]
+	fn ethereum_bridge_follower_reports_the_tracked_transfers() {
+		let mut follower = EthereumBridgeFollower::default();
+		follower.track(&[
+			transfer_to(DestinationChain::Ethereum { chain_id: 1 }),
+			transfer_to(DestinationChain::PolkadotParachain(2034)),
+		]);
+		let status = |updates: Vec<BridgeStatusUpdate>| {
+			updates.into_iter().map(|update| update.status).collect::<Vec<_>>()
+		};
+
+		assert_eq!(
+			status(
+				follower.observe(10, &[OutboundQueueEvent::Queued { id: MESSAGE_ID.to_owned() }])
+			),
+			vec![BridgeStatus::Queued]
+		);
+		// Other messages are ignored
+		assert!(
+			follower
+				.observe(11, &[OutboundQueueEvent::Queued { id: "0x01".to_owned() }])
+				.is_empty()
+		);
+		assert_eq!(
+			status(follower.observe(
+				12,
+				&[
+					OutboundQueueEvent::Accepted { id: MESSAGE_ID.to_owned(), nonce: 7 },
+					OutboundQueueEvent::Committed { root: "0xaa".to_owned() },
+				]
+			)),
+			vec![
+				BridgeStatus::Accepted { nonce: 7 },
+				BridgeStatus::Committed { nonce: 7, commitment: "0xaa".to_owned() }
+			]
+		);
+		// Committed transfers aren't tracked anymore
+		assert!(follower.pending_transfers.is_empty());
+	}
+
+	#[test]
+	fn page_topics_reads_the_topic_of_every_message() {
+		use crate::asset_hub::runtime_types::staging_xcm::v5::Xcm;
+		use subxt::ext::codec::Encode;
+
+		let message = |instructions| VersionedXcm::V5(Xcm(instructions)).encode();
+		let mut page = vec![CONCATENATED_VERSIONED_XCM_FORMAT];
+		page.extend(message(vec![V5Instruction::ClearOrigin, V5Instruction::SetTopic([1; 32])]));
+		// Messages without topic can't be followed
+		page.extend(message(vec![V5Instruction::ClearOrigin]));
+		page.extend(message(vec![V5Instruction::SetTopic([2; 32])]));
+		assert_eq!(
+			page_topics(&page),
+			vec![format!("0x{}", "01".repeat(32)), format!("0x{}", "02".repeat(32))]
+		);
+		// Neither pages of other formats
+		assert!(page_topics(&[1, 0]).is_empty());
+	}
+
+	#[test]
+	fn kusama_bridge_follower_reports_every_leg() {
+		let mut follower = KusamaBridgeFollower::default();
+		follower.track(&[
+			transfer_to(DestinationChain::KusamaParachain(1000)),
+			transfer_to(DestinationChain::Ethereum { chain_id: 1 }),
+		]);
+		let message = |outcome| {
+			vec![ProcessedMessage {
+				message_id: MESSAGE_ID.to_owned(),
+				outcome,
+				trapped_assets_hash: None,
+			}]
+		};
+
+		for (block_number, leg) in KusamaBridgeLeg::ALL.into_iter().enumerate() {
+			assert_eq!(
+				follower.observe(leg, block_number as BlockNumber, &message(Ok(()))),
+				vec![BridgeLegUpdate {
+					transfer_group_id: "8935124-2".to_owned(),
+					message_id: MESSAGE_ID.to_owned(),
+					leg,
+					block_number: block_number as BlockNumber,
+					success: true,
+					error: None,
+					trapped_assets_hash: None,
+				}]
+			);
+		}
+		// Delivered transfers aren't tracked anymore
+		assert!(follower.pending_transfers.is_empty());
+
+		// Neither failed ones, which tell why they failed
+		follower.track(&[transfer_to(DestinationChain::KusamaParachain(1000))]);
+		let updates = follower.observe(
+			KusamaBridgeLeg::PolkadotBridgeHub,
+			1,
+			&message(Err(MessageError::Unsupported)),
+		);
+		assert_eq!(
+			updates
+				.iter()
+				.map(|update| (update.success, update.error.clone()))
+				.collect::<Vec<_>>(),
+			vec![(false, Some(MessageError::Unsupported))]
+		);
+		assert!(
+			follower
+				.observe(KusamaBridgeLeg::KusamaAssetHub, 2, &message(Ok(())))
+				.is_empty()
+		);
+	}
+}
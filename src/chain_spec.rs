@@ -0,0 +1,182 @@
+use crate::Error;
+use serde_json::Value;
+use std::sync::RwLock;
+use subxt::{Metadata, OnlineClient, PolkadotConfig, backend::legacy::LegacyRpcMethods};
+
+// Groups the values that are specific to the indexed chain, so the parsers don't hardcode them and
+// supporting another system parachain or network only requires a new implementation of this trait
+// together with the metadata module generated for it.
+pub(crate) trait ChainSpec {
+	const RPC_ENDPOINT: &'static str;
+	const NATIVE_TOKEN_SYMBOL: &'static str;
+	const NATIVE_TOKEN_DECIMALS: u8;
+	const SS58_PREFIX: u16;
+	const PARA_ID: u32;
+	// Pallet indexes cannot change without breaking the runtime, so it's OK to hardcode them here
+	const ASSETS_PALLET_INDEX: u8;
+
+	// Checks that the metadata module generated by subxt for this chain matches the on-chain
+	// metadata.
+	fn is_codegen_valid_for(metadata: &Metadata) -> bool;
+}
+
+pub(crate) struct PolkadotAssetHub;
+
+impl ChainSpec for PolkadotAssetHub {
+	const RPC_ENDPOINT: &'static str = "wss://polkadot-asset-hub-rpc.polkadot.io";
+	// As DOT is the native currency, it doesn't have metadata as other assets but it's part of the
+	// chainspec. The node reports its symbol and decimals in its properties, which take precedence,
+	// so these values are only the fallback for nodes that don't declare them.
+	const NATIVE_TOKEN_SYMBOL: &'static str = "DOT";
+	const NATIVE_TOKEN_DECIMALS: u8 = 10;
+	const SS58_PREFIX: u16 = 0;
+	const PARA_ID: u32 = 1000;
+	const ASSETS_PALLET_INDEX: u8 = 50;
+
+	fn is_codegen_valid_for(metadata: &Metadata) -> bool {
+		crate::asset_hub::is_codegen_valid_for(metadata)
+	}
+}
+
+// The chain indexed by the binary.
+pub(crate) type Chain = PolkadotAssetHub;
+
+// The values of the chain the parsers assume from the chain spec, read from the node once per
+// connection instead, so the same code indexes the AssetHub of other networks (eg, Kusama, Westend
+// or local networks) and survives a redenomination of the native token.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct ChainProperties {
+	pub(crate) chain_name: String,
+	pub(crate) ss58_prefix: u16,
+	pub(crate) native_token_symbol: String,
+	pub(crate) native_token_decimals: u8,
+	pub(crate) para_id: u32,
+}
+
+// The properties are read every time the CLI connects, so the parsers don't need to carry them
+// around. They're replaced on each connection, eg when failing over to another endpoint.
+static CHAIN_PROPERTIES: RwLock<Option<ChainProperties>> = RwLock::new(None);
+
+// Records the properties of the node connected to, returning the previous ones if they differ, so
+// a failover to an endpoint of another chain is noticed.
+pub(crate) fn set_chain_properties(properties: ChainProperties) -> Option<ChainProperties> {
+	let mut current = CHAIN_PROPERTIES.write().unwrap_or_else(|e| e.into_inner());
+	current
+		.replace(properties)
+		.filter(|previous| Some(previous) != current.as_ref())
+}
+
+fn read_chain_properties<T>(read: impl FnOnce(&ChainProperties) -> T) -> Option<T> {
+	CHAIN_PROPERTIES.read().unwrap_or_else(|e| e.into_inner()).as_ref().map(read)
+}
+
+pub(crate) fn native_token_symbol() -> String {
+	read_chain_properties(|properties| properties.native_token_symbol.clone())
+		.unwrap_or_else(|| Chain::NATIVE_TOKEN_SYMBOL.to_owned())
+}
+
+pub(crate) fn native_token_decimals() -> u8 {
+	read_chain_properties(|properties| properties.native_token_decimals)
+		.unwrap_or(Chain::NATIVE_TOKEN_DECIMALS)
+}
+
+pub(crate) fn para_id() -> u32 {
+	read_chain_properties(|properties| properties.para_id).unwrap_or(Chain::PARA_ID)
+}
+
+// Reads the name and properties reported by the node, and the parachain id from its storage.
+// Properties missing, as some local networks don't declare them, keep the chain spec values.
+pub(crate) async fn fetch_chain_properties(
+	api: &OnlineClient<PolkadotConfig>,
+	rpc: &LegacyRpcMethods<PolkadotConfig>,
+) -> Result<ChainProperties, Error> {
+	let chain_name = rpc.system_chain().await.map_err(subxt::error::Error::from)?;
+	let properties = rpc.system_properties().await.map_err(subxt::error::Error::from)?;
+	let para_id = api
+		.storage()
+		.at_latest()
+		.await?
+		.fetch_or_default(&crate::asset_hub::storage().parachain_info().parachain_id())
+		.await?
+		.0;
+	Ok(parse_chain_properties(chain_name, &properties, para_id))
+}
+
+fn parse_chain_properties(
+	chain_name: String,
+	properties: &serde_json::Map<String, Value>,
+	para_id: u32,
+) -> ChainProperties {
+	// Chains with several tokens report a list of symbols and decimals, the first one being the
+	// native token
+	let native_token = |property: &str| match properties.get(property) {
+		Some(Value::Array(values)) => values.first(),
+		value => value,
+	};
+	let native_token_symbol = native_token("tokenSymbol")
+		.and_then(Value::as_str)
+		.filter(|symbol| !symbol.is_empty())
+		.unwrap_or(Chain::NATIVE_TOKEN_SYMBOL)
+		.to_owned();
+	let native_token_decimals = native_token("tokenDecimals")
+		.and_then(Value::as_u64)
+		.and_then(|decimals| u8::try_from(decimals).ok())
+		.unwrap_or(Chain::NATIVE_TOKEN_DECIMALS);
+	let ss58_prefix = properties
+		.get("ss58Format")
+		.and_then(Value::as_u64)
+		.and_then(|prefix| u16::try_from(prefix).ok())
+		.unwrap_or(Chain::SS58_PREFIX);
+	ChainProperties { chain_name, ss58_prefix, native_token_symbol, native_token_decimals, para_id }
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn chain_properties_fall_back_to_the_chain_spec() {
+		let properties = serde_json::json!({
+			"ss58Format": 42,
+			"tokenSymbol": ["WND", "USDT"],
+			"tokenDecimals": [12, 18]
+		});
+		assert_eq!(
+			parse_chain_properties(
+				"Westend Asset Hub Local".to_owned(),
+				properties.as_object().unwrap(),
+				1000
+			),
+			ChainProperties {
+				chain_name: "Westend Asset Hub Local".to_owned(),
+				ss58_prefix: 42,
+				native_token_symbol: "WND".to_owned(),
+				native_token_decimals: 12,
+				para_id: 1000,
+			}
+		);
+		let properties = parse_chain_properties("Dev".to_owned(), &serde_json::Map::new(), 2000);
+		assert_eq!(properties.ss58_prefix, Chain::SS58_PREFIX);
+		assert_eq!(properties.native_token_symbol, Chain::NATIVE_TOKEN_SYMBOL);
+		assert_eq!(properties.native_token_decimals, Chain::NATIVE_TOKEN_DECIMALS);
+	}
+
+	#[test]
+	fn chain_properties_are_replaced_on_every_connection() {
+		// The values of the chain spec, so the tests reading them concurrently aren't affected
+		let properties = parse_chain_properties(
+			"Polkadot Asset Hub".to_owned(),
+			&serde_json::Map::new(),
+			Chain::PARA_ID,
+		);
+		set_chain_properties(properties.clone());
+		assert_eq!(set_chain_properties(properties.clone()), None);
+		let other_chain =
+			ChainProperties { chain_name: "Kusama Asset Hub".to_owned(), ..properties.clone() };
+		assert_eq!(set_chain_properties(other_chain), Some(properties));
+		assert_eq!(
+			read_chain_properties(|properties| properties.chain_name.clone()),
+			Some("Kusama Asset Hub".to_owned())
+		);
+	}
+}
@@ -0,0 +1,278 @@
+use crate::{
+	incoming_parser::OriginChain,
+	outgoing_parser::{ExecutionOutcome, XcmOutgoingTransfer},
+	types::XcmTransfer,
+};
+use serde::Serialize;
+
+// The columns of the compliance export, following the originator and beneficiary fields required
+// by the Travel Rule, so the records can be ingested by the usual compliance tools.
+pub(crate) const HEADER: &str = "transaction_reference,message_id,block_number,timestamp,direction,originator,originator_public_key,originator_chain,beneficiary,beneficiary_public_key,beneficiary_chain,asset,amount,transfer_type,status";
+
+// Renders a CSV row per asset transferred, with the timestamp of the block they happened in if
+// known. Records which aren't transfers, such as round trips or status updates, are skipped.
+pub(crate) fn render_rows(transfers: &[XcmTransfer], timestamp: Option<u64>) -> Vec<String> {
+	let timestamp = timestamp.map(format_timestamp).unwrap_or_default();
+	let local_chain = label(&OriginChain::PolkadotAssetHub);
+	let mut rows = vec![];
+	for transfer in transfers {
+		match transfer {
+			XcmTransfer::ReceivedTransfer(transfer) => rows.push(vec![
+				String::new(),
+				transfer.message_id.clone().unwrap_or_default(),
+				transfer.block_number.to_string(),
+				timestamp.clone(),
+				"incoming".to_owned(),
+				String::new(),
+				String::new(),
+				label(&transfer.origin_chain),
+				transfer.beneficiary.clone(),
+				transfer.beneficiary_public_key.clone(),
+				local_chain.clone(),
+				transfer.asset.clone(),
+				crate::amount::decimal_string(transfer.amount, transfer.exact_amount.as_deref()),
+				label(&transfer.transfer_type),
+				"Complete".to_owned(),
+			]),
+			XcmTransfer::SentTransfer(transfer) =>
+				rows.push(outgoing_row(transfer, &timestamp, &local_chain)),
+			XcmTransfer::SentTransferGroup(group) =>
+				for asset in &group.assets {
+					let transfer = XcmOutgoingTransfer {
+						block_number: group.block_number,
+						transfer_group_id: group.transfer_group_id.clone(),
+						origin: group.origin.clone(),
+						destination_chain: group.destination_chain.clone(),
+						sender: group.sender.clone(),
+						sender_public_key: group.sender_public_key.clone(),
+						beneficiary: group.beneficiary.clone(),
+						beneficiary_public_key: group.beneficiary_public_key.clone(),
+						asset: asset.asset.clone(),
+						asset_id: None,
+						amount: asset.amount,
+						exact_amount: asset.exact_amount.clone(),
+						transfer_type: asset.transfer_type.clone(),
+						fee_asset_item: group.fee_asset_item,
+						pays_fees: asset.pays_fees,
+						weight_limit: group.weight_limit.clone(),
+						burned_amount: asset.burned_amount,
+						outcome: group.outcome.clone(),
+						message_id: group.message_id.clone(),
+						trapped_assets_hash: group.trapped_assets_hash.clone(),
+						local_fee_dot: group.local_fee_dot,
+						nonce: group.nonce,
+						tip_dot: group.tip_dot,
+						controllers: None,
+						raw_xcm: None,
+						entities: vec![],
+						xc20_address: None,
+					};
+					rows.push(outgoing_row(&transfer, &timestamp, &local_chain));
+				},
+			XcmTransfer::RoundTrip(_) |
+			XcmTransfer::StatusUpdate(_) |
+			XcmTransfer::Anomaly(_) |
+			XcmTransfer::FailedMessage(_) => (),
+		}
+	}
+	rows.into_iter()
+		.map(|row| row.iter().map(|field| escape(field)).collect::<Vec<_>>().join(","))
+		.collect()
+}
+
+fn outgoing_row(transfer: &XcmOutgoingTransfer, timestamp: &str, local_chain: &str) -> Vec<String> {
+	vec![
+		transfer.transfer_group_id.clone(),
+		transfer.message_id.clone().unwrap_or_default(),
+		transfer.block_number.to_string(),
+		timestamp.to_owned(),
+		"outgoing".to_owned(),
+		transfer.sender.clone(),
+		transfer.sender_public_key.clone().unwrap_or_default(),
+		local_chain.to_owned(),
+		transfer.beneficiary.clone(),
+		transfer.beneficiary_public_key.clone(),
+		label(&transfer.destination_chain),
+		transfer.asset.clone(),
+		crate::amount::decimal_string(transfer.amount, transfer.exact_amount.as_deref()),
+		label(&transfer.transfer_type),
+		match &transfer.outcome {
+			Some(ExecutionOutcome::Complete { .. }) => "Complete",
+			Some(ExecutionOutcome::Incomplete { .. }) => "Incomplete",
+			Some(ExecutionOutcome::Error { .. }) => "Error",
+			None => "",
+		}
+		.to_owned(),
+	]
+}
+
+// Enums without data are rendered as their name, the remaining values as their JSON representation.
+fn label<T: Serialize>(value: &T) -> String {
+	match serde_json::to_value(value) {
+		Ok(serde_json::Value::String(name)) => name,
+		Ok(value) => value.to_string(),
+		Err(_) => String::new(),
+	}
+}
+
+// Fields containing commas, quotes or line breaks are quoted, doubling their quotes.
+fn escape(field: &str) -> String {
+	if field.contains([',', '"', '\n', '\r']) {
+		format!("\"{}\"", field.replace('"', "\"\""))
+	} else {
+		field.to_owned()
+	}
+}
+
+// Splits a CSV export back into its rows and fields, unquoting them. Quoted fields may hold line
+// breaks, so the rows can't be split by line.
+pub(crate) fn parse_rows(content: &str) -> Result<Vec<Vec<String>>, String> {
+	let mut rows = vec![];
+	let mut row = vec![];
+	let mut field = String::new();
+	let mut quoted = false;
+	let mut chars = content.chars().peekable();
+	while let Some(c) = chars.next() {
+		match (quoted, c) {
+			(true, '"') if chars.peek() == Some(&'"') => {
+				chars.next();
+				field.push('"');
+			},
+			(true, '"') => quoted = false,
+			(true, c) => field.push(c),
+			(false, '"') if field.is_empty() => quoted = true,
+			(false, ',') => row.push(std::mem::take(&mut field)),
+			(false, '\r') => (),
+			(false, '\n') => {
+				row.push(std::mem::take(&mut field));
+				rows.push(std::mem::take(&mut row));
+			},
+			(false, c) => field.push(c),
+		}
+	}
+	if quoted {
+		return Err("unterminated quoted field".to_owned());
+	}
+	if !field.is_empty() || !row.is_empty() {
+		row.push(field);
+		rows.push(row);
+	}
+	Ok(rows)
+}
+
+// Renders a timestamp in milliseconds as an ISO 8601 UTC date, converting the days since the epoch
+// to a civil date as described in http://howardhinnant.github.io/date_algorithms.html.
+fn format_timestamp(timestamp: u64) -> String {
+	let (days, millis) = (timestamp / 86_400_000, timestamp % 86_400_000);
+	let z = days as i64 + 719_468;
+	let era = z / 146_097;
+	let day_of_era = z - era * 146_097;
+	let year_of_era =
+		(day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+	let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+	let shifted_month = (5 * day_of_year + 2) / 153;
+	let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+	let month = if shifted_month < 10 { shifted_month + 3 } else { shifted_month - 9 };
+	let year = year_of_era + era * 400 + i64::from(month <= 2);
+	format!(
+		"{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+		year,
+		month,
+		day,
+		millis / 3_600_000,
+		millis / 60_000 % 60,
+		millis / 1000 % 60,
+		millis % 1000
+	)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		incoming_parser::XcmIncomingTransfer,
+		outgoing_parser::{DestinationChain, WeightLimit},
+		types::TransferType,
+	};
+
+	#[test]
+	fn format_timestamp_renders_iso_8601_dates() {
+		assert_eq!(format_timestamp(0), "1970-01-01T00:00:00.000Z");
+		assert_eq!(format_timestamp(951_782_400_000), "2000-02-29T00:00:00.000Z");
+		assert_eq!(format_timestamp(1_747_063_524_123), "2025-05-12T15:25:24.123Z");
+	}
+
+	#[test]
+	fn render_rows_outputs_a_row_per_transfer() {
+		let transfers = vec![
+			XcmTransfer::ReceivedTransfer(XcmIncomingTransfer {
+				block_number: 10,
+				origin_chain: OriginChain::PolkadotParachain(2034),
+				message_id: Some("0xcd".to_owned()),
+				beneficiary: "14E5nqKAp3oAJcmzgZhUD2RcptBeUBScxKHgJKU4HPNcKVf3".to_owned(),
+				beneficiary_public_key: "0x01".to_owned(),
+				asset: "USDT".to_owned(),
+				asset_id: None,
+				amount: 1.5,
+				exact_amount: None,
+				transfer_type: TransferType::Reserve,
+				fee_swap: None,
+				entities: vec![],
+				dust_lost: None,
+				trapped_assets_hash: None,
+				new_account: false,
+				xc20_address: None,
+			}),
+			XcmTransfer::SentTransfer(XcmOutgoingTransfer {
+				block_number: 11,
+				transfer_group_id: "11-2".to_owned(),
+				origin: None,
+				destination_chain: DestinationChain::Polkadot,
+				sender: "Alice, \"the sender\"".to_owned(),
+				sender_public_key: Some("0x02".to_owned()),
+				beneficiary: "Bob".to_owned(),
+				beneficiary_public_key: "0x03".to_owned(),
+				asset: "DOT".to_owned(),
+				asset_id: None,
+				amount: 2.0,
+				exact_amount: Some("2.000000000000000001".to_owned()),
+				transfer_type: TransferType::Teleport,
+				fee_asset_item: 0,
+				pays_fees: true,
+				weight_limit: WeightLimit::Unlimited,
+				burned_amount: None,
+				outcome: Some(ExecutionOutcome::Error { error: "Barrier".to_owned() }),
+				message_id: Some("0xab".to_owned()),
+				trapped_assets_hash: None,
+				local_fee_dot: None,
+				nonce: None,
+				tip_dot: None,
+				controllers: None,
+				raw_xcm: None,
+				entities: vec![],
+				xc20_address: None,
+			}),
+		];
+		assert_eq!(
+			render_rows(&transfers, Some(0)),
+			vec![
+				",0xcd,10,1970-01-01T00:00:00.000Z,incoming,,,\"{\"\"PolkadotParachain\"\":2034}\",14E5nqKAp3oAJcmzgZhUD2RcptBeUBScxKHgJKU4HPNcKVf3,0x01,PolkadotAssetHub,USDT,1.5,Reserve,Complete",
+				"11-2,0xab,11,1970-01-01T00:00:00.000Z,outgoing,\"Alice, \"\"the sender\"\"\",0x02,PolkadotAssetHub,Bob,0x03,Polkadot,DOT,2.000000000000000001,Teleport,Error",
+			]
+		);
+		assert_eq!(HEADER.split(',').count(), 15);
+	}
+
+	#[test]
+	fn parse_rows_unquotes_the_fields() {
+		let content = "a,\"b, \"\"c\"\"\",\r\n\"d\ne\",,f\n";
+		assert_eq!(
+			parse_rows(content).unwrap(),
+			vec![
+				vec!["a".to_owned(), "b, \"c\"".to_owned(), String::new()],
+				vec!["d\ne".to_owned(), String::new(), "f".to_owned()],
+			]
+		);
+		assert!(parse_rows("a,\"b").is_err());
+	}
+}
@@ -0,0 +1,138 @@
+use crate::{
+	Error,
+	asset_hub::runtime_types::{cumulus_pallet_xcmp_queue::OutboundState, xcm::VersionedXcm},
+	state_proofs::BlockStorage,
+	types::BlockNumber,
+};
+use serde::Serialize;
+use subxt::{OnlineClient, PolkadotConfig, blocks::Block, ext::codec::Decode};
+
+// The first byte of an outbound XCMP page is the format of its content. Only pages made of
+// concatenated versioned XCMs can be split into messages.
+pub(crate) const CONCATENATED_VERSIONED_XCM_FORMAT: u8 = 0;
+// The delivery fee factor is a FixedU128, which uses 18 decimals.
+const FIXED_U128_DECIMALS: u8 = 18;
+
+// The state of the outbound XCMP channel to a sibling parachain. Messages wait in the queue until
+// they're picked by the relay chain, so growing queues, suspended channels and increasing delivery
+// fee factors explain why transfers take longer to be delivered.
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub(crate) struct ChannelCongestion {
+	pub(crate) block_number: BlockNumber,
+	pub(crate) para_id: u32,
+	pub(crate) suspended: bool,
+	pub(crate) signals_exist: bool,
+	pub(crate) queued_pages: u16,
+	pub(crate) queued_bytes: usize,
+	// Not present if some queued page cannot be split into messages
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub(crate) queued_messages: Option<u32>,
+	pub(crate) delivery_fee_factor: f64,
+}
+
+// Reads the outbound XCMP channels of AssetHub at a certain block, only keeping those which may be
+// delaying messages: channels with queued pages or suspended.
+pub(crate) async fn get_channel_congestion_at_block(
+	block: &Block<PolkadotConfig, OnlineClient<PolkadotConfig>>,
+) -> Result<Vec<ChannelCongestion>, Error> {
+	let storage = BlockStorage::at(block).await?;
+	let channels = storage
+		.fetch_or_default(&crate::asset_hub::storage().xcmp_queue().outbound_xcmp_status())
+		.await?;
+
+	let mut output = vec![];
+	for channel in channels.0 {
+		let suspended = matches!(channel.state, OutboundState::Suspended);
+		let queued_pages = channel.last_index.saturating_sub(channel.first_index);
+		if queued_pages == 0 && !suspended {
+			continue;
+		}
+
+		let mut queued_bytes = 0;
+		let mut queued_messages = Some(0);
+		for page_index in channel.first_index..channel.last_index {
+			let page = storage
+				.fetch_or_default(
+					&crate::asset_hub::storage()
+						.xcmp_queue()
+						.outbound_xcmp_messages(&channel.recipient, page_index),
+				)
+				.await?;
+			queued_bytes += page.0.len();
+			queued_messages = queued_messages
+				.zip(count_page_messages(&page.0))
+				.map(|(queued_messages, page_messages)| queued_messages + page_messages);
+		}
+
+		let delivery_fee_factor = storage
+			.fetch_or_default(
+				&crate::asset_hub::storage().xcmp_queue().delivery_fee_factor(&channel.recipient),
+			)
+			.await?;
+
+		output.push(ChannelCongestion {
+			block_number: block.number(),
+			para_id: channel.recipient.0,
+			suspended,
+			signals_exist: channel.signals_exist,
+			queued_pages,
+			queued_bytes,
+			queued_messages,
+			delivery_fee_factor: crate::amount::to_decimal_f64(
+				delivery_fee_factor.0,
+				FIXED_U128_DECIMALS,
+			),
+		});
+	}
+	Ok(output)
+}
+
+// Counts the messages contained in an outbound XCMP page, which are SCALE encoded one after
+// another after the format byte.
+fn count_page_messages(page: &[u8]) -> Option<u32> {
+	let (&CONCATENATED_VERSIONED_XCM_FORMAT, mut messages) = page.split_first()? else {
+		return None;
+	};
+	let mut count = 0;
+	while !messages.is_empty() {
+		VersionedXcm::decode(&mut messages).ok()?;
+		count += 1;
+	}
+	Some(count)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		asset_hub::runtime_types::staging_xcm::v5::{Instruction, Xcm},
+		chain_spec::{Chain, ChainSpec},
+	};
+	use subxt::ext::codec::Encode;
+
+	#[test]
+	fn count_page_messages_splits_concatenated_messages() {
+		let message = VersionedXcm::V5(Xcm(vec![Instruction::ClearOrigin])).encode();
+		let mut page = vec![CONCATENATED_VERSIONED_XCM_FORMAT];
+		page.extend(&message);
+		page.extend(&message);
+		assert_eq!(count_page_messages(&page), Some(2));
+
+		// A truncated message cannot be decoded
+		page.pop();
+		assert_eq!(count_page_messages(&page), None);
+		// Neither other formats
+		assert_eq!(count_page_messages(&[1, 0]), None);
+		assert_eq!(count_page_messages(&[]), None);
+	}
+
+	#[tokio::test]
+	async fn get_channel_congestion_at_block_test() {
+		let api = OnlineClient::<PolkadotConfig>::from_url(Chain::RPC_ENDPOINT).await.unwrap();
+		let block = api.blocks().at_latest().await.unwrap();
+
+		let channels = get_channel_congestion_at_block(&block).await.unwrap();
+		assert!(channels.iter().all(|channel| channel.queued_pages > 0 || channel.suspended));
+		assert!(channels.iter().all(|channel| channel.delivery_fee_factor >= 1.0));
+	}
+}
@@ -0,0 +1,166 @@
+use crate::{
+	Error,
+	asset_hub::multisig::calls::types::{AsMulti, AsMultiThreshold1},
+	state_proofs::BlockStorage,
+	types::{BlockHash, XcmTransfer},
+};
+use serde::{Deserialize, Serialize};
+use sp_core::hashing::blake2_256;
+use std::collections::HashMap;
+use subxt::{
+	OnlineClient, PolkadotConfig,
+	config::polkadot::AccountId32,
+	ext::codec::{Decode, Encode},
+	utils::MultiAddress,
+};
+
+// The prefix hashed together with the signatories and the threshold to derive a multisig account,
+// as done by pallet_multisig.
+const MULTISIG_ACCOUNT_PREFIX: &[u8; 16] = b"modlpy/utilisuba";
+
+// The real controllers of a sender which isn't a regular account, so compliance can attribute the
+// transfers to them.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+#[cfg_attr(feature = "typescript", derive(ts_rs::TS))]
+pub struct SenderControllers {
+	// The accounts allowed to act on behalf of the sender, if it's a proxied account (eg, a pure
+	// proxy)
+	#[serde(default, skip_serializing_if = "Vec::is_empty")]
+	#[cfg_attr(feature = "typescript", ts(as = "Option<Vec<String>>", optional))]
+	pub proxy_delegates: Vec<String>,
+	// The multisig behind the sender, if the extrinsic was approved by the multisig being either
+	// the sender or one of its delegates
+	#[serde(skip_serializing_if = "Option::is_none")]
+	#[cfg_attr(feature = "typescript", ts(optional))]
+	pub multisig: Option<Multisig>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+#[cfg_attr(feature = "typescript", derive(ts_rs::TS))]
+pub struct Multisig {
+	pub threshold: u16,
+	pub signatories: Vec<String>,
+}
+
+// Derives the account of a multisig from its signatories, sorted, and its threshold.
+pub(crate) fn multisig_account_id(signatories: &[AccountId32], threshold: u16) -> AccountId32 {
+	let mut signatories = signatories.to_vec();
+	signatories.sort();
+	AccountId32(blake2_256(&(MULTISIG_ACCOUNT_PREFIX, signatories, threshold).encode()))
+}
+
+// Resolves the controllers of the senders of the transfers found in a block, reading the proxies
+// of each sender from the storage and the signatories of the multisig calls from the extrinsics.
+// Only transfers sent by signed extrinsics from 32 bytes accounts are resolved.
+pub(crate) async fn resolve_controllers(
+	api: &OnlineClient<PolkadotConfig>,
+	block_hash: BlockHash,
+	transfers: &mut [XcmTransfer],
+) -> Result<(), Error> {
+	let block = api.blocks().at(block_hash).await?;
+	let extrinsics = block.extrinsics().await?;
+	let storage = BlockStorage::at(&block).await?;
+	let mut resolved = HashMap::<(String, String), Option<SenderControllers>>::new();
+
+	for transfer in transfers {
+		let XcmTransfer::SentTransfer(transfer) = transfer else {
+			continue;
+		};
+		let (None, Some(sender_public_key)) = (&transfer.origin, &transfer.sender_public_key)
+		else {
+			continue;
+		};
+		let Some(sender) = hex::decode(sender_public_key.trim_start_matches("0x"))
+			.ok()
+			.and_then(|bytes| <[u8; 32]>::try_from(bytes).ok())
+			.map(AccountId32)
+		else {
+			continue;
+		};
+		// The transfer group id of the transfers sent by extrinsics is `<block>-<extrinsic_index>`
+		let Some(extrinsic_index) = transfer
+			.transfer_group_id
+			.split_once('-')
+			.and_then(|(_, index)| index.parse::<u32>().ok())
+		else {
+			continue;
+		};
+
+		let key = (transfer.transfer_group_id.clone(), sender_public_key.clone());
+		if !resolved.contains_key(&key) {
+			let proxy_delegates = storage
+				.fetch_or_default(&crate::asset_hub::storage().proxy().proxies(&sender))
+				.await?
+				.0
+				.0
+				.into_iter()
+				.map(|proxy| proxy.delegate)
+				.collect::<Vec<_>>();
+			let multisig = extrinsics
+				.iter()
+				.find(|extrinsic| extrinsic.index() == extrinsic_index)
+				.and_then(|extrinsic| {
+					let signer =
+						extrinsic.address_bytes().and_then(|bytes| {
+							match MultiAddress::<AccountId32, ()>::decode(&mut &bytes[..]) {
+								Ok(MultiAddress::Id(account_id)) => Some(account_id),
+								_ => None,
+							}
+						})?;
+					let (threshold, other_signatories) = match (
+						extrinsic.as_extrinsic::<AsMulti>(),
+						extrinsic.as_extrinsic::<AsMultiThreshold1>(),
+					) {
+						(Ok(Some(call)), _) => (call.threshold, call.other_signatories),
+						(_, Ok(Some(call))) => (1, call.other_signatories),
+						_ => return None,
+					};
+					let mut signatories = other_signatories;
+					signatories.push(signer);
+					signatories.sort();
+					Some((threshold, signatories))
+				})
+				.filter(|(threshold, signatories)| {
+					let multisig = multisig_account_id(signatories, *threshold);
+					multisig == sender || proxy_delegates.contains(&multisig)
+				})
+				.map(|(threshold, signatories)| Multisig {
+					threshold,
+					signatories: signatories
+						.iter()
+						.map(crate::helpers::convert_account_id_to_address)
+						.collect(),
+				});
+			let controllers =
+				(!proxy_delegates.is_empty() || multisig.is_some()).then(|| SenderControllers {
+					proxy_delegates: proxy_delegates
+						.iter()
+						.map(crate::helpers::convert_account_id_to_address)
+						.collect(),
+					multisig,
+				});
+			resolved.insert(key.clone(), controllers);
+		}
+		transfer.controllers = resolved[&key].clone();
+	}
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn multisig_account_id_sorts_the_signatories() {
+		let (alice, bob) = (AccountId32([1; 32]), AccountId32([2; 32]));
+		let multisig = multisig_account_id(&[alice.clone(), bob.clone()], 2);
+		assert_eq!(multisig, multisig_account_id(&[bob.clone(), alice.clone()], 2));
+		assert_ne!(multisig, multisig_account_id(&[alice.clone(), bob.clone()], 1));
+		let mut preimage = MULTISIG_ACCOUNT_PREFIX.to_vec();
+		preimage.push(8);
+		preimage.extend([1; 32]);
+		preimage.extend([2; 32]);
+		preimage.extend(2u16.to_le_bytes());
+		assert_eq!(multisig, AccountId32(blake2_256(&preimage)));
+	}
+}
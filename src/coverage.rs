@@ -0,0 +1,104 @@
+use crate::types::BlockNumber;
+use serde::Serialize;
+use std::{collections::BTreeMap, sync::Mutex};
+
+// How the parsers handled an XCM related extrinsic, message or hook dispatch. The reasons are
+// short snake case identifiers, so the report can be aggregated by other tools.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum ParseOutcome {
+	Parsed,
+	PartiallyParsed(String),
+	Skipped(String),
+}
+
+#[derive(Debug, Default, Serialize, Clone, PartialEq)]
+pub(crate) struct CoverageCounts {
+	pub(crate) fully_parsed: u64,
+	pub(crate) partially_parsed: BTreeMap<String, u64>,
+	pub(crate) skipped: BTreeMap<String, u64>,
+}
+
+impl CoverageCounts {
+	fn record(&mut self, outcome: ParseOutcome) {
+		match outcome {
+			ParseOutcome::Parsed => self.fully_parsed += 1,
+			ParseOutcome::PartiallyParsed(reason) =>
+				*self.partially_parsed.entry(reason).or_default() += 1,
+			ParseOutcome::Skipped(reason) => *self.skipped.entry(reason).or_default() += 1,
+		}
+	}
+}
+
+// The parser coverage over a range of blocks:
+// - extrinsics: the PolkadotXcm extrinsics, and any other extrinsic sending an XCM message.
+// - messages: the XCM messages processed by MessageQueue.
+// - dispatches: the XCM messages sent by governance or the runtime hooks.
+#[derive(Debug, Default, Serialize, Clone, PartialEq)]
+pub(crate) struct CoverageReport {
+	pub(crate) from_block: BlockNumber,
+	pub(crate) to_block: BlockNumber,
+	pub(crate) blocks_scanned: u64,
+	pub(crate) extrinsics: CoverageCounts,
+	pub(crate) messages: CoverageCounts,
+	pub(crate) dispatches: CoverageCounts,
+}
+
+// The report being collected, only while the coverage command runs, so the parsers don't need to
+// return their outcomes to every caller.
+static COVERAGE: Mutex<Option<CoverageReport>> = Mutex::new(None);
+
+pub(crate) fn start_coverage(from_block: BlockNumber, to_block: BlockNumber) {
+	if let Ok(mut coverage) = COVERAGE.lock() {
+		*coverage = Some(CoverageReport { from_block, to_block, ..Default::default() });
+	}
+}
+
+pub(crate) fn finish_coverage() -> Option<CoverageReport> {
+	COVERAGE.lock().ok().and_then(|mut coverage| coverage.take())
+}
+
+pub(crate) fn record_block() {
+	with_report(|report| report.blocks_scanned += 1);
+}
+
+pub(crate) fn record_extrinsic(outcome: ParseOutcome) {
+	with_report(|report| report.extrinsics.record(outcome));
+}
+
+pub(crate) fn record_message(outcome: ParseOutcome) {
+	with_report(|report| report.messages.record(outcome));
+}
+
+pub(crate) fn record_dispatch(outcome: ParseOutcome) {
+	with_report(|report| report.dispatches.record(outcome));
+}
+
+fn with_report(f: impl FnOnce(&mut CoverageReport)) {
+	if let Ok(mut coverage) = COVERAGE.lock() {
+		if let Some(report) = coverage.as_mut() {
+			f(report);
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn coverage_counts_group_outcomes_by_reason() {
+		let mut counts = CoverageCounts::default();
+		counts.record(ParseOutcome::Parsed);
+		counts.record(ParseOutcome::Parsed);
+		counts.record(ParseOutcome::PartiallyParsed("recovered_from_events".to_owned()));
+		counts.record(ParseOutcome::Skipped("extrinsic_failed".to_owned()));
+		counts.record(ParseOutcome::Skipped("extrinsic_failed".to_owned()));
+		assert_eq!(counts.fully_parsed, 2);
+		assert_eq!(counts.partially_parsed.get("recovered_from_events"), Some(&1));
+		assert_eq!(counts.skipped.get("extrinsic_failed"), Some(&2));
+		assert_eq!(
+			serde_json::to_string(&counts).unwrap(),
+			r#"{"fully_parsed":2,"partially_parsed":{"recovered_from_events":1},"skipped":{"extrinsic_failed":2}}"#
+		);
+	}
+}
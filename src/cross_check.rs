@@ -0,0 +1,118 @@
+use crate::{
+	Error,
+	types::{BlockHash, ConnectionSettings, XcmTransfer},
+};
+use subxt::{OnlineClient, PolkadotConfig};
+
+// The data of a block as returned by an endpoint, and the transfers parsed from it.
+#[derive(Debug, Default, PartialEq)]
+struct BlockData {
+	events: Vec<u8>,
+	extrinsics: Vec<Vec<u8>>,
+	transfers: Vec<XcmTransfer>,
+}
+
+// Parses every block again against a second endpoint, reporting any divergence with the primary
+// one, so high-stakes consumers aren't exposed to a single compromised or buggy RPC node.
+pub(crate) struct CrossChecker {
+	api: OnlineClient<PolkadotConfig>,
+}
+
+async fn block_data(
+	api: &OnlineClient<PolkadotConfig>,
+	block_hash: BlockHash,
+	transfers: Option<&[XcmTransfer]>,
+) -> Result<BlockData, Error> {
+	let block = api.blocks().at(block_hash).await?;
+	let events = block.events().await?.bytes().to_vec();
+	let extrinsics = block
+		.extrinsics()
+		.await?
+		.iter()
+		.map(|extrinsic| extrinsic.bytes().to_vec())
+		.collect();
+	let transfers = match transfers {
+		Some(transfers) => transfers.to_vec(),
+		None => crate::helpers::get_all_transfers_at_block_hash(api, block_hash).await?,
+	};
+	Ok(BlockData { events, extrinsics, transfers })
+}
+
+impl CrossChecker {
+	pub(crate) async fn connect(url: &str, settings: &ConnectionSettings) -> Result<Self, Error> {
+		let rpc_client = crate::helpers::build_rpc_client(url, settings).await?;
+		Ok(Self { api: OnlineClient::<PolkadotConfig>::from_rpc_client(rpc_client).await? })
+	}
+
+	// Compares a block and the transfers parsed from it by the primary endpoint with the ones of
+	// the second endpoint, returning the divergences found.
+	pub(crate) async fn check(
+		&self,
+		primary_api: &OnlineClient<PolkadotConfig>,
+		block_hash: BlockHash,
+		transfers: &[XcmTransfer],
+	) -> Result<Vec<String>, Error> {
+		let primary = block_data(primary_api, block_hash, Some(transfers)).await?;
+		Ok(match block_data(&self.api, block_hash, None).await {
+			Ok(secondary) => divergences(&primary, &secondary),
+			Err(e) => vec![format!("the cross-check endpoint failed to parse the block: {}", e)],
+		}
+		.into_iter()
+		.map(|divergence| {
+			format!("Block {:?} diverges between endpoints: {}", block_hash, divergence)
+		})
+		.collect())
+	}
+}
+
+fn divergences(primary: &BlockData, secondary: &BlockData) -> Vec<String> {
+	let mut divergences = vec![];
+	if primary.events != secondary.events {
+		divergences.push("the events differ".to_owned());
+	}
+	if primary.extrinsics.len() != secondary.extrinsics.len() {
+		divergences.push(format!(
+			"the block has {} extrinsics according to the primary endpoint and {} according to the cross-check one",
+			primary.extrinsics.len(),
+			secondary.extrinsics.len()
+		));
+	} else {
+		primary
+			.extrinsics
+			.iter()
+			.zip(&secondary.extrinsics)
+			.enumerate()
+			.filter(|(_, (primary, secondary))| primary != secondary)
+			.for_each(|(index, _)| divergences.push(format!("the extrinsic {} differs", index)));
+	}
+	if primary.transfers != secondary.transfers {
+		divergences.push(format!(
+			"{} transfers were parsed from the primary endpoint and {} from the cross-check one, which differ",
+			primary.transfers.len(),
+			secondary.transfers.len()
+		));
+	}
+	divergences
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn divergences_between_endpoints_are_reported() {
+		let primary =
+			BlockData { events: vec![4, 1], extrinsics: vec![vec![1], vec![2]], transfers: vec![] };
+		assert_eq!(divergences(&primary, &primary), Vec::<String>::new());
+
+		let secondary =
+			BlockData { events: vec![4, 2], extrinsics: vec![vec![1], vec![3]], transfers: vec![] };
+		assert_eq!(
+			divergences(&primary, &secondary),
+			vec!["the events differ".to_owned(), "the extrinsic 1 differs".to_owned()]
+		);
+
+		let secondary = BlockData { extrinsics: vec![vec![1]], ..Default::default() };
+		assert_eq!(divergences(&primary, &secondary).len(), 2);
+	}
+}
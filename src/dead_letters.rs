@@ -0,0 +1,131 @@
+use crate::types::{BlockHash, BlockNumber};
+use serde::{Deserialize, Serialize};
+use std::{
+	fs::{self, OpenOptions},
+	io::Write,
+	path::Path,
+	time::{SystemTime, UNIX_EPOCH},
+};
+
+// Where the processing of a block failed.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum FailureStage {
+	// The transfers of the block couldn't be parsed
+	Parsing,
+	// The block kept timing out, see `--block-timeout`
+	Timeout,
+	// The transfers couldn't be delivered to the named sink
+	Delivery(String),
+}
+
+// A block whose processing failed, kept in the dead-letter file with the context of the failure
+// so no data is silently lost in long running subscriptions. The file holds a JSON record per
+// line, and `retry-failed` processes its blocks again.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct DeadLetter {
+	pub(crate) block_hash: BlockHash,
+	pub(crate) block_number: BlockNumber,
+	pub(crate) stage: FailureStage,
+	pub(crate) error: String,
+	// When the failure happened, in seconds since the Unix epoch
+	pub(crate) failed_at: u64,
+}
+
+impl DeadLetter {
+	pub(crate) fn new(
+		block_hash: BlockHash,
+		block_number: BlockNumber,
+		stage: FailureStage,
+		error: String,
+	) -> Self {
+		let failed_at = SystemTime::now()
+			.duration_since(UNIX_EPOCH)
+			.map(|now| now.as_secs())
+			.unwrap_or_default();
+		Self { block_hash, block_number, stage, error, failed_at }
+	}
+}
+
+fn to_line(dead_letter: &DeadLetter) -> Result<String, String> {
+	serde_json::to_string(dead_letter)
+		.map(|json| json + "\n")
+		.map_err(|e| format!("Failed to serialize a dead letter: {}", e))
+}
+
+// Appends a failed block to the dead-letter file. A single write per block, so the lines of
+// concurrent blocks don't interleave.
+pub(crate) fn append_dead_letter(path: &Path, dead_letter: &DeadLetter) -> Result<(), String> {
+	let line = to_line(dead_letter)?;
+	OpenOptions::new()
+		.create(true)
+		.append(true)
+		.open(path)
+		.and_then(|mut file| file.write_all(line.as_bytes()))
+		.map_err(|e| format!("Failed to write to {}: {}", path.display(), e))
+}
+
+// Reads the dead-letter file. A missing file means no block failed.
+pub(crate) fn read_dead_letters(path: &Path) -> Result<Vec<DeadLetter>, String> {
+	let content = match fs::read_to_string(path) {
+		Ok(content) => content,
+		Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+		Err(e) => return Err(format!("Failed to read the dead-letter file: {}", e)),
+	};
+	content
+		.lines()
+		.filter(|line| !line.trim().is_empty())
+		.enumerate()
+		.map(|(index, line)| {
+			serde_json::from_str(line)
+				.map_err(|e| format!("Invalid dead letter at line {}: {}", index + 1, e))
+		})
+		.collect()
+}
+
+// Replaces the dead-letter file with the blocks that are still failing. The file is replaced
+// atomically, so a crash never loses the failed blocks.
+pub(crate) fn write_dead_letters(path: &Path, dead_letters: &[DeadLetter]) -> Result<(), String> {
+	let content = dead_letters.iter().map(to_line).collect::<Result<String, String>>()?;
+	let mut temporary_path = path.as_os_str().to_owned();
+	temporary_path.push(".tmp");
+	fs::write(&temporary_path, content)
+		.and_then(|_| fs::rename(&temporary_path, path))
+		.map_err(|e| format!("Failed to write the dead-letter file: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn dead_letters_are_appended_and_replaced() {
+		let path = std::env::temp_dir()
+			.join(format!("xcm_indexer_dead_letters_{}.jsonl", std::process::id()));
+		let _ = fs::remove_file(&path);
+		assert_eq!(read_dead_letters(&path), Ok(vec![]));
+
+		let parsing = DeadLetter::new(
+			BlockHash::repeat_byte(1),
+			1,
+			FailureStage::Parsing,
+			"Cannot connect to the node".to_owned(),
+		);
+		let delivery = DeadLetter::new(
+			BlockHash::repeat_byte(2),
+			2,
+			FailureStage::Delivery("mqtt".to_owned()),
+			"Connection refused".to_owned(),
+		);
+		append_dead_letter(&path, &parsing).unwrap();
+		append_dead_letter(&path, &delivery).unwrap();
+		assert_eq!(read_dead_letters(&path), Ok(vec![parsing.clone(), delivery]));
+		assert!(fs::read_to_string(&path).unwrap().contains(r#""stage":{"delivery":"mqtt"}"#));
+
+		write_dead_letters(&path, &[parsing.clone()]).unwrap();
+		let dead_letters = read_dead_letters(&path);
+		fs::remove_file(&path).unwrap();
+		assert_eq!(dead_letters, Ok(vec![parsing]));
+	}
+}
@@ -0,0 +1,221 @@
+use crate::types::XcmTransfer;
+use serde::{Deserialize, Serialize};
+use sp_core::crypto::{AccountId32, Ss58Codec};
+use std::{
+	collections::HashMap,
+	fs,
+	path::Path,
+	sync::OnceLock,
+	time::{Duration, SystemTime},
+};
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "typescript", derive(ts_rs::TS))]
+#[serde(rename_all = "snake_case")]
+pub enum EntityKind {
+	Exchange,
+	Bridge,
+	Treasury,
+	#[serde(other)]
+	Other,
+}
+
+// An entry of the labels dataset. The address may be an SS58 address, whatever its prefix, or the
+// hex encoded public key of the account.
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+pub(crate) struct EntityLabel {
+	pub(crate) address: String,
+	pub(crate) name: String,
+	pub(crate) kind: EntityKind,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "typescript", derive(ts_rs::TS))]
+#[serde(rename_all = "snake_case")]
+pub enum EntityRole {
+	Sender,
+	Beneficiary,
+}
+
+// A known entity touched by a transfer.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+#[cfg_attr(feature = "typescript", derive(ts_rs::TS))]
+pub struct EntityTag {
+	pub role: EntityRole,
+	pub name: String,
+	pub kind: EntityKind,
+}
+
+// The known entities, keyed by the hex encoded public key of their account, as rendered in the
+// transfers.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub(crate) struct EntityLabels {
+	entities: HashMap<String, (String, EntityKind)>,
+}
+
+// The labels are loaded once when the CLI starts, so the parsers don't need to carry them around.
+static ENTITY_LABELS: OnceLock<EntityLabels> = OnceLock::new();
+
+pub(crate) fn set_entity_labels(labels: EntityLabels) {
+	let _ = ENTITY_LABELS.set(labels);
+}
+
+impl EntityLabels {
+	// Parses a dataset like `[{ "address": "0x...", "name": "Binance", "kind": "exchange" }]`.
+	pub(crate) fn from_json(content: &str) -> Result<Self, String> {
+		let labels: Vec<EntityLabel> = serde_json::from_str(content)
+			.map_err(|e| format!("Failed to parse the entity labels: {}", e))?;
+		let mut entities = HashMap::new();
+		for label in labels {
+			let public_key = if label.address.starts_with("0x") {
+				hex::decode(&label.address[2..]).ok().map(|_| label.address.to_lowercase())
+			} else {
+				AccountId32::from_ss58check(&label.address)
+					.ok()
+					.map(|account_id| crate::helpers::to_hex_string(account_id.as_ref()))
+			};
+			let Some(public_key) = public_key else {
+				return Err(format!("Invalid address in the entity labels: {}", label.address));
+			};
+			entities.insert(public_key, (label.name, label.kind));
+		}
+		Ok(Self { entities })
+	}
+
+	// Loads the labels from a local file or, with the `entity-labels` feature, from an HTTP(S) URL.
+	// Remote datasets are cached in `cache`, which is used instead of fetching them again while
+	// it's younger than `max_age`, or if fetching them fails.
+	pub(crate) async fn load(
+		source: &str,
+		cache: Option<&Path>,
+		max_age: Duration,
+	) -> Result<(Self, Option<String>), String> {
+		if !source.starts_with("http://") && !source.starts_with("https://") {
+			let content = fs::read_to_string(source)
+				.map_err(|e| format!("Failed to read the entity labels file: {}", e))?;
+			return Ok((Self::from_json(&content)?, None));
+		}
+		let cached = cache.and_then(|path| {
+			let age = fs::metadata(path).and_then(|metadata| metadata.modified()).ok()?;
+			Some((fs::read_to_string(path).ok()?, SystemTime::now().duration_since(age).ok()?))
+		});
+		if let Some((content, _)) = cached.as_ref().filter(|(_, age)| *age < max_age) {
+			return Ok((Self::from_json(content)?, None));
+		}
+		match fetch(source)
+			.await
+			.and_then(|content| Ok((Self::from_json(&content)?, content)))
+		{
+			Ok((labels, content)) => {
+				let warning = cache.and_then(|path| {
+					fs::write(path, content)
+						.err()
+						.map(|e| format!("Failed to cache the entity labels: {}", e))
+				});
+				Ok((labels, warning))
+			},
+			Err(e) => match cached {
+				Some((content, _)) => Ok((
+					Self::from_json(&content)?,
+					Some(format!("{}. Using the cached entity labels", e)),
+				)),
+				None => Err(e),
+			},
+		}
+	}
+
+	fn tag(&self, role: EntityRole, public_key: &str) -> Option<EntityTag> {
+		self.entities.get(&public_key.to_lowercase()).map(|(name, kind)| EntityTag {
+			role,
+			name: name.clone(),
+			kind: *kind,
+		})
+	}
+}
+
+#[cfg(feature = "entity-labels")]
+async fn fetch(url: &str) -> Result<String, String> {
+	let error = |e: reqwest::Error| format!("Failed to fetch the entity labels: {}", e);
+	reqwest::get(url)
+		.await
+		.and_then(reqwest::Response::error_for_status)
+		.map_err(error)?
+		.text()
+		.await
+		.map_err(error)
+}
+
+#[cfg(not(feature = "entity-labels"))]
+async fn fetch(_url: &str) -> Result<String, String> {
+	Err("Fetching the entity labels from a URL requires the entity-labels feature".to_owned())
+}
+
+// Tags the transfers whose sender or beneficiary is a known entity, if the labels were loaded.
+pub(crate) fn tag_transfers(transfers: &mut [XcmTransfer]) {
+	let Some(labels) = ENTITY_LABELS.get() else {
+		return;
+	};
+	for transfer in transfers {
+		match transfer {
+			XcmTransfer::ReceivedTransfer(transfer) => {
+				transfer.entities = labels
+					.tag(EntityRole::Beneficiary, &transfer.beneficiary_public_key)
+					.into_iter()
+					.collect();
+			},
+			XcmTransfer::SentTransfer(transfer) => {
+				transfer.entities = transfer
+					.sender_public_key
+					.as_deref()
+					.and_then(|public_key| labels.tag(EntityRole::Sender, public_key))
+					.into_iter()
+					.chain(labels.tag(EntityRole::Beneficiary, &transfer.beneficiary_public_key))
+					.collect();
+			},
+			_ => (),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn entity_labels_from_json() {
+		let labels = EntityLabels::from_json(
+			r#"[
+				{ "address": "0x0101010101010101010101010101010101010101010101010101010101010101", "name": "Treasury", "kind": "treasury" },
+				{ "address": "5C4hrfjw9DjXZTzV3MwzrrAr9P1MJhSrvWGWqi1eSuyUpnhM", "name": "Exchange", "kind": "exchange" },
+				{ "address": "0xABABABABABABABABABABABABABABABABABABABAB", "name": "Bridge", "kind": "ethereum_bridge" }
+			]"#,
+		)
+		.unwrap();
+		assert_eq!(
+			labels.tag(
+				EntityRole::Sender,
+				"0x0101010101010101010101010101010101010101010101010101010101010101"
+			),
+			Some(EntityTag {
+				role: EntityRole::Sender,
+				name: "Treasury".to_owned(),
+				kind: EntityKind::Treasury
+			})
+		);
+		// The SS58 address of the zero account
+		let exchange = format!("0x{}", "00".repeat(32));
+		assert_eq!(labels.tag(EntityRole::Beneficiary, &exchange).unwrap().name, "Exchange");
+		assert_eq!(
+			labels
+				.tag(EntityRole::Beneficiary, "0xabababababababababababababababababababab")
+				.unwrap()
+				.kind,
+			EntityKind::Other
+		);
+		assert_eq!(labels.tag(EntityRole::Sender, "0x02"), None);
+		assert!(
+			EntityLabels::from_json(r#"[{ "address": "x", "name": "", "kind": "bridge" }]"#)
+				.is_err()
+		);
+	}
+}
@@ -1,5 +1,12 @@
+use crate::types::{BlockHash, BlockNumber};
 use thiserror::Error;
 
+// Error messages returned by Substrate nodes when the state of a block has been discarded by
+// their pruning strategy. Only the exact messages are matched, as any other error must not be
+// skipped as pruned state.
+const PRUNED_STATE_ERROR_MESSAGES: [&str; 2] =
+	["State already discarded for", "UnknownBlock: State already discarded"];
+
 #[derive(Error, Debug)]
 pub enum Error {
 	#[error(
@@ -10,11 +17,51 @@ pub enum Error {
 	#[error(transparent)]
 	Subxt(#[from] Box<subxt::error::Error>),
 
+	#[cfg(not(target_arch = "wasm32"))]
+	#[error("Cannot connect to the node: {0}")]
+	Connection(#[from] jsonrpsee::core::ClientError),
+
 	#[error("A Xcm message didn't complete successfully.")]
 	UnsuccessfulXcmMessage,
 
 	#[error("It wasn't posible generar el payload from the inputs.")]
 	GeneratePayloadFailed,
+
+	#[error("The runtime API call failed: {0}")]
+	RuntimeApiFailed(String),
+
+	#[error(
+		"The state of block {block:?} has been pruned by the node. Use an archive node endpoint to query historical blocks."
+	)]
+	StatePruned { block: BlockHash },
+
+	#[error("The storage proof of block {block:?} isn't valid: {reason}")]
+	InvalidStateProof { block: BlockHash, reason: String },
+
+	#[error("No matching transfer was received within {seconds} seconds")]
+	WaitTimedOut { seconds: u64 },
+
+	#[error("Block {block} doesn't exist yet")]
+	BlockNotFound { block: BlockNumber },
+
+	#[error("Failed to archive the raw block: {0}")]
+	RawArchive(String),
+
+	#[cfg(feature = "amqp")]
+	#[error("AMQP error: {0}")]
+	Amqp(#[from] lapin::Error),
+
+	#[cfg(feature = "amqp")]
+	#[error("The AMQP broker didn't confirm a published message")]
+	UnconfirmedMessage,
+
+	#[cfg(feature = "bigquery")]
+	#[error("BigQuery error: {0}")]
+	BigQuery(String),
+
+	#[cfg(feature = "email")]
+	#[error("Email error: {0}")]
+	Email(String),
 }
 
 impl From<subxt::error::Error> for Error {
@@ -22,3 +69,53 @@ impl From<subxt::error::Error> for Error {
 		Error::Subxt(Box::new(err))
 	}
 }
+
+impl Error {
+	// Subxt doesn't have a dedicated error for pruned state, so the only way to detect it is
+	// looking at the message returned by the node.
+	pub(crate) fn or_state_pruned(self, block: BlockHash) -> Self {
+		match self {
+			Error::Subxt(ref err)
+				if PRUNED_STATE_ERROR_MESSAGES
+					.iter()
+					.any(|message| err.to_string().contains(message)) =>
+				Error::StatePruned { block },
+			err => err,
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn or_state_pruned_detects_pruned_state() {
+		let block = BlockHash::repeat_byte(1);
+		let err = Error::from(subxt::error::Error::Other(
+			"State already discarded for Hash(0x0101…0101)".to_owned(),
+		));
+		assert!(
+			matches!(err.or_state_pruned(block), Error::StatePruned { block: b } if b == block)
+		);
+		let err = Error::from(subxt::error::Error::Other(
+			"UnknownBlock: State already discarded".to_owned(),
+		));
+		assert!(matches!(err.or_state_pruned(block), Error::StatePruned { .. }));
+	}
+
+	#[test]
+	fn or_state_pruned_keeps_other_errors() {
+		let block = BlockHash::repeat_byte(1);
+		let err = Error::from(subxt::error::Error::Other("Connection reset".to_owned()));
+		assert!(matches!(err.or_state_pruned(block), Error::Subxt(_)));
+		// Errors merely mentioning pruning aren't pruned state
+		let err =
+			Error::from(subxt::error::Error::Other("The pruned trie node is missing".to_owned()));
+		assert!(matches!(err.or_state_pruned(block), Error::Subxt(_)));
+		assert!(matches!(
+			Error::UnsuccessfulXcmMessage.or_state_pruned(block),
+			Error::UnsuccessfulXcmMessage
+		));
+	}
+}
@@ -0,0 +1,140 @@
+use crate::{
+	asset_overrides::AssetOverrides,
+	raw_blocks,
+	types::{BlockNumber, XcmTransfer},
+};
+use serde::{Deserialize, Serialize};
+use std::ffi::{CStr, CString, c_char};
+
+// A block handed through the C API. The storage can't be read, so the metadata of the assets
+// involved is given by the caller, in the format of `--asset-overrides`.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct BlockInput {
+	block_number: BlockNumber,
+	// The SCALE encoded `System::Events` of the block, hex encoded
+	events: String,
+	#[serde(default)]
+	assets: AssetOverrides,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum BlockOutput {
+	Transfers(Vec<XcmTransfer>),
+	Error(String),
+}
+
+/// Parses the XCM transfers of a block from its events, without connecting to a node.
+///
+/// `input` is a NUL terminated JSON object: `{ "block_number": <NUMBER>, "events": "0x<HEX>",
+/// "assets": { ... } }`, where `events` are the SCALE encoded `System::Events` of the block and
+/// `assets` optionally gives the symbol and decimals of the assets involved. The returned string
+/// is a JSON object, either `{ "transfers": [...] }` or `{ "error": "<MESSAGE>" }`, which must be
+/// released with `xcm_indexer_free_string`. Outgoing transfers are rebuilt from the messages sent.
+///
+/// # Safety
+///
+/// `input` must be a valid pointer to a NUL terminated string.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn xcm_indexer_parse_block(input: *const c_char) -> *mut c_char {
+	let output = if input.is_null() {
+		BlockOutput::Error("The input is a null pointer".to_owned())
+	} else {
+		// SAFETY: the caller guarantees that the input is a valid NUL terminated string.
+		let input = unsafe { CStr::from_ptr(input) }.to_string_lossy();
+		// Unwinding across the C boundary aborts the caller's process, so a panic while parsing
+		// is reported as any other error.
+		match std::panic::catch_unwind(|| parse_block(&input)) {
+			Ok(Ok(transfers)) => BlockOutput::Transfers(transfers),
+			Ok(Err(e)) => BlockOutput::Error(e),
+			Err(panic) =>
+				BlockOutput::Error(format!("Parsing panicked: {}", panic_message(&*panic))),
+		}
+	};
+	// The output is JSON, so it never contains NUL bytes
+	let json = serde_json::to_string(&output).unwrap_or_default();
+	CString::new(json).unwrap_or_default().into_raw()
+}
+
+/// Releases a string returned by `xcm_indexer_parse_block`.
+///
+/// # Safety
+///
+/// `output` must be a pointer returned by `xcm_indexer_parse_block` not released yet, or null.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn xcm_indexer_free_string(output: *mut c_char) {
+	if !output.is_null() {
+		// SAFETY: the pointer was created by `CString::into_raw` and wasn't released yet.
+		drop(unsafe { CString::from_raw(output) });
+	}
+}
+
+fn parse_block(input: &str) -> Result<Vec<XcmTransfer>, String> {
+	let input: BlockInput =
+		serde_json::from_str(input).map_err(|e| format!("Invalid input: {}", e))?;
+	// The parsers are async only to read the storage, which is already in memory here, so they're
+	// polled without a runtime. That way the caller may be running its own one, eg a Rust host
+	// loading the library.
+	futures::executor::block_on(raw_blocks::parse_events(
+		input.block_number,
+		&input.events,
+		input.assets,
+	))
+}
+
+fn panic_message(panic: &(dyn std::any::Any + Send)) -> &str {
+	panic
+		.downcast_ref::<&str>()
+		.copied()
+		.or_else(|| panic.downcast_ref::<String>().map(String::as_str))
+		.unwrap_or("unknown cause")
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn call(input: &str) -> serde_json::Value {
+		let input = CString::new(input).unwrap();
+		unsafe {
+			let output = xcm_indexer_parse_block(input.as_ptr());
+			let json = CStr::from_ptr(output).to_str().unwrap().to_owned();
+			xcm_indexer_free_string(output);
+			serde_json::from_str(&json).unwrap()
+		}
+	}
+
+	#[test]
+	fn parse_block_without_events_has_no_transfers() {
+		// A block without events: a SCALE encoded empty vector
+		assert_eq!(
+			call(r#"{ "block_number": 1, "events": "0x00" }"#),
+			serde_json::json!({ "transfers": [] })
+		);
+	}
+
+	#[tokio::test]
+	async fn parse_block_within_a_runtime() {
+		assert_eq!(
+			call(r#"{ "block_number": 1, "events": "0x00" }"#),
+			serde_json::json!({ "transfers": [] })
+		);
+	}
+
+	#[test]
+	fn panic_messages_are_reported() {
+		let panic = std::panic::catch_unwind(|| panic!("boom")).unwrap_err();
+		assert_eq!(panic_message(&*panic), "boom");
+		let panic = std::panic::catch_unwind(|| panic!("{}", 42)).unwrap_err();
+		assert_eq!(panic_message(&*panic), "42");
+	}
+
+	#[test]
+	fn parse_block_reports_invalid_input() {
+		let output = call(r#"{ "block_number": 1, "events": "0xzz" }"#);
+		assert!(output["error"].as_str().unwrap().starts_with("Invalid events"));
+		let output = call("not json");
+		assert!(output["error"].as_str().unwrap().starts_with("Invalid input"));
+	}
+}
@@ -0,0 +1,127 @@
+use crate::{
+	Error,
+	types::{BlockHash, BlockNumber, ConnectionSettings},
+};
+use std::{collections::BTreeMap, sync::Mutex, time::Duration};
+use subxt::{PolkadotConfig, backend::legacy::LegacyRpcMethods};
+
+// How often the blocks indexed are checked against the finalized chain of the reference endpoint.
+pub(crate) const FINALITY_CHECK_INTERVAL: Duration = Duration::from_secs(12);
+
+// An indexed block that isn't part of the chain finalized by the reference endpoint.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct FinalityDiscrepancy {
+	pub(crate) block_number: BlockNumber,
+	pub(crate) indexed_hash: BlockHash,
+	// The block finalized by the reference endpoint at that height, if it knows any
+	pub(crate) reference_hash: Option<BlockHash>,
+}
+
+impl std::fmt::Display for FinalityDiscrepancy {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self.reference_hash {
+			Some(reference_hash) => write!(
+				f,
+				"Block {} was indexed with hash {:?}, but the reference endpoint finalized {:?}",
+				self.block_number, self.indexed_hash, reference_hash
+			),
+			None => write!(
+				f,
+				"Block {} was indexed with hash {:?}, but the reference endpoint doesn't know it",
+				self.block_number, self.indexed_hash
+			),
+		}
+	}
+}
+
+// Checks that the blocks indexed belong to the chain finalized according to a second, independent
+// endpoint, flagging the discrepancies between both providers without running a light client. A
+// block is an ancestor of the finalized head of the reference endpoint if it's the block the
+// reference endpoint has at its height, so the blocks are checked once it finalized their height.
+pub(crate) struct FinalityVerifier {
+	rpc: LegacyRpcMethods<PolkadotConfig>,
+	// The blocks waiting for the reference endpoint to finalize their height
+	pending: Mutex<BTreeMap<BlockNumber, BlockHash>>,
+}
+
+impl FinalityVerifier {
+	pub(crate) async fn connect(url: &str, settings: &ConnectionSettings) -> Result<Self, Error> {
+		let rpc_client = crate::helpers::build_rpc_client(url, settings).await?;
+		Ok(Self { rpc: LegacyRpcMethods::new(rpc_client), pending: Mutex::new(BTreeMap::new()) })
+	}
+
+	pub(crate) fn track(&self, block_number: BlockNumber, block_hash: BlockHash) {
+		if let Ok(mut pending) = self.pending.lock() {
+			pending.insert(block_number, block_hash);
+		}
+	}
+
+	pub(crate) fn pending(&self) -> Vec<BlockNumber> {
+		self.pending
+			.lock()
+			.map(|pending| pending.keys().copied().collect())
+			.unwrap_or_default()
+	}
+
+	// Checks the blocks whose height the reference endpoint finalized, returning the ones that
+	// aren't part of its chain.
+	pub(crate) async fn verify_pending(&self) -> Result<Vec<FinalityDiscrepancy>, Error> {
+		let finalized_head = self.rpc.chain_get_finalized_head().await?;
+		let Some(finalized_header) = self.rpc.chain_get_header(Some(finalized_head)).await? else {
+			return Ok(vec![]);
+		};
+		let due = self
+			.pending
+			.lock()
+			.map(|mut pending| take_due(&mut pending, finalized_header.number))
+			.unwrap_or_default();
+		let mut discrepancies = vec![];
+		for (block_number, indexed_hash) in due {
+			let reference_hash = self.rpc.chain_get_block_hash(Some(block_number.into())).await?;
+			if reference_hash != Some(indexed_hash) {
+				discrepancies.push(FinalityDiscrepancy {
+					block_number,
+					indexed_hash,
+					reference_hash,
+				});
+			}
+		}
+		Ok(discrepancies)
+	}
+}
+
+// Takes the pending blocks up to the finalized height.
+fn take_due(
+	pending: &mut BTreeMap<BlockNumber, BlockHash>,
+	finalized_number: BlockNumber,
+) -> Vec<(BlockNumber, BlockHash)> {
+	let not_due = pending.split_off(&(finalized_number + 1));
+	std::mem::replace(pending, not_due).into_iter().collect()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn blocks_are_due_once_their_height_is_finalized() {
+		let mut pending = BTreeMap::from([
+			(9, BlockHash::repeat_byte(9)),
+			(10, BlockHash::repeat_byte(10)),
+			(11, BlockHash::repeat_byte(11)),
+		]);
+		assert_eq!(
+			take_due(&mut pending, 10),
+			vec![(9, BlockHash::repeat_byte(9)), (10, BlockHash::repeat_byte(10))]
+		);
+		assert_eq!(pending.keys().copied().collect::<Vec<_>>(), vec![11]);
+		assert_eq!(take_due(&mut pending, 10), vec![]);
+
+		let discrepancy = FinalityDiscrepancy {
+			block_number: 11,
+			indexed_hash: BlockHash::repeat_byte(11),
+			reference_hash: None,
+		};
+		assert!(discrepancy.to_string().ends_with("but the reference endpoint doesn't know it"));
+	}
+}
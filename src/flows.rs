@@ -0,0 +1,187 @@
+use crate::types::XcmTransfer;
+use std::{collections::HashMap, fs, path::Path, time::Duration};
+
+// The rolling windows the flows are totalled over, with the label they're exposed with.
+const FLOW_WINDOWS: [(&str, Duration); 2] =
+	[("24h", Duration::from_secs(24 * 3600)), ("7d", Duration::from_secs(7 * 24 * 3600))];
+
+// The DOT-denominated value of the transfers of a block.
+#[derive(Debug, Clone, PartialEq)]
+struct BlockFlow {
+	// The timestamp of the block, in milliseconds
+	timestamp: u64,
+	inflow: f64,
+	outflow: f64,
+}
+
+// Totals the value of the transfers received and sent by AssetHub over rolling windows, valued in
+// DOT, so ops can alert on a single number when flows become anomalous. Assets are valued with a
+// static price list; transfers of assets without a price are only counted.
+#[derive(Debug, Default)]
+pub(crate) struct FlowMetrics {
+	// The price of a unit of each asset in DOT, keyed by symbol
+	prices: HashMap<String, f64>,
+	blocks: Vec<BlockFlow>,
+	unpriced_transfers: u64,
+}
+
+impl FlowMetrics {
+	pub(crate) fn new(prices: HashMap<String, f64>) -> Self {
+		Self { prices, ..Default::default() }
+	}
+
+	// Loads the prices from a JSON file like `{ "USDT": 0.25, "USDC": 0.25 }`. DOT is always
+	// valued at 1.
+	pub(crate) fn from_prices_file(path: &Path) -> Result<Self, String> {
+		let content = fs::read_to_string(path)
+			.map_err(|e| format!("Failed to read the DOT prices file: {}", e))?;
+		let prices = serde_json::from_str(&content)
+			.map_err(|e| format!("Failed to parse the DOT prices file: {}", e))?;
+		Ok(Self::new(prices))
+	}
+
+	fn value(&mut self, asset: &str, amount: f64) -> f64 {
+		let price = if asset == crate::chain_spec::native_token_symbol() {
+			Some(1.0)
+		} else {
+			self.prices.get(asset).copied()
+		};
+		price.map(|price| price * amount).unwrap_or_else(|| {
+			self.unpriced_transfers += 1;
+			0.0
+		})
+	}
+
+	pub(crate) fn observe(&mut self, timestamp: u64, transfers: &[XcmTransfer]) {
+		let mut flow = BlockFlow { timestamp, inflow: 0.0, outflow: 0.0 };
+		for transfer in transfers {
+			match transfer {
+				XcmTransfer::ReceivedTransfer(incoming) =>
+					flow.inflow += self.value(&incoming.asset, incoming.amount),
+				XcmTransfer::SentTransfer(outgoing) =>
+					flow.outflow += self.value(&outgoing.asset, outgoing.amount),
+				_ => (),
+			}
+		}
+		self.blocks.push(flow);
+		// The windows are sorted, so the blocks out of the last one aren't needed anymore. Blocks
+		// may be observed out of order by the workers, so all of them are looked at
+		let (_, longest_window) = FLOW_WINDOWS[FLOW_WINDOWS.len() - 1];
+		let since = self.latest_timestamp().saturating_sub(longest_window.as_millis() as u64);
+		self.blocks.retain(|flow| flow.timestamp >= since);
+	}
+
+	fn latest_timestamp(&self) -> u64 {
+		self.blocks.iter().map(|flow| flow.timestamp).max().unwrap_or_default()
+	}
+
+	// The inflow and outflow totals of each window, ending at `now` (in milliseconds).
+	fn totals(&self, now: u64) -> Vec<(&'static str, f64, f64)> {
+		FLOW_WINDOWS
+			.iter()
+			.map(|(label, window)| {
+				let since = now.saturating_sub(window.as_millis() as u64);
+				let (inflow, outflow) = self
+					.blocks
+					.iter()
+					.filter(|flow| flow.timestamp >= since)
+					.fold((0.0, 0.0), |(inflow, outflow), flow| {
+						(inflow + flow.inflow, outflow + flow.outflow)
+					});
+				(*label, inflow, outflow)
+			})
+			.collect()
+	}
+
+	// Renders the metrics in the Prometheus text format. The windows end at the latest block
+	// observed, so a stalled subscription doesn't look like flows stopping.
+	pub(crate) fn render(&self) -> String {
+		let totals = self.totals(self.latest_timestamp());
+		let mut output = String::new();
+		let gauges: [(&str, &str, fn(f64, f64) -> f64); 3] = [
+			(
+				"xcm_indexer_dot_inflow",
+				"DOT-denominated value of the transfers received by AssetHub over the window",
+				|inflow, _| inflow,
+			),
+			(
+				"xcm_indexer_dot_outflow",
+				"DOT-denominated value of the transfers sent from AssetHub over the window",
+				|_, outflow| outflow,
+			),
+			(
+				"xcm_indexer_dot_net_flow",
+				"DOT-denominated inflow minus outflow of AssetHub over the window",
+				|inflow, outflow| inflow - outflow,
+			),
+		];
+		for (name, help, metric) in gauges {
+			output.push_str(&format!("# HELP {} {}\n# TYPE {} gauge\n", name, help, name));
+			for (label, inflow, outflow) in &totals {
+				output.push_str(&format!(
+					"{}{{window=\"{}\"}} {}\n",
+					name,
+					label,
+					metric(*inflow, *outflow)
+				));
+			}
+		}
+		let name = "xcm_indexer_unpriced_transfers_total";
+		output.push_str(&format!(
+			"# HELP {} Transfers left out of the flows as their asset has no DOT price\n",
+			name
+		));
+		output
+			.push_str(&format!("# TYPE {} counter\n{} {}\n", name, name, self.unpriced_transfers));
+		output
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		incoming_parser::{OriginChain, XcmIncomingTransfer},
+		types::TransferType,
+	};
+
+	const HOUR: u64 = 3600 * 1000;
+
+	fn received(asset: &str, amount: f64) -> XcmTransfer {
+		XcmTransfer::ReceivedTransfer(XcmIncomingTransfer {
+			block_number: 8_935_124,
+			origin_chain: OriginChain::PolkadotParachain(2034),
+			message_id: None,
+			beneficiary: "13KsaHFcQKSTd4m73Ub9yVwM1JGCZvipMyTZonHEXEceFYwS".to_owned(),
+			beneficiary_public_key:
+				"0x66dd21797b3ab4b27ff723196324a33f7a80683bda78e0fcebf8e7cebb09a358".to_owned(),
+			asset: asset.to_owned(),
+			asset_id: None,
+			amount,
+			exact_amount: None,
+			transfer_type: TransferType::Teleport,
+			fee_swap: None,
+			entities: vec![],
+			dust_lost: None,
+			trapped_assets_hash: None,
+			new_account: false,
+			xc20_address: None,
+		})
+	}
+
+	#[test]
+	fn flow_metrics_total_the_rolling_windows() {
+		let mut metrics = FlowMetrics::new(HashMap::from([("USDT".to_owned(), 0.25)]));
+		metrics.observe(0, &[received("DOT", 100.0)]);
+		metrics.observe(48 * HOUR, &[received("USDT", 40.0), received("UNKNOWN", 1.0)]);
+		metrics.observe(50 * HOUR, &[received("DOT", 5.0)]);
+		assert_eq!(metrics.totals(50 * HOUR), vec![("24h", 15.0, 0.0), ("7d", 115.0, 0.0)]);
+
+		// Blocks older than the longest window are forgotten
+		metrics.observe(10 * 24 * HOUR, &[]);
+		assert_eq!(metrics.blocks.len(), 1);
+		let rendered = metrics.render();
+		assert!(rendered.contains("xcm_indexer_dot_inflow{window=\"7d\"} 0\n"));
+		assert!(rendered.contains("xcm_indexer_unpriced_transfers_total 1\n"));
+	}
+}
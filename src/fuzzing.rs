@@ -0,0 +1,74 @@
+// The entry points of the fuzz targets in `fuzz/`, feeding arbitrary bytes to the decoding paths
+// the indexer runs on the data of the chain. None of them may panic, as a single malformed event,
+// call or location would stop the long-running subscriptions.
+use crate::{
+	asset_hub::runtime_types::{
+		asset_hub_polkadot_runtime::RuntimeCall,
+		staging_xcm::{v4::location::Location, v5::location::Location as V5Location},
+		xcm::{VersionedLocation, v3::multilocation::MultiLocation},
+	},
+	asset_overrides::AssetOverrides,
+	outgoing_parser::DestinationChain,
+	raw_blocks::InjectedMetadata,
+};
+use std::sync::OnceLock;
+use subxt::ext::codec::Decode;
+use tokio::runtime::Runtime;
+
+// The parsers are async only to read the storage, which is injected here, so a single runtime
+// serves every input.
+static RUNTIME: OnceLock<Runtime> = OnceLock::new();
+
+fn block_on<F: Future>(future: F) -> F::Output {
+	RUNTIME
+		.get_or_init(|| {
+			tokio::runtime::Builder::new_current_thread()
+				.build()
+				.expect("The fuzzing runtime should start")
+		})
+		.block_on(future)
+}
+
+// Arbitrary bytes as the `System::Events` storage value of a block, parsed into transfers.
+pub fn events(data: &[u8]) {
+	let _ = block_on(crate::raw_blocks::parse_events(
+		1,
+		&crate::helpers::to_hex_string(data),
+		AssetOverrides::default(),
+	));
+}
+
+// Arbitrary bytes as the SCALE encoded call of an extrinsic, rendered as the raw XCM is.
+pub fn call(data: &[u8]) {
+	if let Ok(call) = RuntimeCall::decode(&mut &data[..]) {
+		let _ = format!("{:?}", call);
+	}
+}
+
+// Arbitrary bytes as the signer of an extrinsic.
+pub fn sender(data: &[u8]) {
+	let _ = crate::outgoing_parser::render_sender(data);
+}
+
+// Arbitrary bytes as the locations found in XCM calls and events, converted to the chains, accounts
+// and assets of the output.
+pub fn locations(data: &[u8]) {
+	if let Ok(location) = MultiLocation::decode(&mut &data[..]) {
+		let _ = DestinationChain::from(&location);
+	}
+	if let Ok(location) = Location::decode(&mut &data[..]) {
+		let _ = DestinationChain::from(&location);
+		let _ = crate::helpers::is_teleportable_to_sibling(&location, 2034);
+		let _ = block_on(crate::helpers::extract_location_metadata_values(
+			&InjectedMetadata(AssetOverrides::default()),
+			&location,
+		));
+	}
+	if let Ok(location) = V5Location::decode(&mut &data[..]) {
+		let _ = DestinationChain::from(&location);
+		let _ = crate::outgoing_parser::render_v5_account(&location);
+	}
+	if let Ok(location) = VersionedLocation::decode(&mut &data[..]) {
+		let _ = crate::runtime_apis::forwarded_destination(&location);
+	}
+}
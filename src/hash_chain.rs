@@ -0,0 +1,237 @@
+use crate::signing::OutputSigner;
+use serde::{Deserialize, Serialize};
+use sp_core::hashing::blake2_256;
+use std::{
+	fs::{self, OpenOptions},
+	io::Write,
+	path::{Path, PathBuf},
+};
+
+// A link of the hash chain: the bytes written to the output between two offsets, and the hash
+// chaining them to the previous links.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct Checkpoint {
+	pub(crate) start_offset: u64,
+	pub(crate) end_offset: u64,
+	pub(crate) chain_hash: String,
+	// The signature of the chain hash by the signing key of the output, so the chain can't be
+	// recomputed over tampered output. Only missing in the chains kept by previous versions of the
+	// indexer
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub(crate) signature: Option<String>,
+}
+
+// A rolling hash chain over the output written to a file, persisted as a checkpoint per write in a
+// sidecar file next to it, eg `transfers.json.chain`. Every write is hashed together with the
+// previous chain hash, so auditors can detect records tampered with, removed or inserted in
+// historical output files. The checkpoints are signed with the signing key of the output.
+pub(crate) struct HashChain {
+	path: PathBuf,
+	chain_hash: [u8; 32],
+	signer: OutputSigner,
+}
+
+fn sidecar_path(output: &Path) -> PathBuf {
+	let mut path = output.as_os_str().to_owned();
+	path.push(".chain");
+	PathBuf::from(path)
+}
+
+fn read_checkpoints(path: &Path) -> Result<Vec<Checkpoint>, String> {
+	let content = match fs::read_to_string(path) {
+		Ok(content) => content,
+		Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+		Err(e) => return Err(format!("Failed to read the hash chain: {}", e)),
+	};
+	content
+		.lines()
+		.filter(|line| !line.trim().is_empty())
+		.enumerate()
+		.map(|(index, line)| {
+			serde_json::from_str(line)
+				.map_err(|e| format!("Invalid checkpoint at line {}: {}", index + 1, e))
+		})
+		.collect()
+}
+
+fn decode_hash(hash: &str) -> Option<[u8; 32]> {
+	hex::decode(hash.trim_start_matches("0x")).ok()?.try_into().ok()
+}
+
+// The chain starts from a zeroed hash.
+fn link(chain_hash: &[u8; 32], bytes: &[u8]) -> [u8; 32] {
+	blake2_256(&[&chain_hash[..], &blake2_256(bytes)].concat())
+}
+
+impl HashChain {
+	// Starts the chain of an output file. Resumed outputs continue the chain kept next to them,
+	// while new outputs start a new one.
+	pub(crate) fn new(output: &Path, resume: bool, signer: OutputSigner) -> Result<Self, String> {
+		let path = sidecar_path(output);
+		if !resume {
+			fs::write(&path, "").map_err(|e| format!("Failed to create the hash chain: {}", e))?;
+			return Ok(Self { path, chain_hash: [0; 32], signer });
+		}
+		let chain_hash = match read_checkpoints(&path)?.last() {
+			Some(checkpoint) => decode_hash(&checkpoint.chain_hash)
+				.ok_or_else(|| format!("Invalid chain hash in {}", path.display()))?,
+			None => [0; 32],
+		};
+		Ok(Self { path, chain_hash, signer })
+	}
+
+	// Chains the bytes written at the given offset of the output. Called by the output sink while
+	// the output is locked, so the links follow the order of the file.
+	pub(crate) fn record(&mut self, start_offset: u64, bytes: &[u8]) -> Result<(), String> {
+		let chain_hash = link(&self.chain_hash, bytes);
+		let checkpoint = Checkpoint {
+			start_offset,
+			end_offset: start_offset + bytes.len() as u64,
+			chain_hash: format!("0x{}", hex::encode(chain_hash)),
+			signature: Some(self.signer.sign_hash(&chain_hash)),
+		};
+		let line = serde_json::to_string(&checkpoint)
+			.map_err(|e| format!("Failed to serialize a checkpoint: {}", e))? +
+			"\n";
+		OpenOptions::new()
+			.append(true)
+			.open(&self.path)
+			.and_then(|mut file| file.write_all(line.as_bytes()))
+			.map_err(|e| format!("Failed to write the hash chain: {}", e))?;
+		self.chain_hash = chain_hash;
+		Ok(())
+	}
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+pub(crate) struct ChainVerification {
+	pub(crate) checkpoints_verified: usize,
+	pub(crate) bytes_verified: u64,
+	pub(crate) valid: bool,
+	// Why the verification failed, pointing to the first checkpoint not matching the output
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub(crate) error: Option<String>,
+}
+
+// Recomputes the chain of an output file from its content, checking it against its checkpoints,
+// which must be signed by the given public key.
+pub(crate) fn verify(output: &Path, public_key: &str) -> Result<ChainVerification, String> {
+	let content = fs::read(output).map_err(|e| format!("Failed to read the output file: {}", e))?;
+	let checkpoints = read_checkpoints(&sidecar_path(output))?;
+	let mut verification =
+		ChainVerification { checkpoints_verified: 0, bytes_verified: 0, valid: true, error: None };
+	let mut chain_hash = [0; 32];
+	let mut previous_end = 0;
+	for (index, checkpoint) in checkpoints.iter().enumerate() {
+		let error = if checkpoint.start_offset != previous_end {
+			Some(format!(
+				"Checkpoint {} starts at byte {}, but the previous one ends at byte {}",
+				index + 1,
+				checkpoint.start_offset,
+				previous_end
+			))
+		} else if let Some(bytes) =
+			content.get(checkpoint.start_offset as usize..checkpoint.end_offset as usize)
+		{
+			chain_hash = link(&chain_hash, bytes);
+			if decode_hash(&checkpoint.chain_hash) != Some(chain_hash) {
+				Some(format!(
+					"The bytes {} to {} don't match checkpoint {}",
+					checkpoint.start_offset,
+					checkpoint.end_offset,
+					index + 1
+				))
+			} else if !checkpoint.signature.as_deref().is_some_and(|signature| {
+				crate::signing::verify_hash_signature(&chain_hash, signature, public_key)
+			}) {
+				Some(format!("Checkpoint {} isn't signed by {}", index + 1, public_key))
+			} else {
+				None
+			}
+		} else {
+			Some(format!(
+				"The output file ends before checkpoint {}, at byte {}",
+				index + 1,
+				checkpoint.end_offset
+			))
+		};
+		if error.is_some() {
+			verification.valid = false;
+			verification.error = error;
+			break;
+		}
+		verification.checkpoints_verified += 1;
+		verification.bytes_verified = checkpoint.end_offset;
+		previous_end = checkpoint.end_offset;
+	}
+	if verification.valid && content.len() as u64 > previous_end {
+		verification.valid = false;
+		verification.error =
+			Some(format!("The bytes after byte {} aren't part of the chain", previous_end));
+	}
+	Ok(verification)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn signer(seed: u8) -> (OutputSigner, String) {
+		let signer = OutputSigner::from_secret(&format!("0x{}", hex::encode([seed; 32]))).unwrap();
+		let public_key = signer.public_key();
+		(signer, public_key)
+	}
+
+	#[test]
+	fn hash_chain_detects_tampered_output() {
+		let output = std::env::temp_dir()
+			.join(format!("xcm_indexer_hash_chain_{}.json", std::process::id()));
+		let batches: [&[u8]; 3] = [b"[{\"a\":1}]\n", b"[{\"b\":2}]\n", b"[{\"c\":3}]\n"];
+		fs::write(&output, batches[..2].concat()).unwrap();
+		let (signer, public_key) = signer(7);
+		let mut hash_chain = HashChain::new(&output, false, signer.clone()).unwrap();
+		hash_chain.record(0, batches[0]).unwrap();
+		hash_chain.record(batches[0].len() as u64, batches[1]).unwrap();
+
+		// A resumed output continues the chain
+		let mut hash_chain = HashChain::new(&output, true, signer).unwrap();
+		let mut file = OpenOptions::new().append(true).open(&output).unwrap();
+		file.write_all(batches[2]).unwrap();
+		hash_chain
+			.record((batches[0].len() + batches[1].len()) as u64, batches[2])
+			.unwrap();
+		let verification = verify(&output, &public_key).unwrap();
+		assert!(verification.valid);
+		assert_eq!(verification.checkpoints_verified, 3);
+
+		// Records appended out of the chain are detected too
+		file.write_all(batches[0]).unwrap();
+		assert!(!verify(&output, &public_key).unwrap().valid);
+
+		// A record altered in the middle of the output breaks the chain from there
+		let tampered = [batches[0], b"[{\"b\":9}]\n", batches[2]].concat();
+		fs::write(&output, &tampered).unwrap();
+		let verification = verify(&output, &public_key).unwrap();
+		assert!(!verification.valid);
+		assert_eq!(verification.checkpoints_verified, 1);
+		assert_eq!(
+			verification.error.as_deref(),
+			Some("The bytes 10 to 20 don't match checkpoint 2")
+		);
+
+		// Neither can the chain be recomputed over the tampered output without the signing key
+		let (forger, _) = signer(8);
+		let mut forged_chain = HashChain::new(&output, false, forger).unwrap();
+		forged_chain.record(0, &tampered).unwrap();
+		let verification = verify(&output, &public_key).unwrap();
+		let _ = fs::remove_file(sidecar_path(&output));
+		fs::remove_file(&output).unwrap();
+		assert!(!verification.valid);
+		assert_eq!(verification.checkpoints_verified, 0);
+		assert_eq!(
+			verification.error,
+			Some(format!("Checkpoint 1 isn't signed by {}", public_key))
+		);
+	}
+}
@@ -1,30 +1,62 @@
+// Some helpers are only used by the parsers, which may be disabled at compile time.
+#[cfg(any(feature = "incoming", feature = "outgoing", feature = "fuzzing"))]
+use crate::asset_hub::runtime_types::staging_xcm::v4::{
+	junction::Junction, junctions::Junctions, location::Location,
+};
 use crate::{
 	Error,
-	asset_hub::runtime_types::staging_xcm::v4::{
-		junction::Junction, junctions::Junctions, location::Location,
-	},
+	block_source::BlockSource,
+	chain_spec::{Chain, ChainSpec},
+	storage_lookup::StorageLookup,
 	types::{AssetMetadataValues, BlockHash, XcmTransfer},
 };
+#[cfg(not(target_arch = "wasm32"))]
+use crate::{rate_limiter::RateLimitedRpcClient, types::ConnectionSettings};
+#[cfg(not(target_arch = "wasm32"))]
+use jsonrpsee::ws_client::{PingConfig, WsClientBuilder};
 use sp_core::{
 	crypto::{Ss58AddressFormat, Ss58Codec},
 	sr25519::Public as Sr25519Public,
 };
-use subxt::{
-	Metadata, OnlineClient, PolkadotConfig, config::polkadot::AccountId32, storage::Storage,
-};
+use std::sync::atomic::{AtomicU16, Ordering};
+#[cfg(not(target_arch = "wasm32"))]
+use subxt::backend::rpc::RpcClient;
+use subxt::{Metadata, OnlineClient, PolkadotConfig, config::polkadot::AccountId32};
 
 pub(crate) type XcmAggregatedOrigin = crate::asset_hub::message_queue::events::processed::Origin;
 
 pub(crate) fn validate_ah_metadata(metadata: &Metadata) -> Result<(), Error> {
-	if !crate::asset_hub::is_codegen_valid_for(metadata) {
+	if !Chain::is_codegen_valid_for(metadata) {
 		return Err(Error::InvalidMetadata);
 	}
 	Ok(())
 }
 
+// Public endpoints may be slow or drop idle connections, so instead of relying on the default
+// client built by subxt we build our own, keeping the connection alive with periodic pings and
+// bounding how long we wait for the node. Browsers keep their own connections, so it's only built
+// natively.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) async fn build_rpc_client(
+	url: &str,
+	settings: &ConnectionSettings,
+) -> Result<RpcClient, Error> {
+	let client = WsClientBuilder::default()
+		.connection_timeout(settings.connection_timeout)
+		.request_timeout(settings.request_timeout)
+		.enable_ws_ping(PingConfig::new().ping_interval(settings.ping_interval))
+		.build(url)
+		.await?;
+	Ok(match settings.max_rps {
+		Some(max_rps) => RpcClient::new(RateLimitedRpcClient::new(client, max_rps)),
+		None => RpcClient::new(client),
+	})
+}
+
 // An asset in AssetHub is only teleportable to a sibling parachain if it's a concrete asset for
 // that parachain. Only those kind of assets and DOT (with the relaychain) are teleportable in
 // AssetHub
+#[cfg(any(feature = "incoming", feature = "outgoing", feature = "fuzzing"))]
 pub(crate) fn is_teleportable_to_sibling(asset_id: &Location, sibling_parachain_id: u32) -> bool {
 	fn junction_starts_with_para_id(para_id: u32, junction: &Junction) -> bool {
 		matches!(junction, Junction::Parachain(id) if *id==para_id)
@@ -52,75 +84,172 @@ pub(crate) fn is_teleportable_to_sibling(asset_id: &Location, sibling_parachain_
 }
 
 pub(crate) async fn extract_asset_metadata_values(
-	storage_api: &Storage<PolkadotConfig, OnlineClient<PolkadotConfig>>,
+	storage_api: &impl StorageLookup,
 	asset_id: &crate::asset_hub::assets::storage::types::metadata::Param0,
 ) -> Result<AssetMetadataValues, Error> {
-	let asset_metadata_address = crate::asset_hub::storage().assets().metadata(asset_id);
-	let asset_metadata = storage_api.fetch(&asset_metadata_address).await?;
+	let asset_metadata = storage_api.asset_metadata(asset_id).await?;
 	let decimals = asset_metadata.as_ref().map(|metadata| metadata.decimals).unwrap_or_default();
 	let asset_name = if let Some(name_bytes) = asset_metadata.map(|metadata| metadata.name) {
 		String::from_utf8(name_bytes.0).unwrap_or(format!("Asset id: {}", &asset_id))
 	} else {
 		format!("Asset Id: {}", &asset_id)
 	};
-	Ok(AssetMetadataValues { asset_name, decimals })
+	Ok(crate::asset_overrides::override_asset(
+		*asset_id,
+		AssetMetadataValues { asset_name, decimals },
+	))
 }
 
 pub(crate) async fn extract_foreign_asset_metadata_values(
-	storage_api: &Storage<PolkadotConfig, OnlineClient<PolkadotConfig>>,
+	storage_api: &impl StorageLookup,
 	asset_id: &crate::asset_hub::foreign_assets::storage::types::metadata::Param0,
 ) -> Result<AssetMetadataValues, Error> {
-	let asset_metadata_address = crate::asset_hub::storage().foreign_assets().metadata(asset_id);
-	let asset_metadata = storage_api.fetch(&asset_metadata_address).await?;
+	let asset_metadata = storage_api.foreign_asset_metadata(asset_id).await?;
 	let decimals = asset_metadata.as_ref().map(|metadata| metadata.decimals).unwrap_or_default();
+	let unnamed_asset =
+		|| format!("Asset location: {}", crate::locations::encode_location(asset_id));
 	let asset_name = if let Some(name_bytes) = asset_metadata.map(|metadata| metadata.name) {
-		String::from_utf8(name_bytes.0).unwrap_or(format!("Asset location: {:?}", &asset_id))
+		String::from_utf8(name_bytes.0).unwrap_or_else(|_| unnamed_asset())
 	} else {
-		format!("Asset location: {:?}", &asset_id)
+		unnamed_asset()
 	};
-	Ok(AssetMetadataValues { asset_name, decimals })
+	Ok(crate::asset_overrides::override_foreign_asset(
+		asset_id,
+		AssetMetadataValues { asset_name, decimals },
+	))
+}
+
+// Resolves the metadata of an asset given its location as seen from AssetHub: DOT, an asset from
+// pallet_assets or a foreign asset.
+#[cfg(any(feature = "incoming", feature = "fuzzing"))]
+pub(crate) async fn extract_location_metadata_values(
+	storage_api: &impl StorageLookup,
+	location: &Location,
+) -> Result<AssetMetadataValues, Error> {
+	match (location.parents, &location.interior) {
+		(1, Junctions::Here) => Ok(AssetMetadataValues {
+			asset_name: crate::chain_spec::native_token_symbol(),
+			decimals: crate::chain_spec::native_token_decimals(),
+		}),
+		// An asset in pallet_assets
+		(
+			0,
+			Junctions::X2(
+				[
+					Junction::PalletInstance(Chain::ASSETS_PALLET_INDEX),
+					Junction::GeneralIndex(asset_id),
+				],
+			),
+		) =>
+			extract_asset_metadata_values(
+				storage_api,
+				// The GeneralIndex is u128, but this casting is safe due to it represent an
+				// asset_id in pallet_assets.
+				&(*asset_id as crate::asset_hub::assets::storage::types::metadata::Param0),
+			)
+			.await,
+		_ => extract_foreign_asset_metadata_values(storage_api, location).await,
+	}
 }
 
-pub(crate) fn convert_account_id_to_ah_address(account_id: &AccountId32) -> String {
-	Sr25519Public::from_raw(account_id.0).to_ss58check_with_version(Ss58AddressFormat::custom(0))
+// The prefix used to render every SS58 address in the output. It defaults to the chain's one, but
+// consumers may prefer another one (eg, the generic substrate prefix), so it can be set once when
+// the CLI starts.
+static SS58_PREFIX: AtomicU16 = AtomicU16::new(Chain::SS58_PREFIX);
+
+pub(crate) fn set_ss58_prefix(prefix: u16) {
+	SS58_PREFIX.store(prefix, Ordering::Relaxed);
 }
 
-pub(crate) fn convert_account_id_to_general_substrate_address(account_id: &AccountId32) -> String {
-	Sr25519Public::from_raw(account_id.0).to_ss58check()
+pub(crate) fn convert_account_id_to_address(account_id: &AccountId32) -> String {
+	convert_account_id_to_address_with_prefix(account_id, SS58_PREFIX.load(Ordering::Relaxed))
 }
 
-pub(crate) fn to_decimal_f64(value: u128, decimals: u8) -> f64 {
-	let factor = 10u128.pow(decimals as u32) as f64;
-	value as f64 / factor
+pub(crate) fn convert_account_id_to_address_with_prefix(
+	account_id: &AccountId32,
+	prefix: u16,
+) -> String {
+	Sr25519Public::from_raw(account_id.0)
+		.to_ss58check_with_version(Ss58AddressFormat::custom(prefix))
 }
 
-pub(crate) async fn get_all_transfers_at_block_hash(
+// Raw public keys are rendered along SS58 addresses, so downstream systems keying on them don't
+// need to decode SS58.
+pub(crate) fn to_hex_string(bytes: &[u8]) -> String {
+	format!("0x{}", hex::encode(bytes))
+}
+
+// EIP-55 checksummed rendering of 20 bytes accounts: each letter is uppercased if the matching
+// nibble of the keccak256 hash of the lowercase address is 8 or higher, so the address passes the
+// validation of Ethereum tooling.
+pub(crate) fn to_eip55_address(key: &[u8; 20]) -> String {
+	let address = hex::encode(key);
+	let hash = sp_core::hashing::keccak_256(address.as_bytes());
+	let checksummed_address: String = address
+		.chars()
+		.enumerate()
+		.map(|(index, character)| {
+			let nibble = if index % 2 == 0 { hash[index / 2] >> 4 } else { hash[index / 2] & 0x0f };
+			if nibble >= 8 { character.to_ascii_uppercase() } else { character }
+		})
+		.collect();
+	format!("0x{}", checksummed_address)
+}
+
+pub(crate) async fn get_block_timestamp(
 	api: &OnlineClient<PolkadotConfig>,
 	block_hash: BlockHash,
+) -> Result<u64, Error> {
+	api.storage()
+		.at(block_hash)
+		.fetch_or_default(&crate::asset_hub::storage().timestamp().now())
+		.await
+		.map_err(|err| Error::from(err).or_state_pruned(block_hash))
+}
+
+// Without any parser enabled the block isn't read at all.
+#[cfg_attr(
+	not(any(feature = "incoming", feature = "outgoing")),
+	allow(unused_mut, unused_variables)
+)]
+pub(crate) async fn get_all_transfers_at_block_hash(
+	api: &impl BlockSource,
+	block_hash: BlockHash,
 ) -> Result<Vec<XcmTransfer>, Error> {
 	let mut output = vec![];
-	crate::incoming_parser::get_incoming_xcm_transfers_at_block_hash(api, block_hash)
-		.await?
-		.into_iter()
-		.for_each(|incoming_transfer| {
-			output.push(XcmTransfer::ReceivedTransfer(incoming_transfer))
-		});
+	#[cfg(feature = "incoming")]
+	{
+		let incoming_messages =
+			crate::incoming_parser::get_incoming_xcm_transfers_at_block_hash(api, block_hash)
+				.await
+				.map_err(|err| err.or_state_pruned(block_hash))?;
+		output.extend(incoming_messages.transfers.into_iter().map(XcmTransfer::ReceivedTransfer));
+		output
+			.extend(incoming_messages.failed_messages.into_iter().map(XcmTransfer::FailedMessage));
+	}
 
+	#[cfg(feature = "outgoing")]
 	crate::outgoing_parser::get_outgoing_xcm_transfers_at_block_hash(api, block_hash)
-		.await?
+		.await
+		.map_err(|err| err.or_state_pruned(block_hash))?
 		.into_iter()
 		.for_each(|outgoing_transfer| output.push(XcmTransfer::SentTransfer(outgoing_transfer)));
 
+	crate::entities::tag_transfers(&mut output);
 	Ok(output)
 }
 
 #[cfg(test)]
 mod tests {
 	use super::*;
+	use crate::asset_hub::runtime_types::staging_xcm::v4::{
+		junction::Junction, junctions::Junctions, location::Location,
+	};
+	#[cfg(all(feature = "incoming", feature = "outgoing"))]
 	use crate::{
 		incoming_parser::{OriginChain, XcmIncomingTransfer},
-		outgoing_parser::{DestinationChain, XcmOutgoingTransfer},
-		types::TransferType,
+		outgoing_parser::{DestinationChain, ExecutionOutcome, WeightLimit, XcmOutgoingTransfer},
+		types::{AssetIdentifier, TransferType},
 	};
 	use std::str::FromStr;
 
@@ -128,9 +257,7 @@ mod tests {
 
 	#[tokio::test]
 	async fn validate_ah_metadata_with_ah_node() {
-		let api = OnlineClient::<PolkadotConfig>::from_url(crate::types::ASSET_HUB_RPC_ENDPOINT)
-			.await
-			.unwrap();
+		let api = OnlineClient::<PolkadotConfig>::from_url(Chain::RPC_ENDPOINT).await.unwrap();
 		let metadata = api.metadata();
 		assert!(validate_ah_metadata(&metadata).is_ok());
 	}
@@ -142,13 +269,14 @@ mod tests {
 		assert!(matches!(validate_ah_metadata(&metadata).err(), Some(Error::InvalidMetadata)));
 	}
 
+	#[cfg(any(feature = "incoming", feature = "outgoing", feature = "fuzzing"))]
 	#[test]
 	fn is_teleportable_to_sibling_asset_test() {
 		let asset_id = Location {
 			parents: 1,
 			interior: Junctions::X3([
 				Junction::Parachain(2004),
-				Junction::PalletInstance(50),
+				Junction::PalletInstance(Chain::ASSETS_PALLET_INDEX),
 				Junction::GeneralIndex(3014),
 			]),
 		};
@@ -158,9 +286,7 @@ mod tests {
 
 	#[tokio::test]
 	async fn extract_asset_metadata_values_test() {
-		let api = OnlineClient::<PolkadotConfig>::from_url(crate::types::ASSET_HUB_RPC_ENDPOINT)
-			.await
-			.unwrap();
+		let api = OnlineClient::<PolkadotConfig>::from_url(Chain::RPC_ENDPOINT).await.unwrap();
 		let storage_api = api.storage().at_latest().await.unwrap();
 
 		// Asset 1984 is Tether
@@ -172,9 +298,7 @@ mod tests {
 
 	#[tokio::test]
 	async fn extract_foreign_asset_metadata_values_test() {
-		let api = OnlineClient::<PolkadotConfig>::from_url(crate::types::ASSET_HUB_RPC_ENDPOINT)
-			.await
-			.unwrap();
+		let api = OnlineClient::<PolkadotConfig>::from_url(Chain::RPC_ENDPOINT).await.unwrap();
 		let storage_api = api.storage().at_latest().await.unwrap();
 
 		// (1, X1(Parachain(3370))) is LAOS
@@ -189,63 +313,161 @@ mod tests {
 		);
 	}
 
+	#[cfg(any(feature = "incoming", feature = "fuzzing"))]
+	#[tokio::test]
+	async fn extract_location_metadata_values_test() {
+		let api = OnlineClient::<PolkadotConfig>::from_url(Chain::RPC_ENDPOINT).await.unwrap();
+		let storage_api = api.storage().at_latest().await.unwrap();
+
+		assert_eq!(
+			extract_location_metadata_values(
+				&storage_api,
+				&Location { parents: 1, interior: Junctions::Here }
+			)
+			.await
+			.unwrap(),
+			AssetMetadataValues { asset_name: "DOT".to_owned(), decimals: 10 }
+		);
+		assert_eq!(
+			extract_location_metadata_values(
+				&storage_api,
+				&Location {
+					parents: 0,
+					interior: Junctions::X2([
+						Junction::PalletInstance(Chain::ASSETS_PALLET_INDEX),
+						Junction::GeneralIndex(1984)
+					])
+				}
+			)
+			.await
+			.unwrap(),
+			AssetMetadataValues { asset_name: "Tether USD".to_owned(), decimals: 6 }
+		);
+	}
+
 	#[test]
-	fn convert_account_id_to_ah_address_test() {
+	fn convert_account_id_to_address_test() {
 		let address = "15oF4uVJwmo4TdGW7VfQxNLavjCXviqxT9S1MgbjMNHr6Sp5";
 		let account_id = AccountId32::from_str(address).unwrap();
-		assert_eq!(convert_account_id_to_ah_address(&account_id), address);
+		assert_eq!(convert_account_id_to_address(&account_id), address);
 	}
 
 	#[test]
-	fn convert_account_id_to_general_substrate_address_test() {
+	fn convert_account_id_to_address_with_prefix_test() {
 		let address = "5GrwvaEF5zXb26Fz9rcQpDWS57CtERHpNehXCPcNoHGKutQY";
 		let account_id = AccountId32::from_str(address).unwrap();
-		assert_eq!(convert_account_id_to_general_substrate_address(&account_id), address);
+		assert_eq!(convert_account_id_to_address_with_prefix(&account_id, 42), address);
 	}
 
 	#[test]
-	fn to_decimal_f64_test() {
-		assert_eq!(to_decimal_f64(10_000_000_000_000, 18), 0.00001);
-		assert_eq!(to_decimal_f64(123_456_789, 6), 123.456789);
-		assert_eq!(to_decimal_f64(123, 0), 123f64);
+	fn to_hex_string_test() {
+		assert_eq!(to_hex_string(&[0xda, 0x39, 0x01]), "0xda3901");
+		assert_eq!(to_hex_string(&[]), "0x");
 	}
 
+	#[test]
+	fn to_eip55_address_test() {
+		// Test vectors from the EIP
+		for address in [
+			"0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed",
+			"0xfB6916095ca1df60bB79Ce92cE3Ea74c37c5d359",
+			"0xdbF03B407c01E7cD3CBea99509d93f8DDDC8C6FB",
+			"0xD1220A0cf47c7B9Be7A2E6BA89F429762e7b9aDb",
+		] {
+			let key: [u8; 20] = hex::decode(&address[2..]).unwrap().try_into().unwrap();
+			assert_eq!(to_eip55_address(&key), address);
+		}
+	}
+
+	#[cfg(all(feature = "incoming", feature = "outgoing"))]
 	#[tokio::test]
 	async fn get_all_transfers_at_block_hash_test() {
-		let api = OnlineClient::<PolkadotConfig>::from_url(crate::types::ASSET_HUB_RPC_ENDPOINT)
-			.await
-			.unwrap();
+		let api = OnlineClient::<PolkadotConfig>::from_url(Chain::RPC_ENDPOINT).await.unwrap();
 
 		// Received transfers
 		let block_hash_hex = "0x5e45bdca2951ac156e0459a461de60a1ee0a4263b17d7d6a95e4f28b9955c16b";
 		let block_hash: BlockHash = block_hash_hex.parse().unwrap();
-		let xcm_transfer = get_all_transfers_at_block_hash(&api, block_hash).await.unwrap();
+		let mut xcm_transfer = get_all_transfers_at_block_hash(&api, block_hash).await.unwrap();
+		if let Some(XcmTransfer::ReceivedTransfer(transfer)) = xcm_transfer.first_mut() {
+			let message_id = transfer.message_id.take().unwrap();
+			assert!(message_id.starts_with("0x") && message_id.len() == 66);
+		}
 		assert_eq!(
 			xcm_transfer,
 			vec![XcmTransfer::ReceivedTransfer(XcmIncomingTransfer {
 				block_number: 8_898_884,
 				origin_chain: OriginChain::PolkadotParachain(2004),
+				message_id: None,
 				beneficiary: "13KsaHFcQKSTd4m73Ub9yVwM1JGCZvipMyTZonHEXEceFYwS".to_owned(),
+				beneficiary_public_key:
+					"0x66dd21797b3ab4b27ff723196324a33f7a80683bda78e0fcebf8e7cebb09a358".to_owned(),
 				asset: "USD Coin".to_owned(),
+				asset_id: Some(AssetIdentifier::Asset(1337)),
 				amount: 9_401.612723,
-				transfer_type: TransferType::Reserve
+				exact_amount: Some("9401.612723".to_owned()),
+				transfer_type: TransferType::Reserve,
+				fee_swap: None,
+				entities: vec![],
+				dust_lost: None,
+				trapped_assets_hash: None,
+				new_account: false,
+				xc20_address: Some("0xFFfffffF7D2B0B761Af01Ca8e25242976ac0aD7D".to_owned()),
 			})]
 		);
 
 		// Sent transfer
 		let block_hash_hex = "0xc011fd5e3630a90fa2108887d49c7bc0dab52b27af5f85cbd7975ead52b0a7c8";
 		let block_hash: BlockHash = block_hash_hex.parse().unwrap();
-		let xcm_transfer = get_all_transfers_at_block_hash(&api, block_hash).await.unwrap();
+		let mut xcm_transfer = get_all_transfers_at_block_hash(&api, block_hash).await.unwrap();
+		// The outcome and the message id of the transfer are the ones of the `Attempted` and `Sent`
+		// events of the block
+		let events = api.events().at(block_hash).await.unwrap();
+		let attempted_event = events
+			.find_first::<crate::asset_hub::polkadot_xcm::events::Attempted>()
+			.unwrap()
+			.unwrap();
+		let sent_event = events
+			.find_first::<crate::asset_hub::polkadot_xcm::events::Sent>()
+			.unwrap()
+			.unwrap();
+		if let Some(XcmTransfer::SentTransfer(transfer)) = xcm_transfer.first_mut() {
+			let outcome = transfer.outcome.take().unwrap();
+			assert!(matches!(outcome, ExecutionOutcome::Complete { ref_time, .. } if ref_time > 0));
+			assert_eq!(outcome, ExecutionOutcome::from(&attempted_event.outcome));
+			assert_eq!(transfer.message_id.take(), Some(to_hex_string(&sent_event.message_id)));
+		}
 		assert_eq!(
 			xcm_transfer,
 			vec![XcmTransfer::SentTransfer(XcmOutgoingTransfer {
 				block_number: 8_935_124,
+				transfer_group_id: "8935124-2".to_owned(),
+				origin: None,
 				destination_chain: DestinationChain::PolkadotParachain(2004),
 				sender: "13KsaHFcQKSTd4m73Ub9yVwM1JGCZvipMyTZonHEXEceFYwS".to_owned(),
-				beneficiary: "0xda3985513642d591ae95ef6dec4ff6d725373004".to_owned(),
+				sender_public_key: Some(
+					"0x66dd21797b3ab4b27ff723196324a33f7a80683bda78e0fcebf8e7cebb09a358".to_owned()
+				),
+				beneficiary: "0xda3985513642D591aE95EF6dEc4Ff6D725373004".to_owned(),
+				beneficiary_public_key: "0xda3985513642d591ae95ef6dec4ff6d725373004".to_owned(),
 				asset: "DOT".to_owned(),
+				asset_id: Some(AssetIdentifier::Native),
 				amount: 2_022.95,
-				transfer_type: TransferType::Reserve
+				exact_amount: Some("2022.95".to_owned()),
+				transfer_type: TransferType::Reserve,
+				fee_asset_item: 0,
+				pays_fees: true,
+				weight_limit: WeightLimit::Unlimited,
+				burned_amount: None,
+				outcome: None,
+				message_id: None,
+				trapped_assets_hash: None,
+				local_fee_dot: None,
+				nonce: None,
+				tip_dot: None,
+				controllers: None,
+				raw_xcm: None,
+				entities: vec![],
+				xc20_address: Some("0xFfFFfFff1FcaCBd218EDc0EbA20Fc2308C778080".to_owned()),
 			})]
 		);
 	}
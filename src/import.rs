@@ -0,0 +1,473 @@
+use crate::{
+	incoming_parser::XcmIncomingTransfer,
+	outgoing_parser::{WeightLimit, XcmOutgoingTransfer},
+	types::{BlockTransfers, XcmTransfer},
+};
+use serde::{Serialize, de::DeserializeOwned};
+use serde_json::Value;
+use std::{
+	collections::{HashMap, HashSet},
+	fs::{self, OpenOptions},
+	io::Write,
+	path::Path,
+};
+
+// The kinds of records holding transfers, as tagged in the JSON outputs.
+const TRANSFER_KINDS: [&str; 3] = ["ReceivedTransfer", "SentTransfer", "SentTransferGroup"];
+// The fields of the transfers the compliance export doesn't keep, imported as unknown, or as their
+// default if they aren't optional.
+const RECEIVED_FIELDS_NOT_EXPORTED: [&str; 6] =
+	["asset_id", "fee_swap", "dust_lost", "trapped_assets_hash", "new_account", "xc20_address"];
+const SENT_FIELDS_NOT_EXPORTED: [&str; 12] = [
+	"origin",
+	"asset_id",
+	"fee_asset_item",
+	"pays_fees",
+	"weight_limit",
+	"burned_amount",
+	"outcome",
+	"trapped_assets_hash",
+	"local_fee_dot",
+	"nonce",
+	"tip_dot",
+	"xc20_address",
+];
+
+#[derive(Debug, Default, Serialize, PartialEq)]
+pub(crate) struct ImportReport {
+	pub(crate) imported: usize,
+	// Transfers already in the store, or repeated in the files imported
+	pub(crate) duplicates: usize,
+	// Records which aren't transfers, such as status updates, signatures or block markers
+	pub(crate) skipped: usize,
+	// Records tagged as transfers that don't follow their schema, which aren't imported
+	pub(crate) invalid: Vec<String>,
+	// The fields left unknown in the transfers imported from compliance CSV exports
+	pub(crate) incomplete: Vec<String>,
+}
+
+// Identifies a transfer regardless of the optional fields the indexer version that output it
+// filled, so the same transfer indexed twice is only stored once. Sent transfers are identified by
+// the extrinsic that sent them, and received ones by the message that credited them. Records output
+// by previous versions don't have the id of the message, so their received transfers fall back to
+// its origin, which merges identical transfers received in the same block.
+pub(crate) fn dedup_key(transfer: &XcmTransfer) -> Option<String> {
+	match transfer {
+		XcmTransfer::ReceivedTransfer(incoming) => Some(format!(
+			"received:{}:{}:{}:{}:{}",
+			incoming.block_number,
+			incoming
+				.message_id
+				.clone()
+				.unwrap_or_else(|| format!("{:?}", incoming.origin_chain)),
+			incoming.beneficiary_public_key,
+			incoming.asset,
+			incoming.amount
+		)),
+		XcmTransfer::SentTransfer(outgoing) =>
+			Some(format!("sent:{}:{}", outgoing.transfer_group_id, outgoing.asset)),
+		XcmTransfer::SentTransferGroup(group) =>
+			Some(format!("sent_group:{}", group.transfer_group_id)),
+		XcmTransfer::RoundTrip(_) |
+		XcmTransfer::StatusUpdate(_) |
+		XcmTransfer::Anomaly(_) |
+		XcmTransfer::FailedMessage(_) => None,
+	}
+}
+
+// The first exact amount of a transfer read back from a record which isn't the exact decimal
+// rendering of a raw amount, or whose float doesn't match it.
+pub(crate) fn invalid_exact_amount(transfer: &XcmTransfer) -> Option<&str> {
+	let amounts: Vec<(f64, Option<&str>)> = match transfer {
+		XcmTransfer::ReceivedTransfer(incoming) =>
+			vec![(incoming.amount, incoming.exact_amount.as_deref())],
+		XcmTransfer::SentTransfer(outgoing) =>
+			vec![(outgoing.amount, outgoing.exact_amount.as_deref())],
+		XcmTransfer::SentTransferGroup(group) => group
+			.assets
+			.iter()
+			.map(|asset| (asset.amount, asset.exact_amount.as_deref()))
+			.collect(),
+		_ => vec![],
+	};
+	amounts.into_iter().find_map(|(amount, exact_amount)| {
+		exact_amount.filter(|exact_amount| {
+			!crate::amount::is_exact_decimal(exact_amount) ||
+				exact_amount.parse::<f64>() != Ok(amount)
+		})
+	})
+}
+
+// Validates the records of a JSON output of the indexer, whatever its layout: arrays of records,
+// a record per line or blocks holding their transfers. Compliance CSV exports are read by their
+// columns instead.
+fn read_records(path: &Path, report: &mut ImportReport) -> Result<Vec<XcmTransfer>, String> {
+	let content = fs::read_to_string(path)
+		.map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+	if path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("csv")) {
+		return read_csv_records(path, &content, report);
+	}
+	let mut records = vec![];
+	for document in serde_json::Deserializer::from_str(&content).into_iter::<Value>() {
+		match document.map_err(|e| format!("{} isn't a JSON output: {}", path.display(), e))? {
+			Value::Array(values) => records.extend(values),
+			value => records.push(value),
+		}
+	}
+	let mut transfers = vec![];
+	for record in records {
+		if record.get("transfers").is_some() {
+			match serde_json::from_value::<BlockTransfers>(record) {
+				Ok(block) => transfers.extend(block.transfers),
+				Err(e) => report.invalid.push(format!("{}: invalid block: {}", path.display(), e)),
+			}
+			continue;
+		}
+		let is_transfer = record
+			.as_object()
+			.is_some_and(|object| TRANSFER_KINDS.iter().any(|kind| object.contains_key(*kind)));
+		if !is_transfer {
+			report.skipped += 1;
+			continue;
+		}
+		match serde_json::from_value::<XcmTransfer>(record) {
+			Ok(transfer) => match invalid_exact_amount(&transfer) {
+				Some(exact_amount) => report.invalid.push(format!(
+					"{}: invalid transfer: {} isn't the exact amount of the transfer",
+					path.display(),
+					exact_amount
+				)),
+				None => transfers.push(transfer),
+			},
+			Err(e) => report.invalid.push(format!("{}: invalid transfer: {}", path.display(), e)),
+		}
+	}
+	Ok(transfers)
+}
+
+// Rebuilds the transfers of a compliance CSV export from the columns identifying them, in any
+// order. The fields the export doesn't keep are left unknown, and reported once per file.
+fn read_csv_records(
+	path: &Path,
+	content: &str,
+	report: &mut ImportReport,
+) -> Result<Vec<XcmTransfer>, String> {
+	let mut rows = crate::compliance::parse_rows(content)
+		.map_err(|e| format!("{} isn't a CSV export: {}", path.display(), e))?
+		.into_iter();
+	let header = rows.next().unwrap_or_default();
+	let mut columns = HashMap::new();
+	for column in crate::compliance::HEADER.split(',') {
+		let index = header.iter().position(|name| name == column).ok_or_else(|| {
+			format!("{} isn't a compliance CSV export: no {} column", path.display(), column)
+		})?;
+		columns.insert(column, index);
+	}
+	let (mut received, mut sent) = (0, 0);
+	let mut transfers = vec![];
+	for row in rows {
+		// The header is repeated by exports concatenated together
+		if row == header {
+			continue;
+		}
+		match transfer_from_row(&columns, &row) {
+			Ok(transfer) => {
+				match transfer {
+					XcmTransfer::ReceivedTransfer(_) => received += 1,
+					_ => sent += 1,
+				}
+				transfers.push(transfer);
+			},
+			Err(e) => report.invalid.push(format!("{}: invalid row: {}", path.display(), e)),
+		}
+	}
+	if received > 0 {
+		report.incomplete.push(format!(
+			"{}: {} received transfers without {}",
+			path.display(),
+			received,
+			RECEIVED_FIELDS_NOT_EXPORTED.join(", ")
+		));
+	}
+	if sent > 0 {
+		report.incomplete.push(format!(
+			"{}: {} sent transfers without {}",
+			path.display(),
+			sent,
+			SENT_FIELDS_NOT_EXPORTED.join(", ")
+		));
+	}
+	Ok(transfers)
+}
+
+fn transfer_from_row(
+	columns: &HashMap<&str, usize>,
+	row: &[String],
+) -> Result<XcmTransfer, String> {
+	let field = |column: &str| -> Result<&str, String> {
+		row.get(columns[column])
+			.map(String::as_str)
+			.ok_or_else(|| format!("no {}", column))
+	};
+	let optional_field = |column: &str| -> Result<Option<String>, String> {
+		field(column).map(|value| Some(value.to_owned()).filter(|value| !value.is_empty()))
+	};
+	let block_number = field("block_number")?;
+	let block_number = block_number
+		.parse()
+		.map_err(|_| format!("invalid block_number {}", block_number))?;
+	let amount = field("amount")?;
+	let exact_amount =
+		Some(amount.to_owned()).filter(|amount| crate::amount::is_exact_decimal(amount));
+	let amount = amount.parse().map_err(|_| format!("invalid amount {}", amount))?;
+	let transfer_type = from_label(field("transfer_type")?)?;
+	match field("direction")? {
+		"incoming" => Ok(XcmTransfer::ReceivedTransfer(XcmIncomingTransfer {
+			block_number,
+			origin_chain: from_label(field("originator_chain")?)?,
+			message_id: optional_field("message_id")?,
+			beneficiary: field("beneficiary")?.to_owned(),
+			beneficiary_public_key: field("beneficiary_public_key")?.to_owned(),
+			asset: field("asset")?.to_owned(),
+			asset_id: None,
+			amount,
+			exact_amount,
+			transfer_type,
+			fee_swap: None,
+			entities: vec![],
+			dust_lost: None,
+			trapped_assets_hash: None,
+			new_account: false,
+			xc20_address: None,
+		})),
+		"outgoing" => Ok(XcmTransfer::SentTransfer(XcmOutgoingTransfer {
+			block_number,
+			transfer_group_id: field("transaction_reference")?.to_owned(),
+			origin: None,
+			destination_chain: from_label(field("beneficiary_chain")?)?,
+			sender: field("originator")?.to_owned(),
+			sender_public_key: optional_field("originator_public_key")?,
+			beneficiary: field("beneficiary")?.to_owned(),
+			beneficiary_public_key: field("beneficiary_public_key")?.to_owned(),
+			asset: field("asset")?.to_owned(),
+			asset_id: None,
+			amount,
+			exact_amount,
+			transfer_type,
+			fee_asset_item: 0,
+			pays_fees: false,
+			weight_limit: WeightLimit::Unlimited,
+			burned_amount: None,
+			outcome: None,
+			message_id: optional_field("message_id")?,
+			trapped_assets_hash: None,
+			local_fee_dot: None,
+			nonce: None,
+			tip_dot: None,
+			controllers: None,
+			raw_xcm: None,
+			entities: vec![],
+			xc20_address: None,
+		})),
+		direction => Err(format!("invalid direction {}", direction)),
+	}
+}
+
+// Reads back a value rendered by the compliance export: the name of an enum without data, or the
+// JSON representation of the remaining values.
+fn from_label<T: DeserializeOwned>(label: &str) -> Result<T, String> {
+	serde_json::from_str(label)
+		.or_else(|_| serde_json::from_value(Value::String(label.to_owned())))
+		.map_err(|_| format!("invalid value {}", label))
+}
+
+// Imports the transfers of the JSON outputs and compliance exports of the indexer into a store, a
+// file holding a transfer per line which `query` reads, so the outputs of several runs are
+// consolidated without indexing the chain again.
+pub(crate) fn import(files: &[impl AsRef<Path>], store: &Path) -> Result<ImportReport, String> {
+	let mut stored: HashSet<String> = if store.exists() {
+		crate::report::read_stored_transfers(store)?
+			.iter()
+			.filter_map(dedup_key)
+			.collect()
+	} else {
+		HashSet::new()
+	};
+	let mut report = ImportReport::default();
+	let mut lines = String::new();
+	for file in files {
+		for transfer in read_records(file.as_ref(), &mut report)? {
+			let Some(key) = dedup_key(&transfer) else {
+				report.skipped += 1;
+				continue;
+			};
+			if !stored.insert(key) {
+				report.duplicates += 1;
+				continue;
+			}
+			let line = serde_json::to_string(&transfer)
+				.map_err(|e| format!("Failed to serialize a transfer: {}", e))?;
+			lines.push_str(&line);
+			lines.push('\n');
+			report.imported += 1;
+		}
+	}
+	// A single write, so a failed import doesn't leave half of the transfers in the store
+	OpenOptions::new()
+		.create(true)
+		.append(true)
+		.open(store)
+		.and_then(|mut file| file.write_all(lines.as_bytes()))
+		.map_err(|e| format!("Failed to write to {}: {}", store.display(), e))?;
+	Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		incoming_parser::OriginChain,
+		types::{BlockHash, TransferType},
+	};
+
+	fn received(block_number: u32, amount: f64) -> XcmTransfer {
+		XcmTransfer::ReceivedTransfer(XcmIncomingTransfer {
+			block_number,
+			origin_chain: OriginChain::Polkadot,
+			message_id: None,
+			beneficiary: String::new(),
+			beneficiary_public_key: "0x01".to_owned(),
+			asset: "DOT".to_owned(),
+			asset_id: None,
+			amount,
+			exact_amount: None,
+			transfer_type: TransferType::Teleport,
+			fee_swap: None,
+			entities: vec![],
+			dust_lost: None,
+			trapped_assets_hash: None,
+			new_account: false,
+			xc20_address: None,
+		})
+	}
+
+	#[test]
+	fn outputs_are_imported_once() {
+		let directory = std::env::temp_dir();
+		let id = std::process::id();
+		let output = directory.join(format!("xcm_indexer_import_output_{}.json", id));
+		let blocks = directory.join(format!("xcm_indexer_import_blocks_{}.json", id));
+		let store = directory.join(format!("xcm_indexer_import_store_{}.jsonl", id));
+		let _ = fs::remove_file(&store);
+
+		// A subscription output, a document per block, with a record that isn't a transfer, one
+		// not following the schema and one whose exact amount doesn't match its amount
+		let mut mismatched = received(4, 1.0);
+		if let XcmTransfer::ReceivedTransfer(transfer) = &mut mismatched {
+			transfer.exact_amount = Some("1.5".to_owned());
+		}
+		fs::write(
+			&output,
+			format!(
+				"{}\n{}\n{}\n{}\n",
+				serde_json::to_string(&vec![received(1, 1.0), received(2, 2.0)]).unwrap(),
+				r#"{ "batch_hash": "0x00" }"#,
+				r#"[{ "ReceivedTransfer": { "block_number": "3" } }]"#,
+				serde_json::to_string(&mismatched).unwrap(),
+			),
+		)
+		.unwrap();
+		// The same transfer, in the per-block layout
+		let block = BlockTransfers {
+			block_hash: BlockHash::repeat_byte(2),
+			block_number: 2,
+			timestamp: None,
+			transfers: vec![received(2, 2.0)],
+		};
+		fs::write(&blocks, serde_json::to_string(&vec![block]).unwrap()).unwrap();
+
+		let report = import(&[&output, &blocks], &store).unwrap();
+		assert_eq!((report.imported, report.duplicates, report.skipped), (2, 1, 1));
+		assert_eq!(report.invalid.len(), 2);
+		// Importing again doesn't duplicate the stored transfers
+		assert_eq!(import(&[&blocks], &store).unwrap().duplicates, 1);
+		assert_eq!(
+			crate::report::read_stored_transfers(&store).unwrap(),
+			vec![received(1, 1.0), received(2, 2.0)]
+		);
+
+		for path in [output, blocks, store] {
+			fs::remove_file(path).unwrap();
+		}
+	}
+
+	#[test]
+	fn compliance_exports_are_imported_without_the_fields_not_exported() {
+		let directory = std::env::temp_dir();
+		let id = std::process::id();
+		let export = directory.join(format!("xcm_indexer_import_export_{}.csv", id));
+		let store = directory.join(format!("xcm_indexer_import_csv_store_{}.jsonl", id));
+		let _ = fs::remove_file(&store);
+
+		// Two identical transfers credited by different messages, and a sent one
+		let mut first = received(1, 1.5);
+		let mut second = received(1, 1.5);
+		if let (XcmTransfer::ReceivedTransfer(first), XcmTransfer::ReceivedTransfer(second)) =
+			(&mut first, &mut second)
+		{
+			first.message_id = Some("0x01".to_owned());
+			second.message_id = Some("0x02".to_owned());
+		}
+		let sent = XcmTransfer::SentTransfer(XcmOutgoingTransfer {
+			block_number: 2,
+			transfer_group_id: "2-1".to_owned(),
+			origin: None,
+			destination_chain: crate::outgoing_parser::DestinationChain::PolkadotParachain(2034),
+			sender: "Alice".to_owned(),
+			sender_public_key: Some("0x02".to_owned()),
+			beneficiary: "Bob".to_owned(),
+			beneficiary_public_key: "0x03".to_owned(),
+			asset: "USDT".to_owned(),
+			asset_id: None,
+			amount: 2.0,
+			exact_amount: Some("2".to_owned()),
+			transfer_type: TransferType::Reserve,
+			fee_asset_item: 0,
+			pays_fees: false,
+			weight_limit: WeightLimit::Unlimited,
+			burned_amount: None,
+			outcome: None,
+			message_id: Some("0xab".to_owned()),
+			trapped_assets_hash: None,
+			local_fee_dot: None,
+			nonce: None,
+			tip_dot: None,
+			controllers: None,
+			raw_xcm: None,
+			entities: vec![],
+			xc20_address: None,
+		});
+		let rows =
+			crate::compliance::render_rows(&[first.clone(), second.clone(), sent.clone()], None);
+		fs::write(&export, format!("{}\n{}\n", crate::compliance::HEADER, rows.join("\n")))
+			.unwrap();
+
+		let report = import(&[&export], &store).unwrap();
+		assert_eq!((report.imported, report.duplicates), (3, 0));
+		assert_eq!(report.incomplete.len(), 2);
+		// The exported amounts are exact
+		for transfer in [&mut first, &mut second] {
+			if let XcmTransfer::ReceivedTransfer(transfer) = transfer {
+				transfer.exact_amount = Some("1.5".to_owned());
+			}
+		}
+		assert_eq!(
+			crate::report::read_stored_transfers(&store).unwrap(),
+			vec![first, second, sent]
+		);
+
+		for path in [export, store] {
+			fs::remove_file(path).unwrap();
+		}
+	}
+}
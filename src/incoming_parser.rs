@@ -1,57 +1,161 @@
+// Without the `incoming` feature only the output types are kept.
+#[cfg(feature = "incoming")]
 use crate::{
 	Error,
+	asset_hub::runtime_types::staging_xcm::v4::{junction::Junction, junctions::Junctions},
+	block_source::BlockSource,
+	chain_spec::{Chain, ChainSpec},
+	coverage::ParseOutcome,
+	storage_lookup::StorageLookup,
+	types::{AssetMetadataValues, BlockHash},
+	xc20::MoonbeamChain,
+};
+use crate::{
+	entities::EntityTag,
 	helpers::XcmAggregatedOrigin,
-	types::{AssetMetadataValues, BlockHash, BlockNumber, DOT_DECIMALS, TransferType},
+	types::{AssetIdentifier, BlockNumber, MessageError, TransferType},
 };
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "incoming")]
 use subxt::{
-	OnlineClient, PolkadotConfig,
-	blocks::BlockRef,
-	events::{EventDetails, Phase},
-	storage::Storage,
+	PolkadotConfig,
+	events::{EventDetails, Events, Phase},
+	ext::codec::Encode,
 };
 
-#[derive(Debug, Serialize, PartialEq)]
-pub(crate) struct XcmIncomingTransfer {
-	pub(crate) block_number: BlockNumber,
-	pub(crate) origin_chain: OriginChain,
-	pub(crate) beneficiary: String,
-	pub(crate) asset: String,
-	pub(crate) amount: f64,
-	pub(crate) transfer_type: TransferType,
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+#[cfg_attr(feature = "typescript", derive(ts_rs::TS))]
+pub struct XcmIncomingTransfer {
+	pub block_number: BlockNumber,
+	pub origin_chain: OriginChain,
+	// The id of the message that credited the transfer, its topic if the sender set one. Only
+	// missing in the records output by previous versions of the indexer
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	#[cfg_attr(feature = "typescript", ts(optional))]
+	pub message_id: Option<String>,
+	pub beneficiary: String,
+	pub beneficiary_public_key: String,
+	pub asset: String,
+	// Only missing in the records output by previous versions of the indexer
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	#[cfg_attr(feature = "typescript", ts(optional))]
+	pub asset_id: Option<AssetIdentifier>,
+	pub amount: f64,
+	// The exact decimal rendering of the amount, as `amount` is the closest float to it. Only
+	// missing in the records output by previous versions of the indexer
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	#[cfg_attr(feature = "typescript", ts(optional))]
+	pub exact_amount: Option<String>,
+	pub transfer_type: TransferType,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	#[cfg_attr(feature = "typescript", ts(optional))]
+	pub fee_swap: Option<FeeSwap>,
+	// The known entities (eg, exchanges or bridges) touched by the transfer, if entity labels were
+	// loaded
+	#[serde(default, skip_serializing_if = "Vec::is_empty")]
+	#[cfg_attr(feature = "typescript", ts(as = "Option<Vec<EntityTag>>", optional))]
+	pub entities: Vec<EntityTag>,
+	// The part of the credited amount lost as dust by the beneficiary (`Balances::DustLost`), eg
+	// if the deposit couldn't keep its account above the existential deposit. Only for DOT
+	#[serde(skip_serializing_if = "Option::is_none")]
+	#[cfg_attr(feature = "typescript", ts(optional))]
+	pub dust_lost: Option<f64>,
+	// The hash of the assets trapped by the message (`PolkadotXcm::AssetsTrapped`), if part of
+	// what was sent couldn't be credited. The assets can be claimed with this hash
+	#[serde(skip_serializing_if = "Option::is_none")]
+	#[cfg_attr(feature = "typescript", ts(optional))]
+	pub trapped_assets_hash: Option<String>,
+	// Whether the beneficiary account was created by the deposit (`System::NewAccount`)
+	pub new_account: bool,
+	// Only present if the transfer comes from a Moonbeam-based chain
+	#[serde(skip_serializing_if = "Option::is_none")]
+	#[cfg_attr(feature = "typescript", ts(optional))]
+	pub xc20_address: Option<String>,
+}
+
+// A message received by AssetHub that failed, so the chains sending it can debug its delivery from
+// the indexer alone. The id of a message is its topic if the sender set one (as AssetHub does), so
+// it matches the id reported by the sender.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+#[cfg_attr(feature = "typescript", derive(ts_rs::TS))]
+pub struct XcmFailedMessage {
+	pub block_number: BlockNumber,
+	pub origin_chain: OriginChain,
+	pub message_id: String,
+	pub error: MessageError,
+	// The hash of the assets trapped by the message, if its execution stopped halfway
+	#[serde(skip_serializing_if = "Option::is_none")]
+	#[cfg_attr(feature = "typescript", ts(optional))]
+	pub trapped_assets_hash: Option<String>,
+}
+
+// The messages received in a block: the transfers they made and the messages that failed.
+#[cfg(feature = "incoming")]
+#[derive(Debug, Default)]
+pub(crate) struct IncomingMessages {
+	pub(crate) transfers: Vec<XcmIncomingTransfer>,
+	pub(crate) failed_messages: Vec<XcmFailedMessage>,
 }
 
-#[derive(Debug, Serialize, Clone, PartialEq)]
-pub(crate) enum OriginChain {
+// Part of the received asset may be swapped to DOT to pay the execution fees, which explains why
+// the credited amount is lower than the amount sent.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+#[cfg_attr(feature = "typescript", derive(ts_rs::TS))]
+pub struct FeeSwap {
+	pub asset_in: String,
+	pub amount_in: f64,
+	pub asset_out: String,
+	pub amount_out: f64,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+#[cfg_attr(feature = "typescript", derive(ts_rs::TS))]
+pub enum OriginChain {
 	Polkadot,
 	PolkadotAssetHub,
 	PolkadotParachain(u32),
+	// An origin of the runtime the indexer doesn't recognize yet (eg, a bridged consensus), named
+	// as the runtime does, so its messages are still reported
+	Unsupported(String),
 }
 
 impl From<XcmAggregatedOrigin> for OriginChain {
 	fn from(origin: XcmAggregatedOrigin) -> Self {
+		// The aggregate origin is generated from the metadata, so upgrading it may add variants.
+		// They're rendered by their debug representation instead of breaking the conversion.
+		#[allow(unreachable_patterns)]
 		match origin {
 			XcmAggregatedOrigin::Here => Self::PolkadotAssetHub,
 			XcmAggregatedOrigin::Parent => Self::Polkadot,
 			XcmAggregatedOrigin::Sibling(id) => Self::PolkadotParachain(id.0),
+			origin => Self::Unsupported(format!("{:?}", origin)),
 		}
 	}
 }
 
+#[cfg(feature = "incoming")]
 pub(crate) async fn get_incoming_xcm_transfers_at_block_hash(
-	api: &OnlineClient<PolkadotConfig>,
+	source: &impl BlockSource,
 	block_hash: BlockHash,
-) -> Result<Vec<XcmIncomingTransfer>, Error> {
-	let block = api.blocks().at(BlockRef::from_hash(block_hash)).await?;
-
-	let block_number = block.number();
-	let events = block.events().await?.iter();
-	let storage = block.storage();
+) -> Result<IncomingMessages, Error> {
+	let block = source.block(block_hash).await?;
+	parse_incoming_xcm_transfers(block.number, &block.events, &block.storage).await
+}
 
-	let mut output = Vec::new();
+// Decodes the incoming transfers from the events of a block. It doesn't need a connection to a
+// node, so it can be used with block data obtained elsewhere.
+#[cfg(feature = "incoming")]
+pub(crate) async fn parse_incoming_xcm_transfers(
+	block_number: BlockNumber,
+	events: &Events<PolkadotConfig>,
+	storage_api: &impl StorageLookup,
+) -> Result<IncomingMessages, Error> {
+	let mut output = IncomingMessages::default();
 	let mut last_issuance_events = vec![];
+	let mut last_swap_events = vec![];
+	let mut last_deposit_events = vec![];
 
-	for event in events.flatten() {
+	for event in events.iter().flatten() {
 		match (event.phase(), event.pallet_name(), event.variant_name()) {
 			(Phase::Finalization, "Assets", "Issued") |
 			(Phase::Finalization, "ForeignAssets", "Issued") => {
@@ -60,18 +164,44 @@ pub(crate) async fn get_incoming_xcm_transfers_at_block_hash(
 			(Phase::Finalization, "Balances", "Minted") => {
 				last_issuance_events.push(event);
 			},
+			(Phase::Finalization, "AssetConversion", "SwapCreditExecuted") |
+			(Phase::Finalization, "AssetConversion", "SwapExecuted") => {
+				last_swap_events.push(event);
+			},
+			(Phase::Finalization, "Balances", "DustLost") |
+			(Phase::Finalization, "PolkadotXcm", "AssetsTrapped") |
+			(Phase::Finalization, "System", "NewAccount") => {
+				last_deposit_events.push(event);
+			},
 			(Phase::Finalization, "MessageQueue", "Processed") => {
-				if let Ok(payload) = generate_xcm_received_payload(
-					&storage,
+				let deposits = last_issuance_events.len();
+				output.failed_messages.extend(failed_message(
+					block_number,
+					&event,
+					&last_deposit_events,
+				));
+				let payload = generate_xcm_received_payload(
+					storage_api,
 					block_number,
 					last_issuance_events,
+					last_swap_events,
+					&last_deposit_events,
 					event,
 				)
-				.await
-				{
-					output.extend(payload);
+				.await;
+				crate::coverage::record_message(message_outcome(&payload, deposits));
+				if let Ok(payload) = payload {
+					output.transfers.extend(payload);
 				}
 				last_issuance_events = vec![];
+				last_swap_events = vec![];
+				last_deposit_events = vec![];
+			},
+			// Messages discarded or set aside by the queue aren't executed, so they don't
+			// deposit anything
+			(Phase::Finalization, "MessageQueue", "ProcessingFailed") |
+			(Phase::Finalization, "MessageQueue", "OverweightEnqueued") => {
+				output.failed_messages.extend(failed_message(block_number, &event, &[]));
 			},
 			_ => (),
 		}
@@ -80,10 +210,82 @@ pub(crate) async fn get_incoming_xcm_transfers_at_block_hash(
 	Ok(output)
 }
 
+// Every asset deposited by a message should become a transfer. Messages not depositing any asset
+// aren't transfers, eg version notifications or Transact.
+#[cfg(feature = "incoming")]
+fn message_outcome(
+	payload: &Result<Vec<XcmIncomingTransfer>, Error>,
+	deposits: usize,
+) -> ParseOutcome {
+	match payload {
+		Ok(_) if deposits == 0 => ParseOutcome::Skipped("no_assets_deposited".to_owned()),
+		Ok(transfers) if transfers.len() == deposits => ParseOutcome::Parsed,
+		Ok(transfers) if transfers.is_empty() =>
+			ParseOutcome::Skipped("unrecognized_deposits".to_owned()),
+		Ok(_) => ParseOutcome::PartiallyParsed("unrecognized_deposits".to_owned()),
+		Err(Error::UnsuccessfulXcmMessage) => ParseOutcome::Skipped("message_failed".to_owned()),
+		Err(Error::GeneratePayloadFailed) =>
+			ParseOutcome::Skipped("undecodable_message".to_owned()),
+		Err(_) => ParseOutcome::Skipped("storage_lookup_failed".to_owned()),
+	}
+}
+
+// The failure of a message reported by an event of the message queue, if any. Messages whose
+// execution failed may have trapped part of their assets.
+#[cfg(feature = "incoming")]
+fn failed_message(
+	block_number: BlockNumber,
+	event: &EventDetails<PolkadotConfig>,
+	last_deposit_events: &[EventDetails<PolkadotConfig>],
+) -> Option<XcmFailedMessage> {
+	use crate::asset_hub::message_queue::events::{
+		OverweightEnqueued, Processed, ProcessingFailed,
+	};
+	let (id, origin, error) = if let Ok(Some(processed_event)) = event.as_event::<Processed>() {
+		if processed_event.success {
+			return None;
+		}
+		(processed_event.id.0, processed_event.origin, MessageError::ExecutionFailed)
+	} else if let Ok(Some(failed_event)) = event.as_event::<ProcessingFailed>() {
+		(failed_event.id.0, failed_event.origin, MessageError::from(&failed_event.error))
+	} else if let Ok(Some(overweight_event)) = event.as_event::<OverweightEnqueued>() {
+		(
+			overweight_event.id,
+			overweight_event.origin,
+			MessageError::Overweight { ref_time: None, proof_size: None },
+		)
+	} else {
+		return None;
+	};
+
+	Some(XcmFailedMessage {
+		block_number,
+		origin_chain: OriginChain::from(origin),
+		message_id: crate::helpers::to_hex_string(&id),
+		error,
+		trapped_assets_hash: trapped_assets_hash(last_deposit_events),
+	})
+}
+
+// The hash of the assets trapped by a message among the events of its deposits.
+#[cfg(feature = "incoming")]
+fn trapped_assets_hash(last_deposit_events: &[EventDetails<PolkadotConfig>]) -> Option<String> {
+	last_deposit_events.iter().find_map(|event| {
+		event
+			.as_event::<crate::asset_hub::polkadot_xcm::events::AssetsTrapped>()
+			.ok()
+			.flatten()
+			.map(|trapped_event| crate::helpers::to_hex_string(trapped_event.hash.as_ref()))
+	})
+}
+
+#[cfg(feature = "incoming")]
 async fn generate_xcm_received_payload(
-	storage_api: &Storage<PolkadotConfig, OnlineClient<PolkadotConfig>>,
+	storage_api: &impl StorageLookup,
 	block_number: BlockNumber,
 	last_issuance_events: Vec<EventDetails<PolkadotConfig>>,
+	last_swap_events: Vec<EventDetails<PolkadotConfig>>,
+	last_deposit_events: &[EventDetails<PolkadotConfig>],
 	processed_message_event: EventDetails<PolkadotConfig>,
 ) -> Result<Vec<XcmIncomingTransfer>, Error> {
 	let processed_message_event_decoded = if let Ok(Some(event)) =
@@ -98,10 +300,24 @@ async fn generate_xcm_received_payload(
 		return Err(Error::UnsuccessfulXcmMessage);
 	}
 
-	// Extract xcm origin from the message_queue event
+	// Extract xcm origin and id from the message_queue event
 	let origin_chain = OriginChain::from(processed_message_event_decoded.origin);
+	let message_id = crate::helpers::to_hex_string(&processed_message_event_decoded.id.0);
+
+	// Extract the swaps executed by the message to pay fees. They only enrich the transfers, so a
+	// swap whose assets can't be resolved is left out instead of dropping the whole message.
+	let mut fee_swaps = vec![];
+	for swap_event in last_swap_events {
+		if let Some(fee_swap) = decode_fee_swap(storage_api, &swap_event).await.ok().flatten() {
+			fee_swaps.push(fee_swap);
+		}
+	}
+
+	// Extract the assets the message couldn't credit, which are trapped
+	let trapped_assets_hash = trapped_assets_hash(last_deposit_events);
 
 	// Extract all relevant info from issuance_events.
+	let moonbeam_chain = MoonbeamChain::from_origin(&origin_chain);
 	let mut received_assets = vec![];
 	for issuance_event in last_issuance_events {
 		let issuance_info = match (
@@ -121,17 +337,21 @@ async fn generate_xcm_received_payload(
 		) {
 			// DOT from relay is always Teleport
 			(OriginChain::Polkadot, Some(minted_event), None, None) => Some((
-				"DOT".to_owned(),
-				crate::helpers::to_decimal_f64(minted_event.amount, DOT_DECIMALS),
-				crate::helpers::convert_account_id_to_ah_address(&minted_event.who),
+				crate::chain_spec::native_token_symbol(),
+				AssetIdentifier::Native,
+				(minted_event.amount, crate::chain_spec::native_token_decimals()),
+				minted_event.who,
 				TransferType::Teleport,
+				None,
 			)),
 			// DOT from sibling parachains is always reserve
 			(OriginChain::PolkadotParachain(_), Some(minted_event), None, None) => Some((
-				"DOT".to_owned(),
-				crate::helpers::to_decimal_f64(minted_event.amount, DOT_DECIMALS),
-				crate::helpers::convert_account_id_to_ah_address(&minted_event.who),
+				crate::chain_spec::native_token_symbol(),
+				AssetIdentifier::Native,
+				(minted_event.amount, crate::chain_spec::native_token_decimals()),
+				minted_event.who,
 				TransferType::Reserve,
+				moonbeam_chain.and_then(|chain| chain.xc20_address(1, &Junctions::Here.encode())),
 			)),
 			(OriginChain::PolkadotParachain(_), None, Some(issue_event), None) => {
 				let asset_id = issue_event.asset_id;
@@ -140,9 +360,17 @@ async fn generate_xcm_received_payload(
 					crate::helpers::extract_asset_metadata_values(storage_api, &asset_id).await?;
 				Some((
 					asset,
-					crate::helpers::to_decimal_f64(issue_event.amount, decimals),
-					crate::helpers::convert_account_id_to_ah_address(&issue_event.owner),
+					AssetIdentifier::Asset(asset_id),
+					(issue_event.amount, decimals),
+					issue_event.owner,
 					TransferType::Reserve,
+					moonbeam_chain.and_then(|chain| {
+						let interior = Junctions::X2([
+							Junction::PalletInstance(Chain::ASSETS_PALLET_INDEX),
+							Junction::GeneralIndex(asset_id as u128),
+						]);
+						chain.xc20_address(0, &interior.encode())
+					}),
 				))
 			},
 			(OriginChain::PolkadotParachain(sibling_para_id), None, None, Some(issue_event)) => {
@@ -165,22 +393,67 @@ async fn generate_xcm_received_payload(
 					};
 				Some((
 					asset,
-					crate::helpers::to_decimal_f64(issue_event.amount, decimals),
-					crate::helpers::convert_account_id_to_ah_address(&issue_event.owner),
+					AssetIdentifier::foreign_asset(&asset_id),
+					(issue_event.amount, decimals),
+					issue_event.owner,
 					transfer_type,
+					moonbeam_chain.and_then(|chain| {
+						chain.xc20_address(asset_id.parents, &asset_id.interior.encode())
+					}),
 				))
 			},
 			// Any other combination isn't a valid Xcm transfer
 			_ => None,
 		};
-		if let Some((asset, amount, beneficiary, transfer_type)) = issuance_info {
+		if let Some((
+			asset,
+			asset_id,
+			(amount, decimals),
+			beneficiary,
+			transfer_type,
+			xc20_address,
+		)) = issuance_info
+		{
+			let fee_swap = find_fee_swap(&fee_swaps, &asset_id);
+			let is_native = issuance_event.variant_name() == "Minted";
+			let dust_lost = last_deposit_events
+				.iter()
+				.filter_map(|event| {
+					event.as_event::<crate::asset_hub::balances::events::DustLost>().ok().flatten()
+				})
+				.filter(|dust_event| is_native && dust_event.account == beneficiary)
+				.map(|dust_event| dust_event.amount)
+				.reduce(|total, amount| total + amount)
+				.map(|amount| {
+					crate::amount::to_decimal_f64(
+						amount,
+						crate::chain_spec::native_token_decimals(),
+					)
+				});
+			let new_account = last_deposit_events.iter().any(|event| {
+				event
+					.as_event::<crate::asset_hub::system::events::NewAccount>()
+					.ok()
+					.flatten()
+					.is_some_and(|new_account_event| new_account_event.account == beneficiary)
+			});
 			received_assets.push(XcmIncomingTransfer {
 				block_number,
 				origin_chain: origin_chain.clone(),
-				beneficiary,
+				message_id: Some(message_id.clone()),
+				beneficiary: crate::helpers::convert_account_id_to_address(&beneficiary),
+				beneficiary_public_key: crate::helpers::to_hex_string(&beneficiary.0),
 				asset,
-				amount,
+				asset_id: Some(asset_id),
+				amount: crate::amount::to_decimal_f64(amount, decimals),
+				exact_amount: Some(crate::amount::to_decimal_string(amount, decimals)),
 				transfer_type,
+				fee_swap,
+				entities: vec![],
+				dust_lost,
+				trapped_assets_hash: trapped_assets_hash.clone(),
+				new_account,
+				xc20_address,
 			})
 		};
 	}
@@ -188,39 +461,288 @@ async fn generate_xcm_received_payload(
 	Ok(received_assets)
 }
 
-#[cfg(test)]
+// The swap paying the fees with the received asset. Swaps are matched by the asset they take
+// rather than by its name, as several assets may share one.
+#[cfg(feature = "incoming")]
+fn find_fee_swap(
+	fee_swaps: &[(AssetIdentifier, FeeSwap)],
+	asset_id: &AssetIdentifier,
+) -> Option<FeeSwap> {
+	fee_swaps
+		.iter()
+		.find(|(asset_in_id, _)| asset_in_id == asset_id)
+		.map(|(_, fee_swap)| fee_swap.clone())
+}
+
+#[cfg(feature = "incoming")]
+async fn decode_fee_swap(
+	storage_api: &impl StorageLookup,
+	swap_event: &EventDetails<PolkadotConfig>,
+) -> Result<Option<(AssetIdentifier, FeeSwap)>, Error> {
+	let (amount_in, amount_out, path) = match (
+		swap_event
+			.as_event::<crate::asset_hub::asset_conversion::events::SwapCreditExecuted>()
+			.ok()
+			.flatten(),
+		swap_event
+			.as_event::<crate::asset_hub::asset_conversion::events::SwapExecuted>()
+			.ok()
+			.flatten(),
+	) {
+		(Some(swap), None) => (swap.amount_in, swap.amount_out, swap.path),
+		(None, Some(swap)) => (swap.amount_in, swap.amount_out, swap.path),
+		_ => return Ok(None),
+	};
+
+	// The path contains every asset involved in the swap, the first one is the asset given and the
+	// last one the asset received.
+	let (Some((location_in, _)), Some((location_out, _))) = (path.first(), path.last()) else {
+		return Ok(None);
+	};
+	let AssetMetadataValues { asset_name: asset_in, decimals: decimals_in } =
+		crate::helpers::extract_location_metadata_values(storage_api, location_in).await?;
+	let AssetMetadataValues { asset_name: asset_out, decimals: decimals_out } =
+		crate::helpers::extract_location_metadata_values(storage_api, location_out).await?;
+
+	Ok(Some((
+		AssetIdentifier::from_location(location_in),
+		FeeSwap {
+			asset_in,
+			amount_in: crate::amount::to_decimal_f64(amount_in, decimals_in),
+			asset_out,
+			amount_out: crate::amount::to_decimal_f64(amount_out, decimals_out),
+		},
+	)))
+}
+
+#[cfg(all(test, feature = "incoming"))]
 mod tests {
 	use super::*;
+	use crate::asset_hub::runtime_types::staging_xcm::v4::{
+		junction::{Junction, NetworkId},
+		junctions::Junctions,
+		location::Location,
+	};
+	use subxt::OnlineClient;
+
+	// The location of WETH, bridged from Ethereum through Snowbridge
+	fn weth_location() -> Location {
+		Location {
+			parents: 2,
+			interior: Junctions::X2([
+				Junction::GlobalConsensus(NetworkId::Ethereum { chain_id: 1 }),
+				Junction::AccountKey20 {
+					network: None,
+					key: hex::decode("c02aaa39b223fe8d0a0e5c4f27ead9083c756cc2")
+						.unwrap()
+						.try_into()
+						.unwrap(),
+				},
+			]),
+		}
+	}
+
+	#[test]
+	fn message_outcome_explains_skipped_messages() {
+		assert_eq!(
+			message_outcome(&Ok(vec![]), 0),
+			ParseOutcome::Skipped("no_assets_deposited".to_owned())
+		);
+		assert_eq!(
+			message_outcome(&Ok(vec![]), 1),
+			ParseOutcome::Skipped("unrecognized_deposits".to_owned())
+		);
+		assert_eq!(
+			message_outcome(&Err(Error::UnsuccessfulXcmMessage), 1),
+			ParseOutcome::Skipped("message_failed".to_owned())
+		);
+		assert_eq!(
+			message_outcome(&Err(Error::GeneratePayloadFailed), 0),
+			ParseOutcome::Skipped("undecodable_message".to_owned())
+		);
+	}
+
+	#[tokio::test]
+	async fn failed_messages_are_reported_with_their_error() {
+		use crate::{
+			asset_hub::runtime_types::{
+				asset_hub_polkadot_runtime::RuntimeEvent,
+				frame_support::traits::messages::ProcessMessageError,
+				frame_system::{EventRecord, Phase as RecordPhase},
+				pallet_message_queue::pallet::Event,
+				polkadot_parachain_primitives::primitives::Id,
+				sp_weights::weight_v2::Weight,
+			},
+			asset_overrides::AssetOverrides,
+			raw_blocks::InjectedMetadata,
+		};
+		use subxt::utils::H256;
+
+		let finalization = |event| EventRecord {
+			phase: RecordPhase::Finalization,
+			event: RuntimeEvent::MessageQueue(event),
+			topics: Vec::<H256>::new(),
+		};
+		let records = vec![
+			finalization(Event::ProcessingFailed {
+				id: H256::repeat_byte(1),
+				origin: XcmAggregatedOrigin::Sibling(Id(2034)),
+				error: ProcessMessageError::Unsupported,
+			}),
+			finalization(Event::Processed {
+				id: H256::repeat_byte(2),
+				origin: XcmAggregatedOrigin::Parent,
+				weight_used: Weight { ref_time: 0, proof_size: 0 },
+				success: false,
+			}),
+			finalization(Event::OverweightEnqueued {
+				id: [3; 32],
+				origin: XcmAggregatedOrigin::Sibling(Id(2004)),
+				page_index: 0,
+				message_index: 0,
+			}),
+			// Successful messages aren't reported
+			finalization(Event::Processed {
+				id: H256::repeat_byte(4),
+				origin: XcmAggregatedOrigin::Parent,
+				weight_used: Weight { ref_time: 0, proof_size: 0 },
+				success: true,
+			}),
+		];
+		let events = Events::decode_from(records.encode(), crate::raw_blocks::metadata().unwrap());
+
+		let incoming_messages =
+			parse_incoming_xcm_transfers(10, &events, &InjectedMetadata(AssetOverrides::default()))
+				.await
+				.unwrap();
+		assert!(incoming_messages.transfers.is_empty());
+		assert_eq!(
+			incoming_messages
+				.failed_messages
+				.into_iter()
+				.map(|failed_message| (
+					failed_message.origin_chain,
+					failed_message.message_id,
+					failed_message.error
+				))
+				.collect::<Vec<_>>(),
+			vec![
+				(
+					OriginChain::PolkadotParachain(2034),
+					format!("0x{}", "01".repeat(32)),
+					MessageError::Unsupported
+				),
+				(
+					OriginChain::Polkadot,
+					format!("0x{}", "02".repeat(32)),
+					MessageError::ExecutionFailed
+				),
+				(
+					OriginChain::PolkadotParachain(2004),
+					format!("0x{}", "03".repeat(32)),
+					MessageError::Overweight { ref_time: None, proof_size: None }
+				),
+			]
+		);
+	}
+
+	#[test]
+	fn origin_chain_from_aggregated_origin() {
+		use crate::asset_hub::runtime_types::polkadot_parachain_primitives::primitives::Id;
+		assert_eq!(OriginChain::from(XcmAggregatedOrigin::Here), OriginChain::PolkadotAssetHub);
+		assert_eq!(OriginChain::from(XcmAggregatedOrigin::Parent), OriginChain::Polkadot);
+		assert_eq!(
+			OriginChain::from(XcmAggregatedOrigin::Sibling(Id(1001))),
+			OriginChain::PolkadotParachain(1001)
+		);
+		// Unrecognized origins keep their name in the output
+		assert_eq!(
+			serde_json::to_string(&OriginChain::Unsupported("Snowbridge".to_owned())).unwrap(),
+			r#"{"Unsupported":"Snowbridge"}"#
+		);
+	}
+
+	#[test]
+	fn fee_swaps_are_matched_by_asset_id() {
+		let swap = |asset_in: &str, amount_in: f64| FeeSwap {
+			asset_in: asset_in.to_owned(),
+			amount_in,
+			asset_out: "DOT".to_owned(),
+			amount_out: 0.1,
+		};
+		// Two assets sharing a name only match the swap of their own id
+		let fee_swaps = vec![
+			(AssetIdentifier::Asset(1984), swap("USDT", 1.0)),
+			(AssetIdentifier::foreign_asset(&weth_location()), swap("USDT", 2.0)),
+		];
+		assert_eq!(
+			find_fee_swap(&fee_swaps, &AssetIdentifier::foreign_asset(&weth_location())),
+			Some(swap("USDT", 2.0))
+		);
+		assert_eq!(
+			find_fee_swap(&fee_swaps, &AssetIdentifier::Asset(1984)),
+			Some(swap("USDT", 1.0))
+		);
+		assert_eq!(find_fee_swap(&fee_swaps, &AssetIdentifier::Asset(1337)), None);
+	}
 
 	#[tokio::test]
 	async fn get_incoming_xcm_transfers_at_block_hash_with_reserve_transfer() {
-		let api = OnlineClient::<PolkadotConfig>::from_url(crate::types::ASSET_HUB_RPC_ENDPOINT)
-			.await
-			.unwrap();
+		let api = OnlineClient::<PolkadotConfig>::from_url(Chain::RPC_ENDPOINT).await.unwrap();
 
 		// Hydration ordered a transfer of DOT and USDC
 		let block_hash_hex = "0x3ef4a4e3a4032c02343e335a4ed35f1ed4a78365c847b4f58c5e869d302add66";
 		let block_hash: BlockHash = block_hash_hex.parse().unwrap();
-		let xcm_transfer =
-			get_incoming_xcm_transfers_at_block_hash(&api, block_hash).await.unwrap();
+		let mut xcm_transfer = get_incoming_xcm_transfers_at_block_hash(&api, block_hash)
+			.await
+			.unwrap()
+			.transfers;
+		for transfer in &mut xcm_transfer {
+			let message_id = transfer.message_id.take().unwrap();
+			assert!(message_id.starts_with("0x") && message_id.len() == 66);
+		}
 		assert_eq!(
 			xcm_transfer,
 			vec![
 				XcmIncomingTransfer {
 					block_number: 8_900_358,
 					origin_chain: OriginChain::PolkadotParachain(2034),
+					message_id: None,
 					beneficiary: "15B8BaJCPi1HWY7Rty23t3PEUc9d36PGGBHSJ2Y4xzdwvaLK".to_owned(),
+					beneficiary_public_key:
+						"0xb8a990cfdf590a4f2b14a4e214a8483e668bea0f06e23f65bccdafe4a0a05b74"
+							.to_owned(),
 					asset: "DOT".to_owned(),
+					asset_id: Some(AssetIdentifier::Native),
 					amount: 7.5433009963,
-					transfer_type: TransferType::Reserve
+					exact_amount: Some("7.5433009963".to_owned()),
+					transfer_type: TransferType::Reserve,
+					fee_swap: None,
+					entities: vec![],
+					dust_lost: None,
+					trapped_assets_hash: None,
+					new_account: false,
+					xc20_address: None,
 				},
 				XcmIncomingTransfer {
 					block_number: 8_900_358,
 					origin_chain: OriginChain::PolkadotParachain(2034),
+					message_id: None,
 					beneficiary: "12F62Gzyig1CpWEB9qaU7QkmRf4SmvnXJ3BER1poLxDoq12K".to_owned(),
+					beneficiary_public_key:
+						"0x36fa931d38b36181269a8e7c64e706c79f7e6b95c904f8da7189071ec90dd425"
+							.to_owned(),
 					asset: "USD Coin".to_owned(),
+					asset_id: Some(AssetIdentifier::Asset(1337)),
 					amount: 49.292041,
-					transfer_type: TransferType::Reserve
+					exact_amount: Some("49.292041".to_owned()),
+					transfer_type: TransferType::Reserve,
+					fee_swap: None,
+					entities: vec![],
+					dust_lost: None,
+					trapped_assets_hash: None,
+					new_account: false,
+					xc20_address: None,
 				}
 			]
 		);
@@ -228,43 +750,90 @@ mod tests {
 		// Moonbeam ordered a transfer of USD Coin
 		let block_hash_hex = "0x5e45bdca2951ac156e0459a461de60a1ee0a4263b17d7d6a95e4f28b9955c16b";
 		let block_hash: BlockHash = block_hash_hex.parse().unwrap();
-		let xcm_transfer =
-			get_incoming_xcm_transfers_at_block_hash(&api, block_hash).await.unwrap();
+		let mut xcm_transfer = get_incoming_xcm_transfers_at_block_hash(&api, block_hash)
+			.await
+			.unwrap()
+			.transfers;
+		for transfer in &mut xcm_transfer {
+			let message_id = transfer.message_id.take().unwrap();
+			assert!(message_id.starts_with("0x") && message_id.len() == 66);
+		}
 		assert_eq!(
 			xcm_transfer,
 			vec![XcmIncomingTransfer {
 				block_number: 8_898_884,
 				origin_chain: OriginChain::PolkadotParachain(2004),
+				message_id: None,
 				beneficiary: "13KsaHFcQKSTd4m73Ub9yVwM1JGCZvipMyTZonHEXEceFYwS".to_owned(),
+				beneficiary_public_key:
+					"0x66dd21797b3ab4b27ff723196324a33f7a80683bda78e0fcebf8e7cebb09a358".to_owned(),
 				asset: "USD Coin".to_owned(),
+				asset_id: Some(AssetIdentifier::Asset(1337)),
 				amount: 9_401.612723,
-				transfer_type: TransferType::Reserve
+				exact_amount: Some("9401.612723".to_owned()),
+				transfer_type: TransferType::Reserve,
+				fee_swap: None,
+				entities: vec![],
+				dust_lost: None,
+				trapped_assets_hash: None,
+				new_account: false,
+				xc20_address: Some("0xFFfffffF7D2B0B761Af01Ca8e25242976ac0aD7D".to_owned()),
 			}]
 		);
 
 		// BridgeHub ordered a transfer of WETH
 		let block_hash_hex = "0x4bd6df2a92068d2cca88057e3263add68626bb563a8ff5c3435ad5478e6cc0e3";
 		let block_hash: BlockHash = block_hash_hex.parse().unwrap();
-		let xcm_transfer =
-			get_incoming_xcm_transfers_at_block_hash(&api, block_hash).await.unwrap();
+		let mut xcm_transfer = get_incoming_xcm_transfers_at_block_hash(&api, block_hash)
+			.await
+			.unwrap()
+			.transfers;
+		for transfer in &mut xcm_transfer {
+			let message_id = transfer.message_id.take().unwrap();
+			assert!(message_id.starts_with("0x") && message_id.len() == 66);
+		}
 		assert_eq!(
 			xcm_transfer,
 			vec![
 				XcmIncomingTransfer {
 					block_number: 8898898,
 					origin_chain: OriginChain::PolkadotParachain(1002),
+					message_id: None,
 					beneficiary: "12aoZXwbUzsv3z5HF5HCrtEwBJYCeKne6rYsxFEKDZ86Wdv8".to_owned(),
+					beneficiary_public_key:
+						"0x460411e07f93dc4bc2b3a6cb67dad89ca26e8a54054d13916f74c982595c2e0e"
+							.to_owned(),
 					asset: "DOT".to_owned(),
+					asset_id: Some(AssetIdentifier::Native),
 					amount: 0.0325895284,
-					transfer_type: TransferType::Reserve
+					exact_amount: Some("0.0325895284".to_owned()),
+					transfer_type: TransferType::Reserve,
+					fee_swap: None,
+					entities: vec![],
+					dust_lost: None,
+					trapped_assets_hash: None,
+					new_account: false,
+					xc20_address: None,
 				},
 				XcmIncomingTransfer {
 					block_number: 8_898_898,
 					origin_chain: OriginChain::PolkadotParachain(1002),
+					message_id: None,
 					beneficiary: "12aoZXwbUzsv3z5HF5HCrtEwBJYCeKne6rYsxFEKDZ86Wdv8".to_owned(),
+					beneficiary_public_key:
+						"0x460411e07f93dc4bc2b3a6cb67dad89ca26e8a54054d13916f74c982595c2e0e"
+							.to_owned(),
 					asset: "Wrapped Ether".to_owned(),
+					asset_id: Some(AssetIdentifier::foreign_asset(&weth_location())),
 					amount: 0.0001,
-					transfer_type: TransferType::Reserve
+					exact_amount: Some("0.0001".to_owned()),
+					transfer_type: TransferType::Reserve,
+					fee_swap: None,
+					entities: vec![],
+					dust_lost: None,
+					trapped_assets_hash: None,
+					new_account: false,
+					xc20_address: None,
 				}
 			]
 		);
@@ -272,24 +841,39 @@ mod tests {
 
 	#[tokio::test]
 	async fn get_incoming_xcm_transfers_at_block_hash_with_teleport() {
-		let api = OnlineClient::<PolkadotConfig>::from_url(crate::types::ASSET_HUB_RPC_ENDPOINT)
-			.await
-			.unwrap();
+		let api = OnlineClient::<PolkadotConfig>::from_url(Chain::RPC_ENDPOINT).await.unwrap();
 
 		// The relaychain teleported DOT
 		let block_hash_hex = "0x64142906eb815d290cb6678de1cb5d00d011b1c4baa30eae779093cd02e1dde8";
 		let block_hash: BlockHash = block_hash_hex.parse().unwrap();
-		let xcm_transfer =
-			get_incoming_xcm_transfers_at_block_hash(&api, block_hash).await.unwrap();
+		let mut xcm_transfer = get_incoming_xcm_transfers_at_block_hash(&api, block_hash)
+			.await
+			.unwrap()
+			.transfers;
+		for transfer in &mut xcm_transfer {
+			let message_id = transfer.message_id.take().unwrap();
+			assert!(message_id.starts_with("0x") && message_id.len() == 66);
+		}
 		assert_eq!(
 			xcm_transfer,
 			vec![XcmIncomingTransfer {
 				block_number: 8_901_175,
 				origin_chain: OriginChain::Polkadot,
+				message_id: None,
 				beneficiary: "13p9Fcn4eVJzHZL7Z6RXbRhEzjAYLU26BohYmy18yHXnMovT".to_owned(),
+				beneficiary_public_key:
+					"0x7c6cc798551f8869d1ab4bbf3fd56ef99dfd21294b7b052c324298f00894bf0c".to_owned(),
 				asset: "DOT".to_owned(),
+				asset_id: Some(AssetIdentifier::Native),
 				amount: 8.8602977965,
-				transfer_type: TransferType::Teleport
+				exact_amount: Some("8.8602977965".to_owned()),
+				transfer_type: TransferType::Teleport,
+				fee_swap: None,
+				entities: vec![],
+				dust_lost: None,
+				trapped_assets_hash: None,
+				new_account: false,
+				xc20_address: None,
 			},]
 		);
 	}
@@ -0,0 +1,112 @@
+pub use analytics::{
+	RoundTrip, TransferDirection, TransferStatus, TransferStatusUpdate, VolumeAnomaly,
+	VolumeDeviation,
+};
+#[cfg(not(target_arch = "wasm32"))]
+pub use cli::CliCommand;
+pub use controllers::{Multisig, SenderControllers};
+pub use entities::{EntityKind, EntityRole, EntityTag};
+pub use error::Error;
+pub use incoming_parser::{FeeSwap, OriginChain, XcmIncomingTransfer};
+pub use outgoing_parser::{
+	DestinationChain, ExecutionOutcome, TransferOrigin, WeightLimit, XcmOutgoingAsset,
+	XcmOutgoingTransfer, XcmOutgoingTransferGroup,
+};
+pub use raw_xcm::{ForwardedMessage, RawXcm};
+pub use types::{
+	AssetIdentifier, BlockHash, BlockMarker, BlockNumber, BlockTransfers, TransferType, XcmTransfer,
+};
+
+#[subxt::subxt(runtime_metadata_path = "./artifacts/ah_metadata.scale")]
+pub mod asset_hub {}
+#[cfg(feature = "email")]
+pub(crate) mod alerts;
+pub(crate) mod amount;
+#[cfg(feature = "amqp")]
+pub(crate) mod amqp;
+pub(crate) mod analytics;
+#[cfg(feature = "http-api")]
+pub(crate) mod api;
+pub(crate) mod asset_overrides;
+pub(crate) mod batch;
+#[cfg(feature = "bigquery")]
+pub(crate) mod bigquery;
+pub(crate) mod block_source;
+pub(crate) mod bridge;
+pub(crate) mod chain_spec;
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) mod cli;
+pub(crate) mod compliance;
+pub(crate) mod congestion;
+pub(crate) mod controllers;
+pub(crate) mod coverage;
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) mod cross_check;
+pub(crate) mod dead_letters;
+pub(crate) mod entities;
+pub(crate) mod error;
+#[cfg(feature = "ffi")]
+pub(crate) mod ffi;
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) mod finality;
+#[cfg(feature = "http-api")]
+pub(crate) mod flows;
+#[cfg(feature = "fuzzing")]
+pub mod fuzzing;
+pub(crate) mod hash_chain;
+pub(crate) mod helpers;
+pub(crate) mod import;
+pub(crate) mod incoming_parser;
+pub(crate) mod locations;
+pub(crate) mod metadata_snapshot;
+pub(crate) mod migrations;
+#[cfg(feature = "mqtt")]
+pub(crate) mod mqtt;
+pub(crate) mod outgoing_parser;
+pub(crate) mod output;
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) mod prefetch;
+#[cfg(feature = "proto")]
+pub(crate) mod proto;
+#[cfg(feature = "python")]
+mod python;
+pub(crate) mod query;
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) mod rate_limiter;
+pub(crate) mod raw_blocks;
+pub(crate) mod raw_xcm;
+pub(crate) mod recent_transfers;
+pub(crate) mod reconcile;
+pub(crate) mod report;
+pub(crate) mod reserves;
+pub(crate) mod resume;
+pub(crate) mod retention;
+pub(crate) mod retry_list;
+pub(crate) mod rules;
+pub(crate) mod runtime_apis;
+pub(crate) mod select;
+pub(crate) mod sharding;
+pub(crate) mod signing;
+pub(crate) mod sink;
+pub(crate) mod state_proofs;
+pub(crate) mod storage_lookup;
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) mod subscription;
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) mod summary;
+pub(crate) mod supply;
+pub(crate) mod table;
+pub(crate) mod telemetry;
+pub(crate) mod template;
+#[cfg(feature = "http-api")]
+pub(crate) mod totals;
+pub(crate) mod types;
+#[cfg(feature = "typescript")]
+pub(crate) mod typescript;
+pub(crate) mod wait_for;
+pub(crate) mod watch_lists;
+pub(crate) mod watchdog;
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) mod work_queue;
+pub(crate) mod xc20;
+pub(crate) mod xcm_versions;
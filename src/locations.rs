@@ -0,0 +1,302 @@
+use crate::{
+	asset_hub::runtime_types::staging_xcm::v4::{
+		junction::{Junction, NetworkId},
+		junctions::Junctions,
+		location::Location,
+	},
+	helpers::to_hex_string,
+};
+use std::str::FromStr;
+use subxt::ext::codec::{Decode, Encode};
+
+// Renders a location as a canonical string, eg
+// `parents=1/Parachain(2004)/PalletInstance(50)/GeneralIndex(3014)`, which `parse_location` reads
+// back. Unlike the Debug rendering of the generated types, it doesn't depend on the subxt version,
+// so it identifies the foreign assets in the outputs, the CLI and the configuration files alike.
+pub(crate) fn encode_location(location: &Location) -> String {
+	let mut encoded = format!("parents={}", location.parents);
+	for junction in junctions(&location.interior) {
+		encoded.push('/');
+		encoded.push_str(&encode_junction(junction));
+	}
+	encoded
+}
+
+// Parses a location in its canonical string form. Spaces around the junctions are tolerated, but
+// the names of the junctions and networks are case sensitive.
+pub(crate) fn parse_location(s: &str) -> Result<Location, String> {
+	let mut segments = s.trim().split('/').map(str::trim);
+	let parents = segments
+		.next()
+		.and_then(|parents| parents.strip_prefix("parents="))
+		.and_then(|parents| parents.trim().parse().ok())
+		.ok_or_else(|| format!("{} isn't a location, it must start with `parents=<N>`", s))?;
+	let junctions = segments
+		.map(parse_junction)
+		.collect::<Result<Vec<_>, _>>()
+		.map_err(|e| format!("{} isn't a location: {}", s, e))?;
+	let interior = to_junctions(junctions).map_err(|e| format!("{} isn't a location: {}", s, e))?;
+	Ok(Location { parents, interior })
+}
+
+fn junctions(interior: &Junctions) -> &[Junction] {
+	match interior {
+		Junctions::Here => &[],
+		Junctions::X1(junctions) => junctions,
+		Junctions::X2(junctions) => junctions,
+		Junctions::X3(junctions) => junctions,
+		Junctions::X4(junctions) => junctions,
+		Junctions::X5(junctions) => junctions,
+		Junctions::X6(junctions) => junctions,
+		Junctions::X7(junctions) => junctions,
+		Junctions::X8(junctions) => junctions,
+	}
+}
+
+fn to_junctions(junctions: Vec<Junction>) -> Result<Junctions, String> {
+	let too_long = |_: Vec<Junction>| "locations have up to 8 junctions".to_owned();
+	Ok(match junctions.len() {
+		0 => Junctions::Here,
+		1 => Junctions::X1(junctions.try_into().map_err(too_long)?),
+		2 => Junctions::X2(junctions.try_into().map_err(too_long)?),
+		3 => Junctions::X3(junctions.try_into().map_err(too_long)?),
+		4 => Junctions::X4(junctions.try_into().map_err(too_long)?),
+		5 => Junctions::X5(junctions.try_into().map_err(too_long)?),
+		6 => Junctions::X6(junctions.try_into().map_err(too_long)?),
+		7 => Junctions::X7(junctions.try_into().map_err(too_long)?),
+		8 => Junctions::X8(junctions.try_into().map_err(too_long)?),
+		_ => return Err(too_long(junctions)),
+	})
+}
+
+// The network of the account junctions, if any, precedes the account as another argument.
+fn with_network(network: &Option<NetworkId>, account: String) -> String {
+	match network {
+		Some(network) => format!("{},{}", encode_network(network), account),
+		None => account,
+	}
+}
+
+fn encode_junction(junction: &Junction) -> String {
+	match junction {
+		Junction::Parachain(para_id) => format!("Parachain({})", para_id),
+		Junction::AccountId32 { network, id } =>
+			format!("AccountId32({})", with_network(network, to_hex_string(id))),
+		Junction::AccountIndex64 { network, index } =>
+			format!("AccountIndex64({})", with_network(network, index.to_string())),
+		Junction::AccountKey20 { network, key } =>
+			format!("AccountKey20({})", with_network(network, to_hex_string(key))),
+		Junction::PalletInstance(index) => format!("PalletInstance({})", index),
+		Junction::GeneralIndex(index) => format!("GeneralIndex({})", index),
+		Junction::GeneralKey { length, data } =>
+			format!("GeneralKey({})", to_hex_string(&data[..usize::from(*length).min(data.len())])),
+		Junction::OnlyChild => "OnlyChild".to_owned(),
+		// Bodies have too many variants to be worth naming, so they're kept SCALE encoded
+		Junction::Plurality { .. } => format!("Plurality({})", to_hex_string(&junction.encode())),
+		Junction::GlobalConsensus(network) =>
+			format!("GlobalConsensus({})", encode_network(network)),
+	}
+}
+
+fn encode_network(network: &NetworkId) -> String {
+	match network {
+		NetworkId::ByGenesis(genesis_hash) => format!("ByGenesis({})", to_hex_string(genesis_hash)),
+		NetworkId::ByFork { block_number, block_hash } =>
+			format!("ByFork({},{})", block_number, to_hex_string(block_hash)),
+		NetworkId::Polkadot => "Polkadot".to_owned(),
+		NetworkId::Kusama => "Kusama".to_owned(),
+		NetworkId::Westend => "Westend".to_owned(),
+		NetworkId::Rococo => "Rococo".to_owned(),
+		NetworkId::Wococo => "Wococo".to_owned(),
+		NetworkId::Ethereum { chain_id } => format!("Ethereum({})", chain_id),
+		NetworkId::BitcoinCore => "BitcoinCore".to_owned(),
+		NetworkId::BitcoinCash => "BitcoinCash".to_owned(),
+		NetworkId::PolkadotBulletin => "PolkadotBulletin".to_owned(),
+	}
+}
+
+// Splits `Name(arguments)` into its name and its arguments, if any.
+fn split_arguments(s: &str) -> Result<(&str, Option<&str>), String> {
+	match s.split_once('(') {
+		Some((name, arguments)) => arguments
+			.strip_suffix(')')
+			.map(|arguments| (name.trim(), Some(arguments.trim())))
+			.ok_or_else(|| format!("unbalanced parentheses in {}", s)),
+		None => Ok((s, None)),
+	}
+}
+
+// Splits the arguments of an account junction into its network, if any, and the account.
+fn split_network(arguments: &str) -> Result<(Option<NetworkId>, &str), String> {
+	match arguments.rsplit_once(',') {
+		Some((network, account)) => Ok((Some(parse_network(network)?), account.trim())),
+		None => Ok((None, arguments)),
+	}
+}
+
+fn parse_number<T: FromStr>(s: &str) -> Result<T, String> {
+	s.trim().parse().map_err(|_| format!("{} isn't a valid number", s))
+}
+
+fn parse_hex(s: &str) -> Result<Vec<u8>, String> {
+	s.trim()
+		.strip_prefix("0x")
+		.and_then(|bytes| hex::decode(bytes).ok())
+		.ok_or_else(|| format!("{} isn't 0x prefixed hex", s))
+}
+
+fn parse_bytes<const N: usize>(s: &str) -> Result<[u8; N], String> {
+	parse_hex(s)?.try_into().map_err(|_| format!("{} isn't {} bytes long", s, N))
+}
+
+fn parse_junction(s: &str) -> Result<Junction, String> {
+	let unsupported = || format!("{} isn't a supported junction", s);
+	Ok(match split_arguments(s)? {
+		("Parachain", Some(para_id)) => Junction::Parachain(parse_number(para_id)?),
+		("AccountId32", Some(arguments)) => {
+			let (network, id) = split_network(arguments)?;
+			Junction::AccountId32 { network, id: parse_bytes(id)? }
+		},
+		("AccountIndex64", Some(arguments)) => {
+			let (network, index) = split_network(arguments)?;
+			Junction::AccountIndex64 { network, index: parse_number(index)? }
+		},
+		("AccountKey20", Some(arguments)) => {
+			let (network, key) = split_network(arguments)?;
+			Junction::AccountKey20 { network, key: parse_bytes(key)? }
+		},
+		("PalletInstance", Some(index)) => Junction::PalletInstance(parse_number(index)?),
+		("GeneralIndex", Some(index)) => Junction::GeneralIndex(parse_number(index)?),
+		("GeneralKey", Some(key)) => {
+			let key = parse_hex(key)?;
+			let mut data = [0; 32];
+			data.get_mut(..key.len())
+				.ok_or_else(|| format!("{} is longer than 32 bytes", s))?
+				.copy_from_slice(&key);
+			Junction::GeneralKey { length: key.len() as u8, data }
+		},
+		("OnlyChild", None) => Junction::OnlyChild,
+		("Plurality", Some(encoded)) => {
+			let encoded = parse_hex(encoded)?;
+			let mut input = &encoded[..];
+			match Junction::decode(&mut input) {
+				Ok(plurality @ Junction::Plurality { .. }) if input.is_empty() => plurality,
+				_ => return Err(unsupported()),
+			}
+		},
+		("GlobalConsensus", Some(network)) => Junction::GlobalConsensus(parse_network(network)?),
+		_ => return Err(unsupported()),
+	})
+}
+
+fn parse_network(s: &str) -> Result<NetworkId, String> {
+	Ok(match split_arguments(s.trim())? {
+		("ByGenesis", Some(genesis_hash)) => NetworkId::ByGenesis(parse_bytes(genesis_hash)?),
+		("ByFork", Some(arguments)) => {
+			let (block_number, block_hash) = arguments
+				.split_once(',')
+				.ok_or_else(|| format!("{} must be ByFork(<BLOCK_NUMBER>,<BLOCK_HASH>)", s))?;
+			NetworkId::ByFork {
+				block_number: parse_number(block_number)?,
+				block_hash: parse_bytes(block_hash)?,
+			}
+		},
+		("Polkadot", None) => NetworkId::Polkadot,
+		("Kusama", None) => NetworkId::Kusama,
+		("Westend", None) => NetworkId::Westend,
+		("Rococo", None) => NetworkId::Rococo,
+		("Wococo", None) => NetworkId::Wococo,
+		("Ethereum", Some(chain_id)) => NetworkId::Ethereum { chain_id: parse_number(chain_id)? },
+		("BitcoinCore", None) => NetworkId::BitcoinCore,
+		("BitcoinCash", None) => NetworkId::BitcoinCash,
+		("PolkadotBulletin", None) => NetworkId::PolkadotBulletin,
+		_ => return Err(format!("{} isn't a supported network", s)),
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn assert_round_trip(encoded: &str, location: Location) {
+		assert_eq!(encode_location(&location), encoded);
+		assert_eq!(
+			parse_location(encoded).map(|location| location.encode()),
+			Ok(location.encode())
+		);
+	}
+
+	#[test]
+	fn locations_round_trip_through_their_canonical_string() {
+		assert_round_trip("parents=1", Location { parents: 1, interior: Junctions::Here });
+		assert_round_trip(
+			"parents=1/Parachain(2004)/PalletInstance(50)/GeneralIndex(3014)",
+			Location {
+				parents: 1,
+				interior: Junctions::X3([
+					Junction::Parachain(2004),
+					Junction::PalletInstance(50),
+					Junction::GeneralIndex(3014),
+				]),
+			},
+		);
+		assert_round_trip(
+			"parents=2/GlobalConsensus(Ethereum(1))/AccountKey20(0xc02aaa39b223fe8d0a0e5c4f27ead9083c756cc2)",
+			Location {
+				parents: 2,
+				interior: Junctions::X2([
+					Junction::GlobalConsensus(NetworkId::Ethereum { chain_id: 1 }),
+					Junction::AccountKey20 {
+						network: None,
+						key: parse_bytes("0xc02aaa39b223fe8d0a0e5c4f27ead9083c756cc2").unwrap(),
+					},
+				]),
+			},
+		);
+		assert_round_trip(
+			&format!(
+				"parents=0/AccountId32(ByFork(7,0x{}),0x{})/GeneralKey(0x0102)/OnlyChild",
+				"ab".repeat(32),
+				"01".repeat(32)
+			),
+			Location {
+				parents: 0,
+				interior: Junctions::X3([
+					Junction::AccountId32 {
+						network: Some(NetworkId::ByFork {
+							block_number: 7,
+							block_hash: [0xab; 32],
+						}),
+						id: [1; 32],
+					},
+					Junction::GeneralKey {
+						length: 2,
+						data: {
+							let mut data = [0; 32];
+							data[..2].copy_from_slice(&[1, 2]);
+							data
+						},
+					},
+					Junction::OnlyChild,
+				]),
+			},
+		);
+	}
+
+	#[test]
+	fn location_strings_are_validated() {
+		assert_eq!(
+			parse_location(" parents=1 / Parachain( 2004 ) ").map(|location| location.encode()),
+			Ok(Location { parents: 1, interior: Junctions::X1([Junction::Parachain(2004)]) }
+				.encode())
+		);
+		assert!(parse_location("Parachain(2004)").is_err());
+		assert!(parse_location("parents=1/Parachain(-1)").is_err());
+		assert!(parse_location("parents=1/Parachain(2004").is_err());
+		assert!(parse_location("parents=1/Unknown(1)").is_err());
+		assert!(parse_location("parents=2/GlobalConsensus(Ethereum)").is_err());
+		assert!(parse_location("parents=0/AccountKey20(0x1234)").is_err());
+		assert!(parse_location(&format!("parents=0/GeneralKey(0x{})", "00".repeat(33))).is_err());
+		assert!(parse_location(&format!("parents=0{}", "/OnlyChild".repeat(9))).is_err());
+	}
+}
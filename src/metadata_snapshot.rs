@@ -0,0 +1,312 @@
+use crate::{
+	Error,
+	asset_hub::{
+		assets, foreign_assets,
+		runtime_types::{
+			bounded_collections::bounded_vec::BoundedVec, pallet_assets::types::AssetMetadata,
+		},
+	},
+	types::BlockNumber,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+	collections::BTreeMap,
+	fs,
+	path::{Path, PathBuf},
+	sync::{Mutex, OnceLock},
+	time::Duration,
+};
+use subxt::{
+	OnlineClient, PolkadotConfig,
+	backend::legacy::LegacyRpcMethods,
+	events::Events,
+	ext::codec::{Decode, Encode},
+};
+
+// How often the snapshot is written while the subscription runs.
+pub(crate) const SNAPSHOT_INTERVAL: Duration = Duration::from_secs(60);
+
+// Beyond this amount of blocks missed since the snapshot was written, scanning their events for
+// metadata changes costs more than reading the metadata again, so the snapshot is discarded.
+const MAX_CATCH_UP_BLOCKS: BlockNumber = 1_000;
+
+// The metadata stored by both pallet_assets instances, Assets and ForeignAssets.
+type Metadata = assets::storage::types::metadata::Metadata;
+
+// The metadata of the assets read from the storage, SCALE and hex encoded as it's stored, so it's
+// decoded again as the storage value it replaces. Assets without metadata are kept too, as reading
+// them costs the same. `block_number` is the last block whose metadata changes were applied.
+#[derive(Debug, Default, Serialize, Deserialize, PartialEq)]
+pub(crate) struct MetadataSnapshot {
+	block_number: Option<BlockNumber>,
+	entries: BTreeMap<String, String>,
+}
+
+fn asset_key(asset_id: &assets::storage::types::metadata::Param0) -> String {
+	format!("assets:{}", asset_id)
+}
+
+fn foreign_asset_key(asset_id: &foreign_assets::storage::types::metadata::Param0) -> String {
+	format!("foreign_assets:0x{}", hex::encode(asset_id.encode()))
+}
+
+impl MetadataSnapshot {
+	// Reads a snapshot written by a previous run. A missing snapshot means nothing was cached yet.
+	pub(crate) fn load(path: &Path) -> Result<Self, String> {
+		match fs::read_to_string(path) {
+			Ok(content) => serde_json::from_str(&content)
+				.map_err(|e| format!("Invalid metadata snapshot in {}: {}", path.display(), e)),
+			Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+			Err(e) => Err(format!("Failed to read the metadata snapshot: {}", e)),
+		}
+	}
+
+	// The snapshot is replaced atomically, so a crash never leaves it half written.
+	fn save(&self, path: &Path) -> Result<(), String> {
+		let content = serde_json::to_string(self)
+			.map_err(|e| format!("Failed to serialize the metadata snapshot: {}", e))?;
+		let mut temporary_path = path.as_os_str().to_owned();
+		temporary_path.push(".tmp");
+		fs::write(&temporary_path, content)
+			.and_then(|_| fs::rename(&temporary_path, path))
+			.map_err(|e| format!("Failed to write the metadata snapshot: {}", e))
+	}
+
+	// `None` if the asset isn't cached, `Some(None)` if it's cached as having no metadata.
+	fn get<T: Decode>(&self, key: &str) -> Option<Option<T>> {
+		let bytes = hex::decode(self.entries.get(key)?.trim_start_matches("0x")).ok()?;
+		Option::<T>::decode(&mut &bytes[..]).ok()
+	}
+
+	fn insert<T: Encode>(&mut self, key: String, metadata: &Option<T>) {
+		self.entries.insert(key, format!("0x{}", hex::encode(metadata.encode())));
+	}
+
+	// Updates the entries whose metadata changed in a block. Returns the amount of cached entries
+	// updated.
+	fn apply_changes(
+		&mut self,
+		block_number: BlockNumber,
+		changes: Vec<(String, Option<Metadata>)>,
+	) -> usize {
+		let mut updated = 0;
+		for (key, metadata) in changes {
+			updated += usize::from(self.entries.contains_key(&key));
+			self.insert(key, &metadata);
+		}
+		self.block_number = self.block_number.max(Some(block_number));
+		updated
+	}
+}
+
+// The metadata cache of the subscription, set once when the CLI starts if a snapshot is kept, so
+// the storage reads don't need to carry it around.
+struct MetadataCache {
+	path: PathBuf,
+	snapshot: Mutex<MetadataSnapshot>,
+}
+
+static METADATA_CACHE: OnceLock<MetadataCache> = OnceLock::new();
+
+pub(crate) fn set_metadata_cache(path: PathBuf, snapshot: MetadataSnapshot) {
+	let _ = METADATA_CACHE.set(MetadataCache { path, snapshot: Mutex::new(snapshot) });
+}
+
+fn cached<T: Decode>(key: &str) -> Option<Option<T>> {
+	METADATA_CACHE.get()?.snapshot.lock().ok()?.get(key)
+}
+
+fn cache<T: Encode>(key: String, metadata: &Option<T>) {
+	if let Some(Ok(mut snapshot)) = METADATA_CACHE.get().map(|cache| cache.snapshot.lock()) {
+		snapshot.insert(key, metadata);
+	}
+}
+
+pub(crate) fn cached_asset_metadata(
+	asset_id: &assets::storage::types::metadata::Param0,
+) -> Option<Option<assets::storage::types::metadata::Metadata>> {
+	cached(&asset_key(asset_id))
+}
+
+pub(crate) fn cache_asset_metadata(
+	asset_id: &assets::storage::types::metadata::Param0,
+	metadata: &Option<assets::storage::types::metadata::Metadata>,
+) {
+	cache(asset_key(asset_id), metadata)
+}
+
+pub(crate) fn cached_foreign_asset_metadata(
+	asset_id: &foreign_assets::storage::types::metadata::Param0,
+) -> Option<Option<foreign_assets::storage::types::metadata::Metadata>> {
+	cached(&foreign_asset_key(asset_id))
+}
+
+pub(crate) fn cache_foreign_asset_metadata(
+	asset_id: &foreign_assets::storage::types::metadata::Param0,
+	metadata: &Option<foreign_assets::storage::types::metadata::Metadata>,
+) {
+	cache(foreign_asset_key(asset_id), metadata)
+}
+
+// The metadata an event sets, as it's stored. The deposit reserved for it isn't reported, but
+// nothing reads it.
+fn metadata_set(name: Vec<u8>, symbol: Vec<u8>, decimals: u8, is_frozen: bool) -> Metadata {
+	AssetMetadata {
+		deposit: 0,
+		name: BoundedVec(name),
+		symbol: BoundedVec(symbol),
+		decimals,
+		is_frozen,
+	}
+}
+
+// The metadata set or cleared by the events of a block, keyed by the asset it belongs to. The
+// events carry the new metadata, so the cache is updated without reading the storage again.
+fn metadata_changes(events: &Events<PolkadotConfig>) -> Vec<(String, Option<Metadata>)> {
+	let mut changes = vec![];
+	for event in events.iter().flatten() {
+		if let Ok(Some(event)) = event.as_event::<assets::events::MetadataSet>() {
+			changes.push((
+				asset_key(&event.asset_id),
+				Some(metadata_set(event.name, event.symbol, event.decimals, event.is_frozen)),
+			));
+		} else if let Ok(Some(event)) = event.as_event::<assets::events::MetadataCleared>() {
+			changes.push((asset_key(&event.asset_id), None));
+		} else if let Ok(Some(event)) = event.as_event::<foreign_assets::events::MetadataSet>() {
+			changes.push((
+				foreign_asset_key(&event.asset_id),
+				Some(metadata_set(event.name, event.symbol, event.decimals, event.is_frozen)),
+			));
+		} else if let Ok(Some(event)) = event.as_event::<foreign_assets::events::MetadataCleared>()
+		{
+			changes.push((foreign_asset_key(&event.asset_id), None));
+		}
+	}
+	changes
+}
+
+// Applies the metadata changes of a block to the cache. The subscription calls it for every block
+// in the order they're finalized, before they're queued for the workers, so no worker reads the
+// metadata of an asset changed by a previous block from the cache.
+pub(crate) fn observe_block(block_number: BlockNumber, events: &Events<PolkadotConfig>) {
+	let Some(cache) = METADATA_CACHE.get() else {
+		return;
+	};
+	let changes = metadata_changes(events);
+	if let Ok(mut snapshot) = cache.snapshot.lock() {
+		snapshot.apply_changes(block_number, changes);
+	}
+}
+
+// If the metadata changes of a block can't be read, the cache can't be trusted anymore.
+pub(crate) fn clear_cache() {
+	if let Some(Ok(mut snapshot)) = METADATA_CACHE.get().map(|cache| cache.snapshot.lock()) {
+		snapshot.entries.clear();
+	}
+}
+
+// Applies the metadata changes of the blocks finalized while the indexer wasn't running, up to the
+// block the subscription starts from. Returns the amount of cached entries updated, or `None` if
+// the snapshot was discarded.
+pub(crate) async fn catch_up(
+	api: &OnlineClient<PolkadotConfig>,
+	rpc: &LegacyRpcMethods<PolkadotConfig>,
+	to_block: BlockNumber,
+) -> Result<Option<usize>, Error> {
+	let Some(cache) = METADATA_CACHE.get() else {
+		return Ok(Some(0));
+	};
+	let from_block = match cache.snapshot.lock() {
+		Ok(snapshot) => snapshot.block_number.map(|block| block + 1),
+		Err(_) => return Ok(Some(0)),
+	};
+	let from_block = match from_block {
+		Some(from_block) if to_block.saturating_sub(from_block) < MAX_CATCH_UP_BLOCKS => from_block,
+		// A snapshot without block or too old is read again from scratch
+		_ => {
+			if let Ok(mut snapshot) = cache.snapshot.lock() {
+				*snapshot = MetadataSnapshot { block_number: Some(to_block), ..Default::default() };
+			}
+			return Ok(None);
+		},
+	};
+	let mut changes = vec![];
+	for block_number in from_block..=to_block {
+		let block_hash = rpc
+			.chain_get_block_hash(Some(block_number.into()))
+			.await
+			.map_err(subxt::error::Error::from)?
+			.ok_or_else(|| {
+				subxt::error::Error::Other(format!("Block {} doesn't exist", block_number))
+			})?;
+		changes.extend(metadata_changes(&api.blocks().at(block_hash).await?.events().await?));
+	}
+	Ok(cache
+		.snapshot
+		.lock()
+		.map_or(Some(0), |mut snapshot| Some(snapshot.apply_changes(to_block, changes))))
+}
+
+// Writes the cache to its snapshot, so the next run starts with it.
+pub(crate) fn save_snapshot() -> Result<(), String> {
+	let Some(cache) = METADATA_CACHE.get() else {
+		return Ok(());
+	};
+	let snapshot =
+		cache.snapshot.lock().map_err(|_| "The metadata cache is poisoned".to_owned())?;
+	snapshot.save(&cache.path)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn metadata(name: &str, decimals: u8) -> Metadata {
+		metadata_set(name.as_bytes().to_vec(), name.as_bytes().to_vec(), decimals, false)
+	}
+
+	// The decimals cached for an asset, as the storage types can't be compared.
+	fn cached_decimals(snapshot: &MetadataSnapshot, asset_id: u32) -> Option<Option<u8>> {
+		snapshot
+			.get::<Metadata>(&asset_key(&asset_id))
+			.map(|metadata| metadata.map(|metadata| metadata.decimals))
+	}
+
+	#[test]
+	fn snapshots_keep_the_metadata_until_it_changes() {
+		let path = std::env::temp_dir()
+			.join(format!("xcm_indexer_metadata_snapshot_{}.json", std::process::id()));
+		let _ = fs::remove_file(&path);
+
+		let mut snapshot = MetadataSnapshot::load(&path).unwrap();
+		assert_eq!(snapshot, MetadataSnapshot::default());
+		snapshot.insert(asset_key(&1984), &Some(metadata("Tether USD", 6)));
+		snapshot.insert(asset_key(&1337), &None::<Metadata>);
+		snapshot.apply_changes(10, vec![]);
+		snapshot.save(&path).unwrap();
+
+		let mut snapshot = MetadataSnapshot::load(&path).unwrap();
+		assert_eq!(snapshot.block_number, Some(10));
+		assert_eq!(cached_decimals(&snapshot, 1984), Some(Some(6)));
+		assert_eq!(cached_decimals(&snapshot, 1337), Some(None));
+		assert_eq!(cached_decimals(&snapshot, 1), None);
+
+		// The metadata changes update the cache, and older blocks don't move the snapshot
+		// backwards
+		let changes = vec![
+			(asset_key(&1984), Some(metadata("Tether USD", 8))),
+			(asset_key(&1337), Some(metadata("Asset 1337", 12))),
+			(asset_key(&30), None),
+		];
+		assert_eq!(snapshot.apply_changes(12, changes), 2);
+		snapshot.apply_changes(11, vec![(asset_key(&1984), None)]);
+		assert_eq!(cached_decimals(&snapshot, 1984), None);
+		assert_eq!(cached_decimals(&snapshot, 1337), Some(Some(12)));
+		assert_eq!(cached_decimals(&snapshot, 30), Some(None));
+		assert_eq!(snapshot.block_number, Some(12));
+
+		fs::write(&path, "not a snapshot").unwrap();
+		assert!(MetadataSnapshot::load(&path).is_err());
+		fs::remove_file(&path).unwrap();
+	}
+}
@@ -0,0 +1,127 @@
+use serde_json::Value;
+use std::{
+	fs,
+	path::{Path, PathBuf},
+};
+
+// A change of the format of a file the indexer keeps across runs, applied to every record of the
+// files written in the previous version.
+pub(crate) struct Migration {
+	pub(crate) version: u32,
+	pub(crate) description: &'static str,
+	pub(crate) migrate: fn(&mut Value),
+}
+
+// The migrations of the raw blocks archive, in order. Archives written before the versioning are
+// at version 1, the format introduced with `reprocess`.
+pub(crate) const ARCHIVE_MIGRATIONS: &[Migration] = &[Migration {
+	version: 2,
+	description: "Keep the XCM related extrinsics of the blocks",
+	migrate: |block| {
+		if let Value::Object(block) = block {
+			block.entry("extrinsics").or_insert_with(|| Value::Array(vec![]));
+		}
+	},
+}];
+
+fn latest_version(migrations: &[Migration]) -> u32 {
+	migrations.last().map_or(1, |migration| migration.version)
+}
+
+// The version of a file is kept in a sidecar file next to it, eg `archive.jsonl.version`.
+fn version_path(path: &Path) -> PathBuf {
+	let mut version_path = path.as_os_str().to_owned();
+	version_path.push(".version");
+	PathBuf::from(version_path)
+}
+
+fn write_version(path: &Path, version: u32) -> Result<(), String> {
+	fs::write(version_path(path), version.to_string())
+		.map_err(|e| format!("Failed to write the version of {}: {}", path.display(), e))
+}
+
+// Brings a file of JSON lines to the latest version, applying the pending migrations in order, so
+// upgrading the indexer never requires migrating its files by hand. Returns the migrations
+// applied. A missing file will be written in the latest format, so its version is recorded up
+// front, while an existing file without version predates the versioning.
+pub(crate) fn migrate(
+	path: &Path,
+	migrations: &'static [Migration],
+) -> Result<Vec<&'static Migration>, String> {
+	let latest = latest_version(migrations);
+	if !path.exists() {
+		return write_version(path, latest).map(|_| vec![]);
+	}
+	let version = match fs::read_to_string(version_path(path)) {
+		Ok(content) => content
+			.trim()
+			.parse()
+			.map_err(|e| format!("Invalid version in {}: {}", version_path(path).display(), e))?,
+		Err(e) if e.kind() == std::io::ErrorKind::NotFound => 1,
+		Err(e) => return Err(format!("Failed to read the version of {}: {}", path.display(), e)),
+	};
+	if version > latest {
+		return Err(format!(
+			"{} is at version {}, written by a newer indexer supporting up to version {}",
+			path.display(),
+			version,
+			latest
+		));
+	}
+	let pending: Vec<_> =
+		migrations.iter().filter(|migration| migration.version > version).collect();
+	if pending.is_empty() {
+		return Ok(vec![]);
+	}
+
+	let content = fs::read_to_string(path)
+		.map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+	let mut migrated = String::with_capacity(content.len());
+	for (index, line) in content.lines().enumerate().filter(|(_, line)| !line.trim().is_empty()) {
+		let mut record: Value = serde_json::from_str(line).map_err(|e| {
+			format!("Invalid record at line {} of {}: {}", index + 1, path.display(), e)
+		})?;
+		for migration in &pending {
+			(migration.migrate)(&mut record);
+		}
+		migrated.push_str(&record.to_string());
+		migrated.push('\n');
+	}
+	// The file is replaced atomically, and its version only updated once it is, so a crash
+	// migrates it again from its previous version
+	let mut temporary_path = path.as_os_str().to_owned();
+	temporary_path.push(".tmp");
+	fs::write(&temporary_path, migrated)
+		.and_then(|_| fs::rename(&temporary_path, path))
+		.map_err(|e| format!("Failed to migrate {}: {}", path.display(), e))?;
+	write_version(path, latest)?;
+	Ok(pending)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn unversioned_archives_are_migrated() {
+		let path = std::env::temp_dir()
+			.join(format!("xcm_indexer_migrations_{}.jsonl", std::process::id()));
+		let _ = fs::remove_file(&path);
+		let _ = fs::remove_file(version_path(&path));
+		fs::write(&path, "{\"block_hash\":\"0x01\",\"block_number\":1,\"events\":\"0x00\"}\n")
+			.unwrap();
+
+		let applied = migrate(&path, ARCHIVE_MIGRATIONS).unwrap();
+		assert_eq!(applied.iter().map(|migration| migration.version).collect::<Vec<_>>(), vec![2]);
+		let record: Value = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+		assert_eq!(record["extrinsics"], Value::Array(vec![]));
+		assert_eq!(record["block_number"], 1);
+		// Migrated files aren't migrated again
+		assert!(migrate(&path, ARCHIVE_MIGRATIONS).unwrap().is_empty());
+
+		write_version(&path, 3).unwrap();
+		assert!(migrate(&path, ARCHIVE_MIGRATIONS).unwrap_err().contains("newer indexer"));
+		fs::remove_file(&path).unwrap();
+		fs::remove_file(version_path(&path)).unwrap();
+	}
+}
@@ -0,0 +1,332 @@
+use crate::{sink::TransferSink, types::XcmTransfer};
+use std::{
+	collections::HashMap,
+	io,
+	sync::{
+		Arc, Mutex,
+		atomic::{AtomicU16, Ordering},
+	},
+	time::Duration,
+};
+use tokio::{
+	io::{AsyncRead, AsyncReadExt, AsyncWriteExt},
+	net::{
+		TcpStream,
+		tcp::{OwnedReadHalf, OwnedWriteHalf},
+	},
+	sync::oneshot,
+	task::JoinHandle,
+	time::timeout,
+};
+
+// The name of the sink in the routing rules
+pub(crate) const MQTT_SINK: &str = "mqtt";
+
+// MQTT 3.1.1 control packet types, already shifted to the high nibble of the fixed header.
+const CONNECT: u8 = 0x10;
+const CONNACK: u8 = 0x20;
+const PUBLISH: u8 = 0x30;
+const PUBACK: u8 = 0x40;
+const PUBREC: u8 = 0x50;
+// PUBREL must set the reserved flags to 0b0010
+const PUBREL: u8 = 0x62;
+const PUBCOMP: u8 = 0x70;
+const PROTOCOL_LEVEL: u8 = 4;
+const CLEAN_SESSION: u8 = 0x02;
+const CONNECTION_ACCEPTED: u8 = 0;
+// The indexer only publishes, so the keep alive mechanism is disabled
+const KEEP_ALIVE_DISABLED: u16 = 0;
+const CLIENT_ID: &str = "xcm-minimal-indexer";
+// The remaining length of a packet takes at most 4 bytes, so it's at most 268_435_455
+const MAX_REMAINING_LENGTH_BYTES: usize = 4;
+// How long the broker has to accept a connection, a write or to acknowledge a message before
+// giving up on the connection
+const BROKER_TIMEOUT: Duration = Duration::from_secs(10);
+
+// The acknowledgements awaited, by packet type and packet id
+type PendingAcks = Arc<Mutex<HashMap<(u8, u16), oneshot::Sender<()>>>>;
+
+// Publishes a message per transfer to an MQTT broker, under `<topic>/<kind of record>`. The indexer
+// only needs to publish, so instead of pulling a whole MQTT client we speak the handful of MQTT
+// 3.1.1 packets needed over a plain TCP connection, reconnecting if the connection drops.
+pub(crate) struct MqttSink {
+	broker: String,
+	topic: String,
+	qos: u8,
+	// Only locked while writing to the connection. The acknowledgements are read by a task of
+	// the connection and awaited without it, so the deliveries of concurrent blocks don't wait
+	// for each other's round trips.
+	connection: tokio::sync::Mutex<Option<MqttConnection>>,
+	packet_id: AtomicU16,
+}
+
+struct MqttConnection {
+	writer: OwnedWriteHalf,
+	pending_acks: PendingAcks,
+	reader: JoinHandle<()>,
+}
+
+impl Drop for MqttConnection {
+	fn drop(&mut self) {
+		self.reader.abort();
+	}
+}
+
+impl MqttSink {
+	pub(crate) fn new(broker: String, topic: String, qos: u8) -> Self {
+		Self {
+			broker,
+			topic,
+			qos,
+			connection: tokio::sync::Mutex::new(None),
+			packet_id: AtomicU16::new(0),
+		}
+	}
+
+	pub(crate) async fn publish_transfers(&self, transfers: &[XcmTransfer]) -> io::Result<()> {
+		for transfer in transfers {
+			let topic = format!("{}/{}", self.topic, transfer.kind());
+			let payload = serde_json::to_vec(transfer)?;
+			if self.publish(&topic, &payload).await.is_err() {
+				// Retry once over a new connection
+				*self.connection.lock().await = None;
+				self.publish(&topic, &payload).await?;
+			}
+		}
+		Ok(())
+	}
+
+	async fn publish(&self, topic: &str, payload: &[u8]) -> io::Result<()> {
+		// Packet ids must be non-zero
+		let packet_id = self.packet_id.fetch_add(1, Ordering::Relaxed).wrapping_add(1).max(1);
+		let packet = publish_packet(topic, payload, self.qos, packet_id);
+		match self.qos {
+			0 => self.send(&packet, None).await.map(|_| ()),
+			1 => await_ack(self.send(&packet, Some((PUBACK, packet_id))).await?).await,
+			_ => {
+				await_ack(self.send(&packet, Some((PUBREC, packet_id))).await?).await?;
+				let pubrel = ack_packet(PUBREL, packet_id);
+				await_ack(self.send(&pubrel, Some((PUBCOMP, packet_id))).await?).await
+			},
+		}
+	}
+
+	// Writes a packet, connecting first if needed, returning the receiver of the acknowledgement
+	// expected for it, if any. The acknowledgement is registered before writing, so it can't
+	// arrive before being awaited.
+	async fn send(
+		&self,
+		packet: &[u8],
+		ack: Option<(u8, u16)>,
+	) -> io::Result<Option<oneshot::Receiver<()>>> {
+		let mut connection = self.connection.lock().await;
+		if connection.is_none() {
+			*connection = Some(timeout_io(self.connect()).await?);
+		}
+		let connection = connection
+			.as_mut()
+			.ok_or_else(|| io::Error::new(io::ErrorKind::NotConnected, "Not connected"))?;
+		let receiver = ack.map(|ack| {
+			let (sender, receiver) = oneshot::channel();
+			if let Ok(mut pending_acks) = connection.pending_acks.lock() {
+				pending_acks.insert(ack, sender);
+			}
+			receiver
+		});
+		timeout_io(connection.writer.write_all(packet)).await?;
+		Ok(receiver)
+	}
+
+	async fn connect(&self) -> io::Result<MqttConnection> {
+		let mut stream = TcpStream::connect(&self.broker).await?;
+		stream.write_all(&connect_packet(CLIENT_ID)).await?;
+		let (packet_type, body) = read_packet(&mut stream).await?;
+		if packet_type != CONNACK || body.get(1) != Some(&CONNECTION_ACCEPTED) {
+			return Err(io::Error::new(
+				io::ErrorKind::ConnectionRefused,
+				"The MQTT broker refused the connection",
+			));
+		}
+		let (reader, writer) = stream.into_split();
+		let pending_acks = PendingAcks::default();
+		let reader = tokio::spawn(read_acks(reader, pending_acks.clone()));
+		Ok(MqttConnection { writer, pending_acks, reader })
+	}
+}
+
+impl TransferSink for MqttSink {
+	fn name(&self) -> String {
+		MQTT_SINK.to_owned()
+	}
+
+	async fn deliver(&self, transfers: &[XcmTransfer]) -> Result<(), String> {
+		self.publish_transfers(transfers).await.map_err(|e| e.to_string())
+	}
+}
+
+// Reads the packets sent by the broker, handing the acknowledgements to the publishers awaiting
+// them. Once the connection is lost, the acknowledgements still awaited are dropped, failing them.
+async fn read_acks(mut reader: OwnedReadHalf, pending_acks: PendingAcks) {
+	while let Ok((packet_type, body)) = read_packet(&mut reader).await {
+		let Ok(packet_id) = <[u8; 2]>::try_from(&body[..]) else {
+			continue;
+		};
+		let ack = (packet_type, u16::from_be_bytes(packet_id));
+		if let Some(sender) = pending_acks.lock().ok().and_then(|mut acks| acks.remove(&ack)) {
+			let _ = sender.send(());
+		}
+	}
+	if let Ok(mut pending_acks) = pending_acks.lock() {
+		pending_acks.clear();
+	}
+}
+
+async fn await_ack(receiver: Option<oneshot::Receiver<()>>) -> io::Result<()> {
+	let Some(receiver) = receiver else {
+		return Ok(());
+	};
+	timeout_io(async {
+		receiver.await.map_err(|_| {
+			io::Error::new(io::ErrorKind::ConnectionAborted, "The MQTT connection was lost")
+		})
+	})
+	.await
+}
+
+async fn timeout_io<T>(future: impl Future<Output = io::Result<T>>) -> io::Result<T> {
+	timeout(BROKER_TIMEOUT, future).await.unwrap_or_else(|_| {
+		Err(io::Error::new(io::ErrorKind::TimedOut, "The MQTT broker didn't answer in time"))
+	})
+}
+
+// The remaining length of a packet is encoded in 7-bit groups, the high bit marking that more
+// groups follow.
+fn encode_remaining_length(mut length: usize, output: &mut Vec<u8>) {
+	loop {
+		let mut byte = (length % 128) as u8;
+		length /= 128;
+		if length > 0 {
+			byte |= 0x80;
+		}
+		output.push(byte);
+		if length == 0 {
+			break;
+		}
+	}
+}
+
+fn encode_string(value: &str, output: &mut Vec<u8>) {
+	output.extend((value.len() as u16).to_be_bytes());
+	output.extend(value.as_bytes());
+}
+
+fn with_fixed_header(header: u8, body: Vec<u8>) -> Vec<u8> {
+	let mut packet = vec![header];
+	encode_remaining_length(body.len(), &mut packet);
+	packet.extend(body);
+	packet
+}
+
+fn connect_packet(client_id: &str) -> Vec<u8> {
+	let mut body = vec![];
+	encode_string("MQTT", &mut body);
+	body.push(PROTOCOL_LEVEL);
+	body.push(CLEAN_SESSION);
+	body.extend(KEEP_ALIVE_DISABLED.to_be_bytes());
+	encode_string(client_id, &mut body);
+	with_fixed_header(CONNECT, body)
+}
+
+fn publish_packet(topic: &str, payload: &[u8], qos: u8, packet_id: u16) -> Vec<u8> {
+	let mut body = vec![];
+	encode_string(topic, &mut body);
+	// The packet id is only present for QoS 1 and 2
+	if qos > 0 {
+		body.extend(packet_id.to_be_bytes());
+	}
+	body.extend(payload);
+	with_fixed_header(PUBLISH | (qos << 1), body)
+}
+
+fn ack_packet(packet_type: u8, packet_id: u16) -> Vec<u8> {
+	with_fixed_header(packet_type, packet_id.to_be_bytes().to_vec())
+}
+
+async fn read_packet<R: AsyncRead + Unpin>(stream: &mut R) -> io::Result<(u8, Vec<u8>)> {
+	let packet_type = stream.read_u8().await? & 0xf0;
+	let mut length = 0;
+	let mut multiplier = 1;
+	for position in 0.. {
+		if position == MAX_REMAINING_LENGTH_BYTES {
+			return Err(io::Error::new(
+				io::ErrorKind::InvalidData,
+				"Malformed remaining length from the MQTT broker",
+			));
+		}
+		let byte = stream.read_u8().await?;
+		length += (byte & 0x7f) as usize * multiplier;
+		if byte & 0x80 == 0 {
+			break;
+		}
+		multiplier *= 128;
+	}
+	let mut body = vec![0; length];
+	stream.read_exact(&mut body).await?;
+	Ok((packet_type, body))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn encode_remaining_length_uses_variable_length() {
+		let encode = |length| {
+			let mut output = vec![];
+			encode_remaining_length(length, &mut output);
+			output
+		};
+		assert_eq!(encode(0), vec![0x00]);
+		assert_eq!(encode(127), vec![0x7f]);
+		assert_eq!(encode(128), vec![0x80, 0x01]);
+		assert_eq!(encode(16_383), vec![0xff, 0x7f]);
+		assert_eq!(encode(16_384), vec![0x80, 0x80, 0x01]);
+	}
+
+	#[test]
+	fn connect_packet_test() {
+		assert_eq!(
+			connect_packet("id"),
+			vec![0x10, 14, 0, 4, b'M', b'Q', b'T', b'T', 4, 0x02, 0, 0, 0, 2, b'i', b'd']
+		);
+	}
+
+	#[test]
+	fn publish_packet_test() {
+		assert_eq!(
+			publish_packet("a/b", b"{}", 0, 7),
+			vec![0x30, 7, 0, 3, b'a', b'/', b'b', b'{', b'}']
+		);
+		// The packet id is included for QoS above 0
+		assert_eq!(
+			publish_packet("a/b", b"{}", 1, 7),
+			vec![0x32, 9, 0, 3, b'a', b'/', b'b', 0, 7, b'{', b'}']
+		);
+		assert_eq!(ack_packet(PUBREL, 7), vec![0x62, 2, 0, 7]);
+	}
+
+	#[tokio::test]
+	async fn read_packet_rejects_remaining_lengths_over_4_bytes() {
+		let read = |packet: Vec<u8>| async move { read_packet(&mut &packet[..]).await };
+		assert_eq!(read(vec![0x40, 2, 0, 7]).await.unwrap(), (PUBACK, vec![0, 7]));
+		// The largest remaining length is read up to the end of the stream
+		assert_eq!(
+			read(vec![0x30, 0xff, 0xff, 0xff, 0x7f]).await.unwrap_err().kind(),
+			io::ErrorKind::UnexpectedEof
+		);
+		assert_eq!(
+			read(vec![0x30, 0xff, 0xff, 0xff, 0xff, 0x01]).await.unwrap_err().kind(),
+			io::ErrorKind::InvalidData
+		);
+	}
+}
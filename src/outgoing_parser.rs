@@ -1,54 +1,298 @@
+// Without the `outgoing` feature only the output types are kept, as they're also used to build
+// transfers.
+#[cfg(feature = "outgoing")]
 use crate::{
 	Error,
+	asset_hub::runtime_types::{
+		staging_xcm::v5::{Instruction as V5Instruction, asset::Fungibility as V5Fungibility},
+		xcm::{
+			VersionedAssets, VersionedLocation,
+			v3::multiasset::{AssetId, Fungibility},
+		},
+	},
+	block_source::BlockSource,
+	chain_spec::{Chain, ChainSpec},
+	coverage::ParseOutcome,
+	helpers::XcmAggregatedOrigin,
+	storage_lookup::StorageLookup,
+	types::{AssetMetadataValues, BlockHash},
+	xc20::MoonbeamChain,
+};
+use crate::{
 	asset_hub::runtime_types::{
 		staging_xcm::{
 			v3::multilocation::MultiLocation,
 			v4::{
-				junction::Junction as V4Junction, junctions::Junctions as V4Junctions,
+				junction::{Junction as V4Junction, NetworkId as V4NetworkId},
+				junctions::Junctions as V4Junctions,
 				location::Location,
 			},
-		},
-		xcm::{
-			VersionedAssets, VersionedLocation,
-			v3::{
-				junction::{Junction, NetworkId},
-				junctions::Junctions,
-				multiasset::{AssetId, Fungibility},
+			v5::{
+				junction::{Junction as V5Junction, NetworkId as V5NetworkId},
+				junctions::Junctions as V5Junctions,
+				location::Location as V5Location,
+				traits::Outcome as V5Outcome,
 			},
 		},
+		xcm::v3::{
+			WeightLimit as V3WeightLimit,
+			junction::{Junction, NetworkId},
+			junctions::Junctions,
+		},
 	},
-	types::{AssetMetadataValues, BlockHash, BlockNumber, DOT_DECIMALS, TransferType},
+	controllers::SenderControllers,
+	entities::EntityTag,
+	raw_xcm::RawXcm,
+	types::{AssetIdentifier, BlockNumber, TransferType, XcmTransfer},
 };
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "outgoing")]
+use std::collections::BTreeMap;
+use std::str::FromStr;
+#[cfg(feature = "outgoing")]
 use subxt::{
-	OnlineClient, PolkadotConfig,
-	blocks::{BlockRef, ExtrinsicDetails},
-	config::polkadot::AccountId32,
-	storage::Storage,
+	PolkadotConfig,
+	blocks::{ExtrinsicDetails, Extrinsics},
+	client::OfflineClientT,
+	events::{EventDetails, Events, Phase},
+	ext::codec::Encode,
 };
+#[cfg(any(feature = "outgoing", feature = "fuzzing"))]
+use subxt::{config::polkadot::AccountId32, ext::codec::Decode, utils::MultiAddress};
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+#[cfg_attr(feature = "typescript", derive(ts_rs::TS))]
+pub struct XcmOutgoingTransfer {
+	pub block_number: BlockNumber,
+	// Shared by all the assets sent by the same extrinsic: `<block_number>-<extrinsic_index>`
+	pub transfer_group_id: String,
+	// Only present if the transfer wasn't dispatched by a signed extrinsic
+	#[serde(skip_serializing_if = "Option::is_none")]
+	#[cfg_attr(feature = "typescript", ts(optional))]
+	pub origin: Option<TransferOrigin>,
+	pub destination_chain: DestinationChain,
+	pub sender: String,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	#[cfg_attr(feature = "typescript", ts(optional))]
+	pub sender_public_key: Option<String>,
+	pub beneficiary: String,
+	pub beneficiary_public_key: String,
+	pub asset: String,
+	// Only missing in the records output by previous versions of the indexer
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	#[cfg_attr(feature = "typescript", ts(optional))]
+	pub asset_id: Option<AssetIdentifier>,
+	pub amount: f64,
+	// The exact decimal rendering of the amount, as `amount` is the closest float to it. Only
+	// missing in the records output by previous versions of the indexer
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	#[cfg_attr(feature = "typescript", ts(optional))]
+	pub exact_amount: Option<String>,
+	pub transfer_type: TransferType,
+	// The fee arguments of the call, useful to debug transfers stuck at destination. `pays_fees`
+	// marks the asset pointed by `fee_asset_item` in a multi-asset transfer.
+	pub fee_asset_item: u32,
+	pub pays_fees: bool,
+	pub weight_limit: WeightLimit,
+	// The amount burned in AssetHub by the extrinsic for this asset, confirming what actually left
+	// it. Only present if the asset was burned (eg, teleports)
+	#[serde(skip_serializing_if = "Option::is_none")]
+	#[cfg_attr(feature = "typescript", ts(optional))]
+	pub burned_amount: Option<f64>,
+	// The outcome of the local execution of the transfer, if the extrinsic reported it
+	#[serde(skip_serializing_if = "Option::is_none")]
+	#[cfg_attr(feature = "typescript", ts(optional))]
+	pub outcome: Option<ExecutionOutcome>,
+	// The id of the message sent, which identifies it in the chains it goes through
+	#[serde(skip_serializing_if = "Option::is_none")]
+	#[cfg_attr(feature = "typescript", ts(optional))]
+	pub message_id: Option<String>,
+	// The hash of the assets trapped in AssetHub by the local execution
+	// (`PolkadotXcm::AssetsTrapped`), which can be claimed with it
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	#[cfg_attr(feature = "typescript", ts(optional))]
+	pub trapped_assets_hash: Option<String>,
+	// The native fee paid by the sender for the extrinsic, reported by the
+	// `TransactionPayment::TransactionFeePaid` event. Shared by all the assets sent by the
+	// extrinsic
+	#[serde(skip_serializing_if = "Option::is_none")]
+	#[cfg_attr(feature = "typescript", ts(optional))]
+	pub local_fee_dot: Option<f64>,
+	// The nonce of the sender and the tip (in the native token) of the signed extrinsic, so the
+	// transfers of a sender can be ordered and deduplicated. Shared by all the assets sent by the
+	// extrinsic
+	#[serde(skip_serializing_if = "Option::is_none")]
+	#[cfg_attr(feature = "typescript", ts(as = "Option<f64>", optional))]
+	pub nonce: Option<u64>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	#[cfg_attr(feature = "typescript", ts(optional))]
+	pub tip_dot: Option<f64>,
+	// The real controllers of the sender when it's a multisig or a proxied account. Only resolved
+	// on demand, as it requires reading the storage
+	#[serde(skip_serializing_if = "Option::is_none")]
+	#[cfg_attr(feature = "typescript", ts(optional))]
+	pub controllers: Option<SenderControllers>,
+	// The XCM programs behind the transfer, only rendered on demand for debugging. Shared by all
+	// the assets sent by the extrinsic
+	#[serde(skip_serializing_if = "Option::is_none")]
+	#[cfg_attr(feature = "typescript", ts(optional))]
+	pub raw_xcm: Option<RawXcm>,
+	// The known entities (eg, exchanges or bridges) touched by the transfer, if entity labels were
+	// loaded
+	#[serde(default, skip_serializing_if = "Vec::is_empty")]
+	#[cfg_attr(feature = "typescript", ts(as = "Option<Vec<EntityTag>>", optional))]
+	pub entities: Vec<EntityTag>,
+	// Only present if the transfer goes to a Moonbeam-based chain
+	#[serde(skip_serializing_if = "Option::is_none")]
+	#[cfg_attr(feature = "typescript", ts(optional))]
+	pub xc20_address: Option<String>,
+}
+
+// The nested representation of the transfers sent by an extrinsic, grouping all its assets under
+// the same transfer as in the original call.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+#[cfg_attr(feature = "typescript", derive(ts_rs::TS))]
+pub struct XcmOutgoingTransferGroup {
+	pub transfer_group_id: String,
+	pub block_number: BlockNumber,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	#[cfg_attr(feature = "typescript", ts(optional))]
+	pub origin: Option<TransferOrigin>,
+	pub destination_chain: DestinationChain,
+	pub sender: String,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	#[cfg_attr(feature = "typescript", ts(optional))]
+	pub sender_public_key: Option<String>,
+	pub beneficiary: String,
+	pub beneficiary_public_key: String,
+	pub fee_asset_item: u32,
+	pub weight_limit: WeightLimit,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	#[cfg_attr(feature = "typescript", ts(optional))]
+	pub outcome: Option<ExecutionOutcome>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	#[cfg_attr(feature = "typescript", ts(optional))]
+	pub message_id: Option<String>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	#[cfg_attr(feature = "typescript", ts(optional))]
+	pub trapped_assets_hash: Option<String>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	#[cfg_attr(feature = "typescript", ts(optional))]
+	pub local_fee_dot: Option<f64>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	#[cfg_attr(feature = "typescript", ts(as = "Option<f64>", optional))]
+	pub nonce: Option<u64>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	#[cfg_attr(feature = "typescript", ts(optional))]
+	pub tip_dot: Option<f64>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	#[cfg_attr(feature = "typescript", ts(optional))]
+	pub controllers: Option<SenderControllers>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	#[cfg_attr(feature = "typescript", ts(optional))]
+	pub raw_xcm: Option<RawXcm>,
+	#[serde(default, skip_serializing_if = "Vec::is_empty")]
+	#[cfg_attr(feature = "typescript", ts(as = "Option<Vec<EntityTag>>", optional))]
+	pub entities: Vec<EntityTag>,
+	pub assets: Vec<XcmOutgoingAsset>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+#[cfg_attr(feature = "typescript", derive(ts_rs::TS))]
+pub struct XcmOutgoingAsset {
+	pub asset: String,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	#[cfg_attr(feature = "typescript", ts(optional))]
+	pub asset_id: Option<AssetIdentifier>,
+	pub amount: f64,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	#[cfg_attr(feature = "typescript", ts(optional))]
+	pub exact_amount: Option<String>,
+	pub transfer_type: TransferType,
+	pub pays_fees: bool,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	#[cfg_attr(feature = "typescript", ts(optional))]
+	pub burned_amount: Option<f64>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	#[cfg_attr(feature = "typescript", ts(optional))]
+	pub xc20_address: Option<String>,
+}
 
-#[derive(Debug, Serialize, PartialEq)]
-pub(crate) struct XcmOutgoingTransfer {
-	pub(crate) block_number: BlockNumber,
-	pub(crate) destination_chain: DestinationChain,
-	pub(crate) sender: String,
-	pub(crate) beneficiary: String,
-	pub(crate) asset: String,
-	pub(crate) amount: f64,
-	pub(crate) transfer_type: TransferType,
+// Transfers sent outside of signed extrinsics, during the initialization or the finalization of
+// the block: those ordered by the relay chain governance through XCM, and those dispatched by the
+// runtime hooks (eg, by a scheduler).
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+#[cfg_attr(feature = "typescript", derive(ts_rs::TS))]
+pub enum TransferOrigin {
+	Governance,
+	Scheduled,
+}
+
+// Replaces the sent transfers by their groups. The transfers of an extrinsic are contiguous in the
+// output of the parser, so it's enough to compare each transfer with the last group.
+pub(crate) fn group_sent_transfers(transfers: Vec<XcmTransfer>) -> Vec<XcmTransfer> {
+	let mut output: Vec<XcmTransfer> = Vec::with_capacity(transfers.len());
+	for transfer in transfers {
+		let XcmTransfer::SentTransfer(transfer) = transfer else {
+			output.push(transfer);
+			continue;
+		};
+		let asset = XcmOutgoingAsset {
+			asset: transfer.asset,
+			asset_id: transfer.asset_id,
+			amount: transfer.amount,
+			exact_amount: transfer.exact_amount,
+			transfer_type: transfer.transfer_type,
+			pays_fees: transfer.pays_fees,
+			burned_amount: transfer.burned_amount,
+			xc20_address: transfer.xc20_address,
+		};
+		match output.last_mut() {
+			Some(XcmTransfer::SentTransferGroup(group))
+				if group.transfer_group_id == transfer.transfer_group_id =>
+				group.assets.push(asset),
+			_ => output.push(XcmTransfer::SentTransferGroup(XcmOutgoingTransferGroup {
+				transfer_group_id: transfer.transfer_group_id,
+				block_number: transfer.block_number,
+				origin: transfer.origin,
+				destination_chain: transfer.destination_chain,
+				sender: transfer.sender,
+				sender_public_key: transfer.sender_public_key,
+				beneficiary: transfer.beneficiary,
+				beneficiary_public_key: transfer.beneficiary_public_key,
+				fee_asset_item: transfer.fee_asset_item,
+				weight_limit: transfer.weight_limit,
+				outcome: transfer.outcome,
+				message_id: transfer.message_id,
+				trapped_assets_hash: transfer.trapped_assets_hash,
+				local_fee_dot: transfer.local_fee_dot,
+				nonce: transfer.nonce,
+				tip_dot: transfer.tip_dot,
+				controllers: transfer.controllers,
+				raw_xcm: transfer.raw_xcm,
+				entities: transfer.entities,
+				assets: vec![asset],
+			})),
+		}
+	}
+	output
 }
 
 // The types provided by the metadata aren't Serialize as they are intended to be serialized to
 // SCALE. To serialize the destination chain we would need to wrap the Location type and implement
 // custom logic, but it will imply a huge amount of code. So for this small indexer we write a
 // small type that recognize some popular locations.
-#[derive(Debug, Serialize, PartialEq, Clone)]
-pub(crate) enum DestinationChain {
+#[derive(Debug, Deserialize, Serialize, PartialEq, Clone)]
+#[cfg_attr(feature = "typescript", derive(ts_rs::TS))]
+pub enum DestinationChain {
 	Polkadot,
 	Kusama,
 	PolkadotParachain(u32),
 	KusamaParachain(u32),
-	Ethereum { chain_id: u64 },
+	Ethereum {
+		#[cfg_attr(feature = "typescript", ts(as = "f64"))]
+		chain_id: u64,
+	},
 	Unsupported,
 }
 
@@ -80,40 +324,463 @@ impl From<&MultiLocation> for DestinationChain {
 	}
 }
 
+impl From<&Location> for DestinationChain {
+	fn from(location: &Location) -> Self {
+		match (location.parents, &location.interior) {
+			(1, V4Junctions::Here) => Self::Polkadot,
+			(1, V4Junctions::X1([V4Junction::Parachain(id)])) => Self::PolkadotParachain(*id),
+			(2, V4Junctions::X1([V4Junction::GlobalConsensus(network)])) => match network {
+				V4NetworkId::Ethereum { chain_id } => Self::Ethereum { chain_id: *chain_id },
+				V4NetworkId::Kusama => Self::Kusama,
+				_ => Self::Unsupported,
+			},
+			(
+				2,
+				V4Junctions::X2(
+					[V4Junction::GlobalConsensus(V4NetworkId::Kusama), V4Junction::Parachain(id)],
+				),
+			) => Self::KusamaParachain(*id),
+			_ => Self::Unsupported,
+		}
+	}
+}
+
+impl From<&V5Location> for DestinationChain {
+	fn from(location: &V5Location) -> Self {
+		match (location.parents, &location.interior) {
+			(1, V5Junctions::Here) => Self::Polkadot,
+			(1, V5Junctions::X1([V5Junction::Parachain(id)])) => Self::PolkadotParachain(*id),
+			(2, V5Junctions::X1([V5Junction::GlobalConsensus(network)])) => match network {
+				V5NetworkId::Ethereum { chain_id } => Self::Ethereum { chain_id: *chain_id },
+				V5NetworkId::Kusama => Self::Kusama,
+				_ => Self::Unsupported,
+			},
+			(
+				2,
+				V5Junctions::X2(
+					[V5Junction::GlobalConsensus(V5NetworkId::Kusama), V5Junction::Parachain(id)],
+				),
+			) => Self::KusamaParachain(*id),
+			_ => Self::Unsupported,
+		}
+	}
+}
+
+impl DestinationChain {
+	// The V4 location of the destination, as expected by the runtime APIs and extrinsics.
+	pub(crate) fn to_location(&self) -> Option<Location> {
+		match self {
+			Self::Polkadot => Some(Location { parents: 1, interior: V4Junctions::Here }),
+			Self::Kusama => Some(Location {
+				parents: 2,
+				interior: V4Junctions::X1([V4Junction::GlobalConsensus(V4NetworkId::Kusama)]),
+			}),
+			Self::PolkadotParachain(id) => Some(Location {
+				parents: 1,
+				interior: V4Junctions::X1([V4Junction::Parachain(*id)]),
+			}),
+			Self::KusamaParachain(id) => Some(Location {
+				parents: 2,
+				interior: V4Junctions::X2([
+					V4Junction::GlobalConsensus(V4NetworkId::Kusama),
+					V4Junction::Parachain(*id),
+				]),
+			}),
+			Self::Ethereum { chain_id } => Some(Location {
+				parents: 2,
+				interior: V4Junctions::X1([V4Junction::GlobalConsensus(V4NetworkId::Ethereum {
+					chain_id: *chain_id,
+				})]),
+			}),
+			Self::Unsupported => None,
+		}
+	}
+}
+
+// Destinations are given to the CLI as `polkadot`, `kusama`, `polkadot-parachain:<para_id>`,
+// `kusama-parachain:<para_id>`, `ethereum:<chain_id>` or as their location, eg
+// `parents=1/Parachain(2034)`.
+impl FromStr for DestinationChain {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let invalid_destination = || format!("{} isn't a supported destination", s);
+		if let Ok(location) = crate::locations::parse_location(s) {
+			return match Self::from(&location) {
+				Self::Unsupported => Err(invalid_destination()),
+				destination => Ok(destination),
+			};
+		}
+		match s.split_once(':') {
+			None => match s {
+				"polkadot" => Ok(Self::Polkadot),
+				"kusama" => Ok(Self::Kusama),
+				_ => Err(invalid_destination()),
+			},
+			Some((chain, id)) => match chain {
+				"polkadot-parachain" =>
+					id.parse().map(Self::PolkadotParachain).map_err(|_| invalid_destination()),
+				"kusama-parachain" =>
+					id.parse().map(Self::KusamaParachain).map_err(|_| invalid_destination()),
+				"ethereum" => id
+					.parse()
+					.map(|chain_id| Self::Ethereum { chain_id })
+					.map_err(|_| invalid_destination()),
+				_ => Err(invalid_destination()),
+			},
+		}
+	}
+}
+
+// The weight limit set for the execution of the transfer at destination, in a serializable form.
+#[derive(Debug, Deserialize, Serialize, PartialEq, Clone)]
+#[cfg_attr(feature = "typescript", derive(ts_rs::TS))]
+pub enum WeightLimit {
+	Unlimited,
+	Limited {
+		#[cfg_attr(feature = "typescript", ts(as = "f64"))]
+		ref_time: u64,
+		#[cfg_attr(feature = "typescript", ts(as = "f64"))]
+		proof_size: u64,
+	},
+}
+
+impl From<&V3WeightLimit> for WeightLimit {
+	fn from(weight_limit: &V3WeightLimit) -> Self {
+		match weight_limit {
+			V3WeightLimit::Unlimited => Self::Unlimited,
+			V3WeightLimit::Limited(weight) =>
+				Self::Limited { ref_time: weight.ref_time, proof_size: weight.proof_size },
+		}
+	}
+}
+
+// The outcome of the local execution of a transfer reported by the `PolkadotXcm::Attempted` event,
+// so partially executed transfers can be told apart from clean ones.
+#[derive(Debug, Deserialize, Serialize, PartialEq, Clone)]
+#[cfg_attr(feature = "typescript", derive(ts_rs::TS))]
+pub enum ExecutionOutcome {
+	Complete {
+		#[cfg_attr(feature = "typescript", ts(as = "f64"))]
+		ref_time: u64,
+		#[cfg_attr(feature = "typescript", ts(as = "f64"))]
+		proof_size: u64,
+	},
+	Incomplete {
+		#[cfg_attr(feature = "typescript", ts(as = "f64"))]
+		ref_time: u64,
+		#[cfg_attr(feature = "typescript", ts(as = "f64"))]
+		proof_size: u64,
+		error: String,
+	},
+	Error {
+		error: String,
+	},
+}
+
+impl From<&V5Outcome> for ExecutionOutcome {
+	fn from(outcome: &V5Outcome) -> Self {
+		match outcome {
+			V5Outcome::Complete { used } =>
+				Self::Complete { ref_time: used.ref_time, proof_size: used.proof_size },
+			V5Outcome::Incomplete { used, error } => Self::Incomplete {
+				ref_time: used.ref_time,
+				proof_size: used.proof_size,
+				error: format!("{:?}", error),
+			},
+			V5Outcome::Error { error } => Self::Error { error: format!("{:?}", error) },
+		}
+	}
+}
+
+// Finds the outcome of the local execution among the events of an extrinsic.
+#[cfg(feature = "outgoing")]
+fn attempted_outcome(events: &[EventDetails<PolkadotConfig>]) -> Option<ExecutionOutcome> {
+	events.iter().find_map(|event| {
+		event
+			.as_event::<crate::asset_hub::polkadot_xcm::events::Attempted>()
+			.ok()
+			.flatten()
+			.map(|attempted_event| ExecutionOutcome::from(&attempted_event.outcome))
+	})
+}
+
+// Finds the native fee paid for an extrinsic among its events.
+#[cfg(feature = "outgoing")]
+fn transaction_fee_paid(events: &[EventDetails<PolkadotConfig>]) -> Option<f64> {
+	events.iter().find_map(|event| {
+		event
+			.as_event::<crate::asset_hub::transaction_payment::events::TransactionFeePaid>()
+			.ok()
+			.flatten()
+			.map(|fee_paid_event| {
+				crate::amount::to_decimal_f64(
+					fee_paid_event.actual_fee,
+					crate::chain_spec::native_token_decimals(),
+				)
+			})
+	})
+}
+
+// The nonce and the tip of a signed extrinsic, read from its transaction extensions.
+#[cfg(feature = "outgoing")]
+fn nonce_and_tip<C: OfflineClientT<PolkadotConfig>>(
+	extrinsic: &ExtrinsicDetails<PolkadotConfig, C>,
+) -> (Option<u64>, Option<f64>) {
+	extrinsic
+		.transaction_extensions()
+		.map(|extensions| {
+			(
+				extensions.nonce(),
+				extensions.tip().map(|tip| {
+					crate::amount::to_decimal_f64(tip, crate::chain_spec::native_token_decimals())
+				}),
+			)
+		})
+		.unwrap_or_default()
+}
+
+// The hash of the assets trapped by the local execution of an extrinsic among its events.
+#[cfg(feature = "outgoing")]
+fn trapped_assets_hash(events: &[EventDetails<PolkadotConfig>]) -> Option<String> {
+	events.iter().find_map(|event| {
+		event
+			.as_event::<crate::asset_hub::polkadot_xcm::events::AssetsTrapped>()
+			.ok()
+			.flatten()
+			.map(|trapped_event| crate::helpers::to_hex_string(trapped_event.hash.as_ref()))
+	})
+}
+
+// Finds the id of the message sent among the events of an extrinsic.
+#[cfg(feature = "outgoing")]
+fn sent_message_id(events: &[EventDetails<PolkadotConfig>]) -> Option<String> {
+	events.iter().find_map(|event| {
+		event
+			.as_event::<crate::asset_hub::polkadot_xcm::events::Sent>()
+			.ok()
+			.flatten()
+			.map(|sent_event| crate::helpers::to_hex_string(&sent_event.message_id))
+	})
+}
+
+#[cfg(feature = "outgoing")]
 pub(crate) async fn get_outgoing_xcm_transfers_at_block_hash(
-	api: &OnlineClient<PolkadotConfig>,
+	source: &impl BlockSource,
 	block_hash: BlockHash,
 ) -> Result<Vec<XcmOutgoingTransfer>, Error> {
-	let block = api.blocks().at(BlockRef::from_hash(block_hash)).await?;
-
-	let block_number = block.number();
-	let extrinsics = block.extrinsics().await?;
-	let storage = block.storage();
+	let block = source.block(block_hash).await?;
+	match &block.extrinsics {
+		Some(extrinsics) =>
+			parse_outgoing_xcm_transfers(block.number, extrinsics, &block.events, &block.storage)
+				.await,
+		None =>
+			parse_outgoing_xcm_transfers_from_events(block.number, &block.events, &block.storage)
+				.await,
+	}
+}
 
+// Decodes the outgoing transfers from the extrinsics of a block, falling back to their events for
+// the extrinsics whose call isn't known. Extrinsics only need an offline client to be decoded, so
+// it can be used with block data obtained elsewhere.
+#[cfg(feature = "outgoing")]
+pub(crate) async fn parse_outgoing_xcm_transfers<C: OfflineClientT<PolkadotConfig>>(
+	block_number: BlockNumber,
+	extrinsics: &Extrinsics<PolkadotConfig, C>,
+	events: &Events<PolkadotConfig>,
+	storage_api: &impl StorageLookup,
+) -> Result<Vec<XcmOutgoingTransfer>, Error> {
 	let mut output = Vec::new();
 
 	for extrinsic in extrinsics.iter() {
-		if let Ok(payload) =
-			generate_xcm_sent_teleport_payload(&storage, block_number, &extrinsic).await
+		let extrinsic_events: Vec<_> = events
+			.iter()
+			.flatten()
+			.filter(|event| event.phase() == Phase::ApplyExtrinsic(extrinsic.index()))
+			.collect();
+		if let Ok(payload) = generate_xcm_sent_teleport_payload(
+			storage_api,
+			block_number,
+			&extrinsic,
+			&extrinsic_events,
+		)
+		.await
 		{
 			output.extend(payload);
-		} else if let Ok(payload) =
-			generate_xcm_sent_reserve_transfer_payload(&storage, block_number, &extrinsic).await
+			crate::coverage::record_extrinsic(ParseOutcome::Parsed);
+		} else if let Ok(payload) = generate_xcm_sent_reserve_transfer_payload(
+			storage_api,
+			block_number,
+			&extrinsic,
+			&extrinsic_events,
+		)
+		.await
 		{
 			output.extend(payload);
-		} else if let Ok(payload) =
-			generate_xcm_sent_transfer_assets_payload(&storage, block_number, &extrinsic).await
+			crate::coverage::record_extrinsic(ParseOutcome::Parsed);
+		} else if let Ok(payload) = generate_xcm_sent_transfer_assets_payload(
+			storage_api,
+			block_number,
+			&extrinsic,
+			&extrinsic_events,
+		)
+		.await
 		{
 			output.extend(payload);
+			crate::coverage::record_extrinsic(ParseOutcome::Parsed);
+		} else if let Ok(payload) = generate_xcm_sent_payload_from_events(
+			storage_api,
+			block_number,
+			format!("{}-{}", block_number, extrinsic.index()),
+			None,
+			&extrinsic_events,
+		)
+		.await
+		{
+			output.extend(payload);
+			// The call couldn't be decoded, so the transfers were rebuilt from the message sent
+			crate::coverage::record_extrinsic(ParseOutcome::PartiallyParsed(
+				"recovered_from_events".to_owned(),
+			));
+		} else if let (Ok("PolkadotXcm"), Ok(call_name)) =
+			(extrinsic.pallet_name(), extrinsic.variant_name())
+		{
+			let failed = extrinsic_events.iter().any(|event| {
+				event.pallet_name() == "System" && event.variant_name() == "ExtrinsicFailed"
+			});
+			if failed {
+				crate::coverage::record_extrinsic(ParseOutcome::Skipped(
+					"extrinsic_failed".to_owned(),
+				));
+			} else {
+				crate::telemetry::record_unhandled_xcm_call(block_number, call_name);
+				crate::coverage::record_extrinsic(ParseOutcome::Skipped(format!(
+					"unhandled_call:{}",
+					call_name
+				)));
+			}
+		} else if has_sent_event(&extrinsic_events) {
+			crate::coverage::record_extrinsic(ParseOutcome::Skipped(
+				"unparsed_sent_message".to_owned(),
+			));
+		}
+	}
+
+	output.extend(parse_hook_dispatches(block_number, events, storage_api).await);
+	Ok(output)
+}
+
+// Rebuilds the outgoing transfers of a block from its events alone, as done for the extrinsics
+// whose call isn't known, for callers that have the events but can't decode the extrinsics (eg,
+// the C API).
+#[cfg(feature = "outgoing")]
+pub(crate) async fn parse_outgoing_xcm_transfers_from_events(
+	block_number: BlockNumber,
+	events: &Events<PolkadotConfig>,
+	storage_api: &impl StorageLookup,
+) -> Result<Vec<XcmOutgoingTransfer>, Error> {
+	let mut extrinsics_events: BTreeMap<u32, Vec<_>> = BTreeMap::new();
+	for event in events.iter().flatten() {
+		if let Phase::ApplyExtrinsic(index) = event.phase() {
+			extrinsics_events.entry(index).or_default().push(event);
 		}
 	}
 
+	let mut output = Vec::new();
+	for (index, extrinsic_events) in extrinsics_events {
+		if let Ok(payload) = generate_xcm_sent_payload_from_events(
+			storage_api,
+			block_number,
+			format!("{}-{}", block_number, index),
+			None,
+			&extrinsic_events,
+		)
+		.await
+		{
+			output.extend(payload);
+		}
+	}
+	output.extend(parse_hook_dispatches(block_number, events, storage_api).await);
 	Ok(output)
 }
 
+// Transfers dispatched outside of extrinsics can only be detected from their events.
+#[cfg(feature = "outgoing")]
+async fn parse_hook_dispatches(
+	block_number: BlockNumber,
+	events: &Events<PolkadotConfig>,
+	storage_api: &impl StorageLookup,
+) -> Vec<XcmOutgoingTransfer> {
+	let mut output = Vec::new();
+	for (phase_name, phase) in
+		[("initialization", Phase::Initialization), ("finalization", Phase::Finalization)]
+	{
+		let phase_events = events.iter().flatten().filter(|event| event.phase() == phase);
+		for (index, (origin, dispatch_events)) in
+			split_hook_dispatches(phase_events).into_iter().enumerate()
+		{
+			if let Ok(payload) = generate_xcm_sent_payload_from_events(
+				storage_api,
+				block_number,
+				format!("{}-{}-{}", block_number, phase_name, index),
+				Some(origin),
+				&dispatch_events,
+			)
+			.await
+			{
+				output.extend(payload);
+				crate::coverage::record_dispatch(ParseOutcome::Parsed);
+			} else if has_sent_event(&dispatch_events) {
+				crate::coverage::record_dispatch(ParseOutcome::Skipped(
+					"unparsed_sent_message".to_owned(),
+				));
+			}
+		}
+	}
+	output
+}
+
+#[cfg(feature = "outgoing")]
+fn has_sent_event(events: &[EventDetails<PolkadotConfig>]) -> bool {
+	events
+		.iter()
+		.any(|event| event.pallet_name() == "PolkadotXcm" && event.variant_name() == "Sent")
+}
+
+// Splits the events emitted during the initialization or the finalization of a block by the
+// message processed by MessageQueue they belong to, as done for incoming transfers. Messages from
+// the relay chain are governance orders, while messages from siblings are skipped: they may route
+// transfers through AssetHub, but those aren't sent by AssetHub. The events following the last
+// message were emitted by the runtime hooks.
+#[cfg(feature = "outgoing")]
+fn split_hook_dispatches(
+	events: impl Iterator<Item = EventDetails<PolkadotConfig>>,
+) -> Vec<(TransferOrigin, Vec<EventDetails<PolkadotConfig>>)> {
+	let mut dispatches = vec![];
+	let mut dispatch_events = vec![];
+	for event in events {
+		let processed_event = event
+			.as_event::<crate::asset_hub::message_queue::events::Processed>()
+			.ok()
+			.flatten();
+		dispatch_events.push(event);
+		if let Some(processed_event) = processed_event {
+			let message_events = std::mem::take(&mut dispatch_events);
+			if matches!(processed_event.origin, XcmAggregatedOrigin::Parent) {
+				dispatches.push((TransferOrigin::Governance, message_events));
+			}
+		}
+	}
+	if !dispatch_events.is_empty() {
+		dispatches.push((TransferOrigin::Scheduled, dispatch_events));
+	}
+	dispatches
+}
+
 // A macro to reduce repeated code: it returns the decoded extrinsicDetails, the destination chain,
 // the beneficiary and the sender. These parts aree common for generate_xcm_sent_teleport_payload,
 // generate_xcm_sent_reserve_transfer_payload and generate_xcm_sent_transfer_assets_payload
+#[cfg(feature = "outgoing")]
 macro_rules! decode_extrinsic_and_get_info {
 	($raw_extrinsic:ident, $type_to_decode:path) => {{
 		let decoded_extrinsic =
@@ -131,12 +798,12 @@ macro_rules! decode_extrinsic_and_get_info {
 
 		let beneficiary = match *decoded_extrinsic.beneficiary {
 			VersionedLocation::V3(ref location) => match location.interior {
-				Junctions::X1(Junction::AccountId32 { id, .. }) =>
-					crate::helpers::convert_account_id_to_general_substrate_address(&AccountId32(
-						id,
-					)),
+				Junctions::X1(Junction::AccountId32 { id, .. }) => (
+					crate::helpers::convert_account_id_to_address(&AccountId32(id)),
+					crate::helpers::to_hex_string(&id),
+				),
 				Junctions::X1(Junction::AccountKey20 { key, .. }) =>
-					format!("0x{}", hex::encode(key)),
+					(crate::helpers::to_eip55_address(&key), crate::helpers::to_hex_string(&key)),
 				// TODO: Add support for other junctions
 				_ => return Err(Error::GeneratePayloadFailed),
 			},
@@ -145,27 +812,45 @@ macro_rules! decode_extrinsic_and_get_info {
 		};
 
 		let sender = match $raw_extrinsic.address_bytes() {
-			Some(bytes) => {
-				let account_id = AccountId32(
-					// These bytes represent a Multiaddress, so we have to discard the first byte
-					// which represent the enum discriminant
-					bytes[1..].try_into().expect("Signer has 32 bytes in Polkadot AH; qed;"),
-				);
-				crate::helpers::convert_account_id_to_ah_address(&account_id)
-			},
-			_ => "Unsigned message".to_owned(),
+			Some(bytes) => render_sender(bytes),
+			_ => ("Unsigned message".to_owned(), None),
 		};
 
 		(decoded_extrinsic, destination_chain, sender, beneficiary)
 	}};
 }
 
-async fn generate_xcm_sent_teleport_payload(
-	storage_api: &Storage<PolkadotConfig, OnlineClient<PolkadotConfig>>,
+// Renders the signer of an extrinsic from its SCALE encoded MultiAddress, together with its public
+// key if it has one. Signers in Polkadot AH are AccountId32, but Ethereum-style signers and other
+// variants are rendered as well instead of assuming 32 bytes. Anything that cannot be decoded is
+// rendered as the raw hex bytes.
+#[cfg(any(feature = "outgoing", feature = "fuzzing"))]
+pub(crate) fn render_sender(address_bytes: &[u8]) -> (String, Option<String>) {
+	match MultiAddress::<AccountId32, ()>::decode(&mut &address_bytes[..]) {
+		Ok(MultiAddress::Id(AccountId32(bytes))) | Ok(MultiAddress::Address32(bytes)) => (
+			crate::helpers::convert_account_id_to_address(&AccountId32(bytes)),
+			Some(crate::helpers::to_hex_string(&bytes)),
+		),
+		Ok(MultiAddress::Address20(key)) =>
+			(crate::helpers::to_eip55_address(&key), Some(crate::helpers::to_hex_string(&key))),
+		Ok(MultiAddress::Raw(bytes)) => (crate::helpers::to_hex_string(&bytes), None),
+		Ok(MultiAddress::Index(_)) | Err(_) => (crate::helpers::to_hex_string(address_bytes), None),
+	}
+}
+
+#[cfg(feature = "outgoing")]
+async fn generate_xcm_sent_teleport_payload<C: OfflineClientT<PolkadotConfig>>(
+	storage_api: &impl StorageLookup,
 	block_number: BlockNumber,
-	raw_extrinsic: &ExtrinsicDetails<PolkadotConfig, OnlineClient<PolkadotConfig>>,
+	raw_extrinsic: &ExtrinsicDetails<PolkadotConfig, C>,
+	extrinsic_events: &[EventDetails<PolkadotConfig>],
 ) -> Result<Vec<XcmOutgoingTransfer>, Error> {
-	let (decoded_extrinsic, destination_chain, sender, beneficiary) = decode_extrinsic_and_get_info!(
+	let (
+		decoded_extrinsic,
+		destination_chain,
+		(sender, sender_public_key),
+		(beneficiary, beneficiary_public_key),
+	) = decode_extrinsic_and_get_info!(
 		raw_extrinsic,
 		crate::asset_hub::polkadot_xcm::calls::types::LimitedTeleportAssets
 	);
@@ -174,13 +859,37 @@ async fn generate_xcm_sent_teleport_payload(
 	// Asset hub only allows teleports of DOT and foreign assets to its native chain, so it's enough
 	// considering those cases.
 	// TODO: Add support for other asset versions
+	let transfer_group_id = format!("{}-{}", block_number, raw_extrinsic.index());
+	let moonbeam_chain = MoonbeamChain::from_destination(&destination_chain);
+	let fee_asset_item = decoded_extrinsic.fee_asset_item;
+	let weight_limit = WeightLimit::from(&decoded_extrinsic.weight_limit);
+	let mut burned_assets = BurnedAssets::from_events(extrinsic_events);
+	let outcome = attempted_outcome(extrinsic_events);
+	let message_id = sent_message_id(extrinsic_events);
+	let trapped_assets_hash = trapped_assets_hash(extrinsic_events);
+	let local_fee_dot = transaction_fee_paid(extrinsic_events);
+	let (nonce, tip_dot) = nonce_and_tip(raw_extrinsic);
 	if let VersionedAssets::V3(assets) = *decoded_extrinsic.assets {
-		for asset in assets.0 {
+		for (index, asset) in assets.0.into_iter().enumerate() {
+			let encoded_location = match &asset.id {
+				AssetId::Concrete(location) => Some((location.parents, location.interior.encode())),
+				_ => None,
+			};
+			let xc20_address = match (moonbeam_chain, &encoded_location) {
+				(Some(moonbeam_chain), Some((parents, interior))) =>
+					moonbeam_chain.xc20_address(*parents, interior),
+				_ => None,
+			};
 			let asset_details = match (asset.id, asset.fun) {
 				(
 					AssetId::Concrete(MultiLocation { parents: 1, interior: Junctions::Here }),
 					Fungibility::Fungible(amount),
-				) => Some(("DOT".to_owned(), DOT_DECIMALS, amount)),
+				) => Some((
+					crate::chain_spec::native_token_symbol(),
+					AssetIdentifier::Native,
+					crate::chain_spec::native_token_decimals(),
+					amount,
+				)),
 				// To query foreign_asset storage we need to use V4 Locations, so we need to
 				// convert our V3 multilocation into a V4 Location. For simplicity, we only
 				// support native tokens of sibling parachains in this case (which is the
@@ -193,30 +902,55 @@ async fn generate_xcm_sent_teleport_payload(
 					}),
 					Fungibility::Fungible(amount),
 				) => {
+					let location = Location {
+						parents: 1,
+						interior: V4Junctions::X1([V4Junction::Parachain(para_id)]),
+					};
 					let AssetMetadataValues { asset_name, decimals } =
 						crate::helpers::extract_foreign_asset_metadata_values(
 							storage_api,
-							&Location {
-								parents: 1,
-								interior: V4Junctions::X1([V4Junction::Parachain(para_id)]),
-							},
+							&location,
 						)
 						.await?;
-					Some((asset_name, decimals, amount))
+					Some((asset_name, AssetIdentifier::foreign_asset(&location), decimals, amount))
 				},
 				// TODO: Add support for other Assets Ids
 				_ => None,
 			};
 
-			if let Some((asset_name, decimals, amount)) = asset_details {
+			if let Some((asset_name, asset_id, decimals, amount)) = asset_details {
 				output.push(XcmOutgoingTransfer {
 					block_number,
+					transfer_group_id: transfer_group_id.clone(),
+					origin: None,
 					destination_chain: destination_chain.clone(),
 					sender: sender.clone(),
+					sender_public_key: sender_public_key.clone(),
 					beneficiary: beneficiary.clone(),
+					beneficiary_public_key: beneficiary_public_key.clone(),
 					asset: asset_name,
-					amount: crate::helpers::to_decimal_f64(amount, decimals),
+					asset_id: Some(asset_id),
+					amount: crate::amount::to_decimal_f64(amount, decimals),
+					exact_amount: Some(crate::amount::to_decimal_string(amount, decimals)),
 					transfer_type: TransferType::Teleport,
+					fee_asset_item,
+					pays_fees: index as u32 == fee_asset_item,
+					weight_limit: weight_limit.clone(),
+					burned_amount: encoded_location
+						.and_then(|(parents, interior)| {
+							burned_assets.claim(parents, &interior, amount)
+						})
+						.map(|burned| crate::amount::to_decimal_f64(burned, decimals)),
+					outcome: outcome.clone(),
+					message_id: message_id.clone(),
+					trapped_assets_hash: trapped_assets_hash.clone(),
+					local_fee_dot,
+					nonce,
+					tip_dot,
+					controllers: None,
+					raw_xcm: None,
+					entities: vec![],
+					xc20_address,
 				});
 			}
 		}
@@ -224,34 +958,64 @@ async fn generate_xcm_sent_teleport_payload(
 	Ok(output)
 }
 
-async fn generate_xcm_sent_reserve_transfer_payload(
-	storage_api: &Storage<PolkadotConfig, OnlineClient<PolkadotConfig>>,
+#[cfg(feature = "outgoing")]
+async fn generate_xcm_sent_reserve_transfer_payload<C: OfflineClientT<PolkadotConfig>>(
+	storage_api: &impl StorageLookup,
 	block_number: BlockNumber,
-	raw_extrinsic: &ExtrinsicDetails<PolkadotConfig, OnlineClient<PolkadotConfig>>,
+	raw_extrinsic: &ExtrinsicDetails<PolkadotConfig, C>,
+	extrinsic_events: &[EventDetails<PolkadotConfig>],
 ) -> Result<Vec<XcmOutgoingTransfer>, Error> {
-	let (decoded_extrinsic, destination_chain, sender, beneficiary) = decode_extrinsic_and_get_info!(
+	let (
+		decoded_extrinsic,
+		destination_chain,
+		(sender, sender_public_key),
+		(beneficiary, beneficiary_public_key),
+	) = decode_extrinsic_and_get_info!(
 		raw_extrinsic,
 		crate::asset_hub::polkadot_xcm::calls::types::LimitedReserveTransferAssets
 	);
 
 	let mut output = vec![];
 	// TODO: Add support for other assets
+	let transfer_group_id = format!("{}-{}", block_number, raw_extrinsic.index());
+	let moonbeam_chain = MoonbeamChain::from_destination(&destination_chain);
+	let fee_asset_item = decoded_extrinsic.fee_asset_item;
+	let weight_limit = WeightLimit::from(&decoded_extrinsic.weight_limit);
+	let mut burned_assets = BurnedAssets::from_events(extrinsic_events);
+	let outcome = attempted_outcome(extrinsic_events);
+	let message_id = sent_message_id(extrinsic_events);
+	let trapped_assets_hash = trapped_assets_hash(extrinsic_events);
+	let local_fee_dot = transaction_fee_paid(extrinsic_events);
+	let (nonce, tip_dot) = nonce_and_tip(raw_extrinsic);
 	if let VersionedAssets::V3(assets) = *decoded_extrinsic.assets {
-		for asset in assets.0 {
+		for (index, asset) in assets.0.into_iter().enumerate() {
+			let encoded_location = match &asset.id {
+				AssetId::Concrete(location) => Some((location.parents, location.interior.encode())),
+				_ => None,
+			};
+			let xc20_address = match (moonbeam_chain, &encoded_location) {
+				(Some(moonbeam_chain), Some((parents, interior))) =>
+					moonbeam_chain.xc20_address(*parents, interior),
+				_ => None,
+			};
 			let asset_details = match (asset.id, asset.fun) {
 				(
 					AssetId::Concrete(MultiLocation { parents: 1, interior: Junctions::Here }),
 					Fungibility::Fungible(amount),
-				) => Some(("DOT".to_owned(), DOT_DECIMALS, amount)),
-				// Pallet 50 is Assets, to recover the metadata, we cannot look for it as if it
-				// by location but using the AssetId. Pallet indexes cannot change without
-				// breaking the runtime, so it's OK to hardcode it here
+				) => Some((
+					crate::chain_spec::native_token_symbol(),
+					AssetIdentifier::Native,
+					crate::chain_spec::native_token_decimals(),
+					amount,
+				)),
+				// An asset in pallet_assets: to recover the metadata, we cannot look for it by
+				// location but using the AssetId.
 				(
 					AssetId::Concrete(MultiLocation {
 						parents: 0,
 						interior:
 							Junctions::X2(
-								Junction::PalletInstance(50),
+								Junction::PalletInstance(Chain::ASSETS_PALLET_INDEX),
 								Junction::GeneralIndex(asset_id),
 							),
 					}),
@@ -267,7 +1031,7 @@ async fn generate_xcm_sent_reserve_transfer_payload(
 								as crate::asset_hub::assets::storage::types::metadata::Param0),
 						)
 						.await?;
-					Some((asset_name, decimals, amount))
+					Some((asset_name, AssetIdentifier::Asset(asset_id as u32), decimals, amount))
 				},
 				// To query foreign_asset storage we need to use V4 Locations, so we need to
 				// convert our V3 multilocation into a V4 Location. For simplicity, we only
@@ -281,29 +1045,54 @@ async fn generate_xcm_sent_reserve_transfer_payload(
 					}),
 					Fungibility::Fungible(amount),
 				) => {
+					let location = Location {
+						parents: 1,
+						interior: V4Junctions::X1([V4Junction::Parachain(para_id)]),
+					};
 					let AssetMetadataValues { asset_name, decimals } =
 						crate::helpers::extract_foreign_asset_metadata_values(
 							storage_api,
-							&Location {
-								parents: 1,
-								interior: V4Junctions::X1([V4Junction::Parachain(para_id)]),
-							},
+							&location,
 						)
 						.await?;
-					Some((asset_name, decimals, amount))
+					Some((asset_name, AssetIdentifier::foreign_asset(&location), decimals, amount))
 				},
 				// TODO: Add support for other Assets Ids
 				_ => None,
 			};
-			if let Some((asset_name, decimals, amount)) = asset_details {
+			if let Some((asset_name, asset_id, decimals, amount)) = asset_details {
 				output.push(XcmOutgoingTransfer {
 					block_number,
+					transfer_group_id: transfer_group_id.clone(),
+					origin: None,
 					destination_chain: destination_chain.clone(),
 					sender: sender.clone(),
+					sender_public_key: sender_public_key.clone(),
 					beneficiary: beneficiary.clone(),
+					beneficiary_public_key: beneficiary_public_key.clone(),
 					asset: asset_name,
-					amount: crate::helpers::to_decimal_f64(amount, decimals),
+					asset_id: Some(asset_id),
+					amount: crate::amount::to_decimal_f64(amount, decimals),
+					exact_amount: Some(crate::amount::to_decimal_string(amount, decimals)),
 					transfer_type: TransferType::Reserve,
+					fee_asset_item,
+					pays_fees: index as u32 == fee_asset_item,
+					weight_limit: weight_limit.clone(),
+					burned_amount: encoded_location
+						.and_then(|(parents, interior)| {
+							burned_assets.claim(parents, &interior, amount)
+						})
+						.map(|burned| crate::amount::to_decimal_f64(burned, decimals)),
+					outcome: outcome.clone(),
+					message_id: message_id.clone(),
+					trapped_assets_hash: trapped_assets_hash.clone(),
+					local_fee_dot,
+					nonce,
+					tip_dot,
+					controllers: None,
+					raw_xcm: None,
+					entities: vec![],
+					xc20_address,
 				});
 			}
 		}
@@ -311,39 +1100,65 @@ async fn generate_xcm_sent_reserve_transfer_payload(
 	Ok(output)
 }
 
-async fn generate_xcm_sent_transfer_assets_payload(
-	storage_api: &Storage<PolkadotConfig, OnlineClient<PolkadotConfig>>,
+#[cfg(feature = "outgoing")]
+async fn generate_xcm_sent_transfer_assets_payload<C: OfflineClientT<PolkadotConfig>>(
+	storage_api: &impl StorageLookup,
 	block_number: BlockNumber,
-	raw_extrinsic: &ExtrinsicDetails<PolkadotConfig, OnlineClient<PolkadotConfig>>,
+	raw_extrinsic: &ExtrinsicDetails<PolkadotConfig, C>,
+	extrinsic_events: &[EventDetails<PolkadotConfig>],
 ) -> Result<Vec<XcmOutgoingTransfer>, Error> {
-	let (decoded_extrinsic, destination_chain, sender, beneficiary) = decode_extrinsic_and_get_info!(
+	let (
+		decoded_extrinsic,
+		destination_chain,
+		(sender, sender_public_key),
+		(beneficiary, beneficiary_public_key),
+	) = decode_extrinsic_and_get_info!(
 		raw_extrinsic,
 		crate::asset_hub::polkadot_xcm::calls::types::TransferAssets
 	);
 
 	let mut output = vec![];
 	// TODO: Add support for other assets
+	let transfer_group_id = format!("{}-{}", block_number, raw_extrinsic.index());
+	let moonbeam_chain = MoonbeamChain::from_destination(&destination_chain);
+	let fee_asset_item = decoded_extrinsic.fee_asset_item;
+	let weight_limit = WeightLimit::from(&decoded_extrinsic.weight_limit);
+	let mut burned_assets = BurnedAssets::from_events(extrinsic_events);
+	let outcome = attempted_outcome(extrinsic_events);
+	let message_id = sent_message_id(extrinsic_events);
+	let trapped_assets_hash = trapped_assets_hash(extrinsic_events);
+	let local_fee_dot = transaction_fee_paid(extrinsic_events);
+	let (nonce, tip_dot) = nonce_and_tip(raw_extrinsic);
 	if let VersionedAssets::V3(assets) = *decoded_extrinsic.assets {
-		for asset in assets.0 {
+		for (index, asset) in assets.0.into_iter().enumerate() {
+			let encoded_location = match &asset.id {
+				AssetId::Concrete(location) => Some((location.parents, location.interior.encode())),
+				_ => None,
+			};
+			let xc20_address = match (moonbeam_chain, &encoded_location) {
+				(Some(moonbeam_chain), Some((parents, interior))) =>
+					moonbeam_chain.xc20_address(*parents, interior),
+				_ => None,
+			};
 			let asset_details = match (asset.id, asset.fun) {
 				(
 					AssetId::Concrete(MultiLocation { parents: 1, interior: Junctions::Here }),
 					Fungibility::Fungible(amount),
 				) => Some((
-					"DOT".to_owned(),
-					DOT_DECIMALS,
+					crate::chain_spec::native_token_symbol(),
+					AssetIdentifier::Native,
+					crate::chain_spec::native_token_decimals(),
 					amount,
 					matches!(destination_chain, DestinationChain::Polkadot),
 				)),
-				// Pallet 50 is Assets, to recover the metadata, we cannot look for it as if it
-				// were a foriegn asset. Pallet indexes cannot change without breaking the
-				// runtime, so it's OK to hardcode it here
+				// An asset in pallet_assets: to recover the metadata, we cannot look for it as if
+				// it were a foriegn asset.
 				(
 					AssetId::Concrete(MultiLocation {
 						parents: 0,
 						interior:
 							Junctions::X2(
-								Junction::PalletInstance(50),
+								Junction::PalletInstance(Chain::ASSETS_PALLET_INDEX),
 								Junction::GeneralIndex(asset_id),
 							),
 					}),
@@ -360,7 +1175,13 @@ async fn generate_xcm_sent_transfer_assets_payload(
 						)
 						.await?;
 					// These assets aren't teleportable
-					Some((asset_name, decimals, amount, false))
+					Some((
+						asset_name,
+						AssetIdentifier::Asset(asset_id as u32),
+						decimals,
+						amount,
+						false,
+					))
 				},
 				// To query foreign_asset storage we need to use V4 Locations, so we need to
 				// convert our V3 multilocation into a V4 Location. For simplicity, we only
@@ -396,24 +1217,54 @@ async fn generate_xcm_sent_transfer_assets_payload(
 						} else {
 							false
 						};
-					Some((asset_name, decimals, amount, is_teleportable))
+					Some((
+						asset_name,
+						AssetIdentifier::foreign_asset(&asset_location_in_v4),
+						decimals,
+						amount,
+						is_teleportable,
+					))
 				},
 				// TODO: Add support for other Assets Ids
 				_ => None,
 			};
-			if let Some((asset_name, decimals, amount, is_teleportable)) = asset_details {
+			if let Some((asset_name, asset_id, decimals, amount, is_teleportable)) = asset_details {
 				output.push(XcmOutgoingTransfer {
 					block_number,
+					transfer_group_id: transfer_group_id.clone(),
+					origin: None,
 					destination_chain: destination_chain.clone(),
 					sender: sender.clone(),
+					sender_public_key: sender_public_key.clone(),
 					beneficiary: beneficiary.clone(),
+					beneficiary_public_key: beneficiary_public_key.clone(),
 					asset: asset_name,
-					amount: crate::helpers::to_decimal_f64(amount, decimals),
+					asset_id: Some(asset_id),
+					amount: crate::amount::to_decimal_f64(amount, decimals),
+					exact_amount: Some(crate::amount::to_decimal_string(amount, decimals)),
 					transfer_type: if is_teleportable {
 						TransferType::Teleport
 					} else {
 						TransferType::Reserve
 					},
+					fee_asset_item,
+					pays_fees: index as u32 == fee_asset_item,
+					weight_limit: weight_limit.clone(),
+					burned_amount: encoded_location
+						.and_then(|(parents, interior)| {
+							burned_assets.claim(parents, &interior, amount)
+						})
+						.map(|burned| crate::amount::to_decimal_f64(burned, decimals)),
+					outcome: outcome.clone(),
+					message_id: message_id.clone(),
+					trapped_assets_hash: trapped_assets_hash.clone(),
+					local_fee_dot,
+					nonce,
+					tip_dot,
+					controllers: None,
+					raw_xcm: None,
+					entities: vec![],
+					xc20_address,
 				});
 			}
 		}
@@ -421,117 +1272,755 @@ async fn generate_xcm_sent_transfer_assets_payload(
 	Ok(output)
 }
 
+// Fallback for the transfers sent through calls the parsers above don't know (eg, dispatched
+// through utility or proxy): the `PolkadotXcm::Sent` event emitted by the extrinsic is matched with
+// the assets it burned. Delivery fees are burned as well, so only the burns whose amount is carried
+// by the message are considered transfers.
+#[cfg(feature = "outgoing")]
+async fn generate_xcm_sent_payload_from_events(
+	storage_api: &impl StorageLookup,
+	block_number: BlockNumber,
+	transfer_group_id: String,
+	origin: Option<TransferOrigin>,
+	events: &[EventDetails<PolkadotConfig>],
+) -> Result<Vec<XcmOutgoingTransfer>, Error> {
+	let sent_event = if let Some(event) = events.iter().find_map(|event| {
+		event.as_event::<crate::asset_hub::polkadot_xcm::events::Sent>().ok().flatten()
+	}) {
+		event
+	} else {
+		return Err(Error::GeneratePayloadFailed);
+	};
+
+	let destination_chain = DestinationChain::from(&sent_event.destination);
+	// Transfers dispatched by governance or the runtime hooks may not have an account as origin,
+	// those origins are rendered as their SCALE encoded location.
+	let (sender, sender_public_key) = match render_v5_account(&sent_event.origin) {
+		Some((sender, sender_public_key)) => (sender, Some(sender_public_key)),
+		None => (crate::helpers::to_hex_string(&sent_event.origin.encode()), None),
+	};
+
+	// The assets arriving at destination are in the first asset instruction of the message, which
+	// also tells how they're transferred, while the beneficiary is found in DepositAsset.
+	let mut message_assets = None;
+	let mut fees = None;
+	let mut weight_limit = WeightLimit::Unlimited;
+	let mut beneficiary = None;
+	for instruction in &sent_event.message.0 {
+		match instruction {
+			V5Instruction::ReceiveTeleportedAsset(assets) if message_assets.is_none() =>
+				message_assets = Some((TransferType::Teleport, &assets.0)),
+			V5Instruction::ReserveAssetDeposited(assets) | V5Instruction::WithdrawAsset(assets)
+				if message_assets.is_none() =>
+				message_assets = Some((TransferType::Reserve, &assets.0)),
+			V5Instruction::BuyExecution { fees: asset, weight_limit: limit } => {
+				fees = Some(&asset.id);
+				weight_limit = WeightLimit::from(limit);
+			},
+			V5Instruction::PayFees { asset } => fees = Some(&asset.id),
+			V5Instruction::DepositAsset { beneficiary: location, .. } =>
+				beneficiary = render_v5_account(location),
+			_ => (),
+		}
+	}
+	let (Some((transfer_type, message_assets)), Some((beneficiary, beneficiary_public_key))) =
+		(message_assets, beneficiary)
+	else {
+		return Err(Error::GeneratePayloadFailed);
+	};
+	let fee_asset_item = fees
+		.and_then(|fees| message_assets.iter().position(|asset| asset.id.encode() == fees.encode()))
+		.unwrap_or_default() as u32;
+
+	let moonbeam_chain = MoonbeamChain::from_destination(&destination_chain);
+	let outcome = attempted_outcome(events);
+	let message_id = Some(crate::helpers::to_hex_string(&sent_event.message_id));
+	let trapped_assets_hash = trapped_assets_hash(events);
+	let mut output = vec![];
+	for event in events {
+		let burned_asset = match (
+			event.as_event::<crate::asset_hub::balances::events::Burned>().ok().flatten(),
+			event.as_event::<crate::asset_hub::assets::events::Burned>().ok().flatten(),
+			event
+				.as_event::<crate::asset_hub::foreign_assets::events::Burned>()
+				.ok()
+				.flatten(),
+		) {
+			(Some(burned_event), None, None) => Some((
+				crate::chain_spec::native_token_symbol(),
+				AssetIdentifier::Native,
+				crate::chain_spec::native_token_decimals(),
+				burned_event.amount,
+				(1, V4Junctions::Here.encode()),
+			)),
+			(None, Some(burned_event), None) => {
+				let AssetMetadataValues { asset_name, decimals } =
+					crate::helpers::extract_asset_metadata_values(
+						storage_api,
+						&burned_event.asset_id,
+					)
+					.await?;
+				let interior = V4Junctions::X2([
+					V4Junction::PalletInstance(Chain::ASSETS_PALLET_INDEX),
+					V4Junction::GeneralIndex(burned_event.asset_id as u128),
+				]);
+				Some((
+					asset_name,
+					AssetIdentifier::Asset(burned_event.asset_id),
+					decimals,
+					burned_event.balance,
+					(0, interior.encode()),
+				))
+			},
+			(None, None, Some(burned_event)) => {
+				let AssetMetadataValues { asset_name, decimals } =
+					crate::helpers::extract_foreign_asset_metadata_values(
+						storage_api,
+						&burned_event.asset_id,
+					)
+					.await?;
+				let location =
+					(burned_event.asset_id.parents, burned_event.asset_id.interior.encode());
+				Some((
+					asset_name,
+					AssetIdentifier::foreign_asset(&burned_event.asset_id),
+					decimals,
+					burned_event.balance,
+					location,
+				))
+			},
+			_ => None,
+		};
+
+		let Some((asset_name, asset_id, decimals, amount, (parents, interior))) = burned_asset
+		else {
+			continue;
+		};
+		let Some(index) = message_assets.iter().position(
+			|asset| matches!(asset.fun, V5Fungibility::Fungible(message_amount) if message_amount == amount),
+		) else {
+			continue;
+		};
+		output.push(XcmOutgoingTransfer {
+			block_number,
+			transfer_group_id: transfer_group_id.clone(),
+			origin: origin.clone(),
+			destination_chain: destination_chain.clone(),
+			sender: sender.clone(),
+			sender_public_key: sender_public_key.clone(),
+			beneficiary: beneficiary.clone(),
+			beneficiary_public_key: beneficiary_public_key.clone(),
+			asset: asset_name,
+			asset_id: Some(asset_id),
+			amount: crate::amount::to_decimal_f64(amount, decimals),
+			exact_amount: Some(crate::amount::to_decimal_string(amount, decimals)),
+			transfer_type: transfer_type.clone(),
+			fee_asset_item,
+			pays_fees: index as u32 == fee_asset_item,
+			weight_limit: weight_limit.clone(),
+			burned_amount: Some(crate::amount::to_decimal_f64(amount, decimals)),
+			outcome: outcome.clone(),
+			message_id: message_id.clone(),
+			trapped_assets_hash: trapped_assets_hash.clone(),
+			local_fee_dot: None,
+			nonce: None,
+			tip_dot: None,
+			controllers: None,
+			raw_xcm: None,
+			entities: vec![],
+			xc20_address: moonbeam_chain
+				.and_then(|moonbeam_chain| moonbeam_chain.xc20_address(parents, &interior)),
+		});
+	}
+
+	if output.is_empty() { Err(Error::GeneratePayloadFailed) } else { Ok(output) }
+}
+
+// The assets burned by an extrinsic, identified by their location as seen from AssetHub: its
+// parents and its SCALE encoded interior (which is the same for V3 and V4 locations).
+#[cfg(feature = "outgoing")]
+struct BurnedAssets(Vec<((u8, Vec<u8>), u128)>);
+
+#[cfg(feature = "outgoing")]
+impl BurnedAssets {
+	fn from_events(events: &[EventDetails<PolkadotConfig>]) -> Self {
+		let mut burned_assets = vec![];
+		for event in events {
+			if let Ok(Some(burned_event)) =
+				event.as_event::<crate::asset_hub::balances::events::Burned>()
+			{
+				burned_assets.push(((1, V4Junctions::Here.encode()), burned_event.amount));
+			} else if let Ok(Some(burned_event)) =
+				event.as_event::<crate::asset_hub::assets::events::Burned>()
+			{
+				let interior = V4Junctions::X2([
+					V4Junction::PalletInstance(Chain::ASSETS_PALLET_INDEX),
+					V4Junction::GeneralIndex(burned_event.asset_id as u128),
+				]);
+				burned_assets.push(((0, interior.encode()), burned_event.balance));
+			} else if let Ok(Some(burned_event)) =
+				event.as_event::<crate::asset_hub::foreign_assets::events::Burned>()
+			{
+				let location =
+					(burned_event.asset_id.parents, burned_event.asset_id.interior.encode());
+				burned_assets.push((location, burned_event.balance));
+			}
+		}
+		Self(burned_assets)
+	}
+
+	// Claims the burn of an asset transferred by the extrinsic. Delivery fees may be burned as
+	// well, so only the burn of the transferred amount is claimed: a burn of another amount of the
+	// same asset isn't the transfer, and reporting it would misreport the fees as the transfer.
+	fn claim(&mut self, parents: u8, interior: &[u8], amount: u128) -> Option<u128> {
+		let position = self.0.iter().position(|((burned_parents, burned_interior), burned)| {
+			*burned_parents == parents && burned_interior == interior && *burned == amount
+		})?;
+		Some(self.0.remove(position).1)
+	}
+}
+
+// Renders a local account location, as found in the origin and the beneficiary of XCM messages,
+// together with its public key.
+#[cfg(any(feature = "outgoing", feature = "fuzzing"))]
+pub(crate) fn render_v5_account(location: &V5Location) -> Option<(String, String)> {
+	match location {
+		V5Location {
+			parents: 0,
+			interior: V5Junctions::X1([V5Junction::AccountId32 { id, .. }]),
+		} => Some((
+			crate::helpers::convert_account_id_to_address(&AccountId32(*id)),
+			crate::helpers::to_hex_string(id),
+		)),
+		V5Location {
+			parents: 0,
+			interior: V5Junctions::X1([V5Junction::AccountKey20 { key, .. }]),
+		} => Some((crate::helpers::to_eip55_address(key), crate::helpers::to_hex_string(key))),
+		_ => None,
+	}
+}
+
 #[cfg(test)]
 mod tests {
 	use super::*;
+	#[cfg(feature = "outgoing")]
+	use subxt::OnlineClient;
+
+	#[test]
+	fn destination_chain_from_str() {
+		assert_eq!("polkadot".parse(), Ok(DestinationChain::Polkadot));
+		assert_eq!("kusama".parse(), Ok(DestinationChain::Kusama));
+		assert_eq!(
+			"polkadot-parachain:2034".parse(),
+			Ok(DestinationChain::PolkadotParachain(2034))
+		);
+		assert_eq!("kusama-parachain:1000".parse(), Ok(DestinationChain::KusamaParachain(1000)));
+		assert_eq!("ethereum:1".parse(), Ok(DestinationChain::Ethereum { chain_id: 1 }));
+		assert_eq!("parents=1".parse(), Ok(DestinationChain::Polkadot));
+		assert_eq!(
+			"parents=1/Parachain(2034)".parse(),
+			Ok(DestinationChain::PolkadotParachain(2034))
+		);
+		assert_eq!(
+			"parents=2/GlobalConsensus(Kusama)/Parachain(1000)".parse(),
+			Ok(DestinationChain::KusamaParachain(1000))
+		);
+		assert_eq!(
+			"parents=2/GlobalConsensus(Ethereum(1))".parse(),
+			Ok(DestinationChain::Ethereum { chain_id: 1 })
+		);
+		assert!("parents=0/Parachain(2034)".parse::<DestinationChain>().is_err());
+		assert!("westend".parse::<DestinationChain>().is_err());
+		assert!("polkadot-parachain:hydration".parse::<DestinationChain>().is_err());
+	}
+
+	// The outcome and the message id of the transfers are the ones of the `Attempted` and `Sent`
+	// events of the block, as every tested block holds a single transfer extrinsic. The fee, nonce
+	// and tip aren't pinned by the expected transfers, so they're only checked to be present before
+	// comparing the other fields.
+	#[cfg(feature = "outgoing")]
+	async fn take_unpredictable_fields(
+		api: &OnlineClient<PolkadotConfig>,
+		block_hash: BlockHash,
+		transfers: &mut [XcmOutgoingTransfer],
+	) {
+		let events = api.events().at(block_hash).await.unwrap();
+		let attempted_event = events
+			.find_first::<crate::asset_hub::polkadot_xcm::events::Attempted>()
+			.unwrap()
+			.unwrap();
+		let sent_event = events
+			.find_first::<crate::asset_hub::polkadot_xcm::events::Sent>()
+			.unwrap()
+			.unwrap();
+		let outcome = ExecutionOutcome::from(&attempted_event.outcome);
+		assert!(matches!(outcome, ExecutionOutcome::Complete { ref_time, .. } if ref_time > 0));
+		let message_id = crate::helpers::to_hex_string(&sent_event.message_id);
+		assert_eq!(message_id.len(), 66);
+		for transfer in transfers {
+			assert_eq!(transfer.outcome.take(), Some(outcome.clone()));
+			assert_eq!(transfer.message_id.take(), Some(message_id.clone()));
+			assert!(transfer.local_fee_dot.take().is_some());
+			assert!(transfer.nonce.take().is_some());
+			assert!(transfer.tip_dot.take().is_some());
+		}
+	}
+
+	#[cfg(feature = "outgoing")]
+	#[test]
+	fn render_sender_handles_all_multiaddress_variants() {
+		let account_id = AccountId32([1; 32]);
+		let rendered_account_id = (
+			crate::helpers::convert_account_id_to_address(&account_id),
+			Some(format!("0x{}", "01".repeat(32))),
+		);
+		assert_eq!(
+			render_sender(&MultiAddress::<AccountId32, ()>::Id(account_id.clone()).encode()),
+			rendered_account_id
+		);
+		assert_eq!(
+			render_sender(&MultiAddress::<AccountId32, ()>::Address32([1; 32]).encode()),
+			rendered_account_id
+		);
+		assert_eq!(
+			render_sender(&MultiAddress::<AccountId32, ()>::Address20([0xab; 20]).encode()),
+			(
+				"0xABaBaBaBABabABabAbAbABAbABabababaBaBABaB".to_owned(),
+				Some(format!("0x{}", "ab".repeat(20)))
+			)
+		);
+		assert_eq!(
+			render_sender(&MultiAddress::<AccountId32, ()>::Raw(vec![1, 2]).encode()),
+			("0x0102".to_owned(), None)
+		);
+		// Truncated input
+		assert_eq!(render_sender(&[0, 1, 2]), ("0x000102".to_owned(), None));
+	}
+
+	#[test]
+	fn destination_chain_to_location_round_trip() {
+		for destination in [
+			DestinationChain::Polkadot,
+			DestinationChain::Kusama,
+			DestinationChain::PolkadotParachain(2034),
+			DestinationChain::KusamaParachain(1000),
+			DestinationChain::Ethereum { chain_id: 1 },
+		] {
+			let location = destination.to_location().unwrap();
+			assert_eq!(DestinationChain::from(&location), destination);
+		}
+		assert!(DestinationChain::Unsupported.to_location().is_none());
+	}
+
+	#[test]
+	fn destination_chain_from_v5_location() {
+		assert_eq!(
+			DestinationChain::from(&V5Location { parents: 1, interior: V5Junctions::Here }),
+			DestinationChain::Polkadot
+		);
+		assert_eq!(
+			DestinationChain::from(&V5Location {
+				parents: 2,
+				interior: V5Junctions::X2([
+					V5Junction::GlobalConsensus(V5NetworkId::Kusama),
+					V5Junction::Parachain(1000)
+				]),
+			}),
+			DestinationChain::KusamaParachain(1000)
+		);
+		assert_eq!(
+			DestinationChain::from(&V5Location {
+				parents: 2,
+				interior: V5Junctions::X1([V5Junction::GlobalConsensus(V5NetworkId::Ethereum {
+					chain_id: 1
+				})]),
+			}),
+			DestinationChain::Ethereum { chain_id: 1 }
+		);
+	}
+
+	#[cfg(feature = "outgoing")]
+	#[test]
+	fn render_v5_account_handles_local_accounts() {
+		let id = [1; 32];
+		assert_eq!(
+			render_v5_account(&V5Location {
+				parents: 0,
+				interior: V5Junctions::X1([V5Junction::AccountId32 { network: None, id }]),
+			}),
+			Some((
+				crate::helpers::convert_account_id_to_address(&AccountId32(id)),
+				crate::helpers::to_hex_string(&id)
+			))
+		);
+		let key = [0xda; 20];
+		assert_eq!(
+			render_v5_account(&V5Location {
+				parents: 0,
+				interior: V5Junctions::X1([V5Junction::AccountKey20 { network: None, key }]),
+			}),
+			Some((crate::helpers::to_eip55_address(&key), crate::helpers::to_hex_string(&key)))
+		);
+		assert_eq!(
+			render_v5_account(&V5Location {
+				parents: 1,
+				interior: V5Junctions::X1([V5Junction::Parachain(2034)]),
+			}),
+			None
+		);
+	}
+
+	#[cfg(feature = "outgoing")]
+	#[test]
+	fn burned_assets_only_match_the_transferred_amount() {
+		let dot = V4Junctions::Here.encode();
+		let usdt = V4Junctions::X2([
+			V4Junction::PalletInstance(Chain::ASSETS_PALLET_INDEX),
+			V4Junction::GeneralIndex(1984),
+		])
+		.encode();
+		// The first DOT burn pays the delivery fees
+		let mut burned_assets = BurnedAssets(vec![
+			((1, dot.clone()), 300_000_000),
+			((1, dot.clone()), 10_000_000_000),
+			((0, usdt.clone()), 5_000_000),
+		]);
+
+		assert_eq!(burned_assets.claim(1, &dot, 10_000_000_000), Some(10_000_000_000));
+		// Burns of another amount aren't the transfer
+		assert_eq!(burned_assets.claim(0, &usdt, 6_000_000), None);
+		assert_eq!(burned_assets.claim(0, &usdt, 5_000_000), Some(5_000_000));
+		assert_eq!(burned_assets.claim(0, &usdt, 5_000_000), None);
+		// Only the fee burn is left
+		assert_eq!(burned_assets.claim(1, &dot, 10_000_000_000), None);
+	}
 
+	#[test]
+	fn execution_outcome_from_v5() {
+		use crate::asset_hub::runtime_types::{
+			sp_weights::weight_v2::Weight, xcm::v5::traits::Error as V5Error,
+		};
+
+		assert_eq!(
+			ExecutionOutcome::from(&V5Outcome::Complete {
+				used: Weight { ref_time: 1_000, proof_size: 10 }
+			}),
+			ExecutionOutcome::Complete { ref_time: 1_000, proof_size: 10 }
+		);
+		assert_eq!(
+			ExecutionOutcome::from(&V5Outcome::Incomplete {
+				used: Weight { ref_time: 500, proof_size: 5 },
+				error: V5Error::FailedToTransactAsset,
+			}),
+			ExecutionOutcome::Incomplete {
+				ref_time: 500,
+				proof_size: 5,
+				error: "FailedToTransactAsset".to_owned()
+			}
+		);
+		assert_eq!(
+			ExecutionOutcome::from(&V5Outcome::Error { error: V5Error::Trap(7) }),
+			ExecutionOutcome::Error { error: "Trap(7)".to_owned() }
+		);
+	}
+
+	#[test]
+	fn weight_limit_from_v3() {
+		assert_eq!(WeightLimit::from(&V3WeightLimit::Unlimited), WeightLimit::Unlimited);
+		assert_eq!(
+			WeightLimit::from(&V3WeightLimit::Limited(
+				crate::asset_hub::runtime_types::sp_weights::weight_v2::Weight {
+					ref_time: 1_000_000_000,
+					proof_size: 65_536,
+				}
+			)),
+			WeightLimit::Limited { ref_time: 1_000_000_000, proof_size: 65_536 }
+		);
+	}
+
+	#[test]
+	fn group_sent_transfers_groups_assets_of_the_same_extrinsic() {
+		let transfer = |transfer_group_id: &str, asset: &str, pays_fees: bool| {
+			XcmTransfer::SentTransfer(XcmOutgoingTransfer {
+				block_number: 8_935_124,
+				transfer_group_id: transfer_group_id.to_owned(),
+				origin: None,
+				destination_chain: DestinationChain::PolkadotParachain(2034),
+				sender: "16hiHzdGAR7wi29PjCyUkpFCbjTe9Ri6PrnumbEeyhqg75wy".to_owned(),
+				sender_public_key: None,
+				beneficiary: "16hiHzdGAR7wi29PjCyUkpFCbjTe9Ri6PrnumbEeyhqg75wy".to_owned(),
+				beneficiary_public_key:
+					"0xfc39fcf04a8071b7409823b7c82427ce67910c6ed80aa0e5093aff234624c820".to_owned(),
+				asset: asset.to_owned(),
+				asset_id: None,
+				amount: 1.0,
+				exact_amount: None,
+				transfer_type: TransferType::Reserve,
+				fee_asset_item: 0,
+				pays_fees,
+				weight_limit: WeightLimit::Unlimited,
+				burned_amount: None,
+				outcome: None,
+				message_id: None,
+				trapped_assets_hash: None,
+				local_fee_dot: None,
+				nonce: None,
+				tip_dot: None,
+				controllers: None,
+				raw_xcm: None,
+				entities: vec![],
+				xc20_address: None,
+			})
+		};
+		let grouped = group_sent_transfers(vec![
+			transfer("8935124-2", "DOT", true),
+			transfer("8935124-2", "Tether USD", false),
+			transfer("8935124-3", "DOT", true),
+		]);
+
+		assert_eq!(grouped.len(), 2);
+		let XcmTransfer::SentTransferGroup(group) = &grouped[0] else {
+			panic!("Expected a group of transfers");
+		};
+		assert_eq!(group.transfer_group_id, "8935124-2");
+		assert_eq!(
+			group
+				.assets
+				.iter()
+				.map(|asset| (asset.asset.as_str(), asset.pays_fees))
+				.collect::<Vec<_>>(),
+			vec![("DOT", true), ("Tether USD", false)]
+		);
+		let XcmTransfer::SentTransferGroup(group) = &grouped[1] else {
+			panic!("Expected a group of transfers");
+		};
+		assert_eq!(group.transfer_group_id, "8935124-3");
+		assert_eq!(group.assets.len(), 1);
+	}
+
+	#[cfg(feature = "outgoing")]
 	#[tokio::test]
 	async fn get_outgoing_xcm_transfers_at_block_hash_with_limited_teleport_assets() {
-		let api = OnlineClient::<PolkadotConfig>::from_url(crate::types::ASSET_HUB_RPC_ENDPOINT)
-			.await
-			.unwrap();
+		let api = OnlineClient::<PolkadotConfig>::from_url(Chain::RPC_ENDPOINT).await.unwrap();
 
 		// DOT teleport to relaychain
 		let block_hash_hex = "0x087269a9b8446c093ce85eea70fc6127a56ce766fe89843a2001bd20532a1608";
 		let block_hash: BlockHash = block_hash_hex.parse().unwrap();
-		let xcm_transfer =
+		let mut xcm_transfer =
 			get_outgoing_xcm_transfers_at_block_hash(&api, block_hash).await.unwrap();
+		take_unpredictable_fields(&api, block_hash, &mut xcm_transfer).await;
 		assert_eq!(
 			xcm_transfer,
 			vec![XcmOutgoingTransfer {
 				block_number: 8_933_756,
+				transfer_group_id: "8933756-2".to_owned(),
+				origin: None,
 				destination_chain: DestinationChain::Polkadot,
 				sender: "13BV45b5dHe3EAsVJ3qDq4VA671nwyyk51UU31no7Kx1CCnF".to_owned(),
-				beneficiary: "5EFBukL1mWNZndryLQnDguf1EV29FgRbzWjysioSZEvV1kf7".to_owned(),
+				sender_public_key: Some(
+					"0x60775b26d682d631278127b4f2b6c3b9f4382497c566c9c9fab2e9d73119875c".to_owned()
+				),
+				beneficiary: "13BV45b5dHe3EAsVJ3qDq4VA671nwyyk51UU31no7Kx1CCnF".to_owned(),
+				beneficiary_public_key:
+					"0x60775b26d682d631278127b4f2b6c3b9f4382497c566c9c9fab2e9d73119875c".to_owned(),
 				asset: "DOT".to_owned(),
+				asset_id: Some(AssetIdentifier::Native),
 				amount: 500.0317346979,
-				transfer_type: TransferType::Teleport
+				exact_amount: Some("500.0317346979".to_owned()),
+				transfer_type: TransferType::Teleport,
+				fee_asset_item: 0,
+				pays_fees: true,
+				weight_limit: WeightLimit::Unlimited,
+				burned_amount: Some(500.0317346979),
+				outcome: None,
+				message_id: None,
+				trapped_assets_hash: None,
+				local_fee_dot: None,
+				nonce: None,
+				tip_dot: None,
+				controllers: None,
+				raw_xcm: None,
+				entities: vec![],
+				xc20_address: None,
 			}]
 		);
 	}
 
+	#[cfg(feature = "outgoing")]
 	#[tokio::test]
 	async fn get_outgoing_xcm_transfers_at_block_hash_with_limited_reserve_transfer_assets() {
-		let api = OnlineClient::<PolkadotConfig>::from_url(crate::types::ASSET_HUB_RPC_ENDPOINT)
-			.await
-			.unwrap();
+		let api = OnlineClient::<PolkadotConfig>::from_url(Chain::RPC_ENDPOINT).await.unwrap();
 
 		// DOT transfer to Kusama Asset Hub
 		let block_hash_hex = "0xd61d764410e0f638f59943c5ba7a2261098878cb421e95bb5eceb167116aa827";
 		let block_hash: BlockHash = block_hash_hex.parse().unwrap();
-		let xcm_transfer =
+		let mut xcm_transfer =
 			get_outgoing_xcm_transfers_at_block_hash(&api, block_hash).await.unwrap();
+		take_unpredictable_fields(&api, block_hash, &mut xcm_transfer).await;
 		assert_eq!(
 			xcm_transfer,
 			vec![XcmOutgoingTransfer {
 				block_number: 8_901_169,
+				transfer_group_id: "8901169-2".to_owned(),
+				origin: None,
 				destination_chain: DestinationChain::KusamaParachain(1000),
 				sender: "12sovbTyqv8Yvb8YZWtkai73hWxgGFQL8FfDHYaJ2X51v6s6".to_owned(),
-				beneficiary: "5DwWnGCuz8s5V482bsqkSZGtqty2ZwrC3kvj8FawUS3VjgXv".to_owned(),
+				sender_public_key: Some(
+					"0x52fc76ccf7ade0cfdbb5b57ed8e8c2c5112cb1359cf23c085997dae8615bc118".to_owned()
+				),
+				beneficiary: "12sovbTyqv8Yvb8YZWtkai73hWxgGFQL8FfDHYaJ2X51v6s6".to_owned(),
+				beneficiary_public_key:
+					"0x52fc76ccf7ade0cfdbb5b57ed8e8c2c5112cb1359cf23c085997dae8615bc118".to_owned(),
 				asset: "DOT".to_owned(),
+				asset_id: Some(AssetIdentifier::Native),
 				amount: 37.1,
-				transfer_type: TransferType::Reserve
+				exact_amount: Some("37.1".to_owned()),
+				transfer_type: TransferType::Reserve,
+				fee_asset_item: 0,
+				pays_fees: true,
+				weight_limit: WeightLimit::Unlimited,
+				burned_amount: None,
+				outcome: None,
+				message_id: None,
+				trapped_assets_hash: None,
+				local_fee_dot: None,
+				nonce: None,
+				tip_dot: None,
+				controllers: None,
+				raw_xcm: None,
+				entities: vec![],
+				xc20_address: None,
 			}]
 		);
 
 		// Theter transfer to Hydra
 		let block_hash_hex = "0x31507ab8ccd6b298567f09709144428c0f8da95d6bb002b21becf0a09c219566";
 		let block_hash: BlockHash = block_hash_hex.parse().unwrap();
-		let xcm_transfer =
+		let mut xcm_transfer =
 			get_outgoing_xcm_transfers_at_block_hash(&api, block_hash).await.unwrap();
+		take_unpredictable_fields(&api, block_hash, &mut xcm_transfer).await;
 		assert_eq!(
 			xcm_transfer,
 			vec![XcmOutgoingTransfer {
 				block_number: 8_935_101,
+				transfer_group_id: "8935101-2".to_owned(),
+				origin: None,
 				destination_chain: DestinationChain::PolkadotParachain(2034),
 				sender: "16hiHzdGAR7wi29PjCyUkpFCbjTe9Ri6PrnumbEeyhqg75wy".to_owned(),
-				beneficiary: "5HmR9fNCJdrUGV8smZvUcfR3k7TzT89xKN4RcJFJRcp9vdE6".to_owned(),
+				sender_public_key: Some(
+					"0xfc39fcf04a8071b7409823b7c82427ce67910c6ed80aa0e5093aff234624c820".to_owned()
+				),
+				beneficiary: "16hiHzdGAR7wi29PjCyUkpFCbjTe9Ri6PrnumbEeyhqg75wy".to_owned(),
+				beneficiary_public_key:
+					"0xfc39fcf04a8071b7409823b7c82427ce67910c6ed80aa0e5093aff234624c820".to_owned(),
 				asset: "Tether USD".to_owned(),
+				asset_id: Some(AssetIdentifier::Asset(1984)),
 				amount: 6999.013124,
-				transfer_type: TransferType::Reserve
+				exact_amount: Some("6999.013124".to_owned()),
+				transfer_type: TransferType::Reserve,
+				fee_asset_item: 0,
+				pays_fees: true,
+				weight_limit: WeightLimit::Unlimited,
+				burned_amount: None,
+				outcome: None,
+				message_id: None,
+				trapped_assets_hash: None,
+				local_fee_dot: None,
+				nonce: None,
+				tip_dot: None,
+				controllers: None,
+				raw_xcm: None,
+				entities: vec![],
+				xc20_address: None,
 			}]
 		);
 	}
 
+	#[cfg(feature = "outgoing")]
 	#[tokio::test]
 	async fn get_outgoing_xcm_transfers_at_block_hash_with_transfer_assets() {
-		let api = OnlineClient::<PolkadotConfig>::from_url(crate::types::ASSET_HUB_RPC_ENDPOINT)
-			.await
-			.unwrap();
+		let api = OnlineClient::<PolkadotConfig>::from_url(Chain::RPC_ENDPOINT).await.unwrap();
 
 		// DOT teleport to relaychain
 		let block_hash_hex = "0x794ca3dd3f4d19913f5750a57c2725895bd8b9442a781dfef83120e350919d28";
 		let block_hash: BlockHash = block_hash_hex.parse().unwrap();
-		let xcm_transfer =
+		let mut xcm_transfer =
 			get_outgoing_xcm_transfers_at_block_hash(&api, block_hash).await.unwrap();
+		take_unpredictable_fields(&api, block_hash, &mut xcm_transfer).await;
 		assert_eq!(
 			xcm_transfer,
 			vec![XcmOutgoingTransfer {
 				block_number: 8_935_399,
+				transfer_group_id: "8935399-2".to_owned(),
+				origin: None,
 				destination_chain: DestinationChain::Polkadot,
 				sender: "1VzpqfMrYzPYPHxUzow92BpXPY55WD7H926g6hhmVGLpeeW".to_owned(),
-				beneficiary: "5CZhgWQHzmiv6rHSXMkvzsMffmYRPCeyCeHcWoiMDQEpe8PB".to_owned(),
+				sender_public_key: Some(
+					"0x161d86e87bb0e771ad876df784ebd724aff8f9c0ba6aed2b8d5cb695d1d20816".to_owned()
+				),
+				beneficiary: "1VzpqfMrYzPYPHxUzow92BpXPY55WD7H926g6hhmVGLpeeW".to_owned(),
+				beneficiary_public_key:
+					"0x161d86e87bb0e771ad876df784ebd724aff8f9c0ba6aed2b8d5cb695d1d20816".to_owned(),
 				asset: "DOT".to_owned(),
+				asset_id: Some(AssetIdentifier::Native),
 				amount: 18.9672516319,
-				transfer_type: TransferType::Teleport
+				exact_amount: Some("18.9672516319".to_owned()),
+				transfer_type: TransferType::Teleport,
+				fee_asset_item: 0,
+				pays_fees: true,
+				weight_limit: WeightLimit::Unlimited,
+				burned_amount: Some(18.9672516319),
+				outcome: None,
+				message_id: None,
+				trapped_assets_hash: None,
+				local_fee_dot: None,
+				nonce: None,
+				tip_dot: None,
+				controllers: None,
+				raw_xcm: None,
+				entities: vec![],
+				xc20_address: None,
 			}]
 		);
 
 		// DOT reserve transfer to Moonbeam
 		let block_hash_hex = "0xc011fd5e3630a90fa2108887d49c7bc0dab52b27af5f85cbd7975ead52b0a7c8";
 		let block_hash: BlockHash = block_hash_hex.parse().unwrap();
-		let xcm_transfer =
+		let mut xcm_transfer =
 			get_outgoing_xcm_transfers_at_block_hash(&api, block_hash).await.unwrap();
+		take_unpredictable_fields(&api, block_hash, &mut xcm_transfer).await;
 		assert_eq!(
 			xcm_transfer,
 			vec![XcmOutgoingTransfer {
 				block_number: 8_935_124,
+				transfer_group_id: "8935124-2".to_owned(),
+				origin: None,
 				destination_chain: DestinationChain::PolkadotParachain(2004),
 				sender: "13KsaHFcQKSTd4m73Ub9yVwM1JGCZvipMyTZonHEXEceFYwS".to_owned(),
-				beneficiary: "0xda3985513642d591ae95ef6dec4ff6d725373004".to_owned(),
+				sender_public_key: Some(
+					"0x66dd21797b3ab4b27ff723196324a33f7a80683bda78e0fcebf8e7cebb09a358".to_owned()
+				),
+				beneficiary: "0xda3985513642D591aE95EF6dEc4Ff6D725373004".to_owned(),
+				beneficiary_public_key: "0xda3985513642d591ae95ef6dec4ff6d725373004".to_owned(),
 				asset: "DOT".to_owned(),
+				asset_id: Some(AssetIdentifier::Native),
 				amount: 2_022.95,
-				transfer_type: TransferType::Reserve
+				exact_amount: Some("2022.95".to_owned()),
+				transfer_type: TransferType::Reserve,
+				fee_asset_item: 0,
+				pays_fees: true,
+				weight_limit: WeightLimit::Unlimited,
+				burned_amount: None,
+				outcome: None,
+				message_id: None,
+				trapped_assets_hash: None,
+				local_fee_dot: None,
+				nonce: None,
+				tip_dot: None,
+				controllers: None,
+				raw_xcm: None,
+				entities: vec![],
+				xc20_address: Some("0xFfFFfFff1FcaCBd218EDc0EbA20Fc2308C778080".to_owned()),
 			}]
 		);
 	}
@@ -0,0 +1,140 @@
+use crate::{batch::OutputBatch, hash_chain::HashChain, signing::OutputSigner};
+use std::{
+	fs::OpenOptions,
+	io::Write,
+	path::PathBuf,
+	sync::{Arc, Mutex},
+};
+
+// The output of the indexer: the output file if provided, otherwise the standard output, together
+// with the batch buffering it, the signer of the output and the hash chain kept over it. Owned by
+// the command and shared by every task appending to the output, whose writes are serialized by the
+// lock, so the batches, their signatures and the links of the chain follow the order of the output.
+#[derive(Clone)]
+pub(crate) struct OutputSink {
+	path: Option<PathBuf>,
+	writer: Arc<Mutex<OutputWriter>>,
+}
+
+struct OutputWriter {
+	batch: Option<OutputBatch>,
+	signer: Option<OutputSigner>,
+	hash_chain: Option<HashChain>,
+}
+
+impl OutputSink {
+	pub(crate) fn new(
+		path: Option<PathBuf>,
+		batch: Option<OutputBatch>,
+		signer: Option<OutputSigner>,
+		hash_chain: Option<HashChain>,
+	) -> Self {
+		Self { path, writer: Arc::new(Mutex::new(OutputWriter { batch, signer, hash_chain })) }
+	}
+
+	// An output neither batched, signed nor chained.
+	pub(crate) fn plain(path: Option<PathBuf>) -> Self {
+		Self::new(path, None, None, None)
+	}
+
+	pub(crate) fn is_file(&self) -> bool {
+		self.path.is_some()
+	}
+
+	// Appends to the output. The output is buffered if batching is enabled. Returns whether the
+	// output was written (or buffered).
+	pub(crate) fn append(&self, output: &[u8], records: usize) -> bool {
+		let Ok(mut writer) = self.writer.lock() else {
+			return false;
+		};
+		match writer.batch.as_mut().map(|batch| batch.push(output, records)) {
+			Some(Some(output)) => self.write(&mut writer, &output),
+			Some(None) => true,
+			None => self.write(&mut writer, output),
+		}
+	}
+
+	// Writes the buffered records, if any. Called periodically and before shutting down.
+	pub(crate) fn flush(&self) {
+		let Ok(mut writer) = self.writer.lock() else {
+			return;
+		};
+		let output = writer.batch.as_mut().map(OutputBatch::take).unwrap_or_default();
+		if !output.is_empty() {
+			self.write(&mut writer, &output);
+		}
+	}
+
+	// Writes a batch, followed by its signature record if the output is signed. Both are written
+	// at once, so a batch and its signature are never split. Returns whether the batch was
+	// written.
+	fn write(&self, writer: &mut OutputWriter, output: &[u8]) -> bool {
+		let signature = writer.signer.as_ref().and_then(|signer| signer.signature_record(output));
+		let signed_output;
+		let output = match signature {
+			Some(signature) => {
+				signed_output = [output, &signature].concat();
+				&signed_output
+			},
+			None => output,
+		};
+		let result = match &self.path {
+			Some(path) => OpenOptions::new().append(true).open(path).and_then(|mut file| {
+				let start_offset =
+					file.metadata().map(|metadata| metadata.len()).unwrap_or_default();
+				file.write_all(output)?;
+				if let Some(hash_chain) = writer.hash_chain.as_mut() {
+					if let Err(e) = hash_chain.record(start_offset, output) {
+						eprintln!("{}", e);
+					}
+				}
+				Ok(())
+			}),
+			None => std::io::stdout().write_all(output),
+		};
+		if let Err(e) = &result {
+			eprintln!("Failed to write the output: {}", e);
+		}
+		result.is_ok()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::time::Duration;
+
+	#[test]
+	fn output_sink_chains_and_signs_its_batches() {
+		let path = std::env::temp_dir()
+			.join(format!("xcm_indexer_output_sink_{}.json", std::process::id()));
+		std::fs::write(&path, "").unwrap();
+		let signer = OutputSigner::from_secret(&format!("0x{}", hex::encode([7; 32]))).unwrap();
+		let public_key = signer.public_key();
+		let hash_chain = HashChain::new(&path, false, signer.clone()).unwrap();
+		let output = OutputSink::new(
+			Some(path.clone()),
+			Some(OutputBatch::new(2, Duration::from_secs(3600))),
+			Some(signer),
+			Some(hash_chain),
+		);
+
+		// Clones share the writer, so the batch and the chain cover the writes of every task
+		assert!(output.clone().append(b"[{\"a\":1}]\n", 1));
+		assert_eq!(std::fs::read_to_string(&path).unwrap(), "");
+		assert!(output.append(b"[{\"b\":2}]\n", 1));
+		output.append(b"[{\"c\":3}]\n", 1);
+		output.flush();
+		let content = std::fs::read_to_string(&path).unwrap();
+		let verification = crate::hash_chain::verify(&path, &public_key).unwrap();
+		let mut chain_path = path.clone().into_os_string();
+		chain_path.push(".chain");
+		let _ = std::fs::remove_file(chain_path);
+		std::fs::remove_file(&path).unwrap();
+
+		// Every batch is followed by its signature record
+		assert_eq!(content.lines().filter(|line| line.contains("batch_hash")).count(), 2);
+		assert!(verification.valid);
+		assert_eq!(verification.checkpoints_verified, 2);
+	}
+}
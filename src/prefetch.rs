@@ -0,0 +1,51 @@
+use crate::{
+	Error,
+	block_source::{BlockSource, SourceBlock},
+	state_proofs::BlockStorage,
+	types::BlockHash,
+	watchdog::SharedClient,
+};
+use std::{collections::HashMap, sync::Mutex};
+use subxt::{OnlineClient, PolkadotConfig};
+use tokio::task::JoinHandle;
+
+type PrefetchedBlock = Result<SourceBlock<OnlineClient<PolkadotConfig>, BlockStorage>, Error>;
+
+// Downloads the blocks queued for the workers of a subscription ahead of their parsing, once it
+// falls behind the chain (eg, after a reconnection or while the sinks are slow). Blocks are then
+// fetched while the workers are busy, instead of strictly when a worker takes them.
+pub(crate) struct Prefetcher {
+	api: SharedClient,
+	blocks: Mutex<HashMap<BlockHash, JoinHandle<PrefetchedBlock>>>,
+}
+
+impl Prefetcher {
+	pub(crate) fn new(api: SharedClient) -> Self {
+		Self { api, blocks: Mutex::new(HashMap::new()) }
+	}
+
+	pub(crate) fn prefetch(&self, block_hash: BlockHash) {
+		let api = self.api.get();
+		let handle = tokio::spawn(async move { api.block(block_hash).await });
+		if let Ok(mut blocks) = self.blocks.lock() {
+			blocks.insert(block_hash, handle);
+		}
+	}
+}
+
+impl BlockSource for Prefetcher {
+	type Client = OnlineClient<PolkadotConfig>;
+	type Storage = BlockStorage;
+
+	// The prefetched block if any, fetched again if its download failed, as the failure may have
+	// been transient.
+	async fn block(&self, block_hash: BlockHash) -> PrefetchedBlock {
+		let handle = self.blocks.lock().ok().and_then(|mut blocks| blocks.remove(&block_hash));
+		if let Some(handle) = handle {
+			if let Ok(Ok(block)) = handle.await {
+				return Ok(block);
+			}
+		}
+		self.api.get().block(block_hash).await
+	}
+}
@@ -0,0 +1,467 @@
+use crate::{
+	analytics::{RoundTrip as XcmRoundTrip, TransferStatusUpdate, VolumeAnomaly},
+	incoming_parser::{XcmFailedMessage, XcmIncomingTransfer},
+	outgoing_parser::{XcmOutgoingAsset, XcmOutgoingTransfer, XcmOutgoingTransferGroup},
+	types::XcmTransfer,
+};
+use prost::Message;
+use serde::Serialize;
+
+// The protobuf records written with `--format proto`, following the schema in
+// `proto/xcm_transfers.proto`. They're a compact alternative to JSON for high-volume backfills.
+#[derive(Clone, PartialEq, Message)]
+pub(crate) struct Transfer {
+	#[prost(oneof = "transfer::Kind", tags = "1, 2, 3, 4, 5, 6, 7")]
+	pub(crate) kind: Option<transfer::Kind>,
+}
+
+pub(crate) mod transfer {
+	#[derive(Clone, PartialEq, prost::Oneof)]
+	pub(crate) enum Kind {
+		#[prost(message, tag = "1")]
+		ReceivedTransfer(super::ReceivedTransfer),
+		#[prost(message, tag = "2")]
+		SentTransfer(super::SentTransfer),
+		#[prost(message, tag = "3")]
+		SentTransferGroup(super::SentTransferGroup),
+		#[prost(message, tag = "4")]
+		RoundTrip(super::RoundTrip),
+		#[prost(message, tag = "5")]
+		StatusUpdate(super::StatusUpdate),
+		#[prost(message, tag = "6")]
+		Anomaly(super::Anomaly),
+		#[prost(message, tag = "7")]
+		FailedMessage(super::FailedMessage),
+	}
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub(crate) struct ReceivedTransfer {
+	#[prost(uint32, tag = "1")]
+	pub(crate) block_number: u32,
+	#[prost(string, tag = "2")]
+	pub(crate) origin_chain: String,
+	#[prost(string, tag = "3")]
+	pub(crate) beneficiary: String,
+	#[prost(string, tag = "4")]
+	pub(crate) beneficiary_public_key: String,
+	#[prost(string, tag = "5")]
+	pub(crate) asset: String,
+	#[prost(double, tag = "6")]
+	pub(crate) amount: f64,
+	#[prost(string, tag = "7")]
+	pub(crate) transfer_type: String,
+	#[prost(string, optional, tag = "8")]
+	pub(crate) fee_swap: Option<String>,
+	#[prost(string, optional, tag = "9")]
+	pub(crate) xc20_address: Option<String>,
+	#[prost(double, optional, tag = "10")]
+	pub(crate) dust_lost: Option<f64>,
+	#[prost(string, optional, tag = "11")]
+	pub(crate) trapped_assets_hash: Option<String>,
+	#[prost(bool, tag = "12")]
+	pub(crate) new_account: bool,
+	#[prost(string, optional, tag = "13")]
+	pub(crate) asset_id: Option<String>,
+	#[prost(string, optional, tag = "14")]
+	pub(crate) exact_amount: Option<String>,
+	#[prost(string, optional, tag = "15")]
+	pub(crate) message_id: Option<String>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub(crate) struct SentTransfer {
+	#[prost(uint32, tag = "1")]
+	pub(crate) block_number: u32,
+	#[prost(string, tag = "2")]
+	pub(crate) transfer_group_id: String,
+	#[prost(string, optional, tag = "3")]
+	pub(crate) origin: Option<String>,
+	#[prost(string, tag = "4")]
+	pub(crate) destination_chain: String,
+	#[prost(string, tag = "5")]
+	pub(crate) sender: String,
+	#[prost(string, optional, tag = "6")]
+	pub(crate) sender_public_key: Option<String>,
+	#[prost(string, tag = "7")]
+	pub(crate) beneficiary: String,
+	#[prost(string, tag = "8")]
+	pub(crate) beneficiary_public_key: String,
+	#[prost(string, tag = "9")]
+	pub(crate) asset: String,
+	#[prost(double, tag = "10")]
+	pub(crate) amount: f64,
+	#[prost(string, tag = "11")]
+	pub(crate) transfer_type: String,
+	#[prost(uint32, tag = "12")]
+	pub(crate) fee_asset_item: u32,
+	#[prost(bool, tag = "13")]
+	pub(crate) pays_fees: bool,
+	#[prost(string, tag = "14")]
+	pub(crate) weight_limit: String,
+	#[prost(double, optional, tag = "15")]
+	pub(crate) burned_amount: Option<f64>,
+	#[prost(string, optional, tag = "16")]
+	pub(crate) outcome: Option<String>,
+	#[prost(string, optional, tag = "17")]
+	pub(crate) message_id: Option<String>,
+	#[prost(string, optional, tag = "18")]
+	pub(crate) xc20_address: Option<String>,
+	#[prost(double, optional, tag = "19")]
+	pub(crate) local_fee_dot: Option<f64>,
+	#[prost(uint64, optional, tag = "20")]
+	pub(crate) nonce: Option<u64>,
+	#[prost(double, optional, tag = "21")]
+	pub(crate) tip_dot: Option<f64>,
+	#[prost(string, optional, tag = "22")]
+	pub(crate) asset_id: Option<String>,
+	#[prost(string, optional, tag = "23")]
+	pub(crate) exact_amount: Option<String>,
+	#[prost(string, optional, tag = "24")]
+	pub(crate) trapped_assets_hash: Option<String>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub(crate) struct SentAsset {
+	#[prost(string, tag = "1")]
+	pub(crate) asset: String,
+	#[prost(double, tag = "2")]
+	pub(crate) amount: f64,
+	#[prost(string, tag = "3")]
+	pub(crate) transfer_type: String,
+	#[prost(bool, tag = "4")]
+	pub(crate) pays_fees: bool,
+	#[prost(double, optional, tag = "5")]
+	pub(crate) burned_amount: Option<f64>,
+	#[prost(string, optional, tag = "6")]
+	pub(crate) xc20_address: Option<String>,
+	#[prost(string, optional, tag = "7")]
+	pub(crate) asset_id: Option<String>,
+	#[prost(string, optional, tag = "8")]
+	pub(crate) exact_amount: Option<String>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub(crate) struct SentTransferGroup {
+	#[prost(string, tag = "1")]
+	pub(crate) transfer_group_id: String,
+	#[prost(uint32, tag = "2")]
+	pub(crate) block_number: u32,
+	#[prost(string, optional, tag = "3")]
+	pub(crate) origin: Option<String>,
+	#[prost(string, tag = "4")]
+	pub(crate) destination_chain: String,
+	#[prost(string, tag = "5")]
+	pub(crate) sender: String,
+	#[prost(string, optional, tag = "6")]
+	pub(crate) sender_public_key: Option<String>,
+	#[prost(string, tag = "7")]
+	pub(crate) beneficiary: String,
+	#[prost(string, tag = "8")]
+	pub(crate) beneficiary_public_key: String,
+	#[prost(uint32, tag = "9")]
+	pub(crate) fee_asset_item: u32,
+	#[prost(string, tag = "10")]
+	pub(crate) weight_limit: String,
+	#[prost(string, optional, tag = "11")]
+	pub(crate) outcome: Option<String>,
+	#[prost(string, optional, tag = "12")]
+	pub(crate) message_id: Option<String>,
+	#[prost(message, repeated, tag = "13")]
+	pub(crate) assets: Vec<SentAsset>,
+	#[prost(double, optional, tag = "14")]
+	pub(crate) local_fee_dot: Option<f64>,
+	#[prost(uint64, optional, tag = "15")]
+	pub(crate) nonce: Option<u64>,
+	#[prost(double, optional, tag = "16")]
+	pub(crate) tip_dot: Option<f64>,
+	#[prost(string, optional, tag = "17")]
+	pub(crate) trapped_assets_hash: Option<String>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub(crate) struct RoundTrip {
+	#[prost(message, optional, tag = "1")]
+	pub(crate) outgoing: Option<SentTransfer>,
+	#[prost(message, optional, tag = "2")]
+	pub(crate) incoming: Option<ReceivedTransfer>,
+	#[prost(uint32, tag = "3")]
+	pub(crate) blocks_elapsed: u32,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub(crate) struct StatusUpdate {
+	#[prost(string, tag = "1")]
+	pub(crate) transfer_group_id: String,
+	#[prost(string, optional, tag = "2")]
+	pub(crate) message_id: Option<String>,
+	#[prost(string, tag = "3")]
+	pub(crate) status: String,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub(crate) struct Anomaly {
+	#[prost(uint32, tag = "1")]
+	pub(crate) block_number: u32,
+	#[prost(string, tag = "2")]
+	pub(crate) direction: String,
+	#[prost(string, tag = "3")]
+	pub(crate) asset: String,
+	#[prost(string, tag = "4")]
+	pub(crate) counterparty: String,
+	#[prost(string, tag = "5")]
+	pub(crate) deviation: String,
+	#[prost(double, tag = "6")]
+	pub(crate) hourly_volume: f64,
+	#[prost(double, tag = "7")]
+	pub(crate) baseline: f64,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub(crate) struct FailedMessage {
+	#[prost(uint32, tag = "1")]
+	pub(crate) block_number: u32,
+	#[prost(string, tag = "2")]
+	pub(crate) origin_chain: String,
+	#[prost(string, tag = "3")]
+	pub(crate) message_id: String,
+	#[prost(string, tag = "4")]
+	pub(crate) error: String,
+	#[prost(string, optional, tag = "5")]
+	pub(crate) trapped_assets_hash: Option<String>,
+}
+
+// Enums without data are encoded as their name, the remaining values as their JSON representation.
+fn label<T: Serialize>(value: &T) -> String {
+	match serde_json::to_value(value) {
+		Ok(serde_json::Value::String(name)) => name,
+		Ok(value) => value.to_string(),
+		Err(_) => String::new(),
+	}
+}
+
+impl From<&XcmIncomingTransfer> for ReceivedTransfer {
+	fn from(transfer: &XcmIncomingTransfer) -> Self {
+		Self {
+			block_number: transfer.block_number,
+			origin_chain: label(&transfer.origin_chain),
+			beneficiary: transfer.beneficiary.clone(),
+			beneficiary_public_key: transfer.beneficiary_public_key.clone(),
+			asset: transfer.asset.clone(),
+			amount: transfer.amount,
+			transfer_type: label(&transfer.transfer_type),
+			fee_swap: transfer.fee_swap.as_ref().map(label),
+			xc20_address: transfer.xc20_address.clone(),
+			dust_lost: transfer.dust_lost,
+			trapped_assets_hash: transfer.trapped_assets_hash.clone(),
+			new_account: transfer.new_account,
+			asset_id: transfer.asset_id.as_ref().map(label),
+			exact_amount: transfer.exact_amount.clone(),
+			message_id: transfer.message_id.clone(),
+		}
+	}
+}
+
+impl From<&XcmOutgoingTransfer> for SentTransfer {
+	fn from(transfer: &XcmOutgoingTransfer) -> Self {
+		Self {
+			block_number: transfer.block_number,
+			transfer_group_id: transfer.transfer_group_id.clone(),
+			origin: transfer.origin.as_ref().map(label),
+			destination_chain: label(&transfer.destination_chain),
+			sender: transfer.sender.clone(),
+			sender_public_key: transfer.sender_public_key.clone(),
+			beneficiary: transfer.beneficiary.clone(),
+			beneficiary_public_key: transfer.beneficiary_public_key.clone(),
+			asset: transfer.asset.clone(),
+			amount: transfer.amount,
+			transfer_type: label(&transfer.transfer_type),
+			fee_asset_item: transfer.fee_asset_item,
+			pays_fees: transfer.pays_fees,
+			weight_limit: label(&transfer.weight_limit),
+			burned_amount: transfer.burned_amount,
+			outcome: transfer.outcome.as_ref().map(label),
+			message_id: transfer.message_id.clone(),
+			xc20_address: transfer.xc20_address.clone(),
+			local_fee_dot: transfer.local_fee_dot,
+			nonce: transfer.nonce,
+			tip_dot: transfer.tip_dot,
+			asset_id: transfer.asset_id.as_ref().map(label),
+			exact_amount: transfer.exact_amount.clone(),
+			trapped_assets_hash: transfer.trapped_assets_hash.clone(),
+		}
+	}
+}
+
+impl From<&XcmOutgoingAsset> for SentAsset {
+	fn from(asset: &XcmOutgoingAsset) -> Self {
+		Self {
+			asset: asset.asset.clone(),
+			amount: asset.amount,
+			transfer_type: label(&asset.transfer_type),
+			pays_fees: asset.pays_fees,
+			burned_amount: asset.burned_amount,
+			xc20_address: asset.xc20_address.clone(),
+			asset_id: asset.asset_id.as_ref().map(label),
+			exact_amount: asset.exact_amount.clone(),
+		}
+	}
+}
+
+impl From<&XcmOutgoingTransferGroup> for SentTransferGroup {
+	fn from(group: &XcmOutgoingTransferGroup) -> Self {
+		Self {
+			transfer_group_id: group.transfer_group_id.clone(),
+			block_number: group.block_number,
+			origin: group.origin.as_ref().map(label),
+			destination_chain: label(&group.destination_chain),
+			sender: group.sender.clone(),
+			sender_public_key: group.sender_public_key.clone(),
+			beneficiary: group.beneficiary.clone(),
+			beneficiary_public_key: group.beneficiary_public_key.clone(),
+			fee_asset_item: group.fee_asset_item,
+			weight_limit: label(&group.weight_limit),
+			outcome: group.outcome.as_ref().map(label),
+			message_id: group.message_id.clone(),
+			assets: group.assets.iter().map(SentAsset::from).collect(),
+			local_fee_dot: group.local_fee_dot,
+			nonce: group.nonce,
+			tip_dot: group.tip_dot,
+			trapped_assets_hash: group.trapped_assets_hash.clone(),
+		}
+	}
+}
+
+impl From<&XcmRoundTrip> for RoundTrip {
+	fn from(round_trip: &XcmRoundTrip) -> Self {
+		Self {
+			outgoing: Some((&round_trip.outgoing).into()),
+			incoming: Some((&round_trip.incoming).into()),
+			blocks_elapsed: round_trip.blocks_elapsed,
+		}
+	}
+}
+
+impl From<&TransferStatusUpdate> for StatusUpdate {
+	fn from(update: &TransferStatusUpdate) -> Self {
+		Self {
+			transfer_group_id: update.transfer_group_id.clone(),
+			message_id: update.message_id.clone(),
+			status: label(&update.status),
+		}
+	}
+}
+
+impl From<&VolumeAnomaly> for Anomaly {
+	fn from(anomaly: &VolumeAnomaly) -> Self {
+		Self {
+			block_number: anomaly.block_number,
+			direction: label(&anomaly.direction),
+			asset: anomaly.asset.clone(),
+			counterparty: anomaly.counterparty.clone(),
+			deviation: label(&anomaly.deviation),
+			hourly_volume: anomaly.hourly_volume,
+			baseline: anomaly.baseline,
+		}
+	}
+}
+
+impl From<&XcmFailedMessage> for FailedMessage {
+	fn from(failed_message: &XcmFailedMessage) -> Self {
+		Self {
+			block_number: failed_message.block_number,
+			origin_chain: label(&failed_message.origin_chain),
+			message_id: failed_message.message_id.clone(),
+			error: label(&failed_message.error),
+			trapped_assets_hash: failed_message.trapped_assets_hash.clone(),
+		}
+	}
+}
+
+impl From<&XcmTransfer> for Transfer {
+	fn from(transfer: &XcmTransfer) -> Self {
+		let kind = match transfer {
+			XcmTransfer::ReceivedTransfer(transfer) =>
+				transfer::Kind::ReceivedTransfer(transfer.into()),
+			XcmTransfer::SentTransfer(transfer) => transfer::Kind::SentTransfer(transfer.into()),
+			XcmTransfer::SentTransferGroup(group) =>
+				transfer::Kind::SentTransferGroup(group.into()),
+			XcmTransfer::RoundTrip(round_trip) => transfer::Kind::RoundTrip(round_trip.into()),
+			XcmTransfer::StatusUpdate(update) => transfer::Kind::StatusUpdate(update.into()),
+			XcmTransfer::Anomaly(anomaly) => transfer::Kind::Anomaly(anomaly.into()),
+			XcmTransfer::FailedMessage(failed_message) =>
+				transfer::Kind::FailedMessage(failed_message.into()),
+		};
+		Self { kind: Some(kind) }
+	}
+}
+
+// Encodes the transfers as a stream of length-delimited records.
+pub(crate) fn encode_transfers(transfers: &[XcmTransfer]) -> Vec<u8> {
+	let mut output = vec![];
+	for transfer in transfers {
+		// Writing into a vector cannot run out of capacity
+		let _ = Transfer::from(transfer).encode_length_delimited(&mut output);
+	}
+	output
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		outgoing_parser::{DestinationChain, WeightLimit},
+		types::{AssetIdentifier, TransferType},
+	};
+
+	#[test]
+	fn encode_transfers_writes_length_delimited_records() {
+		let transfer = XcmOutgoingTransfer {
+			block_number: 8_935_124,
+			transfer_group_id: "8935124-2".to_owned(),
+			origin: None,
+			destination_chain: DestinationChain::PolkadotParachain(2034),
+			sender: "13KsaHFcQKSTd4m73Ub9yVwM1JGCZvipMyTZonHEXEceFYwS".to_owned(),
+			sender_public_key: None,
+			beneficiary: "16hiHzdGAR7wi29PjCyUkpFCbjTe9Ri6PrnumbEeyhqg75wy".to_owned(),
+			beneficiary_public_key:
+				"0xfc39fcf04a8071b7409823b7c82427ce67910c6ed80aa0e5093aff234624c820".to_owned(),
+			asset: "DOT".to_owned(),
+			asset_id: Some(AssetIdentifier::Native),
+			amount: 1.5,
+			exact_amount: Some("1.5".to_owned()),
+			transfer_type: TransferType::Teleport,
+			fee_asset_item: 0,
+			pays_fees: true,
+			weight_limit: WeightLimit::Unlimited,
+			burned_amount: Some(1.5),
+			outcome: None,
+			message_id: None,
+			trapped_assets_hash: None,
+			local_fee_dot: None,
+			nonce: None,
+			tip_dot: None,
+			controllers: None,
+			raw_xcm: None,
+			entities: vec![],
+			xc20_address: None,
+		};
+		let transfers = vec![XcmTransfer::SentTransfer(transfer.clone()); 2];
+
+		let mut encoded = &encode_transfers(&transfers)[..];
+		for _ in 0..2 {
+			let record = Transfer::decode_length_delimited(&mut encoded).unwrap();
+			let Some(transfer::Kind::SentTransfer(sent_transfer)) = record.kind else {
+				panic!("Expected a sent transfer");
+			};
+			assert_eq!(sent_transfer.destination_chain, r#"{"PolkadotParachain":2034}"#);
+			assert_eq!(sent_transfer.transfer_type, "Teleport");
+			assert_eq!(sent_transfer.weight_limit, "Unlimited");
+			assert_eq!(sent_transfer.burned_amount, Some(1.5));
+			assert_eq!(sent_transfer.exact_amount.as_deref(), Some("1.5"));
+			assert_eq!(sent_transfer.asset_id.as_deref(), Some("native"));
+			assert_eq!(sent_transfer.origin, None);
+		}
+		assert!(encoded.is_empty());
+	}
+}
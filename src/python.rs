@@ -0,0 +1,106 @@
+use crate::{
+	Error,
+	chain_spec::{Chain, ChainSpec},
+	types::{BlockHash, XcmTransfer},
+};
+use pyo3::{
+	exceptions::{PyRuntimeError, PyStopAsyncIteration, PyValueError},
+	prelude::*,
+};
+use pyo3_async_runtimes::tokio::future_into_py;
+use serde::Serialize;
+use std::{collections::VecDeque, sync::Arc};
+use subxt::{OnlineClient, PolkadotConfig, backend::StreamOfResults, blocks::Block};
+use tokio::sync::Mutex;
+
+type BlockStream = StreamOfResults<Block<PolkadotConfig, OnlineClient<PolkadotConfig>>>;
+
+impl From<Error> for PyErr {
+	fn from(err: Error) -> Self {
+		PyRuntimeError::new_err(err.to_string())
+	}
+}
+
+async fn connect(url: &str) -> Result<OnlineClient<PolkadotConfig>, Error> {
+	let api = OnlineClient::<PolkadotConfig>::from_url(url).await?;
+	crate::helpers::validate_ah_metadata(&api.metadata())?;
+	Ok(api)
+}
+
+// The transfers are handed to Python as the plain objects resulting from loading their JSON
+// representation, so notebooks get the same format as the CLI output.
+fn to_python<T: Serialize + ?Sized>(value: &T) -> PyResult<PyObject> {
+	let json =
+		serde_json::to_string(value).map_err(|err| PyValueError::new_err(err.to_string()))?;
+	Python::with_gil(|py| Ok(py.import("json")?.call_method1("loads", (json,))?.unbind()))
+}
+
+/// Get all Xcm transfers that happened in a specific block hash.
+#[pyfunction]
+#[pyo3(signature = (block_hash, url = Chain::RPC_ENDPOINT.to_owned()))]
+fn get_transfers_at(py: Python<'_>, block_hash: String, url: String) -> PyResult<Bound<'_, PyAny>> {
+	future_into_py(py, async move {
+		let block_hash: BlockHash = block_hash.parse().map_err(|_| {
+			PyValueError::new_err(format!("{} isn't a valid block hash", block_hash))
+		})?;
+		let api = connect(&url).await?;
+		let transfers = crate::helpers::get_all_transfers_at_block_hash(&api, block_hash).await?;
+		to_python(&transfers)
+	})
+}
+
+/// Subscribe to the Xcm transfers contained in new finalized blocks. The returned object is an
+/// async iterator yielding the transfers one by one.
+#[pyfunction]
+#[pyo3(signature = (url = Chain::RPC_ENDPOINT.to_owned()))]
+fn subscribe_to_new_transfers(py: Python<'_>, url: String) -> PyResult<Bound<'_, PyAny>> {
+	future_into_py(py, async move {
+		let api = connect(&url).await?;
+		let stream = api.blocks().subscribe_finalized().await.map_err(Error::from)?;
+		Ok(TransferSubscription { api, state: Arc::new(Mutex::new((stream, VecDeque::new()))) })
+	})
+}
+
+#[pyclass]
+struct TransferSubscription {
+	api: OnlineClient<PolkadotConfig>,
+	// A block may contain several transfers, so they're kept here until they're yielded.
+	state: Arc<Mutex<(BlockStream, VecDeque<XcmTransfer>)>>,
+}
+
+#[pymethods]
+impl TransferSubscription {
+	fn __aiter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+		slf
+	}
+
+	fn __anext__<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+		let api = self.api.clone();
+		let state = self.state.clone();
+		future_into_py(py, async move {
+			let mut state = state.lock().await;
+			let (stream, pending_transfers) = &mut *state;
+			loop {
+				if let Some(transfer) = pending_transfers.pop_front() {
+					return to_python(&transfer);
+				}
+				match stream.next().await {
+					Some(Ok(block)) => pending_transfers.extend(
+						crate::helpers::get_all_transfers_at_block_hash(&api, block.hash()).await?,
+					),
+					Some(Err(err)) => return Err(Error::from(err).into()),
+					None =>
+						return Err(PyStopAsyncIteration::new_err("The subscription was closed")),
+				}
+			}
+		})
+	}
+}
+
+#[pymodule]
+fn asset_hub_xcm_indexer(module: &Bound<'_, PyModule>) -> PyResult<()> {
+	module.add_function(wrap_pyfunction!(get_transfers_at, module)?)?;
+	module.add_function(wrap_pyfunction!(subscribe_to_new_transfers, module)?)?;
+	module.add_class::<TransferSubscription>()?;
+	Ok(())
+}
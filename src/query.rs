@@ -0,0 +1,144 @@
+use crate::types::{AssetIdentifier, BlockNumber, XcmTransfer};
+
+// The criteria of `query` over the transfers stored in the JSON outputs of the indexer. Every
+// criterion given must hold: an account matches the transfers it sent or received, and an asset,
+// given by its symbol or its location, the groups holding it among their assets.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub(crate) struct TransferQuery {
+	pub(crate) account_public_key: Option<String>,
+	pub(crate) asset: Option<String>,
+	pub(crate) from_block: Option<BlockNumber>,
+	pub(crate) to_block: Option<BlockNumber>,
+}
+
+impl TransferQuery {
+	// Only the transfers are queried, the other records (eg, status updates) aren't.
+	pub(crate) fn matches(&self, transfer: &XcmTransfer) -> bool {
+		let (block_number, accounts, assets): (
+			_,
+			Vec<Option<&String>>,
+			Vec<(&String, Option<&AssetIdentifier>)>,
+		) = match transfer {
+			XcmTransfer::ReceivedTransfer(incoming) => (
+				incoming.block_number,
+				vec![Some(&incoming.beneficiary_public_key)],
+				vec![(&incoming.asset, incoming.asset_id.as_ref())],
+			),
+			XcmTransfer::SentTransfer(outgoing) => (
+				outgoing.block_number,
+				vec![outgoing.sender_public_key.as_ref(), Some(&outgoing.beneficiary_public_key)],
+				vec![(&outgoing.asset, outgoing.asset_id.as_ref())],
+			),
+			XcmTransfer::SentTransferGroup(group) => (
+				group.block_number,
+				vec![group.sender_public_key.as_ref(), Some(&group.beneficiary_public_key)],
+				group
+					.assets
+					.iter()
+					.map(|asset| (&asset.asset, asset.asset_id.as_ref()))
+					.collect(),
+			),
+			XcmTransfer::RoundTrip(_) |
+			XcmTransfer::StatusUpdate(_) |
+			XcmTransfer::Anomaly(_) |
+			XcmTransfer::FailedMessage(_) => return false,
+		};
+		self.from_block.is_none_or(|from_block| block_number >= from_block) &&
+			self.to_block.is_none_or(|to_block| block_number <= to_block) &&
+			self.account_public_key.as_ref().is_none_or(|public_key| {
+				accounts
+					.into_iter()
+					.flatten()
+					.any(|account| account.eq_ignore_ascii_case(public_key))
+			}) && self.asset.as_ref().is_none_or(|asset| {
+			assets.into_iter().any(|(symbol, asset_id)| {
+				symbol.eq_ignore_ascii_case(asset) ||
+					asset_id.is_some_and(|asset_id| asset_id.is_at(asset))
+			})
+		})
+	}
+
+	// The first `limit` transfers matching, in the order they're stored.
+	pub(crate) fn run(
+		&self,
+		transfers: Vec<XcmTransfer>,
+		limit: Option<usize>,
+	) -> Vec<XcmTransfer> {
+		transfers
+			.into_iter()
+			.filter(|transfer| self.matches(transfer))
+			.take(limit.unwrap_or(usize::MAX))
+			.collect()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		incoming_parser::{OriginChain, XcmIncomingTransfer},
+		types::TransferType,
+	};
+
+	fn received(block_number: BlockNumber, public_key: &str, asset: &str) -> XcmTransfer {
+		XcmTransfer::ReceivedTransfer(XcmIncomingTransfer {
+			block_number,
+			origin_chain: OriginChain::PolkadotParachain(2034),
+			message_id: None,
+			beneficiary: String::new(),
+			beneficiary_public_key: public_key.to_owned(),
+			asset: asset.to_owned(),
+			asset_id: None,
+			amount: 1.0,
+			exact_amount: None,
+			transfer_type: TransferType::Reserve,
+			fee_swap: None,
+			entities: vec![],
+			dust_lost: None,
+			trapped_assets_hash: None,
+			new_account: false,
+			xc20_address: None,
+		})
+	}
+
+	#[test]
+	fn queries_filter_the_stored_transfers() {
+		let transfers = vec![
+			received(10, "0x01", "USDT"),
+			received(11, "0x02", "USDT"),
+			received(12, "0x01", "DOT"),
+			received(13, "0x01", "USDT"),
+		];
+		assert_eq!(TransferQuery::default().run(transfers.clone(), None), transfers);
+
+		let query = TransferQuery {
+			account_public_key: Some("0x01".to_owned()),
+			asset: Some("usdt".to_owned()),
+			..Default::default()
+		};
+		assert_eq!(
+			query.run(transfers.clone(), None),
+			vec![transfers[0].clone(), transfers[3].clone()]
+		);
+		assert_eq!(query.run(transfers.clone(), Some(1)), vec![transfers[0].clone()]);
+
+		let query =
+			TransferQuery { from_block: Some(11), to_block: Some(12), ..Default::default() };
+		assert_eq!(query.run(transfers.clone(), None), transfers[1..3].to_vec());
+
+		// Foreign assets sharing a symbol are told apart by their location
+		let mut foreign_asset = received(14, "0x01", "USDT");
+		if let XcmTransfer::ReceivedTransfer(incoming) = &mut foreign_asset {
+			incoming.asset_id =
+				Some(AssetIdentifier::ForeignAsset("parents=1/Parachain(2004)".to_owned()));
+		}
+		let query = TransferQuery {
+			asset: Some("parents=1/Parachain(2004)".to_owned()),
+			..Default::default()
+		};
+		assert_eq!(
+			query.run([transfers, vec![foreign_asset.clone()]].concat(), None),
+			vec![foreign_asset]
+		);
+	}
+}
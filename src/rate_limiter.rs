@@ -0,0 +1,123 @@
+use std::{num::NonZeroU32, time::Duration};
+use subxt::backend::rpc::{RawRpcFuture, RawRpcSubscription, RawValue, RpcClientT};
+use tokio::{sync::Mutex, time::Instant};
+
+// A token bucket allowing up to `max_rps` requests per second. The bucket starts full, so bursts
+// of `max_rps` requests are allowed as long as the average rate stays under the limit.
+pub(crate) struct RateLimiter {
+	max_rps: f64,
+	bucket: Mutex<Bucket>,
+}
+
+struct Bucket {
+	tokens: f64,
+	last_refill: Instant,
+}
+
+impl RateLimiter {
+	pub(crate) fn new(max_rps: NonZeroU32) -> Self {
+		let max_rps = max_rps.get() as f64;
+		Self {
+			max_rps,
+			bucket: Mutex::new(Bucket { tokens: max_rps, last_refill: Instant::now() }),
+		}
+	}
+
+	pub(crate) async fn acquire(&self) {
+		loop {
+			let wait = {
+				let mut bucket = self.bucket.lock().await;
+				let now = Instant::now();
+				let elapsed = now.duration_since(bucket.last_refill).as_secs_f64();
+				bucket.tokens = (bucket.tokens + elapsed * self.max_rps).min(self.max_rps);
+				bucket.last_refill = now;
+				if bucket.tokens >= 1.0 {
+					bucket.tokens -= 1.0;
+					return;
+				}
+				Duration::from_secs_f64((1.0 - bucket.tokens) / self.max_rps)
+			};
+			tokio::time::sleep(wait).await;
+		}
+	}
+}
+
+// Wraps a RPC client so every request and subscription sent to the node has to go through the
+// rate limiter first. As all the block and storage queries done by subxt end up here, this is
+// enough to keep the whole indexer under the limit.
+pub(crate) struct RateLimitedRpcClient<C> {
+	inner: C,
+	limiter: RateLimiter,
+}
+
+impl<C> RateLimitedRpcClient<C> {
+	pub(crate) fn new(inner: C, max_rps: NonZeroU32) -> Self {
+		Self { inner, limiter: RateLimiter::new(max_rps) }
+	}
+}
+
+impl<C: RpcClientT> RpcClientT for RateLimitedRpcClient<C> {
+	fn request_raw<'a>(
+		&'a self,
+		method: &'a str,
+		params: Option<Box<RawValue>>,
+	) -> RawRpcFuture<'a, Box<RawValue>> {
+		Box::pin(async move {
+			self.limiter.acquire().await;
+			self.inner.request_raw(method, params).await
+		})
+	}
+
+	fn subscribe_raw<'a>(
+		&'a self,
+		sub: &'a str,
+		params: Option<Box<RawValue>>,
+		unsub: &'a str,
+	) -> RawRpcFuture<'a, RawRpcSubscription> {
+		Box::pin(async move {
+			self.limiter.acquire().await;
+			self.inner.subscribe_raw(sub, params, unsub).await
+		})
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	// The clock is paused, so it only moves while the limiter sleeps
+	#[tokio::test(start_paused = true)]
+	async fn rate_limiter_allows_burst_up_to_max_rps() {
+		let limiter = RateLimiter::new(NonZeroU32::new(10).unwrap());
+		let start = Instant::now();
+		for _ in 0..10 {
+			limiter.acquire().await;
+		}
+		assert_eq!(start.elapsed(), Duration::ZERO);
+	}
+
+	#[tokio::test(start_paused = true)]
+	async fn rate_limiter_waits_once_bucket_is_empty() {
+		let limiter = RateLimiter::new(NonZeroU32::new(10).unwrap());
+		let start = Instant::now();
+		for _ in 0..12 {
+			limiter.acquire().await;
+		}
+		// 2 requests over the burst at 10 requests per second need 200ms
+		assert_eq!(start.elapsed(), Duration::from_millis(200));
+	}
+
+	#[tokio::test(start_paused = true)]
+	async fn rate_limiter_refills_over_time() {
+		let limiter = RateLimiter::new(NonZeroU32::new(10).unwrap());
+		for _ in 0..10 {
+			limiter.acquire().await;
+		}
+		tokio::time::advance(Duration::from_millis(500)).await;
+		let start = Instant::now();
+		for _ in 0..5 {
+			limiter.acquire().await;
+		}
+		assert_eq!(start.elapsed(), Duration::ZERO);
+	}
+}
@@ -0,0 +1,354 @@
+use crate::{
+	Error,
+	asset_hub::{
+		assets, foreign_assets,
+		runtime_types::{
+			bounded_collections::bounded_vec::BoundedVec, pallet_assets::types::AssetMetadata,
+		},
+	},
+	asset_overrides::{AssetOverride, AssetOverrides},
+	storage_lookup::StorageLookup,
+	types::{BlockHash, BlockNumber, XcmTransfer},
+};
+use serde::{Deserialize, Serialize};
+use std::{
+	fs::{self, OpenOptions},
+	io::Write,
+	path::Path,
+	sync::{Mutex, OnceLock},
+};
+use subxt::{
+	Metadata, OnlineClient, PolkadotConfig,
+	events::{Events, Phase},
+	ext::codec::Decode,
+};
+
+// The pallets whose events make a block XCM related, and the extrinsics emitting them too.
+const XCM_PALLETS: [&str; 3] = ["PolkadotXcm", "XcmpQueue", "MessageQueue"];
+
+// The metadata the indexer was compiled with, needed to decode the events of the raw blocks.
+static METADATA_BYTES: &[u8] = include_bytes!("../artifacts/ah_metadata.scale");
+static METADATA: OnceLock<Result<Metadata, String>> = OnceLock::new();
+// Serializes the writes to the archives, so the blocks appended while an archive is pruned aren't
+// lost when it's rewritten.
+static ARCHIVE_WRITES: Mutex<()> = Mutex::new(());
+
+// A block kept as raw data, so its transfers can be parsed again without a node, eg once the
+// parsers support new kinds of transfers. Archives hold a block per line.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct RawBlock {
+	pub(crate) block_hash: BlockHash,
+	pub(crate) block_number: BlockNumber,
+	#[serde(default)]
+	pub(crate) timestamp: Option<u64>,
+	// The SCALE encoded `System::Events` of the block, hex encoded
+	pub(crate) events: String,
+	// The SCALE encoded XCM related extrinsics, hex encoded. They aren't parsed again, but they're
+	// kept for the records the parsers couldn't interpret to be analysed
+	#[serde(default)]
+	pub(crate) extrinsics: Vec<String>,
+	// The storage can't be read, so the metadata of the assets involved is kept with the block, in
+	// the format of `--asset-overrides`
+	#[serde(default)]
+	pub(crate) assets: AssetOverrides,
+}
+
+pub(crate) fn read_archive(path: &Path) -> Result<Vec<RawBlock>, String> {
+	let content = fs::read_to_string(path)
+		.map_err(|e| format!("Failed to read the raw blocks archive: {}", e))?;
+	content
+		.lines()
+		.filter(|line| !line.trim().is_empty())
+		.enumerate()
+		.map(|(index, line)| {
+			serde_json::from_str(line)
+				.map_err(|e| format!("Invalid raw block at line {}: {}", index + 1, e))
+		})
+		.collect()
+}
+
+// Appends a block to an archive. A single write per block, so the lines of concurrent blocks don't
+// interleave.
+fn append_to_archive(path: &Path, raw_block: &RawBlock) -> Result<(), String> {
+	let mut line = serde_json::to_string(raw_block)
+		.map_err(|e| format!("Failed to serialize a raw block: {}", e))?;
+	line.push('\n');
+	let _guard = ARCHIVE_WRITES.lock().unwrap_or_else(|e| e.into_inner());
+	OpenOptions::new()
+		.create(true)
+		.append(true)
+		.open(path)
+		.and_then(|mut file| file.write_all(line.as_bytes()))
+		.map_err(|e| format!("Failed to write to {}: {}", path.display(), e))
+}
+
+// Rewrites an archive with the blocks kept by `retain`, returning how many were removed. The
+// archive is replaced atomically, so a crash never loses it. A missing archive holds no blocks.
+pub(crate) fn retain_in_archive(
+	path: &Path,
+	retain: impl FnOnce(Vec<RawBlock>) -> Vec<RawBlock>,
+) -> Result<usize, String> {
+	let _guard = ARCHIVE_WRITES.lock().unwrap_or_else(|e| e.into_inner());
+	if !path.exists() {
+		return Ok(0);
+	}
+	let blocks = read_archive(path)?;
+	let archived = blocks.len();
+	let kept = retain(blocks);
+	if kept.len() == archived {
+		return Ok(0);
+	}
+	let content = kept
+		.iter()
+		.map(|block| serde_json::to_string(block).map(|json| json + "\n"))
+		.collect::<Result<String, _>>()
+		.map_err(|e| format!("Failed to serialize a raw block: {}", e))?;
+	let mut temporary_path = path.as_os_str().to_owned();
+	temporary_path.push(".tmp");
+	fs::write(&temporary_path, content)
+		.and_then(|_| fs::rename(&temporary_path, path))
+		.map_err(|e| format!("Failed to rewrite the raw blocks archive: {}", e))?;
+	Ok(archived - kept.len())
+}
+
+// Archives the raw data of a block if it's XCM related, so it can be reprocessed later.
+pub(crate) async fn archive_block(
+	api: &OnlineClient<PolkadotConfig>,
+	block_hash: BlockHash,
+	block_number: BlockNumber,
+	path: &Path,
+) -> Result<(), String> {
+	match fetch_raw_block(api, block_hash, block_number).await {
+		Ok(Some(raw_block)) => append_to_archive(path, &raw_block),
+		Ok(None) => Ok(()),
+		Err(e) => Err(format!("Failed to archive block {}: {}", block_number, e)),
+	}
+}
+
+async fn fetch_raw_block(
+	api: &OnlineClient<PolkadotConfig>,
+	block_hash: BlockHash,
+	block_number: BlockNumber,
+) -> Result<Option<RawBlock>, Error> {
+	let block = api.blocks().at(block_hash).await?;
+	let events = block.events().await?;
+	let mut extrinsic_indexes = vec![];
+	let mut xcm_related = false;
+	for event in events.iter() {
+		let event = event?;
+		if !XCM_PALLETS.contains(&event.pallet_name()) {
+			continue;
+		}
+		xcm_related = true;
+		if let Phase::ApplyExtrinsic(index) = event.phase() {
+			if !extrinsic_indexes.contains(&index) {
+				extrinsic_indexes.push(index);
+			}
+		}
+	}
+	if !xcm_related {
+		return Ok(None);
+	}
+
+	let extrinsics = block
+		.extrinsics()
+		.await?
+		.iter()
+		.filter(|extrinsic| extrinsic_indexes.contains(&extrinsic.index()))
+		.map(|extrinsic| format!("0x{}", hex::encode(extrinsic.bytes())))
+		.collect();
+	// The parsers run again to learn the metadata of the assets the reprocessing will need
+	let storage = RecordingLookup {
+		storage: crate::state_proofs::BlockStorage::at(&block).await?,
+		assets: Mutex::new(AssetOverrides::default()),
+	};
+	parse_decoded_events(block_number, &events, &storage).await?;
+	Ok(Some(RawBlock {
+		block_hash,
+		block_number,
+		timestamp: Some(crate::helpers::get_block_timestamp(api, block_hash).await?),
+		events: format!("0x{}", hex::encode(events.bytes())),
+		extrinsics,
+		assets: storage.assets.into_inner().unwrap_or_default(),
+	}))
+}
+
+// Reads the metadata of the assets from the storage, keeping it in the format it's archived with.
+struct RecordingLookup<S> {
+	storage: S,
+	assets: Mutex<AssetOverrides>,
+}
+
+fn recorded_override(name: &BoundedVec<u8>, decimals: u8) -> AssetOverride {
+	AssetOverride { symbol: String::from_utf8(name.0.clone()).ok(), decimals: Some(decimals) }
+}
+
+impl<S: StorageLookup> StorageLookup for RecordingLookup<S> {
+	async fn asset_metadata(
+		&self,
+		asset_id: &assets::storage::types::metadata::Param0,
+	) -> Result<Option<assets::storage::types::metadata::Metadata>, Error> {
+		let metadata = self.storage.asset_metadata(asset_id).await?;
+		if let (Some(metadata), Ok(mut assets)) = (&metadata, self.assets.lock()) {
+			assets
+				.assets
+				.insert(*asset_id, recorded_override(&metadata.name, metadata.decimals));
+		}
+		Ok(metadata)
+	}
+
+	async fn foreign_asset_metadata(
+		&self,
+		asset_id: &foreign_assets::storage::types::metadata::Param0,
+	) -> Result<Option<foreign_assets::storage::types::metadata::Metadata>, Error> {
+		let metadata = self.storage.foreign_asset_metadata(asset_id).await?;
+		if let (Some(metadata), Ok(mut assets)) = (&metadata, self.assets.lock()) {
+			assets.foreign_assets.insert(
+				crate::locations::encode_location(asset_id),
+				recorded_override(&metadata.name, metadata.decimals),
+			);
+		}
+		Ok(metadata)
+	}
+}
+
+// The asset metadata kept with a block. Assets without a symbol are treated as assets without
+// metadata, so they're rendered by their id or location.
+pub(crate) struct InjectedMetadata(pub(crate) AssetOverrides);
+
+fn injected_metadata(
+	asset_override: Option<&AssetOverride>,
+) -> Option<assets::storage::types::metadata::Metadata> {
+	let asset_override = asset_override?;
+	let symbol = asset_override.symbol.clone()?.into_bytes();
+	Some(AssetMetadata {
+		deposit: 0,
+		name: BoundedVec(symbol.clone()),
+		symbol: BoundedVec(symbol),
+		decimals: asset_override.decimals.unwrap_or_default(),
+		is_frozen: false,
+	})
+}
+
+impl StorageLookup for InjectedMetadata {
+	async fn asset_metadata(
+		&self,
+		asset_id: &assets::storage::types::metadata::Param0,
+	) -> Result<Option<assets::storage::types::metadata::Metadata>, Error> {
+		Ok(injected_metadata(self.0.assets.get(asset_id)))
+	}
+
+	async fn foreign_asset_metadata(
+		&self,
+		asset_id: &foreign_assets::storage::types::metadata::Param0,
+	) -> Result<Option<foreign_assets::storage::types::metadata::Metadata>, Error> {
+		Ok(injected_metadata(self.0.foreign_asset(asset_id)))
+	}
+}
+
+pub(crate) fn metadata() -> Result<Metadata, String> {
+	METADATA
+		.get_or_init(|| {
+			Metadata::decode(&mut &METADATA_BYTES[..])
+				.map_err(|e| format!("Failed to decode the metadata: {}", e))
+		})
+		.clone()
+}
+
+// Parses the transfers of a block from its hex encoded events. Outgoing transfers are rebuilt from
+// the messages sent, as subxt needs a client to decode the extrinsics.
+pub(crate) async fn parse_events(
+	block_number: BlockNumber,
+	events: &str,
+	assets: AssetOverrides,
+) -> Result<Vec<XcmTransfer>, String> {
+	let metadata = metadata()?;
+	let event_bytes = hex::decode(events.trim_start_matches("0x"))
+		.map_err(|e| format!("Invalid events: {}", e))?;
+	let events = Events::<PolkadotConfig>::decode_from(event_bytes, metadata);
+	parse_decoded_events(block_number, &events, &InjectedMetadata(assets))
+		.await
+		.map_err(|e| e.to_string())
+}
+
+async fn parse_decoded_events(
+	block_number: BlockNumber,
+	events: &Events<PolkadotConfig>,
+	storage: &impl StorageLookup,
+) -> Result<Vec<XcmTransfer>, Error> {
+	let mut output = vec![];
+	#[cfg(feature = "incoming")]
+	{
+		let incoming_messages =
+			crate::incoming_parser::parse_incoming_xcm_transfers(block_number, events, storage)
+				.await?;
+		output.extend(incoming_messages.transfers.into_iter().map(XcmTransfer::ReceivedTransfer));
+		output
+			.extend(incoming_messages.failed_messages.into_iter().map(XcmTransfer::FailedMessage));
+	}
+
+	#[cfg(feature = "outgoing")]
+	crate::outgoing_parser::parse_outgoing_xcm_transfers_from_events(block_number, events, storage)
+		.await?
+		.into_iter()
+		.for_each(|transfer| output.push(XcmTransfer::SentTransfer(transfer)));
+
+	Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[tokio::test]
+	async fn archives_are_parsed_line_by_line() {
+		let path = std::env::temp_dir()
+			.join(format!("xcm_indexer_raw_blocks_{}.jsonl", std::process::id()));
+		let block_hash = format!("0x{}", "01".repeat(32));
+		fs::write(
+			&path,
+			format!(
+				"{{ \"block_hash\": \"{}\", \"block_number\": 1, \"events\": \"0x00\" }}\n\nnot json\n",
+				block_hash
+			),
+		)
+		.unwrap();
+		let error = read_archive(&path).unwrap_err();
+		assert!(error.starts_with("Invalid raw block at line 2"));
+
+		fs::write(
+			&path,
+			format!(
+				"{{ \"block_hash\": \"{}\", \"block_number\": 1, \"events\": \"0x00\" }}\n",
+				block_hash
+			),
+		)
+		.unwrap();
+		// Archived blocks are appended with the metadata of their assets
+		let archived = RawBlock {
+			block_hash: BlockHash::repeat_byte(2),
+			block_number: 2,
+			timestamp: Some(1_700_000_000_000),
+			events: "0x00".to_owned(),
+			extrinsics: vec!["0x04".to_owned()],
+			assets: serde_json::from_str(
+				r#"{ "assets": { "1984": { "symbol": "USDT", "decimals": 6 } } }"#,
+			)
+			.unwrap(),
+		};
+		append_to_archive(&path, &archived).unwrap();
+		let blocks = read_archive(&path).unwrap();
+		fs::remove_file(&path).unwrap();
+		assert_eq!(blocks.len(), 2);
+		assert_eq!(blocks[1].extrinsics, archived.extrinsics);
+		assert_eq!(blocks[1].assets, archived.assets);
+		assert_eq!(blocks[0].block_hash, BlockHash::repeat_byte(1));
+		// A block without events has no transfers
+		assert_eq!(
+			parse_events(blocks[0].block_number, &blocks[0].events, AssetOverrides::default())
+				.await,
+			Ok(vec![])
+		);
+	}
+}
@@ -0,0 +1,158 @@
+use crate::{
+	Error,
+	asset_hub::{
+		polkadot_xcm::calls::types::{Execute, Send},
+		runtime_types::asset_hub_polkadot_runtime::RuntimeCall,
+	},
+	outgoing_parser::DestinationChain,
+	types::{BlockHash, XcmTransfer},
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use subxt::{
+	OnlineClient, PolkadotConfig, blocks::ExtrinsicDetails, config::polkadot::AccountId32,
+	ext::codec::Decode, utils::MultiAddress,
+};
+
+// A human-readable rendering of the XCM programs behind a transfer, to debug transfers behaving
+// unexpectedly at destination.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+#[cfg_attr(feature = "typescript", derive(ts_rs::TS))]
+pub struct RawXcm {
+	// The program given to the `send` or `execute` call that sent the transfer
+	#[serde(skip_serializing_if = "Option::is_none")]
+	#[cfg_attr(feature = "typescript", ts(optional))]
+	pub program: Option<String>,
+	// The messages forwarded to other chains by the extrinsic, as reported by dry running it
+	#[serde(default, skip_serializing_if = "Vec::is_empty")]
+	#[cfg_attr(feature = "typescript", ts(as = "Option<Vec<ForwardedMessage>>", optional))]
+	pub forwarded_messages: Vec<ForwardedMessage>,
+	// Why the extrinsic couldn't be dry run, eg because the runtime of the block didn't have the
+	// dry run API yet
+	#[serde(skip_serializing_if = "Option::is_none")]
+	#[cfg_attr(feature = "typescript", ts(optional))]
+	pub dry_run_error: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+#[cfg_attr(feature = "typescript", derive(ts_rs::TS))]
+pub struct ForwardedMessage {
+	pub destination: DestinationChain,
+	pub message: String,
+}
+
+// The transfer group id of the transfers sent by extrinsics is `<block>-<extrinsic_index>`, while
+// the ones dispatched by hooks carry the phase too.
+fn extrinsic_index(transfer_group_id: &str) -> Option<u32> {
+	transfer_group_id.split_once('-').and_then(|(_, index)| index.parse().ok())
+}
+
+// Attaches the XCM programs behind the transfers sent by the signed extrinsics of a block. The
+// programs of `send` and `execute` are read from the call, while the messages forwarded are
+// obtained by dry running the extrinsic on top of the state of the parent block, so they may
+// differ from the real ones if an earlier extrinsic of the block changed the state they depend on.
+pub(crate) async fn attach_raw_xcm(
+	api: &OnlineClient<PolkadotConfig>,
+	block_hash: BlockHash,
+	transfers: &mut [XcmTransfer],
+) -> Result<(), Error> {
+	let block = api.blocks().at(block_hash).await?;
+	let extrinsics = block.extrinsics().await?;
+	let runtime_api = api.runtime_api().at(block.header().parent_hash).await?;
+	let mut rendered = HashMap::<u32, RawXcm>::new();
+
+	for transfer in transfers {
+		let XcmTransfer::SentTransfer(transfer) = transfer else {
+			continue;
+		};
+		let Some(extrinsic_index) = transfer
+			.origin
+			.is_none()
+			.then(|| extrinsic_index(&transfer.transfer_group_id))
+			.flatten()
+		else {
+			continue;
+		};
+		if !rendered.contains_key(&extrinsic_index) {
+			let Some(extrinsic) =
+				extrinsics.iter().find(|extrinsic| extrinsic.index() == extrinsic_index)
+			else {
+				continue;
+			};
+			let (forwarded_messages, dry_run_error) =
+				match dry_run_extrinsic(&runtime_api, &extrinsic).await {
+					Ok(forwarded_messages) => (forwarded_messages, None),
+					Err(e) => (vec![], Some(e.to_string())),
+				};
+			rendered.insert(
+				extrinsic_index,
+				RawXcm { program: program(&extrinsic), forwarded_messages, dry_run_error },
+			);
+		}
+		transfer.raw_xcm = rendered.get(&extrinsic_index).cloned();
+	}
+	Ok(())
+}
+
+fn program(
+	extrinsic: &ExtrinsicDetails<PolkadotConfig, OnlineClient<PolkadotConfig>>,
+) -> Option<String> {
+	match (extrinsic.as_extrinsic::<Send>(), extrinsic.as_extrinsic::<Execute>()) {
+		(Ok(Some(call)), _) => Some(format!("{:?}", call.message)),
+		(_, Ok(Some(call))) => Some(format!("{:?}", call.message)),
+		_ => None,
+	}
+}
+
+async fn dry_run_extrinsic(
+	runtime_api: &subxt::runtime_api::RuntimeApi<PolkadotConfig, OnlineClient<PolkadotConfig>>,
+	extrinsic: &ExtrinsicDetails<PolkadotConfig, OnlineClient<PolkadotConfig>>,
+) -> Result<Vec<ForwardedMessage>, Error> {
+	let sender = match extrinsic
+		.address_bytes()
+		.map(|bytes| MultiAddress::<AccountId32, ()>::decode(&mut &bytes[..]))
+	{
+		Some(Ok(MultiAddress::Id(account_id))) => account_id,
+		_ =>
+			return Err(Error::RuntimeApiFailed(
+				"Only extrinsics signed by 32 bytes accounts are dry run".to_owned(),
+			)),
+	};
+	let call = extrinsic.as_root_extrinsic::<RuntimeCall>()?;
+	let effects = crate::runtime_apis::dry_run_call(runtime_api, sender, call).await?;
+	Ok(effects
+		.forwarded_xcms
+		.iter()
+		.flat_map(|(destination, messages)| {
+			let destination = crate::runtime_apis::forwarded_destination(destination);
+			messages.iter().map(move |message| ForwardedMessage {
+				destination: destination.clone(),
+				message: format!("{:?}", message),
+			})
+		})
+		.collect())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn only_transfers_sent_by_extrinsics_are_rendered() {
+		assert_eq!(extrinsic_index("9000000-4"), Some(4));
+		assert_eq!(extrinsic_index("9000000-initialization-0"), None);
+
+		let raw_xcm = RawXcm {
+			program: None,
+			forwarded_messages: vec![ForwardedMessage {
+				destination: DestinationChain::PolkadotParachain(2034),
+				message: "V5(Xcm([ClearOrigin]))".to_owned(),
+			}],
+			dry_run_error: None,
+		};
+		assert_eq!(
+			serde_json::to_string(&raw_xcm).unwrap(),
+			r#"{"forwarded_messages":[{"destination":{"PolkadotParachain":2034},"message":"V5(Xcm([ClearOrigin]))"}]}"#
+		);
+	}
+}
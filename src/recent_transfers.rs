@@ -0,0 +1,84 @@
+use crate::types::{BlockNumber, XcmTransfer};
+use std::{collections::BTreeMap, num::NonZeroUsize};
+
+// Keeps the transfers found in the last `capacity` processed blocks, so consumers that briefly
+// disconnect can catch up. Blocks are processed concurrently in subscription mode, so they may be
+// inserted out of order: keeping them sorted by number ensures the oldest one is always evicted.
+pub(crate) struct RecentTransfers {
+	capacity: NonZeroUsize,
+	blocks: BTreeMap<BlockNumber, Vec<XcmTransfer>>,
+}
+
+impl RecentTransfers {
+	pub(crate) fn new(capacity: NonZeroUsize) -> Self {
+		Self { capacity, blocks: BTreeMap::new() }
+	}
+
+	pub(crate) fn insert(&mut self, block_number: BlockNumber, transfers: Vec<XcmTransfer>) {
+		self.blocks.insert(block_number, transfers);
+		while self.blocks.len() > self.capacity.get() {
+			self.blocks.pop_first();
+		}
+	}
+
+	// Adds records to a cached block, eg the round trips it closed, found once the blocks before it
+	// were observed. Blocks no longer cached are left out.
+	pub(crate) fn extend(&mut self, block_number: BlockNumber, transfers: Vec<XcmTransfer>) {
+		if let Some(cached_transfers) = self.blocks.get_mut(&block_number) {
+			cached_transfers.extend(transfers);
+		}
+	}
+
+	pub(crate) fn from_block(&self, block_number: BlockNumber) -> Vec<&XcmTransfer> {
+		self.blocks.range(block_number..).flat_map(|(_, transfers)| transfers).collect()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{incoming_parser::XcmIncomingTransfer, types::TransferType};
+
+	fn transfer_at(block_number: BlockNumber) -> XcmTransfer {
+		XcmTransfer::ReceivedTransfer(XcmIncomingTransfer {
+			block_number,
+			origin_chain: crate::incoming_parser::OriginChain::Polkadot,
+			message_id: None,
+			beneficiary: "13p9Fcn4eVJzHZL7Z6RXbRhEzjAYLU26BohYmy18yHXnMovT".to_owned(),
+			beneficiary_public_key:
+				"0x7c6cc798551f8869d1ab4bbf3fd56ef99dfd21294b7b052c324298f00894bf0c".to_owned(),
+			asset: "DOT".to_owned(),
+			asset_id: None,
+			amount: 1.0,
+			exact_amount: None,
+			transfer_type: TransferType::Teleport,
+			fee_swap: None,
+			entities: vec![],
+			dust_lost: None,
+			trapped_assets_hash: None,
+			new_account: false,
+			xc20_address: None,
+		})
+	}
+
+	#[test]
+	fn recent_transfers_evicts_oldest_blocks() {
+		let mut recent_transfers = RecentTransfers::new(NonZeroUsize::new(2).unwrap());
+		recent_transfers.insert(3, vec![transfer_at(3)]);
+		recent_transfers.insert(1, vec![transfer_at(1)]);
+		recent_transfers.insert(2, vec![]);
+		assert_eq!(recent_transfers.from_block(0), vec![&transfer_at(3)]);
+	}
+
+	#[test]
+	fn recent_transfers_from_block_includes_the_block() {
+		let mut recent_transfers = RecentTransfers::new(NonZeroUsize::new(3).unwrap());
+		recent_transfers.insert(1, vec![transfer_at(1)]);
+		recent_transfers.insert(2, vec![transfer_at(2), transfer_at(2)]);
+		recent_transfers.insert(3, vec![transfer_at(3)]);
+		assert_eq!(
+			recent_transfers.from_block(2),
+			vec![&transfer_at(2), &transfer_at(2), &transfer_at(3)]
+		);
+	}
+}
@@ -0,0 +1,211 @@
+use crate::{
+	Error,
+	runtime_apis::TransferAsset,
+	state_proofs::BlockStorage,
+	types::{AssetMetadataValues, BlockNumber, XcmTransfer},
+};
+use serde::Serialize;
+use std::{collections::BTreeSet, str::FromStr};
+use subxt::{OnlineClient, PolkadotConfig, blocks::Block, config::polkadot::AccountId32};
+
+// The balance change of an account for an asset between two blocks, compared with the XCM
+// transfers indexed in between. The residual is the part of the change the transfers don't
+// explain, coming from non-XCM activity (eg, local transfers, staking or delivery fees).
+#[derive(Debug, Serialize, PartialEq)]
+pub(crate) struct Reconciliation {
+	pub(crate) asset: String,
+	pub(crate) balance_before: f64,
+	pub(crate) balance_after: f64,
+	pub(crate) balance_delta: f64,
+	pub(crate) received: f64,
+	pub(crate) sent: f64,
+	// The fees paid for the extrinsics sending the transfers, only accounted for in the native
+	// token
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub(crate) xcm_fees: Option<f64>,
+	pub(crate) residual: f64,
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+pub(crate) struct ReconciliationReport {
+	pub(crate) account: String,
+	pub(crate) account_public_key: String,
+	pub(crate) from_block: BlockNumber,
+	pub(crate) to_block: BlockNumber,
+	pub(crate) assets: Vec<Reconciliation>,
+}
+
+// Accounts are given to the CLI either as a SS58 address or as their 0x prefixed public key.
+pub(crate) fn parse_account(account: &str) -> Result<AccountId32, String> {
+	let invalid_account = || format!("{} isn't a valid account", account);
+	match account.strip_prefix("0x") {
+		Some(key) => hex::decode(key)
+			.ok()
+			.and_then(|key| key.try_into().ok())
+			.map(AccountId32)
+			.ok_or_else(invalid_account),
+		None => AccountId32::from_str(account).map_err(|_| invalid_account()),
+	}
+}
+
+// The raw free balance of an account for an asset, with the name of the asset as the transfers
+// report it.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct AssetBalance {
+	pub(crate) asset: String,
+	pub(crate) decimals: u8,
+	pub(crate) balance: u128,
+}
+
+// The free balance of the account for every asset at a block.
+pub(crate) async fn balances_at_block(
+	block: &Block<PolkadotConfig, OnlineClient<PolkadotConfig>>,
+	account: &AccountId32,
+	assets: &[TransferAsset],
+) -> Result<Vec<AssetBalance>, Error> {
+	let storage = BlockStorage::at(block).await?;
+	let mut balances = vec![];
+	for asset in assets {
+		let (AssetMetadataValues { asset_name, decimals }, balance) =
+			crate::reserves::get_balance(&storage, account, asset).await?;
+		balances.push(AssetBalance { asset: asset_name, decimals, balance });
+	}
+	Ok(balances)
+}
+
+// Compares the balance change of the account for an asset with the transfers it received and sent,
+// matched by the public key of the beneficiary and the sender. The amounts are added up as raw
+// amounts, read back exactly from the transfers, and only rounded to floats for the report.
+pub(crate) fn reconcile(
+	account: &AccountId32,
+	before: &AssetBalance,
+	balance_after: u128,
+	transfers: &[XcmTransfer],
+) -> Reconciliation {
+	let AssetBalance { asset, decimals, balance: balance_before } = before;
+	let raw = |amount: f64, exact_amount: &Option<String>| {
+		crate::amount::read_raw_amount(amount, exact_amount.as_deref(), *decimals)
+			.unwrap_or_default()
+	};
+	let public_key = crate::helpers::to_hex_string(&account.0);
+	let is_account = |key: Option<&String>| key == Some(&public_key);
+	let (mut received, mut sent) = (0u128, 0u128);
+	// The fees are shared by all the assets sent by an extrinsic, so they're counted once
+	let mut fees = BTreeSet::new();
+	for transfer in transfers {
+		match transfer {
+			XcmTransfer::ReceivedTransfer(transfer)
+				if transfer.asset == *asset &&
+					is_account(Some(&transfer.beneficiary_public_key)) =>
+				received = received.saturating_add(raw(transfer.amount, &transfer.exact_amount)),
+			XcmTransfer::SentTransfer(transfer)
+				if is_account(transfer.sender_public_key.as_ref()) =>
+			{
+				if transfer.asset == *asset {
+					sent = sent.saturating_add(raw(transfer.amount, &transfer.exact_amount));
+				}
+				if let Some(local_fee_dot) = transfer.local_fee_dot {
+					fees.insert((transfer.transfer_group_id.clone(), local_fee_dot.to_bits()));
+				}
+			},
+			XcmTransfer::SentTransferGroup(group)
+				if is_account(group.sender_public_key.as_ref()) =>
+			{
+				sent = group
+					.assets
+					.iter()
+					.filter(|sent_asset| sent_asset.asset == *asset)
+					.map(|sent_asset| raw(sent_asset.amount, &sent_asset.exact_amount))
+					.fold(sent, u128::saturating_add);
+				if let Some(local_fee_dot) = group.local_fee_dot {
+					fees.insert((group.transfer_group_id.clone(), local_fee_dot.to_bits()));
+				}
+			},
+			_ => (),
+		}
+	}
+	let xcm_fees = (*asset == crate::chain_spec::native_token_symbol())
+		.then(|| fees.into_iter().map(|(_, fee)| f64::from_bits(fee)).sum::<f64>());
+	let to_f64 = |amount| crate::amount::to_decimal_f64(amount, *decimals);
+	// The residual is `after - before - (received - sent - fees)`, with the raw terms added up by
+	// sign so only the difference is rounded
+	let unexplained = signed_difference(
+		balance_after.saturating_add(sent),
+		balance_before.saturating_add(received),
+		*decimals,
+	);
+	Reconciliation {
+		asset: asset.clone(),
+		balance_before: to_f64(*balance_before),
+		balance_after: to_f64(balance_after),
+		balance_delta: signed_difference(balance_after, *balance_before, *decimals),
+		received: to_f64(received),
+		sent: to_f64(sent),
+		xcm_fees,
+		residual: unexplained + xcm_fees.unwrap_or_default(),
+	}
+}
+
+fn signed_difference(a: u128, b: u128, decimals: u8) -> f64 {
+	if a >= b {
+		crate::amount::to_decimal_f64(a - b, decimals)
+	} else {
+		-crate::amount::to_decimal_f64(b - a, decimals)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		incoming_parser::{OriginChain, XcmIncomingTransfer},
+		types::TransferType,
+	};
+
+	#[test]
+	fn residual_is_the_balance_change_not_explained_by_transfers() {
+		let account = AccountId32([1; 32]);
+		assert_eq!(parse_account(&crate::helpers::to_hex_string(&[1; 32])), Ok(account.clone()));
+		assert!(parse_account("0x1234").is_err());
+
+		let received = |beneficiary: &AccountId32, amount, exact_amount: &str| {
+			XcmTransfer::ReceivedTransfer(XcmIncomingTransfer {
+				block_number: 10,
+				origin_chain: OriginChain::PolkadotParachain(2034),
+				message_id: None,
+				beneficiary: String::new(),
+				beneficiary_public_key: crate::helpers::to_hex_string(&beneficiary.0),
+				asset: "DOT".to_owned(),
+				asset_id: None,
+				amount,
+				exact_amount: Some(exact_amount.to_owned()),
+				transfer_type: TransferType::Reserve,
+				fee_swap: None,
+				entities: vec![],
+				dust_lost: None,
+				trapped_assets_hash: None,
+				new_account: false,
+				xc20_address: None,
+			})
+		};
+		let dot = |balance| AssetBalance { asset: "DOT".to_owned(), decimals: 10, balance };
+		let transfers =
+			[received(&account, 10.0, "10"), received(&AccountId32([2; 32]), 99.0, "99")];
+		let reconciliation = reconcile(&account, &dot(50_000_000_000), 160_000_000_000, &transfers);
+		assert_eq!(reconciliation.balance_delta, 11.0);
+		assert_eq!(reconciliation.received, 10.0);
+		assert_eq!(reconciliation.xcm_fees, Some(0.0));
+		assert_eq!(reconciliation.residual, 1.0);
+
+		// The exact amounts are added up, where the floats wouldn't (0.1 + 0.2 != 0.3)
+		let transfers = [received(&account, 0.1, "0.1"), received(&account, 0.2, "0.2")];
+		let reconciliation = reconcile(&account, &dot(0), 3_000_000_000, &transfers);
+		assert_eq!(reconciliation.received, 0.3);
+		assert_eq!(reconciliation.residual, 0.0);
+		assert_eq!(reconcile(&account, &dot(3_000_000_000), 0, &[]).balance_delta, -0.3);
+
+		// Only the native token accounts for the fees
+		let usdt = AssetBalance { asset: "USDT".to_owned(), decimals: 6, balance: 0 };
+		assert_eq!(reconcile(&account, &usdt, 0, &transfers).xcm_fees, None);
+	}
+}
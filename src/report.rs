@@ -0,0 +1,432 @@
+use crate::{
+	Error,
+	table::{format_amount, label},
+	types::{BlockNumber, BlockTransfers, XcmTransfer},
+};
+use std::{collections::BTreeMap, fs, path::Path};
+use subxt::{OnlineClient, PolkadotConfig, backend::legacy::LegacyRpcMethods};
+
+const DAY_MILLIS: u64 = 86_400_000;
+
+// An asset transferred, as it's accounted for in the report.
+#[derive(Debug, Clone, PartialEq)]
+struct ReportedTransfer {
+	block_number: BlockNumber,
+	incoming: bool,
+	chain: String,
+	asset: String,
+	amount: f64,
+	from: String,
+	to: String,
+}
+
+#[derive(Debug, Default, PartialEq)]
+struct AssetTotals {
+	received_transfers: usize,
+	received: f64,
+	sent_transfers: usize,
+	sent: f64,
+}
+
+// The transfers of an asset from or to a chain.
+#[derive(Debug, PartialEq)]
+struct Flow {
+	chain: String,
+	incoming: bool,
+	asset: String,
+	transfers: usize,
+	volume: f64,
+}
+
+// The summary of the transfers of a day, as compiled for the daily report.
+#[derive(Debug, PartialEq)]
+pub(crate) struct DailyReport {
+	date: String,
+	from_block: BlockNumber,
+	to_block: BlockNumber,
+	assets: BTreeMap<String, AssetTotals>,
+	// The busiest flows, by number of transfers
+	top_flows: Vec<Flow>,
+	// The largest transfers of every asset
+	large_transfers: Vec<ReportedTransfer>,
+}
+
+// Converts a `YYYY-MM-DD` date to the bounds of the day, in milliseconds since the Unix epoch,
+// converting the civil date to days since the epoch as described in
+// http://howardhinnant.github.io/date_algorithms.html.
+pub(crate) fn day_bounds(date: &str) -> Result<(u64, u64), String> {
+	let invalid = || format!("{} isn't a valid YYYY-MM-DD date", date);
+	let mut parts = date.splitn(3, '-').map(|part| part.parse::<i64>().map_err(|_| invalid()));
+	let (Some(year), Some(month), Some(day)) = (parts.next(), parts.next(), parts.next()) else {
+		return Err(invalid());
+	};
+	let (year, month, day) = (year?, month?, day?);
+	let leap_year = year % 4 == 0 && (year % 100 != 0 || year % 400 == 0);
+	let days_in_month = match month {
+		2 if leap_year => 29,
+		2 => 28,
+		4 | 6 | 9 | 11 => 30,
+		1..=12 => 31,
+		_ => return Err(invalid()),
+	};
+	if !(1..=days_in_month).contains(&day) {
+		return Err(invalid());
+	}
+	let year = if month <= 2 { year - 1 } else { year };
+	let era = year.div_euclid(400);
+	let year_of_era = year - era * 400;
+	let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+	let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+	let days = u64::try_from(era * 146_097 + day_of_era - 719_468)
+		.map_err(|_| format!("{} is before the Unix epoch", date))?;
+	Ok((days * DAY_MILLIS, (days + 1) * DAY_MILLIS))
+}
+
+// Finds the first block up to the given one whose timestamp isn't before the given one, searching
+// by bisection as the timestamps grow with the blocks. If there's none, the block after the last
+// one is returned.
+pub(crate) async fn first_block_from(
+	api: &OnlineClient<PolkadotConfig>,
+	rpc: &LegacyRpcMethods<PolkadotConfig>,
+	last_block: BlockNumber,
+	timestamp: u64,
+) -> Result<BlockNumber, Error> {
+	let (mut low, mut high) = (0, last_block + 1);
+	while low < high {
+		let middle = low + (high - low) / 2;
+		let Some(block_hash) = rpc
+			.chain_get_block_hash(Some(middle.into()))
+			.await
+			.map_err(subxt::error::Error::from)?
+		else {
+			high = middle;
+			continue;
+		};
+		if crate::helpers::get_block_timestamp(api, block_hash).await? < timestamp {
+			low = middle + 1;
+		} else {
+			high = middle;
+		}
+	}
+	Ok(low)
+}
+
+// Reads the transfers stored in a JSON output of the indexer, which can hold several documents if
+// it was written by a subscription. Records other than transfers (eg, signatures or block markers)
+// are skipped, as well as transfers whose exact amount is corrupt.
+pub(crate) fn read_stored_transfers(path: &Path) -> Result<Vec<XcmTransfer>, String> {
+	let content =
+		fs::read_to_string(path).map_err(|e| format!("Failed to read the stored data: {}", e))?;
+	let mut transfers = vec![];
+	for document in serde_json::Deserializer::from_str(&content).into_iter::<serde_json::Value>() {
+		let document =
+			document.map_err(|e| format!("The stored data isn't a JSON output: {}", e))?;
+		let records = match document {
+			serde_json::Value::Array(records) => records,
+			record => vec![record],
+		};
+		for record in records {
+			if let Ok(block) = serde_json::from_value::<BlockTransfers>(record.clone()) {
+				transfers.extend(block.transfers);
+			} else if let Ok(transfer) = serde_json::from_value::<XcmTransfer>(record) {
+				transfers.push(transfer);
+			}
+		}
+	}
+	// Amounts are read back exactly, so transfers whose exact amount is corrupt are skipped too
+	transfers.retain(|transfer| crate::import::invalid_exact_amount(transfer).is_none());
+	Ok(transfers)
+}
+
+fn reported_transfers(transfers: &[XcmTransfer]) -> Vec<ReportedTransfer> {
+	let mut reported = vec![];
+	for transfer in transfers {
+		match transfer {
+			XcmTransfer::ReceivedTransfer(transfer) => reported.push(ReportedTransfer {
+				block_number: transfer.block_number,
+				incoming: true,
+				chain: label(&transfer.origin_chain),
+				asset: transfer.asset.clone(),
+				amount: transfer.amount,
+				from: String::new(),
+				to: transfer.beneficiary.clone(),
+			}),
+			XcmTransfer::SentTransfer(transfer) => reported.push(ReportedTransfer {
+				block_number: transfer.block_number,
+				incoming: false,
+				chain: label(&transfer.destination_chain),
+				asset: transfer.asset.clone(),
+				amount: transfer.amount,
+				from: transfer.sender.clone(),
+				to: transfer.beneficiary.clone(),
+			}),
+			XcmTransfer::SentTransferGroup(group) =>
+				reported.extend(group.assets.iter().map(|asset| ReportedTransfer {
+					block_number: group.block_number,
+					incoming: false,
+					chain: label(&group.destination_chain),
+					asset: asset.asset.clone(),
+					amount: asset.amount,
+					from: group.sender.clone(),
+					to: group.beneficiary.clone(),
+				})),
+			XcmTransfer::RoundTrip(_) |
+			XcmTransfer::StatusUpdate(_) |
+			XcmTransfer::Anomaly(_) |
+			XcmTransfer::FailedMessage(_) => (),
+		}
+	}
+	reported
+}
+
+impl DailyReport {
+	// Compiles the report of the transfers of a day, keeping the `top` busiest flows and the `top`
+	// largest transfers of every asset.
+	pub(crate) fn new(
+		date: &str,
+		from_block: BlockNumber,
+		to_block: BlockNumber,
+		transfers: &[XcmTransfer],
+		top: usize,
+	) -> Self {
+		let transfers = reported_transfers(transfers)
+			.into_iter()
+			.filter(|transfer| (from_block..=to_block).contains(&transfer.block_number))
+			.collect::<Vec<_>>();
+
+		let mut assets = BTreeMap::<String, AssetTotals>::new();
+		let mut flows = BTreeMap::<(String, bool, String), (usize, f64)>::new();
+		for transfer in &transfers {
+			let totals = assets.entry(transfer.asset.clone()).or_default();
+			if transfer.incoming {
+				totals.received_transfers += 1;
+				totals.received += transfer.amount;
+			} else {
+				totals.sent_transfers += 1;
+				totals.sent += transfer.amount;
+			}
+			let flow = flows
+				.entry((transfer.chain.clone(), transfer.incoming, transfer.asset.clone()))
+				.or_default();
+			flow.0 += 1;
+			flow.1 += transfer.amount;
+		}
+		let mut top_flows = flows
+			.into_iter()
+			.map(|((chain, incoming, asset), (transfers, volume))| Flow {
+				chain,
+				incoming,
+				asset,
+				transfers,
+				volume,
+			})
+			.collect::<Vec<_>>();
+		// The sort is stable, so flows with as many transfers keep their order
+		top_flows.sort_by(|a, b| b.transfers.cmp(&a.transfers));
+		top_flows.truncate(top);
+
+		let mut large_transfers = vec![];
+		for asset in assets.keys() {
+			let mut asset_transfers =
+				transfers.iter().filter(|transfer| &transfer.asset == asset).collect::<Vec<_>>();
+			asset_transfers.sort_by(|a, b| b.amount.total_cmp(&a.amount));
+			large_transfers.extend(asset_transfers.into_iter().take(top).cloned());
+		}
+
+		Self { date: date.to_owned(), from_block, to_block, assets, top_flows, large_transfers }
+	}
+
+	fn direction(incoming: bool) -> &'static str {
+		if incoming { "in" } else { "out" }
+	}
+
+	// The sections of the report, as the header and the rows of their tables.
+	fn sections(&self) -> [(&'static str, Vec<&'static str>, Vec<Vec<String>>); 3] {
+		[
+			(
+				"Totals per asset",
+				vec!["Asset", "Received transfers", "Received", "Sent transfers", "Sent"],
+				self.assets
+					.iter()
+					.map(|(asset, totals)| {
+						vec![
+							asset.clone(),
+							totals.received_transfers.to_string(),
+							format_amount(totals.received),
+							totals.sent_transfers.to_string(),
+							format_amount(totals.sent),
+						]
+					})
+					.collect(),
+			),
+			(
+				"Top flows per chain",
+				vec!["Chain", "Direction", "Asset", "Transfers", "Volume"],
+				self.top_flows
+					.iter()
+					.map(|flow| {
+						vec![
+							flow.chain.clone(),
+							Self::direction(flow.incoming).to_owned(),
+							flow.asset.clone(),
+							flow.transfers.to_string(),
+							format_amount(flow.volume),
+						]
+					})
+					.collect(),
+			),
+			(
+				"Largest transfers",
+				vec!["Block", "Direction", "Chain", "Asset", "Amount", "From", "To"],
+				self.large_transfers
+					.iter()
+					.map(|transfer| {
+						vec![
+							transfer.block_number.to_string(),
+							Self::direction(transfer.incoming).to_owned(),
+							transfer.chain.clone(),
+							transfer.asset.clone(),
+							format_amount(transfer.amount),
+							transfer.from.clone(),
+							transfer.to.clone(),
+						]
+					})
+					.collect(),
+			),
+		]
+	}
+
+	fn title(&self) -> String {
+		format!("XCM transfers of {}", self.date)
+	}
+
+	fn blocks(&self) -> String {
+		format!("Blocks {} to {} of Polkadot AssetHub.", self.from_block, self.to_block)
+	}
+
+	pub(crate) fn render_markdown(&self) -> String {
+		let mut output = format!("# {}\n\n{}\n", self.title(), self.blocks());
+		for (title, header, rows) in self.sections() {
+			output.push_str(&format!("\n## {}\n\n", title));
+			if rows.is_empty() {
+				output.push_str("No transfers.\n");
+				continue;
+			}
+			output.push_str(&format!("| {} |\n", header.join(" | ")));
+			output.push_str(&format!("|{}\n", " --- |".repeat(header.len())));
+			for row in rows {
+				let row = row.iter().map(|cell| cell.replace('|', "\\|")).collect::<Vec<_>>();
+				output.push_str(&format!("| {} |\n", row.join(" | ")));
+			}
+		}
+		output
+	}
+
+	pub(crate) fn render_html(&self) -> String {
+		let mut output = format!(
+			"<!DOCTYPE html>\n<html>\n<head><meta charset=\"utf-8\"><title>{}</title></head>\n<body>\n<h1>{}</h1>\n<p>{}</p>\n",
+			escape_html(&self.title()),
+			escape_html(&self.title()),
+			escape_html(&self.blocks())
+		);
+		for (title, header, rows) in self.sections() {
+			output.push_str(&format!("<h2>{}</h2>\n", escape_html(title)));
+			if rows.is_empty() {
+				output.push_str("<p>No transfers.</p>\n");
+				continue;
+			}
+			let cells = |row: &[String], tag: &str| {
+				row.iter()
+					.map(|cell| format!("<{}>{}</{}>", tag, escape_html(cell), tag))
+					.collect::<String>()
+			};
+			let header = header.iter().map(|column| column.to_string()).collect::<Vec<_>>();
+			output.push_str(&format!("<table>\n<tr>{}</tr>\n", cells(&header, "th")));
+			for row in rows {
+				output.push_str(&format!("<tr>{}</tr>\n", cells(&row, "td")));
+			}
+			output.push_str("</table>\n");
+		}
+		output.push_str("</body>\n</html>");
+		output
+	}
+}
+
+fn escape_html(text: &str) -> String {
+	text.replace('&', "&amp;")
+		.replace('<', "&lt;")
+		.replace('>', "&gt;")
+		.replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		incoming_parser::{OriginChain, XcmIncomingTransfer},
+		types::TransferType,
+	};
+
+	fn received(block_number: BlockNumber, asset: &str, amount: f64) -> XcmTransfer {
+		XcmTransfer::ReceivedTransfer(XcmIncomingTransfer {
+			block_number,
+			origin_chain: OriginChain::PolkadotParachain(2034),
+			message_id: None,
+			beneficiary: "13KsaHFcQKSTd4m73Ub9yVwM1JGCZvipMyTZonHEXEceFYwS".to_owned(),
+			beneficiary_public_key: String::new(),
+			asset: asset.to_owned(),
+			asset_id: None,
+			amount,
+			exact_amount: None,
+			transfer_type: TransferType::Reserve,
+			fee_swap: None,
+			entities: vec![],
+			dust_lost: None,
+			trapped_assets_hash: None,
+			new_account: false,
+			xc20_address: None,
+		})
+	}
+
+	#[test]
+	fn day_bounds_follow_the_calendar() {
+		assert_eq!(day_bounds("1970-01-01"), Ok((0, DAY_MILLIS)));
+		assert_eq!(day_bounds("2000-02-29").map(|(start, _)| start), Ok(951_782_400_000));
+		assert!(day_bounds("2023-02-29").is_err());
+		assert!(day_bounds("2024-13-01").is_err());
+		assert!(day_bounds("1969-12-31").is_err());
+		assert!(day_bounds("yesterday").is_err());
+	}
+
+	#[test]
+	fn daily_report_summarizes_the_transfers_of_the_day() {
+		let transfers = [
+			received(10, "DOT", 5.0),
+			received(11, "DOT", 50.0),
+			received(12, "USDT", 1.5),
+			// Out of the day
+			received(20, "DOT", 1000.0),
+		];
+		let report = DailyReport::new("2025-01-01", 10, 12, &transfers, 1);
+		assert_eq!(
+			report.assets["DOT"],
+			AssetTotals { received_transfers: 2, received: 55.0, sent_transfers: 0, sent: 0.0 }
+		);
+		assert_eq!(report.top_flows.len(), 1);
+		assert_eq!(report.top_flows[0].transfers, 2);
+		assert_eq!(
+			report
+				.large_transfers
+				.iter()
+				.map(|transfer| transfer.amount)
+				.collect::<Vec<_>>(),
+			vec![50.0, 1.5]
+		);
+
+		let markdown = report.render_markdown();
+		assert!(markdown.starts_with("# XCM transfers of 2025-01-01\n\nBlocks 10 to 12"));
+		assert!(markdown.contains("| DOT | 2 | 55 | 0 | 0 |"));
+		let html = report.render_html();
+		assert!(html.contains("<tr><td>USDT</td><td>1</td><td>1.5</td><td>0</td><td>0</td></tr>"));
+	}
+}
@@ -0,0 +1,125 @@
+use crate::{
+	Error,
+	runtime_apis::TransferAsset,
+	state_proofs::BlockStorage,
+	types::{AssetMetadataValues, BlockNumber},
+};
+use serde::Serialize;
+use subxt::{OnlineClient, PolkadotConfig, blocks::Block, config::polkadot::AccountId32};
+
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub(crate) struct ReserveSnapshot {
+	pub(crate) block_number: BlockNumber,
+	pub(crate) para_id: u32,
+	pub(crate) sovereign_account: String,
+	pub(crate) sovereign_account_public_key: String,
+	pub(crate) asset: String,
+	pub(crate) balance: f64,
+}
+
+// The account controlled by a sibling parachain in AssetHub is derived from the `sibl` prefix
+// followed by the SCALE encoded para id, padded with zeros up to 32 bytes.
+pub(crate) fn sibling_sovereign_account(para_id: u32) -> AccountId32 {
+	let mut account_id = [0u8; 32];
+	account_id[..4].copy_from_slice(b"sibl");
+	account_id[4..8].copy_from_slice(&para_id.to_le_bytes());
+	AccountId32(account_id)
+}
+
+// Takes a snapshot of the balances held by the sovereign accounts of the given parachains at a
+// certain block, so they can be reconciled against the reserve transfers indexed.
+pub(crate) async fn get_reserve_snapshots_at_block(
+	block: &Block<PolkadotConfig, OnlineClient<PolkadotConfig>>,
+	para_ids: &[u32],
+	assets: &[TransferAsset],
+) -> Result<Vec<ReserveSnapshot>, Error> {
+	let storage = BlockStorage::at(block).await?;
+	let mut output = vec![];
+	for para_id in para_ids {
+		let sovereign_account = sibling_sovereign_account(*para_id);
+		for asset in assets {
+			let (AssetMetadataValues { asset_name, decimals }, balance) =
+				get_balance(&storage, &sovereign_account, asset).await?;
+			output.push(ReserveSnapshot {
+				block_number: block.number(),
+				para_id: *para_id,
+				sovereign_account: crate::helpers::convert_account_id_to_address(
+					&sovereign_account,
+				),
+				sovereign_account_public_key: crate::helpers::to_hex_string(&sovereign_account.0),
+				asset: asset_name,
+				balance: crate::amount::to_decimal_f64(balance, decimals),
+			});
+		}
+	}
+	Ok(output)
+}
+
+pub(crate) async fn get_balance(
+	storage_api: &BlockStorage,
+	account_id: &AccountId32,
+	asset: &TransferAsset,
+) -> Result<(AssetMetadataValues, u128), Error> {
+	match asset {
+		TransferAsset::Native => {
+			let account_address = crate::asset_hub::storage().system().account(account_id);
+			let balance = storage_api
+				.fetch(&account_address)
+				.await?
+				.map(|account| account.data.free)
+				.unwrap_or_default();
+			Ok((
+				AssetMetadataValues {
+					asset_name: crate::chain_spec::native_token_symbol(),
+					decimals: crate::chain_spec::native_token_decimals(),
+				},
+				balance,
+			))
+		},
+		TransferAsset::Asset(asset_id) => {
+			let account_address =
+				crate::asset_hub::storage().assets().account(asset_id, account_id);
+			let balance = storage_api
+				.fetch(&account_address)
+				.await?
+				.map(|account| account.balance)
+				.unwrap_or_default();
+			let metadata =
+				crate::helpers::extract_asset_metadata_values(storage_api, asset_id).await?;
+			Ok((metadata, balance))
+		},
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::chain_spec::{Chain, ChainSpec};
+
+	#[test]
+	fn sibling_sovereign_account_test() {
+		let account_id = sibling_sovereign_account(2034);
+		assert_eq!(&account_id.0[..4], b"sibl");
+		assert_eq!(&account_id.0[4..8], &[0xf2, 0x07, 0, 0]);
+		assert!(account_id.0[8..].iter().all(|byte| *byte == 0));
+	}
+
+	#[tokio::test]
+	async fn get_reserve_snapshots_at_block_test() {
+		let api = OnlineClient::<PolkadotConfig>::from_url(Chain::RPC_ENDPOINT).await.unwrap();
+		let block = api.blocks().at_latest().await.unwrap();
+
+		let snapshots = get_reserve_snapshots_at_block(
+			&block,
+			&[2034],
+			&[TransferAsset::Native, TransferAsset::Asset(1984)],
+		)
+		.await
+		.unwrap();
+		assert_eq!(
+			snapshots.iter().map(|snapshot| snapshot.asset.as_str()).collect::<Vec<_>>(),
+			vec!["DOT", "Tether USD"]
+		);
+		assert!(snapshots.iter().all(|snapshot| snapshot.para_id == 2034));
+	}
+}
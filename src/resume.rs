@@ -0,0 +1,146 @@
+use crate::types::BlockNumber;
+use std::{
+	collections::BTreeSet,
+	fs,
+	path::{Path, PathBuf},
+};
+
+// The progress of a subscription writing to a file, persisted in a sidecar file next to it, so a
+// restarted subscription appends to the output without duplicating the blocks already written.
+// As the blocks are processed concurrently, the state persisted is a low watermark: the highest
+// block such that every block queued up to it was written or given up. Blocks still in process, or
+// set aside to be retried, hold it back. Blocks given up don't, as a restarted subscription
+// doesn't process them again either: they're recovered from the dead-letter file.
+#[derive(Debug, PartialEq)]
+pub(crate) struct ResumeState {
+	path: PathBuf,
+	last_block: Option<BlockNumber>,
+	// The blocks queued and not written yet
+	pending: BTreeSet<BlockNumber>,
+	highest_written: Option<BlockNumber>,
+}
+
+impl ResumeState {
+	// Reads the state kept next to the output file, eg `transfers.json.state`. A missing state
+	// means nothing was written yet.
+	pub(crate) fn load(output: &Path) -> Result<Self, String> {
+		let mut path = output.as_os_str().to_owned();
+		path.push(".state");
+		let path = PathBuf::from(path);
+		let content = match fs::read_to_string(&path) {
+			Ok(content) => content,
+			Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Self::new(path, None)),
+			Err(e) => return Err(format!("Failed to read the resume state: {}", e)),
+		};
+		let last_block = content
+			.trim()
+			.parse()
+			.map_err(|e| format!("Invalid resume state in {}: {}", path.display(), e))?;
+		Ok(Self::new(path, Some(last_block)))
+	}
+
+	fn new(path: PathBuf, last_block: Option<BlockNumber>) -> Self {
+		Self { path, last_block, pending: BTreeSet::new(), highest_written: last_block }
+	}
+
+	pub(crate) fn last_block(&self) -> Option<BlockNumber> {
+		self.last_block
+	}
+
+	pub(crate) fn is_written(&self, block_number: BlockNumber) -> bool {
+		self.last_block.is_some_and(|last_block| block_number <= last_block)
+	}
+
+	// Records a block as queued to be written, holding the state back until it's written.
+	pub(crate) fn queue(&mut self, block_number: BlockNumber) {
+		if !self.is_written(block_number) {
+			self.pending.insert(block_number);
+		}
+	}
+
+	// Records a block as written, persisting the state if every block queued below it was written
+	// or given up too.
+	pub(crate) fn record(&mut self, block_number: BlockNumber) -> Result<(), String> {
+		self.highest_written = Some(
+			self.highest_written
+				.map_or(block_number, |highest_written| highest_written.max(block_number)),
+		);
+		self.release(block_number)
+	}
+
+	// Records a block which won't be written, eg it couldn't be parsed or ran out of retries, so it
+	// doesn't hold the state back forever.
+	pub(crate) fn give_up(&mut self, block_number: BlockNumber) -> Result<(), String> {
+		self.release(block_number)
+	}
+
+	// Stops waiting for a block, persisting the state if it moved. The state is replaced
+	// atomically, so a crash never leaves it half written.
+	fn release(&mut self, block_number: BlockNumber) -> Result<(), String> {
+		self.pending.remove(&block_number);
+		let Some(highest_written) = self.highest_written else {
+			return Ok(());
+		};
+		let watermark = match self.pending.first() {
+			Some(first_pending) =>
+				first_pending.checked_sub(1).map(|below| below.min(highest_written)),
+			None => Some(highest_written),
+		};
+		let Some(watermark) = watermark.filter(|watermark| !self.is_written(*watermark)) else {
+			return Ok(());
+		};
+		let mut temporary_path = self.path.as_os_str().to_owned();
+		temporary_path.push(".tmp");
+		fs::write(&temporary_path, watermark.to_string())
+			.and_then(|_| fs::rename(&temporary_path, &self.path))
+			.map_err(|e| format!("Failed to write the resume state: {}", e))?;
+		self.last_block = Some(watermark);
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn resume_state_persists_the_last_block_written() {
+		let output = std::env::temp_dir()
+			.join(format!("xcm_indexer_resume_state_{}.json", std::process::id()));
+		let state_path = output.with_extension("json.state");
+		let _ = fs::remove_file(&state_path);
+
+		let mut state = ResumeState::load(&output).unwrap();
+		assert_eq!(state.last_block(), None);
+		assert!(!state.is_written(1));
+		(8..=10).for_each(|block_number| state.queue(block_number));
+		// Blocks finishing out of order don't move the state past the ones still pending
+		state.record(10).unwrap();
+		assert_eq!(state.last_block(), None);
+		state.record(8).unwrap();
+		assert_eq!(fs::read_to_string(&state_path).unwrap(), "8");
+		state.record(9).unwrap();
+		assert_eq!(fs::read_to_string(&state_path).unwrap(), "10");
+
+		// A block in process holds the state back
+		(11..=12).for_each(|block_number| state.queue(block_number));
+		state.record(12).unwrap();
+		assert_eq!(fs::read_to_string(&state_path).unwrap(), "10");
+		// Until it's given up
+		state.queue(13);
+		state.give_up(11).unwrap();
+		assert_eq!(fs::read_to_string(&state_path).unwrap(), "12");
+		// A block given up ahead of the ones written doesn't move the state past them
+		state.give_up(13).unwrap();
+		assert_eq!(fs::read_to_string(&state_path).unwrap(), "12");
+
+		let state = ResumeState::load(&output).unwrap();
+		assert_eq!(state.last_block(), Some(12));
+		assert!(state.is_written(12));
+		assert!(!state.is_written(13));
+
+		fs::write(&state_path, "not a block").unwrap();
+		assert!(ResumeState::load(&output).is_err());
+		fs::remove_file(&state_path).unwrap();
+	}
+}
@@ -0,0 +1,113 @@
+use crate::{raw_blocks::RawBlock, types::BlockNumber};
+use std::{
+	path::Path,
+	time::{SystemTime, UNIX_EPOCH},
+};
+
+const DAY_MILLIS: u64 = 24 * 3600 * 1000;
+
+// How long the archived raw blocks are kept by long running subscriptions, so the archive doesn't
+// grow unbounded.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Retention {
+	// The blocks up to this amount behind the latest archived one
+	Blocks(BlockNumber),
+	// The blocks produced in this amount of days before now
+	Days(u64),
+}
+
+impl Retention {
+	// The archived blocks to keep at `now` (in milliseconds). Blocks archived without their
+	// timestamp are dated by their number when retaining days: those older than a block out of the
+	// retention are out of it too, while the remaining ones are kept.
+	pub(crate) fn retain(&self, blocks: Vec<RawBlock>, now: u64) -> Vec<RawBlock> {
+		match *self {
+			Self::Blocks(amount) => {
+				let latest =
+					blocks.iter().map(|block| block.block_number).max().unwrap_or_default();
+				let since = latest.saturating_sub(amount.saturating_sub(1));
+				blocks.into_iter().filter(|block| block.block_number >= since).collect()
+			},
+			Self::Days(days) => {
+				let since = now.saturating_sub(days.saturating_mul(DAY_MILLIS));
+				let latest_expired = blocks
+					.iter()
+					.filter(|block| block.timestamp.is_some_and(|timestamp| timestamp < since))
+					.map(|block| block.block_number)
+					.max();
+				blocks
+					.into_iter()
+					.filter(|block| match block.timestamp {
+						Some(timestamp) => timestamp >= since,
+						None => latest_expired.is_none_or(|latest| block.block_number > latest),
+					})
+					.collect()
+			},
+		}
+	}
+}
+
+// Prunes the blocks out of the retention from the archive, returning how many were removed. The
+// archive is rewritten without them, so their space is reclaimed.
+pub(crate) fn prune_archive(path: &Path, retention: Retention) -> Result<usize, String> {
+	let now = SystemTime::now()
+		.duration_since(UNIX_EPOCH)
+		.map(|now| now.as_millis() as u64)
+		.unwrap_or_default();
+	crate::raw_blocks::retain_in_archive(path, |blocks| retention.retain(blocks, now))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::types::BlockHash;
+
+	fn block(block_number: BlockNumber, timestamp: Option<u64>) -> RawBlock {
+		RawBlock {
+			block_hash: BlockHash::repeat_byte(block_number as u8),
+			block_number,
+			timestamp,
+			events: "0x00".to_owned(),
+			extrinsics: vec![],
+			assets: Default::default(),
+		}
+	}
+
+	fn block_numbers(blocks: Vec<RawBlock>) -> Vec<BlockNumber> {
+		blocks.into_iter().map(|block| block.block_number).collect()
+	}
+
+	#[test]
+	fn retention_keeps_the_latest_blocks() {
+		let blocks = || {
+			vec![block(0, None), block(1, Some(0)), block(5, None), block(3, Some(9 * DAY_MILLIS))]
+		};
+		assert_eq!(block_numbers(Retention::Blocks(3).retain(blocks(), 0)), vec![5, 3]);
+		assert_eq!(block_numbers(Retention::Blocks(100).retain(blocks(), 0)), vec![0, 1, 5, 3]);
+		// The block without timestamp older than a pruned one is pruned too
+		assert_eq!(block_numbers(Retention::Days(2).retain(blocks(), 10 * DAY_MILLIS)), vec![5, 3]);
+		// Without dated blocks out of the retention, every block is kept
+		assert_eq!(
+			block_numbers(Retention::Days(u64::MAX).retain(blocks(), 10 * DAY_MILLIS)),
+			vec![0, 1, 5, 3]
+		);
+	}
+
+	#[test]
+	fn pruning_rewrites_the_archive() {
+		let path = std::env::temp_dir()
+			.join(format!("xcm_indexer_retention_{}.jsonl", std::process::id()));
+		let _ = std::fs::remove_file(&path);
+		assert_eq!(prune_archive(&path, Retention::Blocks(1)), Ok(0));
+
+		let archive = [block(1, None), block(2, None)]
+			.iter()
+			.map(|block| serde_json::to_string(block).unwrap() + "\n")
+			.collect::<String>();
+		std::fs::write(&path, archive).unwrap();
+		assert_eq!(prune_archive(&path, Retention::Blocks(1)), Ok(1));
+		let blocks = crate::raw_blocks::read_archive(&path).unwrap();
+		std::fs::remove_file(&path).unwrap();
+		assert_eq!(block_numbers(blocks), vec![2]);
+	}
+}
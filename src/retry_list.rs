@@ -0,0 +1,78 @@
+use crate::types::{BlockHash, BlockNumber};
+use std::{collections::BTreeMap, time::Duration};
+
+// How often the blocks that timed out are processed again.
+pub(crate) const RETRY_INTERVAL: Duration = Duration::from_secs(60);
+
+// The blocks whose processing timed out, eg pathological blocks with huge batches or slow storage
+// reads. They're set aside so they don't stall the subscription, and retried later by a background
+// task until they're processed or run out of attempts.
+#[derive(Debug, Default)]
+pub(crate) struct RetryList {
+	max_retries: u32,
+	// The hash of each block and how many times it timed out
+	blocks: BTreeMap<BlockNumber, (BlockHash, u32)>,
+}
+
+impl RetryList {
+	pub(crate) fn new(max_retries: u32) -> Self {
+		Self { max_retries, ..Default::default() }
+	}
+
+	// Records that a block timed out, returning whether it'll be retried. A block is given up once
+	// it has been retried `max_retries` times.
+	pub(crate) fn record_timeout(
+		&mut self,
+		block_hash: BlockHash,
+		block_number: BlockNumber,
+	) -> bool {
+		let attempts =
+			self.blocks.get(&block_number).map(|(_, attempts)| *attempts).unwrap_or(0) + 1;
+		if attempts > self.max_retries {
+			self.blocks.remove(&block_number);
+			return false;
+		}
+		self.blocks.insert(block_number, (block_hash, attempts));
+		true
+	}
+
+	pub(crate) fn record_processed(&mut self, block_number: BlockNumber) {
+		self.blocks.remove(&block_number);
+	}
+
+	pub(crate) fn contains(&self, block_number: BlockNumber) -> bool {
+		self.blocks.contains_key(&block_number)
+	}
+
+	// The blocks waiting to be retried, oldest first.
+	pub(crate) fn pending(&self) -> Vec<(BlockHash, BlockNumber)> {
+		self.blocks
+			.iter()
+			.map(|(block_number, (block_hash, _))| (*block_hash, *block_number))
+			.collect()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn blocks_are_retried_until_they_run_out_of_attempts() {
+		let mut retry_list = RetryList::new(2);
+		assert!(retry_list.record_timeout(BlockHash::repeat_byte(2), 2));
+		assert!(retry_list.record_timeout(BlockHash::repeat_byte(1), 1));
+		assert_eq!(
+			retry_list.pending(),
+			vec![(BlockHash::repeat_byte(1), 1), (BlockHash::repeat_byte(2), 2)]
+		);
+
+		assert!(retry_list.contains(1));
+		retry_list.record_processed(1);
+		assert!(!retry_list.contains(1));
+		assert!(retry_list.record_timeout(BlockHash::repeat_byte(2), 2));
+		// The third timeout of the block exceeds the retries allowed
+		assert!(!retry_list.record_timeout(BlockHash::repeat_byte(2), 2));
+		assert!(retry_list.pending().is_empty());
+	}
+}
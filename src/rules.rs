@@ -0,0 +1,526 @@
+use crate::{
+	analytics::{TransferStatus, TransferStatusUpdate},
+	outgoing_parser::{DestinationChain, ExecutionOutcome, XcmOutgoingTransfer},
+	types::{AssetIdentifier, XcmTransfer},
+	watch_lists::WatchList,
+};
+use serde::Deserialize;
+use std::{
+	collections::HashMap,
+	fs,
+	path::{Path, PathBuf},
+	sync::{Arc, RwLock},
+	time::Duration,
+};
+
+// The sink standing for the regular output of the indexer: the output file or the terminal.
+pub(crate) const OUTPUT_SINK: &str = "output";
+// How often the rules file is checked for changes while subscribed.
+pub(crate) const RULES_RELOAD_INTERVAL: Duration = Duration::from_secs(5);
+
+// The conditions a record must meet for a rule to apply. Every given condition must hold, and
+// conditions about fields a record doesn't have (eg, the asset of a status update) don't hold.
+#[derive(Debug, Clone, Default, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct Condition {
+	// The kind of record: received, sent, round_trip, status_update, anomaly or failed_message
+	pub(crate) kind: Option<String>,
+	pub(crate) asset: Option<String>,
+	pub(crate) min_amount: Option<f64>,
+	pub(crate) max_amount: Option<f64>,
+	// The sender or the beneficiary of the transfer, given by its address or public key
+	pub(crate) account: Option<String>,
+	// The counterparty chain, given as the destinations of the CLI, eg `polkadot-parachain:2034`
+	pub(crate) chain: Option<String>,
+	// Teleport or Reserve
+	pub(crate) transfer_type: Option<String>,
+	// Whether the message of the transfer failed or got trapped
+	pub(crate) failed: Option<bool>,
+}
+
+// What to do with the records matching a rule.
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum Action {
+	// Notify the record through a notifier, eg `email`
+	Alert(String),
+	// Send the record to a sink, eg `mqtt`. Sinks targeted by some route only receive the records
+	// routed to them, the remaining ones receive every record
+	Route(String),
+	// Discard the record, so it doesn't reach any sink nor notifier
+	Drop,
+}
+
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct Rule {
+	pub(crate) name: String,
+	#[serde(default)]
+	pub(crate) when: Condition,
+	pub(crate) action: Action,
+}
+
+// The records of a block after applying the rules.
+#[derive(Debug, Default, PartialEq)]
+pub(crate) struct RuledTransfers {
+	// The records that weren't dropped
+	pub(crate) kept: Vec<XcmTransfer>,
+	pub(crate) routes: HashMap<String, Vec<XcmTransfer>>,
+	// The records to notify per notifier, together with the name of the rule they matched
+	pub(crate) alerts: HashMap<String, Vec<(XcmTransfer, String)>>,
+	// The records that weren't dropped per watch list, regardless of the routes
+	pub(crate) watch_lists: HashMap<String, Vec<XcmTransfer>>,
+}
+
+impl RuledTransfers {
+	// The records that a sink must receive.
+	pub(crate) fn for_sink<'a>(&'a self, engine: &RuleEngine, sink: &str) -> &'a [XcmTransfer] {
+		if engine.routes_to(sink) {
+			self.routes.get(sink).map(Vec::as_slice).unwrap_or_default()
+		} else {
+			&self.kept
+		}
+	}
+
+	pub(crate) fn alerts_for(&self, notifier: &str) -> &[(XcmTransfer, String)] {
+		self.alerts.get(notifier).map(Vec::as_slice).unwrap_or_default()
+	}
+
+	pub(crate) fn for_watch_list(&self, watch_list: &str) -> &[XcmTransfer] {
+		self.watch_lists.get(watch_list).map(Vec::as_slice).unwrap_or_default()
+	}
+}
+
+// A small rule engine deciding, for every record, which sinks and notifiers receive it. Rules are
+// evaluated in order, and every matching rule applies.
+#[derive(Debug, Default, Clone, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct RuleEngine {
+	#[serde(default)]
+	rules: Vec<Rule>,
+	#[serde(default)]
+	watch_lists: Vec<WatchList>,
+}
+
+impl RuleEngine {
+	// Parses the content of a rules file like `{ "rules": [{ "name": "Large DOT", "when": {
+	// "asset": "DOT", "min_amount": 10000 }, "action": { "alert": "email" } }] }`, optionally
+	// together with the watch lists.
+	fn from_json(content: &str) -> Result<Self, String> {
+		let engine: Self = serde_json::from_str(content)
+			.map_err(|e| format!("Failed to parse the rules file: {}", e))?;
+		for rule in &engine.rules {
+			if let Some(chain) = &rule.when.chain {
+				chain.parse::<DestinationChain>()?;
+			}
+		}
+		for (index, watch_list) in engine.watch_lists.iter().enumerate() {
+			watch_list.validate()?;
+			if engine.watch_lists[..index].iter().any(|other| other.name == watch_list.name) {
+				return Err(format!("There are several watch lists named {}", watch_list.name));
+			}
+		}
+		Ok(engine)
+	}
+
+	pub(crate) fn push(&mut self, rule: Rule) {
+		self.rules.push(rule);
+	}
+
+	pub(crate) fn routes_to(&self, sink: &str) -> bool {
+		self.rules
+			.iter()
+			.any(|rule| matches!(&rule.action, Action::Route(target) if target == sink))
+	}
+
+	pub(crate) fn watch_lists(&self) -> &[WatchList] {
+		&self.watch_lists
+	}
+
+	pub(crate) fn apply(&self, transfers: Vec<XcmTransfer>) -> RuledTransfers {
+		let mut output = RuledTransfers::default();
+		for transfer in transfers {
+			let matching_rules = self
+				.rules
+				.iter()
+				.filter(|rule| rule.when.matches(&transfer))
+				.collect::<Vec<_>>();
+			if matching_rules.iter().any(|rule| rule.action == Action::Drop) {
+				continue;
+			}
+			for rule in matching_rules {
+				match &rule.action {
+					Action::Alert(notifier) => output
+						.alerts
+						.entry(notifier.clone())
+						.or_default()
+						.push((transfer.clone(), rule.name.clone())),
+					Action::Route(sink) =>
+						output.routes.entry(sink.clone()).or_default().push(transfer.clone()),
+					Action::Drop => (),
+				}
+			}
+			for watch_list in self.watch_lists.iter().filter(|list| list.matches(&transfer)) {
+				output
+					.watch_lists
+					.entry(watch_list.name.clone())
+					.or_default()
+					.push(transfer.clone());
+			}
+			output.kept.push(transfer);
+		}
+		output
+	}
+}
+
+fn read_rules_file(path: &Path) -> Result<String, String> {
+	fs::read_to_string(path).map_err(|e| format!("Failed to read the rules file: {}", e))
+}
+
+// The rules of a subscription, reloaded when the rules file changes so monitoring adjustments
+// don't require a restart, which would leave a gap in the indexed blocks. Each block is processed
+// with the rules in place when its processing starts.
+#[derive(Debug)]
+pub(crate) struct ReloadableRules {
+	path: Option<PathBuf>,
+	// The rules not coming from the file (eg, from the alert flags), kept across reloads
+	extra_rules: Vec<Rule>,
+	// The last content seen in the file, together with the rules in place
+	state: RwLock<(String, Arc<RuleEngine>)>,
+}
+
+impl ReloadableRules {
+	pub(crate) fn load(path: Option<&Path>, extra_rules: Vec<Rule>) -> Result<Self, String> {
+		let content = path.map(read_rules_file).transpose()?.unwrap_or_default();
+		let engine = Self::engine(path.map(|_| content.as_str()), &extra_rules)?;
+		Ok(Self {
+			path: path.map(Path::to_path_buf),
+			extra_rules,
+			state: RwLock::new((content, Arc::new(engine))),
+		})
+	}
+
+	fn engine(content: Option<&str>, extra_rules: &[Rule]) -> Result<RuleEngine, String> {
+		let mut engine = match content {
+			Some(content) => RuleEngine::from_json(content)?,
+			None => RuleEngine::default(),
+		};
+		extra_rules.iter().cloned().for_each(|rule| engine.push(rule));
+		Ok(engine)
+	}
+
+	pub(crate) fn current(&self) -> Arc<RuleEngine> {
+		self.state.read().map(|state| state.1.clone()).unwrap_or_default()
+	}
+
+	// Reloads the rules if the content of the file changed, returning whether they were replaced.
+	// Invalid rules are reported once and the rules in place are kept until the file is fixed.
+	pub(crate) fn reload(&self) -> Result<bool, String> {
+		let Some(path) = &self.path else {
+			return Ok(false);
+		};
+		let content = read_rules_file(path)?;
+		let Ok(mut state) = self.state.write() else {
+			return Ok(false);
+		};
+		if state.0 == content {
+			return Ok(false);
+		}
+		state.0 = content;
+		let engine = Self::engine(Some(&state.0), &self.extra_rules)?;
+		state.1 = Arc::new(engine);
+		Ok(true)
+	}
+}
+
+impl Condition {
+	pub(crate) fn matches(&self, transfer: &XcmTransfer) -> bool {
+		// Round trips are described by their outgoing leg
+		let transfer = match transfer {
+			XcmTransfer::RoundTrip(round_trip) if self.kind.is_none() =>
+				&XcmTransfer::SentTransfer(round_trip.outgoing.clone()),
+			transfer => transfer,
+		};
+		let fields = Fields::of(transfer);
+
+		self.kind.as_ref().is_none_or(|kind| kind == transfer.kind()) &&
+			self.asset.as_ref().is_none_or(|asset| {
+				fields.asset == Some(asset) ||
+					fields.asset_id.is_some_and(|asset_id| asset_id.is_at(asset))
+			}) && self
+			.min_amount
+			.is_none_or(|min| fields.amount.is_some_and(|amount| amount >= min)) &&
+			self.max_amount
+				.is_none_or(|max| fields.amount.is_some_and(|amount| amount <= max)) &&
+			self.account
+				.as_ref()
+				.is_none_or(|account| fields.accounts.iter().any(|other| *other == account)) &&
+			self.chain.as_ref().is_none_or(|chain| {
+				let chain = chain.parse::<DestinationChain>().ok().map(|chain| to_json(&chain));
+				chain.is_some() && fields.chain == chain
+			}) && self
+			.transfer_type
+			.as_ref()
+			.is_none_or(|transfer_type| fields.transfer_type.as_ref() == Some(transfer_type)) &&
+			self.failed.is_none_or(|failed| fields.failed == failed)
+	}
+}
+
+// The fields of a record the conditions look at.
+struct Fields<'a> {
+	asset: Option<&'a String>,
+	asset_id: Option<&'a AssetIdentifier>,
+	amount: Option<f64>,
+	accounts: Vec<&'a String>,
+	chain: Option<serde_json::Value>,
+	transfer_type: Option<String>,
+	failed: bool,
+}
+
+impl<'a> Fields<'a> {
+	fn of(transfer: &'a XcmTransfer) -> Self {
+		match transfer {
+			XcmTransfer::ReceivedTransfer(incoming) => Self {
+				asset: Some(&incoming.asset),
+				asset_id: incoming.asset_id.as_ref(),
+				amount: Some(incoming.amount),
+				accounts: vec![&incoming.beneficiary, &incoming.beneficiary_public_key],
+				chain: Some(to_json(&incoming.origin_chain)),
+				transfer_type: Some(format!("{:?}", incoming.transfer_type)),
+				failed: false,
+			},
+			XcmTransfer::SentTransfer(outgoing) => Self {
+				asset: Some(&outgoing.asset),
+				asset_id: outgoing.asset_id.as_ref(),
+				amount: Some(outgoing.amount),
+				accounts: [
+					&outgoing.sender,
+					&outgoing.beneficiary,
+					&outgoing.beneficiary_public_key,
+				]
+				.into_iter()
+				.chain(outgoing.sender_public_key.as_ref())
+				.collect(),
+				chain: Some(to_json(&outgoing.destination_chain)),
+				transfer_type: Some(format!("{:?}", outgoing.transfer_type)),
+				failed: matches!(
+					outgoing,
+					XcmOutgoingTransfer {
+						outcome: Some(
+							ExecutionOutcome::Incomplete { .. } | ExecutionOutcome::Error { .. }
+						),
+						..
+					}
+				),
+			},
+			XcmTransfer::StatusUpdate(TransferStatusUpdate { status, .. }) => Self {
+				asset: None,
+				asset_id: None,
+				amount: None,
+				accounts: vec![],
+				chain: None,
+				transfer_type: None,
+				failed: matches!(
+					status,
+					TransferStatus::Failed { .. } |
+						TransferStatus::Trapped |
+						TransferStatus::TimedOut { .. }
+				),
+			},
+			// Anomalies are matched by their asset and hourly volume
+			XcmTransfer::Anomaly(anomaly) => Self {
+				asset: Some(&anomaly.asset),
+				asset_id: None,
+				amount: Some(anomaly.hourly_volume),
+				accounts: vec![],
+				chain: None,
+				transfer_type: None,
+				failed: false,
+			},
+			// Failed messages are matched by their origin chain
+			XcmTransfer::FailedMessage(failed_message) => Self {
+				asset: None,
+				asset_id: None,
+				amount: None,
+				accounts: vec![],
+				chain: Some(to_json(&failed_message.origin_chain)),
+				transfer_type: None,
+				failed: true,
+			},
+			_ => Self {
+				asset: None,
+				asset_id: None,
+				amount: None,
+				accounts: vec![],
+				chain: None,
+				transfer_type: None,
+				failed: false,
+			},
+		}
+	}
+}
+
+// Origin and destination chains are compared through their serialized form, which is shared.
+fn to_json<T: serde::Serialize>(value: &T) -> serde_json::Value {
+	serde_json::to_value(value).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		incoming_parser::{OriginChain, XcmIncomingTransfer},
+		outgoing_parser::WeightLimit,
+		types::TransferType,
+	};
+
+	const ACCOUNT: &str = "13KsaHFcQKSTd4m73Ub9yVwM1JGCZvipMyTZonHEXEceFYwS";
+
+	#[test]
+	fn reloadable_rules_follow_the_file() {
+		let path =
+			std::env::temp_dir().join(format!("xcm_indexer_rules_{}.json", std::process::id()));
+		let drop_rule = r#"{ "rules": [{ "name": "Dust", "action": "drop" }] }"#;
+		fs::write(&path, r#"{ "rules": [] }"#).unwrap();
+		let alert = Rule {
+			name: "Failed".to_owned(),
+			when: Condition { failed: Some(true), ..Default::default() },
+			action: Action::Alert("email".to_owned()),
+		};
+
+		let rules = ReloadableRules::load(Some(&path), vec![alert.clone()]).unwrap();
+		assert_eq!(rules.current().rules, vec![alert.clone()]);
+		assert_eq!(rules.reload(), Ok(false));
+
+		fs::write(&path, drop_rule).unwrap();
+		assert_eq!(rules.reload(), Ok(true));
+		assert_eq!(rules.current().rules.len(), 2);
+		assert_eq!(rules.current().rules[1], alert);
+
+		// Invalid rules are reported once, keeping the previous ones
+		fs::write(&path, "not json").unwrap();
+		assert!(rules.reload().is_err());
+		assert_eq!(rules.reload(), Ok(false));
+		assert_eq!(rules.current().rules.len(), 2);
+		fs::remove_file(&path).unwrap();
+	}
+
+	fn sent(amount: f64, outcome: Option<ExecutionOutcome>) -> XcmTransfer {
+		XcmTransfer::SentTransfer(XcmOutgoingTransfer {
+			block_number: 8_935_124,
+			transfer_group_id: "8935124-2".to_owned(),
+			origin: None,
+			destination_chain: DestinationChain::PolkadotParachain(2034),
+			sender: ACCOUNT.to_owned(),
+			sender_public_key: None,
+			beneficiary: "16hiHzdGAR7wi29PjCyUkpFCbjTe9Ri6PrnumbEeyhqg75wy".to_owned(),
+			beneficiary_public_key:
+				"0xfc39fcf04a8071b7409823b7c82427ce67910c6ed80aa0e5093aff234624c820".to_owned(),
+			asset: "DOT".to_owned(),
+			asset_id: Some(AssetIdentifier::Native),
+			amount,
+			exact_amount: None,
+			transfer_type: TransferType::Reserve,
+			fee_asset_item: 0,
+			pays_fees: true,
+			weight_limit: WeightLimit::Unlimited,
+			burned_amount: None,
+			outcome,
+			message_id: None,
+			trapped_assets_hash: None,
+			local_fee_dot: None,
+			nonce: None,
+			tip_dot: None,
+			controllers: None,
+			raw_xcm: None,
+			entities: vec![],
+			xc20_address: None,
+		})
+	}
+
+	fn received(amount: f64) -> XcmTransfer {
+		XcmTransfer::ReceivedTransfer(XcmIncomingTransfer {
+			block_number: 8_935_124,
+			origin_chain: OriginChain::PolkadotParachain(2034),
+			message_id: None,
+			beneficiary: ACCOUNT.to_owned(),
+			beneficiary_public_key:
+				"0x66dd21797b3ab4b27ff723196324a33f7a80683bda78e0fcebf8e7cebb09a358".to_owned(),
+			asset: "USDT".to_owned(),
+			asset_id: None,
+			amount,
+			exact_amount: None,
+			transfer_type: TransferType::Teleport,
+			fee_swap: None,
+			entities: vec![],
+			dust_lost: None,
+			trapped_assets_hash: None,
+			new_account: false,
+			xc20_address: None,
+		})
+	}
+
+	#[test]
+	fn condition_matches_the_record_fields() {
+		let condition =
+			|when: serde_json::Value| -> Condition { serde_json::from_value(when).unwrap() };
+		assert!(Condition::default().matches(&sent(1.0, None)));
+		assert!(condition(serde_json::json!({ "kind": "sent" })).matches(&sent(1.0, None)));
+		assert!(!condition(serde_json::json!({ "kind": "sent" })).matches(&received(1.0)));
+		assert!(
+			condition(serde_json::json!({ "asset": "DOT", "min_amount": 10, "max_amount": 20 }))
+				.matches(&sent(15.0, None))
+		);
+		assert!(!condition(serde_json::json!({ "min_amount": 10 })).matches(&sent(5.0, None)));
+		assert!(condition(serde_json::json!({ "account": ACCOUNT })).matches(&sent(1.0, None)));
+		assert!(condition(serde_json::json!({ "account": ACCOUNT })).matches(&received(1.0)));
+		// Origin and destination chains are matched alike
+		let chain = condition(serde_json::json!({ "chain": "polkadot-parachain:2034" }));
+		assert!(chain.matches(&sent(1.0, None)) && chain.matches(&received(1.0)));
+		assert!(!condition(serde_json::json!({ "chain": "polkadot" })).matches(&received(1.0)));
+		// Assets and chains may be given by their location too
+		assert!(condition(serde_json::json!({ "asset": "parents=1" })).matches(&sent(1.0, None)));
+		assert!(
+			condition(serde_json::json!({ "chain": "parents=1/Parachain(2034)" }))
+				.matches(&received(1.0))
+		);
+		assert!(
+			condition(serde_json::json!({ "transfer_type": "Teleport" })).matches(&received(1.0))
+		);
+		let failed = condition(serde_json::json!({ "failed": true }));
+		assert!(!failed.matches(&sent(1.0, None)));
+		assert!(
+			failed
+				.matches(&sent(1.0, Some(ExecutionOutcome::Error { error: "Barrier".to_owned() })))
+		);
+		assert!(failed.matches(&XcmTransfer::StatusUpdate(TransferStatusUpdate {
+			transfer_group_id: "8935124-2".to_owned(),
+			message_id: None,
+			status: TransferStatus::Trapped,
+		})));
+	}
+
+	#[test]
+	fn rule_engine_applies_the_actions() {
+		let engine: RuleEngine = serde_json::from_value(serde_json::json!({
+			"rules": [
+				{ "name": "Dust", "when": { "max_amount": 1 }, "action": "drop" },
+				{ "name": "Large", "when": { "min_amount": 100 }, "action": { "alert": "email" } },
+				{ "name": "USDT", "when": { "asset": "USDT" }, "action": { "route": "mqtt" } },
+			],
+			"watch_lists": [
+				{ "name": "DOT desk", "assets": ["DOT"], "sink": { "file": "dot.jsonl" } },
+			]
+		}))
+		.unwrap();
+		let ruled = engine.apply(vec![sent(0.5, None), sent(200.0, None), received(10.0)]);
+
+		assert_eq!(ruled.kept, vec![sent(200.0, None), received(10.0)]);
+		assert_eq!(ruled.alerts_for("email"), &[(sent(200.0, None), "Large".to_owned())]);
+		assert_eq!(ruled.for_sink(&engine, "mqtt"), &[received(10.0)]);
+		// Sinks without routes receive every record kept
+		assert_eq!(ruled.for_sink(&engine, OUTPUT_SINK), &ruled.kept[..]);
+		// Watch lists receive the records kept touching them, whatever the routes
+		assert_eq!(ruled.for_watch_list("DOT desk"), &[sent(200.0, None)]);
+	}
+}
@@ -0,0 +1,432 @@
+use crate::{
+	Error,
+	asset_hub::runtime_types::{
+		asset_hub_polkadot_runtime::{OriginCaller, RuntimeCall, RuntimeEvent},
+		frame_support::dispatch::RawOrigin,
+		pallet_xcm::pallet::Call as XcmCall,
+		sp_weights::weight_v2::Weight,
+		staging_xcm::{
+			v3::multilocation::MultiLocation,
+			v4::{
+				asset::{Asset, AssetId, Assets, Fungibility},
+				junction::Junction,
+				junctions::Junctions,
+				location::Location,
+			},
+			v5,
+		},
+		xcm::{
+			VersionedAssetId, VersionedAssets, VersionedLocation,
+			v3::{
+				WeightLimit,
+				junctions::Junctions as V3Junctions,
+				multiasset::{AssetId as V3AssetId, Fungibility as V3Fungibility},
+			},
+		},
+		xcm_runtime_apis::dry_run::CallDryRunEffects,
+	},
+	chain_spec::{Chain, ChainSpec},
+	outgoing_parser::DestinationChain,
+	types::AssetIdentifier,
+};
+use serde::Serialize;
+use std::str::FromStr;
+use subxt::{OnlineClient, PolkadotConfig, config::polkadot::AccountId32, runtime_api::RuntimeApi};
+
+// The XCM version used to build the calls and in which the runtime APIs are asked to answer.
+const XCM_VERSION: u32 = 4;
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum TransferAsset {
+	Native,
+	Asset(crate::asset_hub::assets::storage::types::metadata::Param0),
+}
+
+// Assets are given to the CLI as `DOT`, as their id in pallet_assets or as their location, eg
+// `parents=0/PalletInstance(50)/GeneralIndex(1984)`.
+impl FromStr for TransferAsset {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		// The symbol reported by the node is only known once connected
+		if s.eq_ignore_ascii_case(Chain::NATIVE_TOKEN_SYMBOL) ||
+			s.eq_ignore_ascii_case(&crate::chain_spec::native_token_symbol())
+		{
+			return Ok(Self::Native);
+		}
+		if let Ok(location) = crate::locations::parse_location(s) {
+			return match AssetIdentifier::from_location(&location) {
+				AssetIdentifier::Native => Ok(Self::Native),
+				AssetIdentifier::Asset(asset_id) => Ok(Self::Asset(asset_id)),
+				AssetIdentifier::ForeignAsset(_) => Err(format!("{} isn't a supported asset", s)),
+			};
+		}
+		s.parse().map(Self::Asset).map_err(|_| format!("{} isn't a supported asset", s))
+	}
+}
+
+impl TransferAsset {
+	fn to_location(&self) -> Location {
+		match self {
+			Self::Native => Location { parents: 1, interior: Junctions::Here },
+			Self::Asset(asset_id) => Location {
+				parents: 0,
+				interior: Junctions::X2([
+					Junction::PalletInstance(Chain::ASSETS_PALLET_INDEX),
+					Junction::GeneralIndex(*asset_id as u128),
+				]),
+			},
+		}
+	}
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Beneficiary {
+	AccountId32([u8; 32]),
+	AccountKey20([u8; 20]),
+}
+
+// Beneficiaries are given to the CLI either as a SS58 address or as a 0x prefixed 20 bytes key.
+impl FromStr for Beneficiary {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let invalid_beneficiary = || format!("{} isn't a valid beneficiary", s);
+		match s.strip_prefix("0x") {
+			Some(key) => hex::decode(key)
+				.ok()
+				.and_then(|key| key.try_into().ok())
+				.map(Self::AccountKey20)
+				.ok_or_else(invalid_beneficiary),
+			None => AccountId32::from_str(s)
+				.map(|account_id| Self::AccountId32(account_id.0))
+				.map_err(|_| invalid_beneficiary()),
+		}
+	}
+}
+
+impl Beneficiary {
+	fn to_location(&self) -> Location {
+		let junction = match self {
+			Self::AccountId32(id) => Junction::AccountId32 { network: None, id: *id },
+			Self::AccountKey20(key) => Junction::AccountKey20 { network: None, key: *key },
+		};
+		Location { parents: 0, interior: Junctions::X1([junction]) }
+	}
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct TransferRequest {
+	pub(crate) sender: AccountId32,
+	pub(crate) destination: DestinationChain,
+	pub(crate) beneficiary: Beneficiary,
+	pub(crate) asset: TransferAsset,
+	pub(crate) amount: u128,
+}
+
+impl TransferRequest {
+	// Builds the `transfer_assets` call that the sender would submit, letting the runtime decide
+	// whether the asset is teleported or reserve transferred.
+	fn to_transfer_assets_call(&self) -> Result<RuntimeCall, Error> {
+		let destination = self.destination.to_location().ok_or(Error::GeneratePayloadFailed)?;
+		Ok(RuntimeCall::PolkadotXcm(XcmCall::transfer_assets {
+			dest: Box::new(VersionedLocation::V4(destination)),
+			beneficiary: Box::new(VersionedLocation::V4(self.beneficiary.to_location())),
+			assets: Box::new(VersionedAssets::V4(Assets(vec![Asset {
+				id: AssetId(self.asset.to_location()),
+				fun: Fungibility::Fungible(self.amount),
+			}]))),
+			fee_asset_item: 0,
+			weight_limit: WeightLimit::Unlimited,
+		}))
+	}
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+pub(crate) struct FeeEstimation {
+	pub(crate) local_execution_fee: Option<f64>,
+	pub(crate) delivery_fees: Vec<DeliveryFee>,
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+pub(crate) struct DeliveryFee {
+	pub(crate) destination: DestinationChain,
+	pub(crate) fee: f64,
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+pub(crate) struct TransferSimulation {
+	pub(crate) dispatch_error: Option<String>,
+	pub(crate) emitted_events: Vec<String>,
+	pub(crate) forwarded_xcms: Vec<ForwardedXcm>,
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+pub(crate) struct ForwardedXcm {
+	pub(crate) destination: DestinationChain,
+	pub(crate) messages: Vec<String>,
+}
+
+// Dry runs the transfer to learn the weight it consumes in AssetHub and the messages it sends,
+// and then asks XcmPaymentApi for the price of that weight and of delivering those messages. All
+// the fees are expressed in DOT.
+pub(crate) async fn estimate_fees(
+	api: &OnlineClient<PolkadotConfig>,
+	request: &TransferRequest,
+) -> Result<FeeEstimation, Error> {
+	let runtime_api = api.runtime_api().at_latest().await?;
+	let effects = dry_run_transfer(&runtime_api, request).await?;
+
+	let local_execution_fee = match effects.execution_result {
+		Ok(post_info) => match post_info.actual_weight {
+			Some(weight) => Some(weight_to_dot_fee(&runtime_api, weight).await?),
+			None => None,
+		},
+		Err(e) =>
+			return Err(Error::RuntimeApiFailed(format!("The transfer would fail: {:?}", e.error))),
+	};
+
+	let mut delivery_fees = vec![];
+	for (destination, messages) in effects.forwarded_xcms {
+		let destination = forwarded_destination(&destination);
+		for message in messages {
+			// The location is moved into the runtime API call, so it's rebuilt for each message
+			// from our own representation.
+			let Some(location) = destination.to_location() else {
+				continue;
+			};
+			let query_delivery_fees = crate::asset_hub::apis()
+				.xcm_payment_api()
+				.query_delivery_fees(VersionedLocation::V4(location), message);
+			let fees = runtime_api
+				.call(query_delivery_fees)
+				.await?
+				.map_err(|e| Error::RuntimeApiFailed(format!("{:?}", e)))?;
+			delivery_fees.push(DeliveryFee {
+				destination: destination.clone(),
+				fee: crate::amount::to_decimal_f64(
+					native_amount(&fees),
+					crate::chain_spec::native_token_decimals(),
+				),
+			});
+		}
+	}
+
+	Ok(FeeEstimation { local_execution_fee, delivery_fees })
+}
+
+// Dry runs the transfer, reporting the events it would emit in AssetHub and the messages it would
+// send to other chains. This is what the indexer would see if the transfer were actually executed.
+pub(crate) async fn simulate_transfer(
+	api: &OnlineClient<PolkadotConfig>,
+	request: &TransferRequest,
+) -> Result<TransferSimulation, Error> {
+	let runtime_api = api.runtime_api().at_latest().await?;
+	let effects = dry_run_transfer(&runtime_api, request).await?;
+
+	Ok(TransferSimulation {
+		dispatch_error: effects.execution_result.err().map(|e| format!("{:?}", e.error)),
+		emitted_events: effects.emitted_events.iter().map(|event| format!("{:?}", event)).collect(),
+		forwarded_xcms: effects
+			.forwarded_xcms
+			.iter()
+			.map(|(destination, messages)| ForwardedXcm {
+				destination: forwarded_destination(destination),
+				messages: messages.iter().map(|message| format!("{:?}", message)).collect(),
+			})
+			.collect(),
+	})
+}
+
+async fn dry_run_transfer(
+	runtime_api: &RuntimeApi<PolkadotConfig, OnlineClient<PolkadotConfig>>,
+	request: &TransferRequest,
+) -> Result<CallDryRunEffects<RuntimeEvent>, Error> {
+	dry_run_call(runtime_api, request.sender.clone(), request.to_transfer_assets_call()?).await
+}
+
+// Dry runs a call signed by the given account in the state the runtime API points to.
+pub(crate) async fn dry_run_call(
+	runtime_api: &RuntimeApi<PolkadotConfig, OnlineClient<PolkadotConfig>>,
+	sender: AccountId32,
+	call: RuntimeCall,
+) -> Result<CallDryRunEffects<RuntimeEvent>, Error> {
+	let dry_run = crate::asset_hub::apis().dry_run_api().dry_run_call(
+		OriginCaller::system(RawOrigin::Signed(sender)),
+		call,
+		XCM_VERSION,
+	);
+	runtime_api
+		.call(dry_run)
+		.await?
+		.map_err(|e| Error::RuntimeApiFailed(format!("{:?}", e)))
+}
+
+// The chain a message forwarded by a dry run is delivered to.
+pub(crate) fn forwarded_destination(destination: &VersionedLocation) -> DestinationChain {
+	match destination {
+		VersionedLocation::V3(location) => DestinationChain::from(location),
+		VersionedLocation::V4(location) => DestinationChain::from(location),
+		_ => DestinationChain::Unsupported,
+	}
+}
+
+async fn weight_to_dot_fee(
+	runtime_api: &RuntimeApi<PolkadotConfig, OnlineClient<PolkadotConfig>>,
+	weight: Weight,
+) -> Result<f64, Error> {
+	let query_weight_to_asset_fee =
+		crate::asset_hub::apis().xcm_payment_api().query_weight_to_asset_fee(
+			weight,
+			VersionedAssetId::V4(AssetId(TransferAsset::Native.to_location())),
+		);
+	let fee = runtime_api
+		.call(query_weight_to_asset_fee)
+		.await?
+		.map_err(|e| Error::RuntimeApiFailed(format!("{:?}", e)))?;
+	Ok(crate::amount::to_decimal_f64(fee, crate::chain_spec::native_token_decimals()))
+}
+
+// The amount of DOT contained in a set of assets, whatever their XCM version is.
+fn native_amount(assets: &VersionedAssets) -> u128 {
+	match assets {
+		VersionedAssets::V3(assets) => assets
+			.0
+			.iter()
+			.filter_map(|asset| match (&asset.id, &asset.fun) {
+				(
+					V3AssetId::Concrete(MultiLocation { parents: 1, interior: V3Junctions::Here }),
+					V3Fungibility::Fungible(amount),
+				) => Some(*amount),
+				_ => None,
+			})
+			.sum(),
+		VersionedAssets::V4(assets) => assets
+			.0
+			.iter()
+			.filter_map(|asset| match (&asset.id, &asset.fun) {
+				(
+					AssetId(Location { parents: 1, interior: Junctions::Here }),
+					Fungibility::Fungible(amount),
+				) => Some(*amount),
+				_ => None,
+			})
+			.sum(),
+		VersionedAssets::V5(assets) => assets
+			.0
+			.iter()
+			.filter_map(|asset| match (&asset.id, &asset.fun) {
+				(
+					v5::asset::AssetId(v5::location::Location {
+						parents: 1,
+						interior: v5::junctions::Junctions::Here,
+					}),
+					v5::asset::Fungibility::Fungible(amount),
+				) => Some(*amount),
+				_ => None,
+			})
+			.sum(),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn transfer_asset_from_str() {
+		assert_eq!("DOT".parse(), Ok(TransferAsset::Native));
+		assert_eq!("1984".parse(), Ok(TransferAsset::Asset(1984)));
+		assert_eq!("parents=1".parse(), Ok(TransferAsset::Native));
+		assert_eq!(
+			"parents=0/PalletInstance(50)/GeneralIndex(1984)".parse(),
+			Ok(TransferAsset::Asset(1984))
+		);
+		assert!("USDT".parse::<TransferAsset>().is_err());
+		assert!("parents=1/Parachain(3370)".parse::<TransferAsset>().is_err());
+	}
+
+	#[test]
+	fn beneficiary_from_str() {
+		assert_eq!(
+			"0xda3985513642d591ae95ef6dec4ff6d725373004".parse(),
+			Ok(Beneficiary::AccountKey20(
+				hex::decode("da3985513642d591ae95ef6dec4ff6d725373004")
+					.unwrap()
+					.try_into()
+					.unwrap()
+			))
+		);
+		assert_eq!(
+			"15oF4uVJwmo4TdGW7VfQxNLavjCXviqxT9S1MgbjMNHr6Sp5".parse(),
+			Ok(Beneficiary::AccountId32(
+				AccountId32::from_str("15oF4uVJwmo4TdGW7VfQxNLavjCXviqxT9S1MgbjMNHr6Sp5")
+					.unwrap()
+					.0
+			))
+		);
+		assert!("0x1234".parse::<Beneficiary>().is_err());
+		assert!("not an address".parse::<Beneficiary>().is_err());
+	}
+
+	#[test]
+	fn native_amount_only_counts_dot() {
+		let assets = VersionedAssets::V4(Assets(vec![
+			Asset {
+				id: AssetId(TransferAsset::Native.to_location()),
+				fun: Fungibility::Fungible(10),
+			},
+			Asset {
+				id: AssetId(TransferAsset::Asset(1984).to_location()),
+				fun: Fungibility::Fungible(20),
+			},
+		]));
+		assert_eq!(native_amount(&assets), 10);
+	}
+
+	#[tokio::test]
+	async fn simulate_dot_transfer_to_hydration() {
+		let api = OnlineClient::<PolkadotConfig>::from_url(Chain::RPC_ENDPOINT).await.unwrap();
+
+		let request = TransferRequest {
+			sender: AccountId32::from_str("13KsaHFcQKSTd4m73Ub9yVwM1JGCZvipMyTZonHEXEceFYwS")
+				.unwrap(),
+			destination: DestinationChain::PolkadotParachain(2034),
+			beneficiary: "13KsaHFcQKSTd4m73Ub9yVwM1JGCZvipMyTZonHEXEceFYwS".parse().unwrap(),
+			asset: TransferAsset::Native,
+			amount: 1_000_000_000,
+		};
+		let simulation = simulate_transfer(&api, &request).await.unwrap();
+		assert_eq!(simulation.dispatch_error, None);
+		// DOT is reserve transferred from AssetHub, so a single message is sent to Hydration
+		assert_eq!(simulation.forwarded_xcms.len(), 1);
+		assert_eq!(
+			simulation.forwarded_xcms[0].destination,
+			DestinationChain::PolkadotParachain(2034)
+		);
+		assert_eq!(simulation.forwarded_xcms[0].messages.len(), 1);
+	}
+
+	#[tokio::test]
+	async fn estimate_fees_for_dot_transfer_to_hydration() {
+		let api = OnlineClient::<PolkadotConfig>::from_url(Chain::RPC_ENDPOINT).await.unwrap();
+
+		let request = TransferRequest {
+			sender: AccountId32::from_str("13KsaHFcQKSTd4m73Ub9yVwM1JGCZvipMyTZonHEXEceFYwS")
+				.unwrap(),
+			destination: DestinationChain::PolkadotParachain(2034),
+			beneficiary: "13KsaHFcQKSTd4m73Ub9yVwM1JGCZvipMyTZonHEXEceFYwS".parse().unwrap(),
+			asset: TransferAsset::Native,
+			amount: 1_000_000_000,
+		};
+		let estimation = estimate_fees(&api, &request).await.unwrap();
+		assert!(estimation.local_execution_fee.is_some_and(|fee| fee > 0.0));
+		// The fee of delivering the single message sent to Hydration
+		assert_eq!(estimation.delivery_fees.len(), 1);
+		assert_eq!(
+			estimation.delivery_fees[0].destination,
+			DestinationChain::PolkadotParachain(2034)
+		);
+		assert!(estimation.delivery_fees[0].fee > 0.0);
+	}
+}
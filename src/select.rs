@@ -0,0 +1,352 @@
+use crate::types::XcmTransfer;
+use serde_json::Value;
+use std::{cmp::Ordering, str::FromStr};
+
+// A filter over the output records, written as a small expression language, eg
+// `amount > 1000 && asset == "DOT"`. Operands are the fields of the records, accessed with dots
+// when nested (eg `destination_chain.PolkadotParachain == 2034`), and number, string, `true`,
+// `false` or `null` literals. `kind` stands for the kind of record unless the record has a field
+// with that name. Fields missing in a record are `null`. Comparisons (`==`, `!=`, `>`, `>=`, `<`,
+// `<=`) can be combined with `&&`, `||`, `!` and parentheses, while a field alone holds if it's
+// truthy: not `null`, `false`, `0` nor empty.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Selection(Expr);
+
+#[derive(Debug, Clone, PartialEq)]
+enum Expr {
+	Or(Box<Expr>, Box<Expr>),
+	And(Box<Expr>, Box<Expr>),
+	Not(Box<Expr>),
+	Compare(Operand, Comparison, Operand),
+	Truthy(Operand),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Operand {
+	Field(Vec<String>),
+	Literal(Value),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Comparison {
+	Eq,
+	Ne,
+	Gt,
+	Ge,
+	Lt,
+	Le,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+	Ident(String),
+	Number(f64),
+	Str(String),
+	Comparison(Comparison),
+	And,
+	Or,
+	Not,
+	LeftParen,
+	RightParen,
+}
+
+impl FromStr for Selection {
+	type Err = String;
+
+	fn from_str(expression: &str) -> Result<Self, Self::Err> {
+		let tokens = tokenize(expression)?;
+		let mut parser = Parser { tokens: &tokens, position: 0 };
+		let expr = parser.or()?;
+		match parser.peek() {
+			None => Ok(Self(expr)),
+			Some(token) => Err(format!("Unexpected {:?} in the expression", token)),
+		}
+	}
+}
+
+impl Selection {
+	pub(crate) fn matches(&self, transfer: &XcmTransfer) -> bool {
+		let record = serde_json::to_value(transfer).unwrap_or(Value::Null);
+		// The records are serialized as `{ "<Kind>": { ..fields } }`
+		let fields = record.as_object().and_then(|record| record.values().next());
+		self.0.eval(&Record { kind: transfer.kind(), fields })
+	}
+}
+
+struct Record<'a> {
+	kind: &'static str,
+	fields: Option<&'a Value>,
+}
+
+impl Expr {
+	fn eval(&self, record: &Record) -> bool {
+		match self {
+			Self::Or(left, right) => left.eval(record) || right.eval(record),
+			Self::And(left, right) => left.eval(record) && right.eval(record),
+			Self::Not(expr) => !expr.eval(record),
+			Self::Compare(left, comparison, right) =>
+				compare(&left.eval(record), *comparison, &right.eval(record)),
+			Self::Truthy(operand) => match operand.eval(record) {
+				Value::Null => false,
+				Value::Bool(value) => value,
+				Value::Number(value) => value.as_f64() != Some(0.0),
+				Value::String(value) => !value.is_empty(),
+				Value::Array(values) => !values.is_empty(),
+				Value::Object(values) => !values.is_empty(),
+			},
+		}
+	}
+}
+
+impl Operand {
+	fn eval(&self, record: &Record) -> Value {
+		match self {
+			Self::Literal(value) => value.clone(),
+			Self::Field(path) => {
+				let value = record
+					.fields
+					.and_then(|fields| path.iter().try_fold(fields, |value, key| value.get(key)));
+				match value {
+					Some(value) => value.clone(),
+					None if path.len() == 1 && path[0] == "kind" => record.kind.into(),
+					None => Value::Null,
+				}
+			},
+		}
+	}
+}
+
+fn compare(left: &Value, comparison: Comparison, right: &Value) -> bool {
+	let ordering = match (left, right) {
+		(Value::Number(left), Value::Number(right)) => left
+			.as_f64()
+			.zip(right.as_f64())
+			.and_then(|(left, right)| left.partial_cmp(&right)),
+		(Value::String(left), Value::String(right)) => Some(left.cmp(right)),
+		_ => None,
+	};
+	match (comparison, ordering) {
+		(Comparison::Eq, Some(ordering)) => ordering == Ordering::Equal,
+		(Comparison::Eq, None) => left == right,
+		(Comparison::Ne, Some(ordering)) => ordering != Ordering::Equal,
+		(Comparison::Ne, None) => left != right,
+		(Comparison::Gt, Some(ordering)) => ordering == Ordering::Greater,
+		(Comparison::Ge, Some(ordering)) => ordering != Ordering::Less,
+		(Comparison::Lt, Some(ordering)) => ordering == Ordering::Less,
+		(Comparison::Le, Some(ordering)) => ordering != Ordering::Greater,
+		// Values of different types can't be ordered
+		(_, None) => false,
+	}
+}
+
+fn tokenize(expression: &str) -> Result<Vec<Token>, String> {
+	let mut tokens = vec![];
+	let mut chars = expression.chars().peekable();
+	while let Some(&char) = chars.peek() {
+		match char {
+			' ' | '\t' | '\n' => {
+				chars.next();
+			},
+			'(' | ')' => {
+				chars.next();
+				tokens.push(if char == '(' { Token::LeftParen } else { Token::RightParen });
+			},
+			'&' | '|' => {
+				chars.next();
+				if chars.next() != Some(char) {
+					return Err(format!("Expected {}{} in the expression", char, char));
+				}
+				tokens.push(if char == '&' { Token::And } else { Token::Or });
+			},
+			'=' | '!' | '>' | '<' => {
+				chars.next();
+				let followed_by_equal = chars.next_if_eq(&'=').is_some();
+				tokens.push(match (char, followed_by_equal) {
+					('=', true) => Token::Comparison(Comparison::Eq),
+					('!', true) => Token::Comparison(Comparison::Ne),
+					('!', false) => Token::Not,
+					('>', true) => Token::Comparison(Comparison::Ge),
+					('>', false) => Token::Comparison(Comparison::Gt),
+					('<', true) => Token::Comparison(Comparison::Le),
+					('<', false) => Token::Comparison(Comparison::Lt),
+					_ => return Err("Expected == in the expression".to_owned()),
+				});
+			},
+			'"' => {
+				chars.next();
+				let mut value = String::new();
+				loop {
+					match chars.next() {
+						Some('"') => break,
+						Some('\\') => value.extend(chars.next()),
+						Some(char) => value.push(char),
+						None => return Err("Unterminated string in the expression".to_owned()),
+					}
+				}
+				tokens.push(Token::Str(value));
+			},
+			char if char.is_ascii_digit() || char == '-' => {
+				let mut value = String::new();
+				while let Some(char) = chars.next_if(|char| {
+					char.is_ascii_digit() || matches!(char, '-' | '.' | 'e' | 'E' | '_')
+				}) {
+					if char != '_' {
+						value.push(char);
+					}
+				}
+				let number =
+					value.parse().map_err(|_| format!("{} isn't a valid number", value))?;
+				tokens.push(Token::Number(number));
+			},
+			char if char.is_alphanumeric() || char == '_' => {
+				let mut value = String::new();
+				while let Some(char) =
+					chars.next_if(|char| char.is_alphanumeric() || matches!(char, '_' | '.'))
+				{
+					value.push(char);
+				}
+				tokens.push(Token::Ident(value));
+			},
+			char => return Err(format!("Unexpected {} in the expression", char)),
+		}
+	}
+	Ok(tokens)
+}
+
+// A recursive descent parser, from the lowest precedence operator (`||`) to the highest (`!`).
+struct Parser<'a> {
+	tokens: &'a [Token],
+	position: usize,
+}
+
+impl Parser<'_> {
+	fn peek(&self) -> Option<&Token> {
+		self.tokens.get(self.position)
+	}
+
+	fn next(&mut self) -> Option<&Token> {
+		self.position += 1;
+		self.tokens.get(self.position - 1)
+	}
+
+	fn or(&mut self) -> Result<Expr, String> {
+		let mut expr = self.and()?;
+		while self.peek() == Some(&Token::Or) {
+			self.next();
+			expr = Expr::Or(Box::new(expr), Box::new(self.and()?));
+		}
+		Ok(expr)
+	}
+
+	fn and(&mut self) -> Result<Expr, String> {
+		let mut expr = self.not()?;
+		while self.peek() == Some(&Token::And) {
+			self.next();
+			expr = Expr::And(Box::new(expr), Box::new(self.not()?));
+		}
+		Ok(expr)
+	}
+
+	fn not(&mut self) -> Result<Expr, String> {
+		match self.peek() {
+			Some(Token::Not) => {
+				self.next();
+				Ok(Expr::Not(Box::new(self.not()?)))
+			},
+			Some(Token::LeftParen) => {
+				self.next();
+				let expr = self.or()?;
+				match self.next() {
+					Some(Token::RightParen) => Ok(expr),
+					_ => Err("Expected ) in the expression".to_owned()),
+				}
+			},
+			_ => self.comparison(),
+		}
+	}
+
+	fn comparison(&mut self) -> Result<Expr, String> {
+		let left = self.operand()?;
+		match self.peek() {
+			Some(&Token::Comparison(comparison)) => {
+				self.next();
+				Ok(Expr::Compare(left, comparison, self.operand()?))
+			},
+			_ => Ok(Expr::Truthy(left)),
+		}
+	}
+
+	fn operand(&mut self) -> Result<Operand, String> {
+		match self.next() {
+			Some(Token::Number(value)) => Ok(Operand::Literal((*value).into())),
+			Some(Token::Str(value)) => Ok(Operand::Literal(value.clone().into())),
+			Some(Token::Ident(value)) => Ok(match value.as_str() {
+				"true" => Operand::Literal(true.into()),
+				"false" => Operand::Literal(false.into()),
+				"null" => Operand::Literal(Value::Null),
+				path => Operand::Field(path.split('.').map(str::to_owned).collect()),
+			}),
+			Some(token) => Err(format!("Expected a field or a value, found {:?}", token)),
+			None => Err("The expression ended unexpectedly".to_owned()),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		incoming_parser::{OriginChain, XcmIncomingTransfer},
+		types::TransferType,
+	};
+
+	fn received(asset: &str, amount: f64) -> XcmTransfer {
+		XcmTransfer::ReceivedTransfer(XcmIncomingTransfer {
+			block_number: 8_935_124,
+			origin_chain: OriginChain::PolkadotParachain(2034),
+			message_id: None,
+			beneficiary: "13KsaHFcQKSTd4m73Ub9yVwM1JGCZvipMyTZonHEXEceFYwS".to_owned(),
+			beneficiary_public_key:
+				"0x66dd21797b3ab4b27ff723196324a33f7a80683bda78e0fcebf8e7cebb09a358".to_owned(),
+			asset: asset.to_owned(),
+			asset_id: None,
+			amount,
+			exact_amount: None,
+			transfer_type: TransferType::Reserve,
+			fee_swap: None,
+			entities: vec![],
+			dust_lost: None,
+			trapped_assets_hash: None,
+			new_account: false,
+			xc20_address: None,
+		})
+	}
+
+	fn matches(expression: &str, transfer: &XcmTransfer) -> bool {
+		expression.parse::<Selection>().unwrap().matches(transfer)
+	}
+
+	#[test]
+	fn selection_matches_the_record_fields() {
+		let transfer = received("DOT", 1500.0);
+		assert!(matches(r#"amount > 1000 && asset == "DOT""#, &transfer));
+		assert!(!matches(r#"amount > 1000 && asset == "USDT""#, &transfer));
+		assert!(matches(r#"asset == "USDT" || amount >= 1_500"#, &transfer));
+		assert!(matches(r#"kind == "received" && transfer_type != "Teleport""#, &transfer));
+		assert!(matches("origin_chain.PolkadotParachain == 2034", &transfer));
+		assert!(matches("!(amount < 1000 || fee_swap) && beneficiary", &transfer));
+		// Missing fields are null, which can't be ordered
+		assert!(matches("sender == null", &transfer));
+		assert!(!matches("sender > 0", &transfer));
+		assert!(!matches("amount > 1000", &received("DOT", 999.5)));
+	}
+
+	#[test]
+	fn selection_from_str_rejects_invalid_expressions() {
+		for expression in
+			["", "amount >", "amount = 1", "(amount > 1", "asset == \"DOT", "a & b", "a b"]
+		{
+			assert!(expression.parse::<Selection>().is_err(), "{}", expression);
+		}
+	}
+}
@@ -0,0 +1,113 @@
+use crate::types::BlockNumber;
+use std::{
+	num::NonZeroU32,
+	ops::RangeInclusive,
+	sync::{
+		Mutex,
+		atomic::{AtomicUsize, Ordering},
+	},
+};
+
+// Splits a range of blocks into consecutive sub-ranges of up to `size` blocks.
+pub(crate) fn split_range(
+	from_block: BlockNumber,
+	to_block: BlockNumber,
+	size: NonZeroU32,
+) -> Vec<RangeInclusive<BlockNumber>> {
+	let mut shards = vec![];
+	let mut start = from_block;
+	while start <= to_block {
+		let end = start.saturating_add(size.get() - 1).min(to_block);
+		shards.push(start..=end);
+		match end.checked_add(1) {
+			Some(next) => start = next,
+			None => break,
+		}
+	}
+	shards
+}
+
+// Processes the shards of a range with a worker per endpoint. Every worker takes the next shard
+// not taken yet once it's done with its own, so faster endpoints process more of them, and the
+// results are aggregated in the order of the shards. The first error stops the processing.
+pub(crate) async fn process_shards<T, E, F, Fut>(
+	shards: Vec<RangeInclusive<BlockNumber>>,
+	workers: usize,
+	process: F,
+) -> Result<Vec<T>, E>
+where
+	F: Fn(usize, RangeInclusive<BlockNumber>) -> Fut,
+	Fut: Future<Output = Result<Vec<T>, E>>,
+{
+	let next_shard = AtomicUsize::new(0);
+	let results: Mutex<Vec<Option<Vec<T>>>> =
+		Mutex::new(std::iter::repeat_with(|| None).take(shards.len()).collect());
+	let worker = |worker: usize| {
+		let (shards, next_shard, results, process) = (&shards, &next_shard, &results, &process);
+		async move {
+			loop {
+				let index = next_shard.fetch_add(1, Ordering::Relaxed);
+				let Some(shard) = shards.get(index) else {
+					return Ok::<(), E>(());
+				};
+				let processed = process(worker, shard.clone()).await?;
+				if let Ok(mut results) = results.lock() {
+					results[index] = Some(processed);
+				}
+			}
+		}
+	};
+	futures::future::try_join_all((0..workers.max(1)).map(worker)).await?;
+	Ok(results
+		.into_inner()
+		.unwrap_or_default()
+		.into_iter()
+		.flat_map(Option::unwrap_or_default)
+		.collect())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::time::Duration;
+
+	#[test]
+	fn ranges_are_split_in_shards() {
+		let size = NonZeroU32::new(10).unwrap();
+		assert_eq!(split_range(1, 25, size), vec![1..=10, 11..=20, 21..=25]);
+		assert_eq!(split_range(5, 5, size), vec![5..=5]);
+		assert_eq!(split_range(6, 5, size), vec![]);
+		assert_eq!(split_range(BlockNumber::MAX - 1, BlockNumber::MAX, size).len(), 1);
+	}
+
+	#[tokio::test]
+	async fn shards_are_aggregated_in_order() {
+		let used_workers = Mutex::new(vec![]);
+		let shards = split_range(1, 100, NonZeroU32::new(7).unwrap());
+		let blocks = process_shards(shards, 3, |worker, shard| {
+			used_workers.lock().unwrap().push(worker);
+			async move {
+				// The first worker is the slowest, so the shards complete out of order
+				tokio::time::sleep(Duration::from_millis(if worker == 0 { 5 } else { 1 })).await;
+				Ok::<_, ()>(shard.collect::<Vec<_>>())
+			}
+		})
+		.await
+		.unwrap();
+		assert_eq!(blocks, (1..=100).collect::<Vec<_>>());
+		let mut used_workers = used_workers.into_inner().unwrap();
+		used_workers.sort();
+		used_workers.dedup();
+		assert_eq!(used_workers, vec![0, 1, 2]);
+
+		let failed = process_shards(
+			split_range(1, 10, NonZeroU32::new(2).unwrap()),
+			2,
+			|_, shard| async move {
+				if shard.contains(&5) { Err("unreachable block") } else { Ok(vec![]) }
+			},
+		)
+		.await;
+		assert_eq!(failed, Err::<Vec<()>, _>("unreachable block"));
+	}
+}
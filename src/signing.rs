@@ -0,0 +1,105 @@
+use serde::Serialize;
+use sp_core::{Pair, ed25519, hashing::blake2_256};
+use std::{fs, path::Path};
+
+// The record appended after every batch of output, so consumers ingesting the feed over untrusted
+// transports can verify its integrity and origin. The batch is the bytes output since the previous
+// signature record (or the beginning of the output), identified by their BLAKE2-256 hash, which is
+// what the ed25519 key signs.
+#[derive(Debug, Serialize, PartialEq)]
+pub(crate) struct BatchSignature {
+	pub(crate) batch_hash: String,
+	pub(crate) signature: String,
+	pub(crate) public_key: String,
+}
+
+#[derive(Clone)]
+pub(crate) struct OutputSigner(ed25519::Pair);
+
+impl OutputSigner {
+	// Loads the key from a file holding its hex encoded seed or a secret URI (eg, a mnemonic).
+	pub(crate) fn from_file(path: &Path) -> Result<Self, String> {
+		let content = fs::read_to_string(path)
+			.map_err(|e| format!("Failed to read the signing key file: {}", e))?;
+		Self::from_secret(content.trim())
+	}
+
+	pub(crate) fn from_secret(secret: &str) -> Result<Self, String> {
+		ed25519::Pair::from_string(secret, None)
+			.map(Self)
+			.map_err(|e| format!("Invalid signing key: {:?}", e))
+	}
+
+	pub(crate) fn public_key(&self) -> String {
+		format!("0x{}", hex::encode(self.0.public()))
+	}
+
+	pub(crate) fn sign(&self, batch: &[u8]) -> BatchSignature {
+		let batch_hash = blake2_256(batch);
+		BatchSignature {
+			batch_hash: format!("0x{}", hex::encode(batch_hash)),
+			signature: self.sign_hash(&batch_hash),
+			public_key: self.public_key(),
+		}
+	}
+
+	// The hex encoded signature of a hash.
+	pub(crate) fn sign_hash(&self, hash: &[u8; 32]) -> String {
+		format!("0x{}", hex::encode(self.0.sign(hash)))
+	}
+
+	// The signature record of a batch about to be written.
+	pub(crate) fn signature_record(&self, batch: &[u8]) -> Option<Vec<u8>> {
+		serde_json::to_string(&self.sign(batch))
+			.ok()
+			.map(|json| format!("{}\n", json).into_bytes())
+	}
+}
+
+// Checks a hex encoded signature of a hash against a hex encoded public key.
+pub(crate) fn verify_hash_signature(hash: &[u8; 32], signature: &str, public_key: &str) -> bool {
+	let decode = |hex_string: &str| hex::decode(hex_string.trim_start_matches("0x")).ok();
+	let (Some(signature), Some(public_key)) = (
+		decode(signature).and_then(|bytes| <[u8; 64]>::try_from(bytes).ok()),
+		decode(public_key).and_then(|bytes| <[u8; 32]>::try_from(bytes).ok()),
+	) else {
+		return false;
+	};
+	ed25519::Pair::verify(
+		&ed25519::Signature::from_raw(signature),
+		hash,
+		&ed25519::Public::from_raw(public_key),
+	)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn batch_signatures_verify_with_the_public_key() {
+		let signer = OutputSigner(ed25519::Pair::from_seed(&[7; 32]));
+		let batch = b"[{\"ReceivedTransfer\":{}}]\n";
+		let signature = signer.sign(batch);
+		assert_eq!(signature.batch_hash, format!("0x{}", hex::encode(blake2_256(batch))));
+		assert_eq!(signature.public_key, format!("0x{}", hex::encode(signer.0.public())));
+
+		let signature_bytes: [u8; 64] =
+			hex::decode(&signature.signature[2..]).unwrap().try_into().unwrap();
+		let signature = ed25519::Signature::from_raw(signature_bytes);
+		assert!(ed25519::Pair::verify(&signature, blake2_256(batch), &signer.0.public()));
+		assert!(!ed25519::Pair::verify(&signature, blake2_256(b"tampered"), &signer.0.public()));
+	}
+
+	#[test]
+	fn hash_signatures_verify_with_the_public_key() {
+		let signer = OutputSigner(ed25519::Pair::from_seed(&[7; 32]));
+		let public_key = signer.public_key();
+		let signature = signer.sign_hash(&[1; 32]);
+		assert!(verify_hash_signature(&[1; 32], &signature, &public_key));
+		assert!(!verify_hash_signature(&[2; 32], &signature, &public_key));
+		let other_signer = OutputSigner(ed25519::Pair::from_seed(&[8; 32]));
+		assert!(!verify_hash_signature(&[1; 32], &other_signer.sign_hash(&[1; 32]), &public_key));
+		assert!(!verify_hash_signature(&[1; 32], "0x00", &public_key));
+	}
+}
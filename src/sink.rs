@@ -0,0 +1,214 @@
+use crate::{
+	batch::OutputBatch,
+	types::{BlockHash, BlockNumber, XcmTransfer},
+};
+use std::{
+	sync::{Mutex, MutexGuard},
+	time::Duration,
+};
+
+// A destination the transfers are delivered to besides the output, eg a message broker or the
+// webhook of a watch list. Sinks are shared by the workers, so deliveries may run concurrently.
+// The future is `Send` so the sinks can be driven from spawned tasks.
+pub(crate) trait TransferSink {
+	// The name of the sink, as given to the dead letters of the failed deliveries
+	fn name(&self) -> String;
+
+	fn deliver(&self, transfers: &[XcmTransfer])
+	-> impl Future<Output = Result<(), String>> + Send;
+}
+
+// A delivery that failed, together with the blocks of the transfers delivered, as a batch may hold
+// the transfers of several blocks.
+#[derive(Debug, PartialEq)]
+pub(crate) struct DeliveryError {
+	pub(crate) error: String,
+	pub(crate) blocks: Vec<(BlockHash, BlockNumber)>,
+}
+
+// Delivers the transfers to a sink, buffered in batches like the output if batching is enabled, so
+// every sink saves on round trips during backfills and high-activity periods alike.
+pub(crate) struct BatchedSink<S> {
+	sink: S,
+	// Only locked while buffering, so the workers don't wait for each other's deliveries
+	pending: Mutex<PendingTransfers>,
+}
+
+struct PendingTransfers {
+	batch: Option<OutputBatch<XcmTransfer>>,
+	// The blocks with transfers in the batch
+	blocks: Vec<(BlockHash, BlockNumber)>,
+}
+
+type DueTransfers = (Vec<XcmTransfer>, Vec<(BlockHash, BlockNumber)>);
+
+impl PendingTransfers {
+	// Buffers the transfers of a block, returning the transfers due to be delivered along with
+	// their blocks.
+	fn push(
+		&mut self,
+		block_hash: BlockHash,
+		block_number: BlockNumber,
+		transfers: &[XcmTransfer],
+	) -> Option<DueTransfers> {
+		if !transfers.is_empty() {
+			self.blocks.push((block_hash, block_number));
+		}
+		let transfers = match self.batch.as_mut() {
+			Some(batch) => batch.push(transfers, transfers.len())?,
+			None => transfers.to_vec(),
+		};
+		Some((transfers, std::mem::take(&mut self.blocks)))
+	}
+
+	fn take(&mut self) -> DueTransfers {
+		let transfers = self.batch.as_mut().map(OutputBatch::take).unwrap_or_default();
+		(transfers, std::mem::take(&mut self.blocks))
+	}
+}
+
+impl<S: TransferSink> BatchedSink<S> {
+	pub(crate) fn new(sink: S, batch: Option<(usize, Duration)>) -> Self {
+		let batch = batch.map(|(size, interval)| OutputBatch::new(size, interval));
+		Self { sink, pending: Mutex::new(PendingTransfers { batch, blocks: vec![] }) }
+	}
+
+	pub(crate) fn name(&self) -> String {
+		self.sink.name()
+	}
+
+	pub(crate) fn sink(&self) -> &S {
+		&self.sink
+	}
+
+	// Delivers the transfers of a block, or buffers them until the batch is due.
+	pub(crate) async fn deliver(
+		&self,
+		block_hash: BlockHash,
+		block_number: BlockNumber,
+		transfers: &[XcmTransfer],
+	) -> Result<(), DeliveryError> {
+		let due = self.pending().push(block_hash, block_number, transfers);
+		match due {
+			Some(due) => self.send(due).await,
+			None => Ok(()),
+		}
+	}
+
+	// Delivers the buffered transfers, if any. Called before shutting down.
+	pub(crate) async fn flush(&self) -> Result<(), DeliveryError> {
+		let due = self.pending().take();
+		self.send(due).await
+	}
+
+	// Delivers the buffered transfers once the interval of the batch elapsed, also without new
+	// transfers. Called periodically.
+	pub(crate) async fn flush_due(&self) -> Result<(), DeliveryError> {
+		let due = {
+			let mut pending = self.pending();
+			match &pending.batch {
+				Some(batch) if batch.is_due() => Some(pending.take()),
+				_ => None,
+			}
+		};
+		match due {
+			Some(due) => self.send(due).await,
+			None => Ok(()),
+		}
+	}
+
+	fn pending(&self) -> MutexGuard<'_, PendingTransfers> {
+		self.pending.lock().unwrap_or_else(|e| e.into_inner())
+	}
+
+	async fn send(&self, (transfers, blocks): DueTransfers) -> Result<(), DeliveryError> {
+		if transfers.is_empty() {
+			return Ok(());
+		}
+		self.sink
+			.deliver(&transfers)
+			.await
+			.map_err(|error| DeliveryError { error, blocks })
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::analytics::{TransferStatus, TransferStatusUpdate};
+
+	#[derive(Default)]
+	struct RecordingSink {
+		deliveries: Mutex<Vec<usize>>,
+		fail: bool,
+	}
+
+	impl RecordingSink {
+		fn deliveries(&self) -> Vec<usize> {
+			self.deliveries.lock().unwrap().clone()
+		}
+	}
+
+	impl TransferSink for RecordingSink {
+		fn name(&self) -> String {
+			"recording".to_owned()
+		}
+
+		async fn deliver(&self, transfers: &[XcmTransfer]) -> Result<(), String> {
+			if self.fail {
+				return Err("Unreachable".to_owned());
+			}
+			self.deliveries.lock().unwrap().push(transfers.len());
+			Ok(())
+		}
+	}
+
+	fn transfers(block_number: BlockNumber, amount: usize) -> Vec<XcmTransfer> {
+		let status_update = XcmTransfer::StatusUpdate(TransferStatusUpdate {
+			transfer_group_id: format!("{}-2", block_number),
+			message_id: None,
+			status: TransferStatus::Delivered,
+		});
+		vec![status_update; amount]
+	}
+
+	#[tokio::test]
+	async fn batched_sinks_deliver_full_batches() {
+		let mut sink =
+			BatchedSink::new(RecordingSink::default(), Some((3, Duration::from_secs(3600))));
+		let hash = |block_number: u8| BlockHash::repeat_byte(block_number);
+		sink.deliver(hash(1), 1, &transfers(1, 2)).await.unwrap();
+		assert!(sink.sink().deliveries().is_empty());
+		sink.deliver(hash(2), 2, &transfers(2, 2)).await.unwrap();
+		assert_eq!(sink.sink().deliveries(), vec![4]);
+		sink.deliver(hash(3), 3, &transfers(3, 1)).await.unwrap();
+		// Nothing is due before the interval elapses
+		sink.flush_due().await.unwrap();
+		assert_eq!(sink.sink().deliveries(), vec![4]);
+		sink.flush().await.unwrap();
+		assert_eq!(sink.sink().deliveries(), vec![4, 1]);
+		// Nothing is delivered without buffered transfers
+		sink.flush().await.unwrap();
+		assert_eq!(sink.sink().deliveries(), vec![4, 1]);
+
+		// Failed deliveries point to every block of the batch
+		sink.sink.fail = true;
+		sink.deliver(hash(4), 4, &transfers(4, 1)).await.unwrap();
+		sink.deliver(hash(5), 5, &[]).await.unwrap();
+		assert_eq!(
+			sink.deliver(hash(6), 6, &transfers(6, 2)).await,
+			Err(DeliveryError {
+				error: "Unreachable".to_owned(),
+				blocks: vec![(hash(4), 4), (hash(6), 6)]
+			})
+		);
+	}
+
+	#[tokio::test]
+	async fn unbatched_sinks_deliver_every_block() {
+		let sink = BatchedSink::new(RecordingSink::default(), None);
+		sink.deliver(BlockHash::zero(), 1, &transfers(1, 2)).await.unwrap();
+		sink.deliver(BlockHash::zero(), 2, &[]).await.unwrap();
+		assert_eq!(sink.sink().deliveries(), vec![2]);
+	}
+}
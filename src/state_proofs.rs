@@ -0,0 +1,229 @@
+use crate::{
+	Error,
+	asset_hub::{assets, foreign_assets},
+	storage_lookup::StorageLookup,
+	types::BlockHash,
+};
+use sp_core::{Blake2Hasher, H256, hashing::blake2_256};
+use sp_trie::{LayoutV1, StorageProof, read_trie_value};
+use std::sync::OnceLock;
+use subxt::{
+	Config, OnlineClient, PolkadotConfig,
+	backend::{legacy::LegacyRpcMethods, rpc::RpcClient},
+	blocks::Block,
+	ext::codec::{Decode, Encode},
+	storage::{Address, Storage},
+	utils::Yes,
+};
+
+// The clients used to fetch the storage proofs, set once when the CLI starts if the proofs are
+// verified, so the storage reads don't need to carry them around. The state roots the proofs are
+// verified against are read from a second, independent endpoint (the reference), as the header
+// served by the provider of the proofs could carry a spoofed root matching spoofed proofs.
+pub(crate) struct ProofClients {
+	api: OnlineClient<PolkadotConfig>,
+	rpc: LegacyRpcMethods<PolkadotConfig>,
+	reference: LegacyRpcMethods<PolkadotConfig>,
+}
+
+static PROOF_CLIENTS: OnceLock<ProofClients> = OnceLock::new();
+
+pub(crate) fn set_verify_state_proofs(
+	api: OnlineClient<PolkadotConfig>,
+	rpc_client: RpcClient,
+	reference_rpc_client: RpcClient,
+) {
+	let _ = PROOF_CLIENTS.set(ProofClients {
+		api,
+		rpc: LegacyRpcMethods::new(rpc_client),
+		reference: LegacyRpcMethods::new(reference_rpc_client),
+	});
+}
+
+// The storage of a block, read as the RPC provider returns it or, in the paranoid mode, with a
+// proof verified against the state root of the block, so a malicious provider can't spoof the
+// names, decimals or balances of the assets.
+pub(crate) enum BlockStorage {
+	Unverified(Storage<PolkadotConfig, OnlineClient<PolkadotConfig>>),
+	Proven { clients: &'static ProofClients, block_hash: BlockHash, state_root: H256 },
+}
+
+impl BlockStorage {
+	// In the paranoid mode, the state root is taken from the header of the block served by the
+	// reference endpoint, failing if it doesn't have the block at its height.
+	pub(crate) async fn at(
+		block: &Block<PolkadotConfig, OnlineClient<PolkadotConfig>>,
+	) -> Result<Self, Error> {
+		let Some(clients) = PROOF_CLIENTS.get() else {
+			return Ok(Self::Unverified(block.storage()));
+		};
+		let block_hash = block.hash();
+		let invalid_header = |reason: &str| Error::InvalidStateProof {
+			block: block_hash,
+			reason: format!("No verified header to take the state root from: {}", reason),
+		};
+		let reference_hash =
+			clients.reference.chain_get_block_hash(Some(block.number().into())).await?;
+		if reference_hash != Some(block_hash) {
+			return Err(invalid_header("the reference endpoint has another block at its height"));
+		}
+		let header = clients
+			.reference
+			.chain_get_header(Some(block_hash))
+			.await?
+			.ok_or_else(|| invalid_header("the reference endpoint doesn't have its header"))?;
+		let state_root = verified_state_root(&header, block_hash).ok_or_else(|| {
+			invalid_header("the header served by the reference endpoint doesn't match")
+		})?;
+		Ok(Self::Proven { clients, block_hash, state_root })
+	}
+
+	pub(crate) async fn fetch<Addr>(&self, address: &Addr) -> Result<Option<Addr::Target>, Error>
+	where
+		Addr: Address<IsFetchable = Yes>,
+		Addr::Target: Decode,
+	{
+		let (clients, block_hash, state_root) = match self {
+			Self::Unverified(storage) => return Ok(storage.fetch(address).await?),
+			Self::Proven { clients, block_hash, state_root } => (clients, *block_hash, state_root),
+		};
+		let invalid_proof = |reason: String| Error::InvalidStateProof { block: block_hash, reason };
+		let key = clients.api.storage().address_bytes(address)?;
+		let read_proof =
+			clients.rpc.state_get_read_proof([key.as_slice()], Some(block_hash)).await?;
+		let proof_nodes = read_proof.proof.into_iter().map(|node| node.0);
+		read_proven_value(proof_nodes, state_root, &key)
+			.map_err(invalid_proof)?
+			.map(|value| Addr::Target::decode(&mut &value[..]))
+			.transpose()
+			.map_err(|e| invalid_proof(format!("Failed to decode the proven value: {}", e)))
+	}
+}
+
+impl BlockStorage {
+	// Like `fetch`, but giving the default value of the storage entry if it's missing.
+	pub(crate) async fn fetch_or_default<Addr>(&self, address: &Addr) -> Result<Addr::Target, Error>
+	where
+		Addr: Address<IsFetchable = Yes, IsDefaultable = Yes>,
+		Addr::Target: Decode,
+	{
+		let (clients, block_hash) = match self {
+			Self::Unverified(storage) => return Ok(storage.fetch_or_default(address).await?),
+			Self::Proven { clients, block_hash, .. } => (clients, *block_hash),
+		};
+		if let Some(value) = self.fetch(address).await? {
+			return Ok(value);
+		}
+		let invalid_proof = |reason: String| Error::InvalidStateProof { block: block_hash, reason };
+		let metadata = clients.api.metadata();
+		let default_bytes = metadata
+			.pallet_by_name(address.pallet_name())
+			.and_then(|pallet| pallet.storage())
+			.and_then(|storage| storage.entry_by_name(address.entry_name()))
+			.map(|entry| entry.default_bytes().to_vec())
+			.ok_or_else(|| invalid_proof("The storage entry isn't in the metadata".to_owned()))?;
+		Addr::Target::decode(&mut &default_bytes[..])
+			.map_err(|e| invalid_proof(format!("Failed to decode the default value: {}", e)))
+	}
+}
+
+// The state root of a header, only trusted if it's the header hashing to the block hash.
+fn verified_state_root(
+	header: &<PolkadotConfig as Config>::Header,
+	block_hash: BlockHash,
+) -> Option<H256> {
+	(BlockHash::from(blake2_256(&header.encode())) == block_hash).then(|| H256(header.state_root.0))
+}
+
+// Reads a value from the trie nodes of a proof. The proof only holds the nodes leading to the
+// value, so a value that isn't the one committed in the state root fails to be read.
+fn read_proven_value(
+	proof_nodes: impl IntoIterator<Item = Vec<u8>>,
+	state_root: &H256,
+	key: &[u8],
+) -> Result<Option<Vec<u8>>, String> {
+	let db = StorageProof::new(proof_nodes).into_memory_db::<Blake2Hasher>();
+	read_trie_value::<LayoutV1<Blake2Hasher>, _>(&db, state_root, key, None, None)
+		.map_err(|e| format!("{:?}", e))
+}
+
+// The metadata of the assets is served from the cache of the subscription, if it keeps one, unless
+// the reads are proven.
+impl StorageLookup for BlockStorage {
+	async fn asset_metadata(
+		&self,
+		asset_id: &assets::storage::types::metadata::Param0,
+	) -> Result<Option<assets::storage::types::metadata::Metadata>, Error> {
+		if let Self::Proven { .. } = self {
+			return self.fetch(&crate::asset_hub::storage().assets().metadata(asset_id)).await;
+		}
+		if let Some(metadata) = crate::metadata_snapshot::cached_asset_metadata(asset_id) {
+			return Ok(metadata);
+		}
+		let metadata = self.fetch(&crate::asset_hub::storage().assets().metadata(asset_id)).await?;
+		crate::metadata_snapshot::cache_asset_metadata(asset_id, &metadata);
+		Ok(metadata)
+	}
+
+	async fn foreign_asset_metadata(
+		&self,
+		asset_id: &foreign_assets::storage::types::metadata::Param0,
+	) -> Result<Option<foreign_assets::storage::types::metadata::Metadata>, Error> {
+		if let Self::Proven { .. } = self {
+			return self
+				.fetch(&crate::asset_hub::storage().foreign_assets().metadata(asset_id))
+				.await;
+		}
+		if let Some(metadata) = crate::metadata_snapshot::cached_foreign_asset_metadata(asset_id) {
+			return Ok(metadata);
+		}
+		let metadata = self
+			.fetch(&crate::asset_hub::storage().foreign_assets().metadata(asset_id))
+			.await?;
+		crate::metadata_snapshot::cache_foreign_asset_metadata(asset_id, &metadata);
+		Ok(metadata)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use sp_trie::{MemoryDB, TrieMut, trie_types::TrieDBMutBuilderV1};
+
+	#[test]
+	fn proven_values_are_checked_against_the_state_root() {
+		let mut db = MemoryDB::<Blake2Hasher>::default();
+		let mut state_root = H256::default();
+		{
+			let mut trie = TrieDBMutBuilderV1::new(&mut db, &mut state_root).build();
+			trie.insert(b"metadata", b"USDT").unwrap();
+			trie.insert(b"balance", &[42; 40]).unwrap();
+		}
+		let proof_nodes = db
+			.drain()
+			.into_values()
+			.filter(|(_, rc)| *rc > 0)
+			.map(|(node, _)| node)
+			.collect::<Vec<_>>();
+
+		assert_eq!(
+			read_proven_value(proof_nodes.clone(), &state_root, b"metadata"),
+			Ok(Some(b"USDT".to_vec()))
+		);
+		assert_eq!(read_proven_value(proof_nodes.clone(), &state_root, b"missing"), Ok(None));
+		// A proof for another state doesn't verify
+		assert!(read_proven_value(proof_nodes, &H256::repeat_byte(1), b"metadata").is_err());
+	}
+
+	#[test]
+	fn state_roots_are_only_taken_from_the_header_of_the_block() {
+		// The parent hash, the compact block number, the state and extrinsics roots and an empty
+		// digest
+		let encoded_header = [&[1; 32][..], &[40], &[2; 32], &[3; 32], &[0]].concat();
+		let header = <PolkadotConfig as Config>::Header::decode(&mut &encoded_header[..]).unwrap();
+		let block_hash = BlockHash::from(blake2_256(&encoded_header));
+
+		assert_eq!(verified_state_root(&header, block_hash), Some(H256::repeat_byte(2)));
+		assert_eq!(verified_state_root(&header, BlockHash::repeat_byte(2)), None);
+	}
+}
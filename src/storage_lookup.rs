@@ -0,0 +1,88 @@
+use crate::{
+	Error,
+	asset_hub::{assets, foreign_assets},
+};
+use subxt::{OnlineClient, PolkadotConfig, storage::Storage};
+
+// The storage items read by the parsers to give context to the transfers they find. Decoding the
+// block data doesn't need a connection to a node, so abstracting these lookups allows running the
+// parsers against injected block data (eg, in a browser explorer) instead of an `OnlineClient`.
+pub(crate) trait StorageLookup {
+	async fn asset_metadata(
+		&self,
+		asset_id: &assets::storage::types::metadata::Param0,
+	) -> Result<Option<assets::storage::types::metadata::Metadata>, Error>;
+
+	async fn foreign_asset_metadata(
+		&self,
+		asset_id: &foreign_assets::storage::types::metadata::Param0,
+	) -> Result<Option<foreign_assets::storage::types::metadata::Metadata>, Error>;
+}
+
+impl StorageLookup for Storage<PolkadotConfig, OnlineClient<PolkadotConfig>> {
+	async fn asset_metadata(
+		&self,
+		asset_id: &assets::storage::types::metadata::Param0,
+	) -> Result<Option<assets::storage::types::metadata::Metadata>, Error> {
+		Ok(self.fetch(&crate::asset_hub::storage().assets().metadata(asset_id)).await?)
+	}
+
+	async fn foreign_asset_metadata(
+		&self,
+		asset_id: &foreign_assets::storage::types::metadata::Param0,
+	) -> Result<Option<foreign_assets::storage::types::metadata::Metadata>, Error> {
+		Ok(self
+			.fetch(&crate::asset_hub::storage().foreign_assets().metadata(asset_id))
+			.await?)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		asset_hub::runtime_types::{
+			bounded_collections::bounded_vec::BoundedVec, pallet_assets::types::AssetMetadata,
+		},
+		types::AssetMetadataValues,
+	};
+	use std::collections::HashMap;
+
+	// Block data injected without a node: only assets from pallet_assets are known.
+	struct InjectedStorage(HashMap<u32, (&'static str, u8)>);
+
+	impl StorageLookup for InjectedStorage {
+		async fn asset_metadata(
+			&self,
+			asset_id: &assets::storage::types::metadata::Param0,
+		) -> Result<Option<assets::storage::types::metadata::Metadata>, Error> {
+			Ok(self.0.get(asset_id).map(|(name, decimals)| AssetMetadata {
+				deposit: 0,
+				name: BoundedVec(name.as_bytes().to_vec()),
+				symbol: BoundedVec(vec![]),
+				decimals: *decimals,
+				is_frozen: false,
+			}))
+		}
+
+		async fn foreign_asset_metadata(
+			&self,
+			_asset_id: &foreign_assets::storage::types::metadata::Param0,
+		) -> Result<Option<foreign_assets::storage::types::metadata::Metadata>, Error> {
+			Ok(None)
+		}
+	}
+
+	#[tokio::test]
+	async fn metadata_is_extracted_from_injected_storage() {
+		let storage = InjectedStorage(HashMap::from([(1984, ("Tether USD", 6))]));
+		assert_eq!(
+			crate::helpers::extract_asset_metadata_values(&storage, &1984).await.unwrap(),
+			AssetMetadataValues { asset_name: "Tether USD".to_owned(), decimals: 6 }
+		);
+		assert_eq!(
+			crate::helpers::extract_asset_metadata_values(&storage, &1337).await.unwrap(),
+			AssetMetadataValues { asset_name: "Asset Id: 1337".to_owned(), decimals: 0 }
+		);
+	}
+}
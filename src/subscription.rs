@@ -0,0 +1,1736 @@
+#[cfg(feature = "email")]
+use crate::alerts::{EmailNotifier, email_alert_rules};
+#[cfg(feature = "amqp")]
+use crate::amqp::AmqpSink;
+#[cfg(feature = "bigquery")]
+use crate::bigquery::{AccessTokenSource, BigQuerySink};
+#[cfg(feature = "http-api")]
+use crate::flows::FlowMetrics;
+#[cfg(feature = "mqtt")]
+use crate::mqtt::MqttSink;
+#[cfg(feature = "http-api")]
+use crate::totals::TransferTotals;
+use crate::{
+	Error,
+	analytics::{
+		AnomalyDetector, RoundTripDetector, TransferStatus, TransferStatusUpdate, TransferTracker,
+	},
+	batch::OutputBatch,
+	bridge::{
+		BridgeStatus, EthereumBridgeFollower, KUSAMA_ASSET_HUB_PARA_ID, KusamaBridgeFollower,
+		KusamaBridgeLeg,
+	},
+	cli::{
+		CliCommand, Connection, OutputFormat, OutputLayout, Subscribe, append_output, append_text,
+		cross_check_block,
+	},
+	cross_check::CrossChecker,
+	dead_letters::{DeadLetter, FailureStage},
+	finality::FinalityVerifier,
+	hash_chain::HashChain,
+	metadata_snapshot::MetadataSnapshot,
+	outgoing_parser::DestinationChain,
+	output::OutputSink,
+	prefetch::Prefetcher,
+	recent_transfers::RecentTransfers,
+	resume::ResumeState,
+	retention::Retention,
+	retry_list::RetryList,
+	rules::ReloadableRules,
+	select::Selection,
+	signing::OutputSigner,
+	sink::{BatchedSink, DeliveryError, TransferSink},
+	summary::RunSummary,
+	types::{BlockHash, BlockMarker, BlockNumber, BlockTransfers, XcmTransfer},
+	watch_lists::WatchList,
+	watchdog::{FailoverEndpoints, SharedClient},
+};
+use clap::{Command, error::ErrorKind};
+use std::{
+	collections::HashMap,
+	num::NonZeroUsize,
+	path::PathBuf,
+	sync::{Arc, Mutex},
+	time::Duration,
+};
+use subxt::{OnlineClient, PolkadotConfig, backend::legacy::LegacyRpcMethods};
+#[cfg(feature = "http-api")]
+use tokio::net::TcpListener;
+
+impl CliCommand {
+	// Follows the finalized blocks until the subscription is stopped, processing them with a pool
+	// of workers and delivering their transfers to the output and the sinks.
+	pub(crate) async fn subscribe(
+		&self,
+		cmd: &mut Command,
+		args: &Subscribe,
+		summary: &Arc<Mutex<RunSummary>>,
+	) -> Result<(), Error> {
+		let Connection { api, rpc, cross_checker, connection_settings } =
+			self.connect(cmd, summary).await?;
+		self.create_output_file(cmd);
+		let Subscribe {
+			cache_blocks,
+			#[cfg(feature = "http-api")]
+			api_address,
+			#[cfg(feature = "http-api")]
+			flow_metrics,
+			#[cfg(feature = "http-api")]
+			dot_prices,
+			#[cfg(feature = "http-api")]
+			transfer_totals,
+			#[cfg(feature = "mqtt")]
+			mqtt_broker,
+			#[cfg(feature = "mqtt")]
+			mqtt_topic,
+			#[cfg(feature = "mqtt")]
+			mqtt_qos,
+			#[cfg(feature = "amqp")]
+			amqp_url,
+			#[cfg(feature = "amqp")]
+			amqp_exchange,
+			#[cfg(feature = "amqp")]
+			amqp_routing_key,
+			#[cfg(feature = "bigquery")]
+			bigquery_table,
+			#[cfg(feature = "bigquery")]
+			bigquery_access_token,
+			#[cfg(feature = "bigquery")]
+			bigquery_token_command,
+			#[cfg(feature = "bigquery")]
+			bigquery_batch_size,
+			#[cfg(feature = "bigquery")]
+			bigquery_flush_interval,
+			#[cfg(feature = "email")]
+			smtp_url,
+			#[cfg(feature = "email")]
+			email_from,
+			#[cfg(feature = "email")]
+			email_to,
+			#[cfg(feature = "email")]
+			email_subject_template,
+			#[cfg(feature = "email")]
+			email_body_template,
+			#[cfg(feature = "email")]
+			alert_large_transfer,
+			#[cfg(feature = "email")]
+			alert_account,
+			#[cfg(feature = "email")]
+			alert_failed,
+			round_trip_window,
+			workers,
+			prefetch_blocks,
+			block_timeout,
+			block_retries,
+			dead_letters,
+			pending_timeout,
+			anomaly_multiple,
+			rules,
+			congestion_metrics,
+			xcm_version_events,
+			empty_block_markers,
+			resume,
+			batch_size,
+			batch_interval,
+			signing_key,
+			hash_chain,
+			retain_blocks,
+			retain_days,
+			prune_interval,
+			metadata_snapshot,
+			verify_finality_url,
+			finality_stall_timeout,
+			failover_urls,
+			follow_ethereum_bridge,
+			follow_kusama_bridge,
+			bridge_hub_url,
+			kusama_bridge_hub_url,
+			kusama_asset_hub_url,
+			follow_parachains,
+		} = args;
+		if (self.format != OutputFormat::Json || self.template.is_some()) &&
+			(*congestion_metrics ||
+				*xcm_version_events ||
+				*empty_block_markers ||
+				*follow_ethereum_bridge ||
+				*follow_kusama_bridge)
+		{
+			cmd.error(
+				ErrorKind::ArgumentConflict,
+				"The congestion metrics, the XCM version events, the empty block markers and the bridge followers only support the JSON output",
+			)
+			.exit()
+		}
+		let signer = signing_key.as_ref().map(|path| {
+			if self.format != OutputFormat::Json || self.template.is_some() {
+				cmd.error(
+					ErrorKind::ArgumentConflict,
+					"Signing the output requires the JSON output",
+				)
+				.exit()
+			}
+			match OutputSigner::from_file(path) {
+				Ok(signer) => signer,
+				Err(e) => cmd.error(ErrorKind::InvalidValue, e).exit(),
+			}
+		});
+		let resume_state = match (resume, &self.output_file) {
+			(true, Some(path)) => match ResumeState::load(path) {
+				Ok(resume_state) => Some(resume_state),
+				Err(e) => cmd.error(ErrorKind::InvalidValue, e).exit(),
+			},
+			(true, None) => cmd
+				.error(ErrorKind::MissingRequiredArgument, "Resuming requires an output file")
+				.exit(),
+			(false, _) => None,
+		};
+		let hash_chain = match (hash_chain, &self.output_file, &signer) {
+			(true, Some(path), Some(signer)) => match HashChain::new(path, *resume, signer.clone())
+			{
+				Ok(hash_chain) => Some(hash_chain),
+				Err(e) => cmd.error(ErrorKind::Io, e).exit(),
+			},
+			(true, None, _) => cmd
+				.error(ErrorKind::MissingRequiredArgument, "The hash chain requires an output file")
+				.exit(),
+			(true, _, None) => cmd
+				.error(ErrorKind::MissingRequiredArgument, "The hash chain requires a signing key")
+				.exit(),
+			(false, ..) => None,
+		};
+		let batch_interval = Duration::from_secs(*batch_interval);
+		let output_batch =
+			batch_size.map(|batch_size| OutputBatch::new(batch_size.get(), batch_interval));
+		let output_sink =
+			OutputSink::new(self.output_file.clone(), output_batch, signer, hash_chain);
+		let retention = retain_blocks
+			.map(|blocks| Retention::Blocks(blocks.get()))
+			.or(retain_days.map(|days| Retention::Days(days.get().into())));
+		match (retention, &self.archive_raw) {
+			(Some(retention), Some(path)) => {
+				let path = path.clone();
+				let prune_interval = Duration::from_secs(*prune_interval);
+				let summary = summary.clone();
+				tokio::spawn(async move {
+					let mut interval = tokio::time::interval(prune_interval);
+					loop {
+						interval.tick().await;
+						// Rewriting the archive blocks, holding the lock of its writes, so
+						// it's kept off the runtime threads
+						let path = path.clone();
+						let result = tokio::task::spawn_blocking(move || {
+							crate::retention::prune_archive(&path, retention)
+						})
+						.await
+						.unwrap_or_else(|e| Err(e.to_string()));
+						match result {
+							Ok(0) => (),
+							Ok(pruned) =>
+								eprintln!("Pruned {} blocks from the raw blocks archive", pruned),
+							Err(e) =>
+								if let Ok(mut summary) = summary.lock() {
+									summary.warn(format!(
+										"Failed to prune the raw blocks archive: {}",
+										e
+									));
+								},
+						}
+					}
+				});
+			},
+			(Some(_), None) => cmd
+				.error(
+					ErrorKind::MissingRequiredArgument,
+					"The retention requires a raw blocks archive",
+				)
+				.exit(),
+			(None, _) => (),
+		}
+		// The metadata changed while the indexer wasn't running isn't reused
+		if let Some(path) = metadata_snapshot {
+			match MetadataSnapshot::load(path) {
+				Ok(snapshot) =>
+					crate::metadata_snapshot::set_metadata_cache(path.clone(), snapshot),
+				Err(e) => cmd.error(ErrorKind::InvalidValue, e).exit(),
+			}
+			let latest_block = api.blocks().at_latest().await?.number();
+			match crate::metadata_snapshot::catch_up(&api, &rpc, latest_block).await {
+				Ok(Some(0)) => (),
+				Ok(Some(updated)) => eprintln!(
+					"Updated the metadata of {} assets of the snapshot, changed since it was written",
+					updated
+				),
+				Ok(None) => eprintln!(
+					"The metadata snapshot is too old to be updated, the metadata will be read again"
+				),
+				Err(e) => cmd
+					.error(ErrorKind::Io, format!("Failed to check the metadata snapshot: {}", e))
+					.exit(),
+			}
+			let summary = summary.clone();
+			tokio::spawn(async move {
+				let mut interval =
+					tokio::time::interval(crate::metadata_snapshot::SNAPSHOT_INTERVAL);
+				// The first tick completes immediately
+				interval.tick().await;
+				loop {
+					interval.tick().await;
+					if let (Err(e), Ok(mut summary)) =
+						(crate::metadata_snapshot::save_snapshot(), summary.lock())
+					{
+						summary.warn(e);
+					}
+				}
+			});
+		}
+		let resumed = resume_state.as_ref().and_then(ResumeState::last_block).is_some();
+		if self.format == OutputFormat::Compliance && !resumed {
+			append_text(&output_sink, crate::compliance::HEADER, 0);
+		}
+		if self.format == OutputFormat::Table && !resumed {
+			append_text(&output_sink, &crate::table::header(), 0);
+		}
+		let resume_state = resume_state.map(|state| Arc::new(Mutex::new(state)));
+		if batch_size.is_some() {
+			// Partial batches are written once the interval elapses, also without new
+			// records
+			let output_sink = output_sink.clone();
+			tokio::spawn(async move {
+				let mut interval = tokio::time::interval(batch_interval);
+				loop {
+					interval.tick().await;
+					output_sink.flush();
+				}
+			});
+		}
+		// The sinks are batched like the output
+		let sink_batch = batch_size.map(|batch_size| (batch_size.get(), batch_interval));
+		let recent_transfers =
+			cache_blocks.map(|capacity| Arc::new(Mutex::new(RecentTransfers::new(capacity))));
+		let round_trip_detector =
+			round_trip_window.map(|window| Arc::new(Mutex::new(RoundTripDetector::new(window))));
+		#[allow(unused_mut)]
+		let mut extra_rules = vec![];
+		#[cfg(feature = "mqtt")]
+		let mqtt_sink = mqtt_broker.clone().map(|broker| {
+			BatchedSink::new(MqttSink::new(broker, mqtt_topic.clone(), *mqtt_qos), sink_batch)
+		});
+		#[cfg(feature = "amqp")]
+		let amqp_sink = match amqp_url {
+			Some(url) =>
+				match AmqpSink::connect(url, amqp_exchange.clone(), amqp_routing_key.clone()).await
+				{
+					Ok(amqp_sink) => Some(BatchedSink::new(amqp_sink, sink_batch)),
+					Err(e) => cmd
+						.error(
+							ErrorKind::Io,
+							format!("Failed to connect to the AMQP broker: {}", e),
+						)
+						.exit(),
+				},
+			None => None,
+		};
+		#[cfg(feature = "bigquery")]
+		let bigquery_sink = match bigquery_table {
+			Some(table) => {
+				let (Some((project, dataset, table)), Some(token_source)) = (
+					table.split_once('.').and_then(|(project, table)| {
+						table.split_once('.').map(|(dataset, table)| (project, dataset, table))
+					}),
+					bigquery_access_token
+						.clone()
+						.map(AccessTokenSource::Token)
+						.or_else(|| bigquery_token_command.clone().map(AccessTokenSource::Command)),
+				) else {
+					cmd.error(
+						ErrorKind::InvalidValue,
+						"The BigQuery sink needs a <PROJECT>.<DATASET>.<TABLE> table and an access token or a token command",
+					)
+					.exit()
+				};
+				match BigQuerySink::connect(
+					token_source,
+					project.to_owned(),
+					dataset.to_owned(),
+					table.to_owned(),
+				)
+				.await
+				{
+					// BigQuery is always batched, as streaming rows one block at a time
+					// is slow and costly
+					Ok(bigquery_sink) => Some(BatchedSink::new(
+						bigquery_sink,
+						Some((*bigquery_batch_size, Duration::from_secs(*bigquery_flush_interval))),
+					)),
+					Err(e) => cmd.error(ErrorKind::Io, e.to_string()).exit(),
+				}
+			},
+			None => None,
+		};
+		#[cfg(feature = "email")]
+		let email_notifier = match (smtp_url, email_from) {
+			(Some(smtp_url), Some(email_from)) => {
+				extra_rules.extend(email_alert_rules(
+					alert_large_transfer,
+					alert_account,
+					*alert_failed,
+				));
+				match EmailNotifier::new(
+					smtp_url,
+					email_from.clone(),
+					email_to.clone(),
+					email_subject_template.clone(),
+					// Line breaks are escaped when given through the command line
+					email_body_template.replace("\\n", "\n"),
+				) {
+					Ok(email_notifier) => Some(Arc::new(email_notifier)),
+					Err(e) => cmd.error(ErrorKind::InvalidValue, e.to_string()).exit(),
+				}
+			},
+			_ => None,
+		};
+		let watch_rules = rules.is_some();
+		let rules = match ReloadableRules::load(rules.as_deref(), extra_rules) {
+			Ok(rules) => Arc::new(rules),
+			Err(e) => cmd.error(ErrorKind::InvalidValue, e).exit(),
+		};
+		if watch_rules {
+			let rules = rules.clone();
+			let summary = summary.clone();
+			tokio::spawn(async move {
+				let mut interval = tokio::time::interval(crate::rules::RULES_RELOAD_INTERVAL);
+				loop {
+					interval.tick().await;
+					match rules.reload() {
+						Ok(true) => eprintln!("Reloaded the rules"),
+						Ok(false) => (),
+						Err(e) =>
+							if let Ok(mut summary) = summary.lock() {
+								summary.warn(format!("Keeping the previous rules: {}", e));
+							},
+					}
+				}
+			});
+		}
+		let selection = self.select.clone().map(Arc::new);
+		let template = self.output_template().map(Arc::<str>::from);
+		let transfer_tracker =
+			pending_timeout.map(|timeout| Arc::new(Mutex::new(TransferTracker::new(timeout))));
+		let anomaly_detector = match anomaly_multiple {
+			Some(multiple) if *multiple <= 1.0 => cmd
+				.error(ErrorKind::InvalidValue, "The anomaly multiple must be greater than 1")
+				.exit(),
+			multiple =>
+				multiple.map(|multiple| Arc::new(Mutex::new(AnomalyDetector::new(multiple)))),
+		};
+		let bridge_follower = if *follow_ethereum_bridge {
+			let bridge_hub_api =
+				match OnlineClient::<PolkadotConfig>::from_url(bridge_hub_url).await {
+					Ok(bridge_hub_api) => bridge_hub_api,
+					Err(e) => cmd
+						.error(ErrorKind::Io, format!("Failed to connect to BridgeHub: {}", e))
+						.exit(),
+				};
+			let bridge_follower = Arc::new(Mutex::new(EthereumBridgeFollower::default()));
+			tokio::spawn(follow_ethereum_bridge_task(
+				bridge_hub_api,
+				bridge_follower.clone(),
+				transfer_tracker.clone(),
+				output_sink.clone(),
+				self.format,
+				template.clone(),
+			));
+			Some(bridge_follower)
+		} else {
+			None
+		};
+		let kusama_bridge_follower = if *follow_kusama_bridge {
+			let kusama_bridge_follower = Arc::new(Mutex::new(KusamaBridgeFollower::default()));
+			for (leg, url) in KusamaBridgeLeg::ALL.into_iter().zip([
+				bridge_hub_url,
+				kusama_bridge_hub_url,
+				kusama_asset_hub_url,
+			]) {
+				let leg_api = match OnlineClient::<PolkadotConfig>::from_url(url).await {
+					Ok(leg_api) => leg_api,
+					Err(e) => cmd
+						.error(ErrorKind::Io, format!("Failed to connect to {}: {}", url, e))
+						.exit(),
+				};
+				tokio::spawn(follow_kusama_bridge_leg_task(
+					leg_api,
+					leg,
+					kusama_bridge_follower.clone(),
+					transfer_tracker.clone(),
+					output_sink.clone(),
+					self.format,
+					template.clone(),
+				));
+			}
+			Some(kusama_bridge_follower)
+		} else {
+			None
+		};
+		if let Some(transfer_tracker) = &transfer_tracker {
+			for endpoint in follow_parachains {
+				let parachain_api =
+					match OnlineClient::<PolkadotConfig>::from_url(&endpoint.url).await {
+						Ok(parachain_api) => parachain_api,
+						Err(e) => cmd
+							.error(
+								ErrorKind::Io,
+								format!("Failed to connect to {}: {}", endpoint.url, e),
+							)
+							.exit(),
+					};
+				tokio::spawn(follow_parachain_task(
+					parachain_api,
+					endpoint.para_id,
+					transfer_tracker.clone(),
+					output_sink.clone(),
+					self.format,
+					template.clone(),
+				));
+			}
+		}
+
+		#[cfg(feature = "http-api")]
+		let flow_metrics = match (*flow_metrics, dot_prices) {
+			(false, _) => None,
+			(true, Some(path)) => match FlowMetrics::from_prices_file(path) {
+				Ok(flow_metrics) => Some(Arc::new(Mutex::new(flow_metrics))),
+				Err(e) => cmd.error(ErrorKind::Io, e).exit(),
+			},
+			(true, None) => Some(Arc::new(Mutex::new(FlowMetrics::default()))),
+		};
+		#[cfg(feature = "http-api")]
+		let transfer_totals = transfer_totals.then(|| Arc::new(Mutex::new(TransferTotals::default())));
+
+		#[cfg(feature = "http-api")]
+		if let (Some(address), Some(recent_transfers)) = (api_address, &recent_transfers) {
+			let listener = match TcpListener::bind(address).await {
+				Ok(listener) => listener,
+				Err(e) => cmd
+					.error(ErrorKind::Io, format!("Failed to bind the API address: {}", e))
+					.exit(),
+			};
+			tokio::spawn(crate::api::serve(
+				listener,
+				recent_transfers.clone(),
+				flow_metrics.clone(),
+				transfer_totals.clone(),
+			));
+		}
+
+		let mut stream = if let Ok(stream) = api.blocks().subscribe_finalized().await {
+			stream
+		} else {
+			cmd.error(ErrorKind::Io, "Failed to subscribe to finalized blocks").exit()
+		};
+
+		// The workers follow the endpoint the watchdog fails over to
+		let shared_api = SharedClient::new(api.clone());
+		let prefetcher = prefetch_blocks.map(|_| Arc::new(Prefetcher::new(shared_api.clone())));
+		let (sender, receiver) = tokio::sync::mpsc::channel(
+			workers.get() + prefetch_blocks.map_or(0, NonZeroUsize::get),
+		);
+		let receiver = Arc::new(tokio::sync::Mutex::new(receiver));
+		#[cfg(feature = "email")]
+		let stall_notifier = email_notifier.clone();
+		let block_worker = Arc::new(BlockWorker {
+			api: shared_api.clone(),
+			prefetcher: prefetcher.clone(),
+			output_sink: output_sink.clone(),
+			raw_archive: self.archive_raw.clone(),
+			cross_checker: cross_checker.clone(),
+			recent_transfers: recent_transfers.clone(),
+			#[cfg(feature = "http-api")]
+			flow_metrics,
+			#[cfg(feature = "http-api")]
+			transfer_totals,
+			round_trip_detector,
+			transfer_tracker,
+			anomaly_detector,
+			#[cfg(feature = "mqtt")]
+			mqtt_sink,
+			#[cfg(feature = "amqp")]
+			amqp_sink,
+			#[cfg(feature = "bigquery")]
+			bigquery_sink,
+			watch_list_sinks: Mutex::new(HashMap::new()),
+			sink_batch,
+			#[cfg(feature = "email")]
+			email_notifier,
+			bridge_follower,
+			kusama_bridge_follower,
+			group_assets: self.group_assets,
+			resolve_controllers: self.resolve_controllers,
+			include_raw_xcm: self.include_raw_xcm,
+			format: self.format,
+			layout: self.layout,
+			empty_block_markers: *empty_block_markers,
+			resume_state: resume_state.clone(),
+			rules,
+			selection,
+			summary: summary.clone(),
+			template,
+			block_timeout: block_timeout.map(Duration::from_secs),
+			retry_list: block_timeout.map(|_| Arc::new(Mutex::new(RetryList::new(*block_retries)))),
+			dead_letters: dead_letters.clone(),
+		});
+		// Partial batches of the sinks are delivered once their interval elapses, also
+		// without new transfers
+		let sink_flush_handle = {
+			let block_worker = block_worker.clone();
+			tokio::spawn(async move {
+				let mut interval = tokio::time::interval(Duration::from_secs(1));
+				loop {
+					interval.tick().await;
+					block_worker.flush_sinks(false).await;
+				}
+			})
+		};
+		let worker_handles = (0..workers.get())
+			.map(|_| {
+				let receiver = receiver.clone();
+				let block_worker = block_worker.clone();
+				tokio::spawn(async move {
+					let block_worker = &block_worker;
+					crate::work_queue::run_worker(
+						&receiver,
+						move |(block_hash, block_number)| async move {
+							if block_worker.process_in_time(block_hash, block_number).await {
+								block_worker.record_written(block_number);
+							} else {
+								block_worker.record_failed(block_number);
+							}
+						},
+					)
+					.await
+				})
+			})
+			.collect::<Vec<_>>();
+		// The blocks that timed out are retried in the background
+		let retry_handle = block_worker.retry_list.clone().map(|retry_list| {
+			let block_worker = block_worker.clone();
+			tokio::spawn(async move {
+				let mut interval = tokio::time::interval(crate::retry_list::RETRY_INTERVAL);
+				// The first tick completes immediately
+				interval.tick().await;
+				loop {
+					interval.tick().await;
+					let pending = retry_list
+						.lock()
+						.map(|retry_list| retry_list.pending())
+						.unwrap_or_default();
+					for (block_hash, block_number) in pending {
+						if block_worker.process_in_time(block_hash, block_number).await {
+							block_worker.record_written(block_number);
+						} else {
+							block_worker.record_failed(block_number);
+						}
+					}
+				}
+			})
+		});
+
+		// The blocks indexed are checked in the background against the reference endpoint
+		let finality_verifier = match verify_finality_url {
+			Some(url) => match FinalityVerifier::connect(url, &connection_settings).await {
+				Ok(verifier) => Some(Arc::new(verifier)),
+				Err(e) => cmd
+					.error(
+						ErrorKind::Io,
+						format!("Failed to connect to the finality endpoint: {}", e),
+					)
+					.exit(),
+			},
+			None => None,
+		};
+		let finality_handle = finality_verifier.clone().map(|verifier| {
+			let summary = summary.clone();
+			tokio::spawn(async move {
+				let mut interval = tokio::time::interval(crate::finality::FINALITY_CHECK_INTERVAL);
+				loop {
+					interval.tick().await;
+					warn_finality_discrepancies(&verifier, &summary).await;
+				}
+			})
+		});
+
+		// Stop gracefully when asked to, so the pending blocks and records are output
+		let shutdown = shutdown_signal();
+		tokio::pin!(shutdown);
+		// Without finalized blocks for too long, the watchdog warns about it and fails over
+		// to the next endpoint
+		let stall_window = finality_stall_timeout.map(|seconds| Duration::from_secs(seconds.get()));
+		let mut failover_endpoints = FailoverEndpoints::new(&self.rpc_url, failover_urls);
+		let mut failover_rpc = None;
+		let mut last_block_number = None;
+		loop {
+			let block = tokio::select! {
+				block = async {
+					match stall_window {
+						Some(stall_window) =>
+							tokio::time::timeout(stall_window, stream.next()).await.ok(),
+						None => Some(stream.next().await),
+					}
+				} => block,
+				_ = &mut shutdown => Some(None),
+			};
+			let Some(block) = block else {
+				let warning = crate::watchdog::stall_warning(
+					stall_window.unwrap_or_default(),
+					last_block_number,
+					failover_endpoints.current(),
+				);
+				crate::telemetry::record_finality_stall();
+				#[cfg(feature = "email")]
+				if let Some(email_notifier) = &stall_notifier {
+					if let Err(e) =
+						email_notifier.notify_incident("Stalled finality", &warning).await
+					{
+						eprintln!("Failed to notify the stalled finality: {}", e);
+					}
+				}
+				if let Ok(mut summary) = summary.lock() {
+					summary.warn(warning);
+				}
+				let url = failover_endpoints.advance().to_owned();
+				let failover = async {
+					let rpc_client =
+						crate::helpers::build_rpc_client(&url, &connection_settings).await?;
+					let failover_api =
+						OnlineClient::<PolkadotConfig>::from_rpc_client(rpc_client.clone()).await?;
+					let failover_stream = failover_api.blocks().subscribe_finalized().await?;
+					let rpc = LegacyRpcMethods::<PolkadotConfig>::new(rpc_client);
+					let properties =
+						crate::chain_spec::fetch_chain_properties(&failover_api, &rpc).await;
+					Ok::<_, Error>((failover_api, failover_stream, rpc, properties))
+				};
+				match failover.await {
+					Ok((failover_api, failover_stream, rpc, properties)) => {
+						eprintln!("Failed over to {}", url);
+						match properties {
+							Ok(properties) => self.apply_chain_properties(properties, summary),
+							Err(e) =>
+								if let Ok(mut summary) = summary.lock() {
+									summary.warn(format!(
+										"Failed to read the chain properties of {}, the previous ones are kept: {}",
+										url, e
+									));
+								},
+						}
+						shared_api.set(failover_api);
+						stream = failover_stream;
+						failover_rpc = Some(rpc);
+					},
+					Err(e) =>
+						if let Ok(mut summary) = summary.lock() {
+							summary.warn(format!("Failed to fail over to {}: {}", url, e));
+						},
+				}
+				continue;
+			};
+			let Some(Ok(block)) = block else {
+				break;
+			};
+			let block_hash = block.hash();
+			let block_number = block.number();
+			println!("Received block {}", block_hash);
+
+			// The endpoint failed over to may yield blocks already received, and doesn't
+			// yield the ones finalized while the previous one stalled
+			if last_block_number.is_some_and(|last_block_number| block_number <= last_block_number)
+			{
+				continue;
+			}
+			if let (Some(rpc), Some(last_block_number)) = (failover_rpc.take(), last_block_number) {
+				// The metadata changes of the missed blocks aren't observed
+				if metadata_snapshot.is_some() && block_number > last_block_number + 1 {
+					crate::metadata_snapshot::clear_cache();
+				}
+				for missed_number in last_block_number + 1..block_number {
+					let missed_hash = rpc
+						.chain_get_block_hash(Some(missed_number.into()))
+						.await
+						.map_err(|e| e.to_string())
+						.and_then(|hash| hash.ok_or_else(|| "unknown block".to_owned()));
+					// Missed blocks are queued too, so the resume state isn't moved past
+					// them until they're written or given up
+					block_worker.record_queued(missed_number);
+					match missed_hash {
+						Ok(missed_hash) => {
+							if sender.send((missed_hash, missed_number)).await.is_err() {
+								break;
+							}
+							if let Some(finality_verifier) = &finality_verifier {
+								finality_verifier.track(missed_number, missed_hash);
+							}
+						},
+						Err(e) => {
+							block_worker.record_failed(missed_number);
+							if let Ok(mut summary) = summary.lock() {
+								summary.warn(format!(
+									"Failed to get block {}, finalized while the finality stalled: {}",
+									missed_number, e
+								));
+							}
+						},
+					}
+				}
+			}
+			last_block_number = Some(block_number);
+
+			if resume_state.as_ref().is_some_and(|resume_state| {
+				resume_state.lock().is_ok_and(|state| state.is_written(block_number))
+			}) {
+				println!("Skipping block {}, already written", block_hash);
+				continue;
+			}
+
+			// The metadata changes are applied in the order the blocks are finalized,
+			// before the workers may read the metadata cache
+			if metadata_snapshot.is_some() {
+				match block.events().await {
+					Ok(events) => crate::metadata_snapshot::observe_block(block_number, &events),
+					Err(e) => {
+						crate::metadata_snapshot::clear_cache();
+						if let Ok(mut summary) = summary.lock() {
+							summary.warn(format!(
+								"Failed to read the metadata changes of block {}, the metadata cache was cleared: {}",
+								block_number, e
+							));
+						}
+					},
+				}
+			}
+
+			// Blocks queued behind busy workers mean the subscription fell behind, so
+			// they're downloaded while they wait
+			if let (Some(prefetcher), true) =
+				(&prefetcher, sender.capacity() < sender.max_capacity())
+			{
+				prefetcher.prefetch(block_hash);
+			}
+			// Waits for a worker to be available if too many blocks are queued
+			block_worker.record_queued(block_number);
+			if sender.send((block_hash, block_number)).await.is_err() {
+				break;
+			}
+			if let Some(finality_verifier) = &finality_verifier {
+				finality_verifier.track(block_number, block_hash);
+			}
+
+			// The block state is read by a separate task so it doesn't delay the next block
+			if *congestion_metrics || *xcm_version_events {
+				let output_sink = output_sink.clone();
+				let (congestion_metrics, xcm_version_events) =
+					(*congestion_metrics, *xcm_version_events);
+				tokio::spawn(async move {
+					if congestion_metrics {
+						match crate::congestion::get_channel_congestion_at_block(&block).await {
+							Ok(channels) if !channels.is_empty() =>
+								append_output(&output_sink, &channels, channels.len()),
+							Ok(_) => (),
+							Err(e) => eprintln!(
+								"Failed to read the XCMP channels at block {}: {}",
+								block_number, e
+							),
+						}
+					}
+					if xcm_version_events {
+						match crate::xcm_versions::get_xcm_version_events_at_block(&block).await {
+							Ok(events) if !events.is_empty() =>
+								append_output(&output_sink, &events, events.len()),
+							Ok(_) => (),
+							Err(e) => eprintln!(
+								"Failed to read the XCM version events at block {}: {}",
+								block_number, e
+							),
+						}
+					}
+				});
+			}
+		}
+
+		// Let the workers process the queued blocks before finishing
+		drop(sender);
+		for worker_handle in worker_handles {
+			let _ = worker_handle.await;
+		}
+		if let Some(retry_handle) = retry_handle {
+			retry_handle.abort();
+		}
+		if let (Some(finality_verifier), Some(finality_handle)) =
+			(&finality_verifier, finality_handle)
+		{
+			finality_handle.abort();
+			warn_finality_discrepancies(finality_verifier, summary).await;
+			if let Ok(mut summary) = summary.lock() {
+				for block_number in finality_verifier.pending() {
+					summary.warn(format!(
+						"Block {} wasn't finalized by the finality endpoint before the subscription ended",
+						block_number
+					));
+				}
+			}
+		}
+		if let (Some(Ok(retry_list)), Ok(mut summary)) =
+			(block_worker.retry_list.as_ref().map(|retry_list| retry_list.lock()), summary.lock())
+		{
+			for (_, block_number) in retry_list.pending() {
+				summary.warn(format!(
+					"Block {} timed out and wasn't retried before the subscription ended",
+					block_number
+				));
+			}
+		}
+		output_sink.flush();
+		if let (Err(e), Ok(mut summary)) =
+			(crate::metadata_snapshot::save_snapshot(), summary.lock())
+		{
+			summary.warn(e);
+		}
+
+		// Deliver the transfers still buffered by the sinks once the subscription ends
+		sink_flush_handle.abort();
+		block_worker.flush_sinks(true).await;
+		Ok(())
+	}
+}
+
+// Processes the finalized blocks of the subscription. A fixed amount of workers share it, so the
+// client, the sinks and the state are reused across blocks instead of being cloned per block.
+struct BlockWorker {
+	api: SharedClient,
+	prefetcher: Option<Arc<Prefetcher>>,
+	output_sink: OutputSink,
+	raw_archive: Option<PathBuf>,
+	cross_checker: Option<Arc<CrossChecker>>,
+	recent_transfers: Option<Arc<Mutex<RecentTransfers>>>,
+	#[cfg(feature = "http-api")]
+	flow_metrics: Option<Arc<Mutex<FlowMetrics>>>,
+	#[cfg(feature = "http-api")]
+	transfer_totals: Option<Arc<Mutex<TransferTotals>>>,
+	round_trip_detector: Option<Arc<Mutex<RoundTripDetector>>>,
+	transfer_tracker: Option<Arc<Mutex<TransferTracker>>>,
+	anomaly_detector: Option<Arc<Mutex<AnomalyDetector>>>,
+	#[cfg(feature = "mqtt")]
+	mqtt_sink: Option<BatchedSink<MqttSink>>,
+	#[cfg(feature = "amqp")]
+	amqp_sink: Option<BatchedSink<AmqpSink>>,
+	#[cfg(feature = "bigquery")]
+	bigquery_sink: Option<BatchedSink<BigQuerySink>>,
+	// The sinks of the watch lists by name, created as the lists show up in the rules
+	watch_list_sinks: Mutex<HashMap<String, Arc<BatchedSink<WatchList>>>>,
+	sink_batch: Option<(usize, Duration)>,
+	#[cfg(feature = "email")]
+	email_notifier: Option<Arc<EmailNotifier>>,
+	bridge_follower: Option<Arc<Mutex<EthereumBridgeFollower>>>,
+	kusama_bridge_follower: Option<Arc<Mutex<KusamaBridgeFollower>>>,
+	group_assets: bool,
+	resolve_controllers: bool,
+	include_raw_xcm: bool,
+	format: OutputFormat,
+	layout: OutputLayout,
+	empty_block_markers: bool,
+	resume_state: Option<Arc<Mutex<ResumeState>>>,
+	rules: Arc<ReloadableRules>,
+	selection: Option<Arc<Selection>>,
+	summary: Arc<Mutex<RunSummary>>,
+	template: Option<Arc<str>>,
+	block_timeout: Option<Duration>,
+	retry_list: Option<Arc<Mutex<RetryList>>>,
+	dead_letters: Option<PathBuf>,
+}
+
+impl BlockWorker {
+	// Records a block as queued to the workers, so the resume state isn't moved past it until it's
+	// written, and the round trips are detected in the order the blocks were queued.
+	fn record_queued(&self, block_number: BlockNumber) {
+		if let Some(Ok(mut resume_state)) = self.resume_state.as_ref().map(|state| state.lock()) {
+			resume_state.queue(block_number);
+		}
+		if let Some(Ok(mut round_trip_detector)) =
+			self.round_trip_detector.as_ref().map(|detector| detector.lock())
+		{
+			round_trip_detector.expect(block_number);
+		}
+	}
+
+	// Records a block that couldn't be processed, so the round trips of the blocks queued after it
+	// aren't held back. Unless it's set aside to be retried, the block is given up, so it doesn't
+	// hold the resume state back either.
+	fn record_failed(&self, block_number: BlockNumber) {
+		if let Some(Ok(mut round_trip_detector)) =
+			self.round_trip_detector.as_ref().map(|detector| detector.lock())
+		{
+			round_trip_detector.release(block_number);
+		}
+		let retried = self.retry_list.as_ref().is_some_and(|retry_list| {
+			retry_list.lock().is_ok_and(|list| list.contains(block_number))
+		});
+		if retried {
+			return;
+		}
+		if let Some(Ok(mut resume_state)) = self.resume_state.as_ref().map(|state| state.lock()) {
+			let result = resume_state.give_up(block_number);
+			if let (Err(e), Ok(mut summary)) = (result, self.summary.lock()) {
+				summary.warn(e);
+			}
+		}
+	}
+
+	// Records a block as written, so a resumed subscription skips it.
+	fn record_written(&self, block_number: BlockNumber) {
+		if let Some(Ok(mut resume_state)) = self.resume_state.as_ref().map(|state| state.lock()) {
+			let result = resume_state.record(block_number);
+			if let (Err(e), Ok(mut summary)) = (result, self.summary.lock()) {
+				summary.warn(e);
+			}
+		}
+	}
+
+	// Processes a block, setting it aside to be retried if it takes longer than the block timeout.
+	// Returns whether the block was processed and its output written.
+	async fn process_in_time(&self, block_hash: BlockHash, block_number: BlockNumber) -> bool {
+		let Some(block_timeout) = self.block_timeout else {
+			return self.process(block_hash, block_number).await;
+		};
+		let result =
+			tokio::time::timeout(block_timeout, self.process(block_hash, block_number)).await;
+		let processed = result.is_ok();
+		if let (Some(Ok(mut retry_list)), Ok(mut summary)) =
+			(self.retry_list.as_ref().map(|retry_list| retry_list.lock()), self.summary.lock())
+		{
+			if processed {
+				retry_list.record_processed(block_number);
+			} else if retry_list.record_timeout(block_hash, block_number) {
+				summary.warn(format!(
+					"Block {} timed out after {}s, it'll be retried later",
+					block_number,
+					block_timeout.as_secs()
+				));
+			} else {
+				let error = format!(
+					"Block {} timed out after {}s too many times, giving up on it",
+					block_number,
+					block_timeout.as_secs()
+				);
+				summary.warn(error.clone());
+				drop(summary);
+				self.dead_letter(block_hash, block_number, FailureStage::Timeout, error);
+			}
+		}
+		result.unwrap_or(false)
+	}
+
+	// Records a failed block in the dead-letter file, if any.
+	fn dead_letter(
+		&self,
+		block_hash: BlockHash,
+		block_number: BlockNumber,
+		stage: FailureStage,
+		error: String,
+	) {
+		let Some(path) = &self.dead_letters else {
+			return;
+		};
+		let dead_letter = DeadLetter::new(block_hash, block_number, stage, error);
+		if let (Err(e), Ok(mut summary)) =
+			(crate::dead_letters::append_dead_letter(path, &dead_letter), self.summary.lock())
+		{
+			summary.warn(e);
+		}
+	}
+
+	// Delivers the transfers of a block to a sink, which may buffer them until its batch is due.
+	async fn deliver<S: TransferSink>(
+		&self,
+		sink: &BatchedSink<S>,
+		block_hash: BlockHash,
+		block_number: BlockNumber,
+		transfers: &[XcmTransfer],
+	) {
+		let result = sink.deliver(block_hash, block_number, transfers).await;
+		self.record_delivery(sink.name(), result);
+	}
+
+	// Delivers the transfers buffered by a sink, all of them or only if its batch is due.
+	async fn flush_sink<S: TransferSink>(&self, sink: &BatchedSink<S>, all: bool) {
+		let result = if all { sink.flush().await } else { sink.flush_due().await };
+		self.record_delivery(sink.name(), result);
+	}
+
+	// Delivers the transfers buffered by every sink, all of them when shutting down or otherwise
+	// those of the batches due.
+	async fn flush_sinks(&self, all: bool) {
+		#[cfg(feature = "mqtt")]
+		if let Some(mqtt_sink) = &self.mqtt_sink {
+			self.flush_sink(mqtt_sink, all).await;
+		}
+		#[cfg(feature = "amqp")]
+		if let Some(amqp_sink) = &self.amqp_sink {
+			self.flush_sink(amqp_sink, all).await;
+		}
+		#[cfg(feature = "bigquery")]
+		if let Some(bigquery_sink) = &self.bigquery_sink {
+			self.flush_sink(bigquery_sink, all).await;
+		}
+		let watch_list_sinks = self
+			.watch_list_sinks
+			.lock()
+			.map(|sinks| sinks.values().cloned().collect::<Vec<_>>())
+			.unwrap_or_default();
+		for watch_list_sink in watch_list_sinks {
+			self.flush_sink(&watch_list_sink, all).await;
+		}
+	}
+
+	// The sink of a watch list, created the first time the list shows up in the rules. A list
+	// changed by reloading the rules gets a new sink, once the previous one delivered its batch.
+	async fn watch_list_sink(&self, watch_list: &WatchList) -> Arc<BatchedSink<WatchList>> {
+		let (watch_list_sink, previous_sink) = {
+			let mut watch_list_sinks =
+				self.watch_list_sinks.lock().unwrap_or_else(|e| e.into_inner());
+			match watch_list_sinks.get(&watch_list.name) {
+				Some(watch_list_sink) if watch_list_sink.sink() == watch_list =>
+					(watch_list_sink.clone(), None),
+				_ => {
+					let watch_list_sink =
+						Arc::new(BatchedSink::new(watch_list.clone(), self.sink_batch));
+					let previous_sink =
+						watch_list_sinks.insert(watch_list.name.clone(), watch_list_sink.clone());
+					(watch_list_sink, previous_sink)
+				},
+			}
+		};
+		if let Some(previous_sink) = previous_sink {
+			self.flush_sink(&previous_sink, true).await;
+		}
+		watch_list_sink
+	}
+
+	// Records every block of a failed delivery in the dead-letter file, as a batch may hold the
+	// transfers of several blocks.
+	fn record_delivery(&self, sink: String, result: Result<(), DeliveryError>) {
+		let Err(e) = result else {
+			return;
+		};
+		eprintln!("Failed to deliver the transfers to the {} sink: {}", sink, e.error);
+		for (block_hash, block_number) in e.blocks {
+			let stage = FailureStage::Delivery(sink.clone());
+			self.dead_letter(block_hash, block_number, stage, e.error.clone());
+		}
+	}
+
+	// Processes a block, returning whether its output was written.
+	async fn process(&self, block_hash: BlockHash, block_number: BlockNumber) -> bool {
+		let &Self {
+			api: ref shared_api,
+			ref prefetcher,
+			output_sink: _,
+			ref raw_archive,
+			ref cross_checker,
+			ref recent_transfers,
+			#[cfg(feature = "http-api")]
+			ref flow_metrics,
+			#[cfg(feature = "http-api")]
+			ref transfer_totals,
+			ref round_trip_detector,
+			ref transfer_tracker,
+			ref anomaly_detector,
+			#[cfg(feature = "mqtt")]
+				mqtt_sink: _,
+			#[cfg(feature = "amqp")]
+				amqp_sink: _,
+			#[cfg(feature = "bigquery")]
+				bigquery_sink: _,
+			watch_list_sinks: _,
+			sink_batch: _,
+			#[cfg(feature = "email")]
+				email_notifier: _,
+			ref bridge_follower,
+			ref kusama_bridge_follower,
+			group_assets: _,
+			resolve_controllers,
+			include_raw_xcm,
+			format: _,
+			layout: _,
+			empty_block_markers: _,
+			resume_state: _,
+			rules: _,
+			selection: _,
+			ref summary,
+			template: _,
+			block_timeout: _,
+			retry_list: _,
+			dead_letters: _,
+		} = self;
+		let api = &shared_api.get();
+
+		// Archived even if the transfers can't be parsed, so the block can be analysed later
+		if let Some(raw_archive) = raw_archive {
+			if let (Err(e), Ok(mut summary)) = (
+				crate::raw_blocks::archive_block(api, block_hash, block_number, raw_archive).await,
+				summary.lock(),
+			) {
+				summary.warn(e);
+			}
+		}
+
+		let transfers = match prefetcher {
+			Some(prefetcher) =>
+				crate::helpers::get_all_transfers_at_block_hash(prefetcher.as_ref(), block_hash)
+					.await,
+			None => crate::helpers::get_all_transfers_at_block_hash(api, block_hash).await,
+		};
+		let mut transfers = match (transfers, summary.lock()) {
+			(Ok(transfers), Ok(mut summary)) => {
+				summary.record_block(transfers.len());
+				transfers
+			},
+			(Err(e), Ok(mut summary)) => {
+				summary
+					.warn(format!("Failed to get the transfers at block {}: {}", block_number, e));
+				drop(summary);
+				self.dead_letter(block_hash, block_number, FailureStage::Parsing, e.to_string());
+				return false;
+			},
+			_ => return false,
+		};
+		cross_check_block(cross_checker.as_deref(), api, block_hash, &transfers, summary).await;
+
+		if resolve_controllers {
+			if let Err(e) =
+				crate::controllers::resolve_controllers(api, block_hash, &mut transfers).await
+			{
+				if let Ok(mut summary) = summary.lock() {
+					summary.warn(format!(
+						"Failed to resolve the controllers of the senders at block {}: {}",
+						block_number, e
+					));
+				}
+			}
+		}
+		if include_raw_xcm {
+			if let Err(e) = crate::raw_xcm::attach_raw_xcm(api, block_hash, &mut transfers).await {
+				if let Ok(mut summary) = summary.lock() {
+					summary.warn(format!(
+						"Failed to render the XCM programs at block {}: {}",
+						block_number, e
+					));
+				}
+			}
+		}
+
+		// The round trips closed by the blocks observed after this one, which were buffered until
+		// this block was observed, are output with their own blocks
+		let mut later_round_trips = vec![];
+		if let Some(Ok(mut round_trip_detector)) =
+			round_trip_detector.as_ref().map(|detector| detector.lock())
+		{
+			for (round_trip_hash, round_trip_number, round_trips) in
+				round_trip_detector.observe(block_hash, block_number, &transfers)
+			{
+				let round_trips = round_trips.into_iter().map(XcmTransfer::RoundTrip);
+				if round_trip_number == block_number {
+					transfers.extend(round_trips);
+				} else {
+					later_round_trips.push((
+						round_trip_hash,
+						round_trip_number,
+						round_trips.collect(),
+					));
+				}
+			}
+		}
+
+		if let Some(Ok(mut transfer_tracker)) =
+			transfer_tracker.as_ref().map(|tracker| tracker.lock())
+		{
+			let mut updates = transfer_tracker.track(&transfers);
+			updates.extend(transfer_tracker.expire(block_number));
+			transfers.extend(updates.into_iter().map(XcmTransfer::StatusUpdate));
+		}
+
+		if let Some(Ok(mut anomaly_detector)) =
+			anomaly_detector.as_ref().map(|detector| detector.lock())
+		{
+			let anomalies = anomaly_detector.observe(block_number, &transfers);
+			transfers.extend(anomalies.into_iter().map(XcmTransfer::Anomaly));
+		}
+
+		if let Some(Ok(mut recent_transfers)) =
+			recent_transfers.as_ref().map(|recent_transfers| recent_transfers.lock())
+		{
+			recent_transfers.insert(block_number, transfers.clone());
+		}
+
+		// The flows are valued as parsed, before the rules apply
+		#[cfg(feature = "http-api")]
+		if let (Some(flow_metrics), false) = (flow_metrics, transfers.is_empty()) {
+			match crate::helpers::get_block_timestamp(api, block_hash).await {
+				Ok(timestamp) =>
+					if let Ok(mut flow_metrics) = flow_metrics.lock() {
+						flow_metrics.observe(timestamp, &transfers);
+					},
+				Err(e) =>
+					if let Ok(mut summary) = summary.lock() {
+						summary.warn(format!(
+							"Failed to get the timestamp of block {} for the flow metrics: {}",
+							block_number, e
+						));
+					},
+			}
+		}
+
+		// Empty blocks are observed too, so the range of blocks the totals cover is known
+		#[cfg(feature = "http-api")]
+		if let Some(Ok(mut transfer_totals)) =
+			transfer_totals.as_ref().map(|transfer_totals| transfer_totals.lock())
+		{
+			transfer_totals.observe(block_number, &transfers);
+		}
+
+		if let Some(Ok(mut bridge_follower)) =
+			bridge_follower.as_ref().map(|follower| follower.lock())
+		{
+			bridge_follower.track(&transfers);
+		}
+
+		if let Some(Ok(mut kusama_bridge_follower)) =
+			kusama_bridge_follower.as_ref().map(|follower| follower.lock())
+		{
+			kusama_bridge_follower.track(&transfers);
+		}
+
+		let written = self.output(api, block_hash, block_number, transfers).await;
+		for (round_trip_hash, round_trip_number, round_trips) in later_round_trips {
+			if let Some(Ok(mut recent_transfers)) =
+				recent_transfers.as_ref().map(|recent_transfers| recent_transfers.lock())
+			{
+				recent_transfers.extend(round_trip_number, round_trips.clone());
+			}
+			self.output(api, round_trip_hash, round_trip_number, round_trips).await;
+		}
+		written
+	}
+
+	// Applies the rules to the records of a block, delivering them to the sinks and writing them
+	// to the output. Returns whether the output was written.
+	async fn output(
+		&self,
+		api: &OnlineClient<PolkadotConfig>,
+		block_hash: BlockHash,
+		block_number: BlockNumber,
+		transfers: Vec<XcmTransfer>,
+	) -> bool {
+		let &Self {
+			ref output_sink,
+			#[cfg(feature = "mqtt")]
+			ref mqtt_sink,
+			#[cfg(feature = "amqp")]
+			ref amqp_sink,
+			#[cfg(feature = "bigquery")]
+			ref bigquery_sink,
+			#[cfg(feature = "email")]
+			ref email_notifier,
+			group_assets,
+			format,
+			layout,
+			empty_block_markers,
+			ref rules,
+			ref selection,
+			ref template,
+			..
+		} = self;
+
+		if transfers.is_empty() {
+			return !empty_block_markers ||
+				append_empty_block(output_sink, layout, block_hash, block_number);
+		}
+
+		let rule_engine = rules.current();
+		let ruled_transfers = rule_engine.apply(transfers);
+		let transfers_for = |sink: &str| {
+			let transfers = ruled_transfers.for_sink(&rule_engine, sink).to_vec();
+			if group_assets {
+				crate::outgoing_parser::group_sent_transfers(transfers)
+			} else {
+				transfers
+			}
+		};
+
+		#[cfg(feature = "mqtt")]
+		if let Some(mqtt_sink) = mqtt_sink {
+			let transfers = transfers_for(crate::mqtt::MQTT_SINK);
+			self.deliver(mqtt_sink, block_hash, block_number, &transfers).await;
+		}
+
+		#[cfg(feature = "amqp")]
+		if let Some(amqp_sink) = amqp_sink {
+			let transfers = transfers_for(crate::amqp::AMQP_SINK);
+			self.deliver(amqp_sink, block_hash, block_number, &transfers).await;
+		}
+
+		#[cfg(feature = "bigquery")]
+		if let Some(bigquery_sink) = bigquery_sink {
+			let transfers = transfers_for(crate::bigquery::BIGQUERY_SINK);
+			self.deliver(bigquery_sink, block_hash, block_number, &transfers).await;
+		}
+
+		for watch_list in rule_engine.watch_lists() {
+			let transfers = ruled_transfers.for_watch_list(&watch_list.name).to_vec();
+			if transfers.is_empty() {
+				continue;
+			}
+			let transfers = if group_assets {
+				crate::outgoing_parser::group_sent_transfers(transfers)
+			} else {
+				transfers
+			};
+			let watch_list_sink = self.watch_list_sink(watch_list).await;
+			self.deliver(&watch_list_sink, block_hash, block_number, &transfers).await;
+		}
+
+		#[cfg(feature = "email")]
+		if let Some(email_notifier) = email_notifier {
+			let alerts = ruled_transfers.alerts_for(crate::alerts::EMAIL_NOTIFIER);
+			if let Err(e) = email_notifier.notify(alerts).await {
+				eprintln!("Failed to send the alert emails: {}", e);
+				let stage = FailureStage::Delivery(crate::alerts::EMAIL_NOTIFIER.to_owned());
+				self.dead_letter(block_hash, block_number, stage, e.to_string());
+			}
+		}
+
+		let mut transfers = transfers_for(crate::rules::OUTPUT_SINK);
+		if let Some(selection) = selection {
+			transfers.retain(|transfer| selection.matches(transfer));
+		}
+		if transfers.is_empty() {
+			return !empty_block_markers ||
+				append_empty_block(output_sink, layout, block_hash, block_number);
+		}
+
+		if format != OutputFormat::Json || template.is_some() {
+			// The compliance records carry the timestamp of their block
+			let timestamp = match format {
+				OutputFormat::Compliance =>
+					crate::helpers::get_block_timestamp(api, block_hash).await.ok(),
+				_ => None,
+			};
+			return append_transfers(
+				output_sink,
+				format,
+				template.as_deref(),
+				timestamp,
+				&transfers,
+			);
+		}
+
+		let records = transfers.len();
+		let json = match layout {
+			OutputLayout::Flat => serde_json::to_string_pretty(&transfers),
+			OutputLayout::PerBlock => serde_json::to_string_pretty(&BlockTransfers {
+				block_hash,
+				block_number,
+				timestamp: crate::helpers::get_block_timestamp(api, block_hash).await.ok(),
+				transfers,
+			}),
+		};
+		let Ok(json) = json else {
+			return false;
+		};
+
+		if output_sink.is_file() {
+			println!("xcm transfer found at block {}", block_hash);
+		}
+		append_text(output_sink, &json, records)
+	}
+}
+
+// Resolves once the process is asked to stop: on Ctrl+C, or on SIGTERM as sent by container
+// runtimes.
+async fn shutdown_signal() {
+	#[cfg(unix)]
+	if let Ok(mut terminate) =
+		tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+	{
+		tokio::select! {
+			_ = tokio::signal::ctrl_c() => (),
+			_ = terminate.recv() => (),
+		}
+		return;
+	}
+	let _ = tokio::signal::ctrl_c().await;
+}
+
+// Checks the blocks indexed whose height the finality endpoint finalized, warning about the ones
+// that aren't part of its chain.
+async fn warn_finality_discrepancies(
+	finality_verifier: &FinalityVerifier,
+	summary: &Arc<Mutex<RunSummary>>,
+) {
+	let result = finality_verifier.verify_pending().await;
+	if let Ok(mut summary) = summary.lock() {
+		match result {
+			Ok(discrepancies) => discrepancies
+				.into_iter()
+				.for_each(|discrepancy| summary.warn(discrepancy.to_string())),
+			Err(e) => summary.warn(format!("Failed to check the finality of the blocks: {}", e)),
+		}
+	}
+}
+
+// Appends the marker of a block without transfers, following the layout of the output.
+fn append_empty_block(
+	output_sink: &OutputSink,
+	layout: OutputLayout,
+	block_hash: BlockHash,
+	block_number: BlockNumber,
+) -> bool {
+	match layout {
+		OutputLayout::Flat =>
+			append_output(output_sink, &[BlockMarker::EmptyBlock { block_hash, block_number }], 1),
+		OutputLayout::PerBlock => append_output(
+			output_sink,
+			&BlockTransfers { block_hash, block_number, timestamp: None, transfers: vec![] },
+			1,
+		),
+	}
+}
+
+// Appends the transfers to the output file if provided, otherwise they're printed, encoded in the
+// given format or rendered from the template if any. The compliance records carry the timestamp of
+// the block of the transfers, if known. Returns whether the transfers were written.
+fn append_transfers(
+	output_sink: &OutputSink,
+	format: OutputFormat,
+	template: Option<&str>,
+	timestamp: Option<u64>,
+	transfers: &[XcmTransfer],
+) -> bool {
+	if let Some(template) = template {
+		return append_text(
+			output_sink,
+			&crate::template::render_transfers(template, transfers),
+			transfers.len(),
+		);
+	}
+	match format {
+		OutputFormat::Json => append_output(output_sink, transfers, transfers.len()),
+		#[cfg(feature = "proto")]
+		OutputFormat::Proto =>
+			output_sink.append(&crate::proto::encode_transfers(transfers), transfers.len()),
+		OutputFormat::Compliance => {
+			let rows = crate::compliance::render_rows(transfers, timestamp);
+			rows.is_empty() || append_text(output_sink, &rows.join("\n"), rows.len())
+		},
+		OutputFormat::Table => {
+			let rows = crate::table::render_rows(transfers);
+			rows.is_empty() || append_text(output_sink, &rows.join("\n"), rows.len())
+		},
+	}
+}
+
+// Outputs the status updates of the pending transfers resolved by a follower.
+fn append_status_updates(
+	output_sink: &OutputSink,
+	format: OutputFormat,
+	template: Option<&str>,
+	updates: Vec<TransferStatusUpdate>,
+) {
+	if !updates.is_empty() {
+		append_transfers(
+			output_sink,
+			format,
+			template,
+			None,
+			&updates.into_iter().map(XcmTransfer::StatusUpdate).collect::<Vec<_>>(),
+		);
+	}
+}
+
+// Follows the finalized blocks of BridgeHub, outputting the status updates of the transfers to
+// Ethereum tracked by the follower.
+async fn follow_ethereum_bridge_task(
+	bridge_hub_api: OnlineClient<PolkadotConfig>,
+	bridge_follower: Arc<Mutex<EthereumBridgeFollower>>,
+	transfer_tracker: Option<Arc<Mutex<TransferTracker>>>,
+	output_sink: OutputSink,
+	format: OutputFormat,
+	template: Option<Arc<str>>,
+) {
+	let mut stream = match bridge_hub_api.blocks().subscribe_finalized().await {
+		Ok(stream) => stream,
+		Err(e) => {
+			eprintln!("Failed to subscribe to the finalized blocks of BridgeHub: {}", e);
+			return;
+		},
+	};
+
+	while let Some(Ok(block)) = stream.next().await {
+		let events = match crate::bridge::get_outbound_queue_events(&block).await {
+			Ok(events) => events,
+			Err(e) => {
+				eprintln!("Failed to read the BridgeHub block {}: {}", block.number(), e);
+				continue;
+			},
+		};
+		let updates = match bridge_follower.lock() {
+			Ok(mut bridge_follower) => bridge_follower.observe(block.number(), &events),
+			Err(_) => return,
+		};
+		if !updates.is_empty() {
+			append_output(&output_sink, &updates, updates.len());
+		}
+
+		// The commitment sent to Ethereum is the last step observable from Polkadot, so the
+		// transfers committed are delivered as far as the indexer can tell. Before that, they may
+		// fail or trap their assets in BridgeHub
+		if let Some(transfer_tracker) = &transfer_tracker {
+			let messages = match crate::bridge::get_processed_messages(&block).await {
+				Ok(messages) => messages,
+				Err(e) => {
+					eprintln!("Failed to read the BridgeHub block {}: {}", block.number(), e);
+					continue;
+				},
+			};
+			let mut status_updates = vec![];
+			match transfer_tracker.lock() {
+				Ok(mut transfer_tracker) => {
+					for message in &messages {
+						status_updates.extend(transfer_tracker.resolve_processed(
+							"BridgeHub",
+							None,
+							message,
+						));
+					}
+					for update in &updates {
+						if let BridgeStatus::Committed { .. } = update.status {
+							status_updates.extend(
+								transfer_tracker
+									.resolve(&update.message_id, TransferStatus::Delivered),
+							);
+						}
+					}
+				},
+				Err(_) => return,
+			}
+			append_status_updates(&output_sink, format, template.as_deref(), status_updates);
+		}
+	}
+}
+
+// Follows the finalized blocks of the chain observed by a leg of the Polkadot-Kusama bridge,
+// outputting the updates of the transfers to Kusama AssetHub tracked by the follower.
+async fn follow_kusama_bridge_leg_task(
+	leg_api: OnlineClient<PolkadotConfig>,
+	leg: KusamaBridgeLeg,
+	kusama_bridge_follower: Arc<Mutex<KusamaBridgeFollower>>,
+	transfer_tracker: Option<Arc<Mutex<TransferTracker>>>,
+	output_sink: OutputSink,
+	format: OutputFormat,
+	template: Option<Arc<str>>,
+) {
+	let mut stream = match leg_api.blocks().subscribe_finalized().await {
+		Ok(stream) => stream,
+		Err(e) => {
+			eprintln!("Failed to subscribe to the finalized blocks of the {:?} leg: {}", leg, e);
+			return;
+		},
+	};
+
+	while let Some(Ok(block)) = stream.next().await {
+		let messages = match crate::bridge::get_leg_messages(leg, &block).await {
+			Ok(messages) => messages,
+			Err(e) => {
+				eprintln!(
+					"Failed to read the block {} of the {:?} leg: {}",
+					block.number(),
+					leg,
+					e
+				);
+				continue;
+			},
+		};
+		let updates = match kusama_bridge_follower.lock() {
+			Ok(mut kusama_bridge_follower) =>
+				kusama_bridge_follower.observe(leg, block.number(), &messages),
+			Err(_) => return,
+		};
+		if !updates.is_empty() {
+			append_output(&output_sink, &updates, updates.len());
+		}
+
+		// The bridge legs are the evidence of the fate of the transfers to Kusama AssetHub
+		if let Some(Ok(mut transfer_tracker)) =
+			transfer_tracker.as_ref().map(|tracker| tracker.lock())
+		{
+			let destination = DestinationChain::KusamaParachain(KUSAMA_ASSET_HUB_PARA_ID);
+			let destination = (leg == KusamaBridgeLeg::KusamaAssetHub).then_some(&destination);
+			let status_updates = messages
+				.iter()
+				.filter_map(|message| {
+					transfer_tracker.resolve_processed(
+						&format!("{:?} leg", leg),
+						destination,
+						message,
+					)
+				})
+				.collect();
+			append_status_updates(&output_sink, format, template.as_deref(), status_updates);
+		}
+	}
+}
+
+// Follows the finalized blocks of a parachain, resolving the pending transfers to it from the
+// messages it processes, and those to further chains which fail or trap their assets in it.
+async fn follow_parachain_task(
+	parachain_api: OnlineClient<PolkadotConfig>,
+	para_id: u32,
+	transfer_tracker: Arc<Mutex<TransferTracker>>,
+	output_sink: OutputSink,
+	format: OutputFormat,
+	template: Option<Arc<str>>,
+) {
+	let mut stream = match parachain_api.blocks().subscribe_finalized().await {
+		Ok(stream) => stream,
+		Err(e) => {
+			eprintln!(
+				"Failed to subscribe to the finalized blocks of the parachain {}: {}",
+				para_id, e
+			);
+			return;
+		},
+	};
+
+	let destination = DestinationChain::PolkadotParachain(para_id);
+	let chain = format!("{:?}", destination);
+	while let Some(Ok(block)) = stream.next().await {
+		let messages = match crate::bridge::get_processed_messages(&block).await {
+			Ok(messages) => messages,
+			Err(e) => {
+				eprintln!(
+					"Failed to read the block {} of the parachain {}: {}",
+					block.number(),
+					para_id,
+					e
+				);
+				continue;
+			},
+		};
+		let status_updates = match transfer_tracker.lock() {
+			Ok(mut transfer_tracker) => messages
+				.iter()
+				.filter_map(|message| {
+					transfer_tracker.resolve_processed(&chain, Some(&destination), message)
+				})
+				.collect(),
+			Err(_) => return,
+		};
+		append_status_updates(&output_sink, format, template.as_deref(), status_updates);
+	}
+}
@@ -0,0 +1,85 @@
+use crate::Error;
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+// The exit codes of the CLI, so scripts can tell the outcome of a run apart. Invalid arguments and
+// other errors setting up the run exit with code 2, as reported by clap.
+pub(crate) const SUCCESS: u8 = 0;
+pub(crate) const FAILURE: u8 = 1;
+pub(crate) const NO_TRANSFERS_FOUND: u8 = 3;
+pub(crate) const COMPLETED_WITH_WARNINGS: u8 = 4;
+pub(crate) const RPC_FAILURE: u8 = 5;
+pub(crate) const WAIT_TIMED_OUT: u8 = 6;
+
+// What a run did, emitted as JSON once it finishes.
+#[derive(Debug, Default, Serialize, Clone, PartialEq)]
+pub(crate) struct RunSummary {
+	pub(crate) blocks_processed: u64,
+	pub(crate) transfers_found: u64,
+	// The blocks that were skipped or failed to be processed, without aborting the run
+	pub(crate) warnings: Vec<String>,
+	// The `polkadot_xcm` calls seen that the indexer couldn't parse, counted by call name
+	#[serde(skip_serializing_if = "BTreeMap::is_empty")]
+	pub(crate) unhandled_xcm_calls: BTreeMap<String, u64>,
+	pub(crate) error: Option<String>,
+	pub(crate) exit_code: u8,
+}
+
+impl RunSummary {
+	pub(crate) fn record_block(&mut self, transfers_found: usize) {
+		self.blocks_processed += 1;
+		self.transfers_found += transfers_found as u64;
+	}
+
+	pub(crate) fn warn(&mut self, warning: String) {
+		eprintln!("{}", warning);
+		self.warnings.push(warning);
+	}
+
+	// Completes the summary with the result of the run. Runs looking for transfers that don't find
+	// any exit with a dedicated code, as it usually points to a wrong block or range.
+	pub(crate) fn finish(&mut self, result: Result<(), Error>) {
+		self.exit_code = match &result {
+			Err(Error::Subxt(_) | Error::Connection(_) | Error::StatePruned { .. }) => RPC_FAILURE,
+			Err(Error::WaitTimedOut { .. }) => WAIT_TIMED_OUT,
+			Err(_) => FAILURE,
+			Ok(()) if !self.warnings.is_empty() => COMPLETED_WITH_WARNINGS,
+			Ok(()) if self.blocks_processed > 0 && self.transfers_found == 0 => NO_TRANSFERS_FOUND,
+			Ok(()) => SUCCESS,
+		};
+		self.error = result.err().map(|e| e.to_string());
+		self.unhandled_xcm_calls = crate::telemetry::unhandled_xcm_calls();
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn finish_sets_the_exit_code() {
+		let finish = |summary: &RunSummary, result| {
+			let mut summary = summary.clone();
+			summary.finish(result);
+			summary
+		};
+		let mut summary = RunSummary::default();
+		// Modes not looking for transfers succeed without finding any
+		assert_eq!(finish(&summary, Ok(())).exit_code, SUCCESS);
+		summary.record_block(0);
+		assert_eq!(finish(&summary, Ok(())).exit_code, NO_TRANSFERS_FOUND);
+		summary.record_block(2);
+		assert_eq!(finish(&summary, Ok(())).exit_code, SUCCESS);
+		let failed = finish(&summary, Err(Error::GeneratePayloadFailed));
+		assert_eq!(failed.exit_code, FAILURE);
+		assert_eq!(failed.error, Some(Error::GeneratePayloadFailed.to_string()));
+		let rpc_error = Error::from(subxt::error::Error::Other("Connection reset".to_owned()));
+		assert_eq!(finish(&summary, Err(rpc_error)).exit_code, RPC_FAILURE);
+		let timed_out = Error::WaitTimedOut { seconds: 60 };
+		assert_eq!(finish(&summary, Err(timed_out)).exit_code, WAIT_TIMED_OUT);
+		summary.warn("Skipping block 1".to_owned());
+		assert_eq!(finish(&summary, Ok(())).exit_code, COMPLETED_WITH_WARNINGS);
+		assert_eq!(summary.blocks_processed, 2);
+		assert_eq!(summary.transfers_found, 2);
+	}
+}
@@ -0,0 +1,188 @@
+use crate::{
+	Error,
+	asset_hub::runtime_types::staging_xcm::v4::{
+		junction::{Junction, NetworkId},
+		junctions::Junctions,
+		location::Location,
+	},
+	state_proofs::BlockStorage,
+	types::{AssetMetadataValues, BlockNumber},
+};
+use serde::Serialize;
+use std::str::FromStr;
+use subxt::{OnlineClient, PolkadotConfig, blocks::Block};
+
+// Wrapped Ether contract in Ethereum mainnet, bridged to AssetHub through Snowbridge.
+const WETH_CONTRACT: [u8; 20] = [
+	0xc0, 0x2a, 0xaa, 0x39, 0xb2, 0x23, 0xfe, 0x8d, 0x0a, 0x0e, 0x5c, 0x4f, 0x27, 0xea, 0xd9, 0x08,
+	0x3c, 0x75, 0x6c, 0xc2,
+];
+const ETHEREUM_MAINNET_CHAIN_ID: u64 = 1;
+
+// Assets minted in AssetHub as a result of a bridge transfer. Those are stored in
+// pallet_foreign_assets and their supply should match what's locked in the origin consensus.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum BridgedAsset {
+	Ksm,
+	KusamaParachainNative(u32),
+	Erc20 { chain_id: u64, contract: [u8; 20] },
+}
+
+// Bridged assets are given to the CLI as `KSM`, `WETH`, `kusama-parachain:<para_id>`,
+// `ethereum:<chain_id>:<0x prefixed contract address>` or as their location, eg
+// `parents=2/GlobalConsensus(Kusama)`.
+impl FromStr for BridgedAsset {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let invalid_asset = || format!("{} isn't a supported bridged asset", s);
+		if let Ok(location) = crate::locations::parse_location(s) {
+			return Self::from_location(&location).ok_or_else(invalid_asset);
+		}
+		match s.split(':').collect::<Vec<_>>().as_slice() {
+			["KSM"] => Ok(Self::Ksm),
+			["WETH"] =>
+				Ok(Self::Erc20 { chain_id: ETHEREUM_MAINNET_CHAIN_ID, contract: WETH_CONTRACT }),
+			["kusama-parachain", para_id] =>
+				para_id.parse().map(Self::KusamaParachainNative).map_err(|_| invalid_asset()),
+			["ethereum", chain_id, contract] => {
+				let chain_id = chain_id.parse().map_err(|_| invalid_asset())?;
+				let contract = contract
+					.strip_prefix("0x")
+					.and_then(|contract| hex::decode(contract).ok())
+					.and_then(|contract| contract.try_into().ok())
+					.ok_or_else(invalid_asset)?;
+				Ok(Self::Erc20 { chain_id, contract })
+			},
+			_ => Err(invalid_asset()),
+		}
+	}
+}
+
+impl BridgedAsset {
+	fn from_location(location: &Location) -> Option<Self> {
+		match (location.parents, &location.interior) {
+			(2, Junctions::X1([Junction::GlobalConsensus(NetworkId::Kusama)])) => Some(Self::Ksm),
+			(
+				2,
+				Junctions::X2(
+					[Junction::GlobalConsensus(NetworkId::Kusama), Junction::Parachain(para_id)],
+				),
+			) => Some(Self::KusamaParachainNative(*para_id)),
+			(
+				2,
+				Junctions::X2(
+					[
+						Junction::GlobalConsensus(NetworkId::Ethereum { chain_id }),
+						Junction::AccountKey20 { network: None, key },
+					],
+				),
+			) => Some(Self::Erc20 { chain_id: *chain_id, contract: *key }),
+			_ => None,
+		}
+	}
+
+	pub(crate) fn to_location(&self) -> Location {
+		match self {
+			Self::Ksm => Location {
+				parents: 2,
+				interior: Junctions::X1([Junction::GlobalConsensus(NetworkId::Kusama)]),
+			},
+			Self::KusamaParachainNative(para_id) => Location {
+				parents: 2,
+				interior: Junctions::X2([
+					Junction::GlobalConsensus(NetworkId::Kusama),
+					Junction::Parachain(*para_id),
+				]),
+			},
+			Self::Erc20 { chain_id, contract } => Location {
+				parents: 2,
+				interior: Junctions::X2([
+					Junction::GlobalConsensus(NetworkId::Ethereum { chain_id: *chain_id }),
+					Junction::AccountKey20 { network: None, key: *contract },
+				]),
+			},
+		}
+	}
+}
+
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub(crate) struct SupplySnapshot {
+	pub(crate) block_number: BlockNumber,
+	pub(crate) asset: String,
+	pub(crate) supply: f64,
+}
+
+pub(crate) async fn get_bridged_supply_at_block(
+	block: &Block<PolkadotConfig, OnlineClient<PolkadotConfig>>,
+	assets: &[BridgedAsset],
+) -> Result<Vec<SupplySnapshot>, Error> {
+	let storage = BlockStorage::at(block).await?;
+	let mut output = vec![];
+	for asset in assets {
+		let location = asset.to_location();
+		let asset_address = crate::asset_hub::storage().foreign_assets().asset(&location);
+		let supply = storage
+			.fetch(&asset_address)
+			.await?
+			.map(|details| details.supply)
+			.unwrap_or_default();
+		let AssetMetadataValues { asset_name, decimals } =
+			crate::helpers::extract_foreign_asset_metadata_values(&storage, &location).await?;
+		output.push(SupplySnapshot {
+			block_number: block.number(),
+			asset: asset_name,
+			supply: crate::amount::to_decimal_f64(supply, decimals),
+		});
+	}
+	Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::chain_spec::{Chain, ChainSpec};
+
+	#[test]
+	fn bridged_asset_from_str() {
+		assert_eq!("KSM".parse(), Ok(BridgedAsset::Ksm));
+		assert_eq!(
+			"WETH".parse(),
+			Ok(BridgedAsset::Erc20 { chain_id: 1, contract: WETH_CONTRACT })
+		);
+		assert_eq!(
+			"ethereum:1:0xc02aaa39b223fe8d0a0e5c4f27ead9083c756cc2".parse(),
+			Ok(BridgedAsset::Erc20 { chain_id: 1, contract: WETH_CONTRACT })
+		);
+		assert_eq!("kusama-parachain:2023".parse(), Ok(BridgedAsset::KusamaParachainNative(2023)));
+		assert_eq!("parents=2/GlobalConsensus(Kusama)".parse(), Ok(BridgedAsset::Ksm));
+		assert_eq!(
+			"parents=2/GlobalConsensus(Kusama)/Parachain(2023)".parse(),
+			Ok(BridgedAsset::KusamaParachainNative(2023))
+		);
+		assert_eq!(
+			"parents=2/GlobalConsensus(Ethereum(1))/AccountKey20(0xc02aaa39b223fe8d0a0e5c4f27ead9083c756cc2)"
+				.parse(),
+			Ok(BridgedAsset::Erc20 { chain_id: 1, contract: WETH_CONTRACT })
+		);
+		assert!("parents=1/Parachain(2023)".parse::<BridgedAsset>().is_err());
+		assert!("ethereum:1:0x1234".parse::<BridgedAsset>().is_err());
+		assert!("DOT".parse::<BridgedAsset>().is_err());
+	}
+
+	#[tokio::test]
+	async fn get_bridged_supply_at_block_test() {
+		let api = OnlineClient::<PolkadotConfig>::from_url(Chain::RPC_ENDPOINT).await.unwrap();
+		let block = api.blocks().at_latest().await.unwrap();
+
+		let snapshots =
+			get_bridged_supply_at_block(&block, &[BridgedAsset::Ksm, "WETH".parse().unwrap()])
+				.await
+				.unwrap();
+		assert_eq!(
+			snapshots.iter().map(|snapshot| snapshot.asset.as_str()).collect::<Vec<_>>(),
+			vec!["KSM", "Wrapped Ether"]
+		);
+		assert!(snapshots.iter().all(|snapshot| snapshot.supply > 0.0));
+	}
+}
@@ -0,0 +1,352 @@
+use crate::{
+	outgoing_parser::{ExecutionOutcome, XcmOutgoingAsset, XcmOutgoingTransferGroup},
+	types::{BlockNumber, TransferType, XcmTransfer},
+};
+use serde::Serialize;
+use std::sync::OnceLock;
+
+// The columns of the table and their width. The widths are fixed, so the rows output by a
+// subscription stay aligned with the header written when it starts. Longer values are truncated.
+const COLUMNS: [(&str, usize); 8] = [
+	("BLOCK", 10),
+	("DIRECTION", 9),
+	("CHAIN", 24),
+	("TYPE", 8),
+	("ASSET", 10),
+	("AMOUNT", 20),
+	("FROM", 13),
+	("TO", 13),
+];
+
+// The ANSI escape codes of the colors used in the table.
+const RED: &str = "\x1b[1;31m";
+const GREEN: &str = "\x1b[32m";
+const BLUE: &str = "\x1b[34m";
+const MAGENTA: &str = "\x1b[35m";
+const CYAN: &str = "\x1b[36m";
+const RESET: &str = "\x1b[0m";
+
+// How the amounts are written, as the table is read by non-engineering staff used to the
+// conventions of their locale.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq)]
+pub(crate) enum NumberLocale {
+	// 1234567.89
+	#[default]
+	Plain,
+	// 1,234,567.89
+	En,
+	// 1.234.567,89
+	De,
+	// 1 234 567,89
+	Fr,
+	// 1'234'567.89
+	Ch,
+}
+
+impl NumberLocale {
+	fn separators(self) -> (Option<char>, char) {
+		match self {
+			Self::Plain => (None, '.'),
+			Self::En => (Some(','), '.'),
+			Self::De => (Some('.'), ','),
+			Self::Fr => (Some(' '), ','),
+			Self::Ch => (Some('\''), '.'),
+		}
+	}
+
+	fn format(self, amount: f64) -> String {
+		let amount = amount.to_string();
+		let (thousands_separator, decimal_separator) = self.separators();
+		let (sign, amount) = match amount.strip_prefix('-') {
+			Some(amount) => ("-", amount),
+			None => ("", amount.as_str()),
+		};
+		let (integer, decimals) = match amount.split_once('.') {
+			Some((integer, decimals)) => (integer, Some(decimals)),
+			None => (amount, None),
+		};
+		let mut output = sign.to_owned();
+		for (index, digit) in integer.chars().enumerate() {
+			if let Some(separator) = thousands_separator {
+				if index > 0 && (integer.len() - index) % 3 == 0 {
+					output.push(separator);
+				}
+			}
+			output.push(digit);
+		}
+		if let Some(decimals) = decimals {
+			output.push(decimal_separator);
+			output.push_str(decimals);
+		}
+		output
+	}
+}
+
+// Whether the table is colored and how its amounts are written are decided once when the CLI
+// starts, so the functions writing the output don't need to carry them around.
+static COLORED: OnceLock<bool> = OnceLock::new();
+static NUMBER_LOCALE: OnceLock<NumberLocale> = OnceLock::new();
+
+pub(crate) fn set_colored(colored: bool) {
+	let _ = COLORED.set(colored);
+}
+
+pub(crate) fn set_number_locale(number_locale: NumberLocale) {
+	let _ = NUMBER_LOCALE.set(number_locale);
+}
+
+// Writes an amount following the number locale of the human-readable outputs.
+pub(crate) fn format_amount(amount: f64) -> String {
+	NUMBER_LOCALE.get().copied().unwrap_or_default().format(amount)
+}
+
+// The values shown for an asset transferred.
+struct Row<'a> {
+	block_number: BlockNumber,
+	incoming: bool,
+	chain: String,
+	transfer_type: &'a TransferType,
+	asset: &'a str,
+	amount: f64,
+	from: &'a str,
+	to: &'a str,
+	// Whether the transfer failed or its assets were trapped
+	failed: bool,
+}
+
+pub(crate) fn header() -> String {
+	render_cells(COLUMNS.map(|(name, _)| (name.to_owned(), None)), None, false)
+}
+
+// Renders a row per asset transferred. Records which aren't transfers, such as round trips or
+// status updates, are skipped.
+pub(crate) fn render_rows(transfers: &[XcmTransfer]) -> Vec<String> {
+	render_rows_colored(transfers, COLORED.get().copied().unwrap_or_default())
+}
+
+// The table of some transfers, with its header.
+pub(crate) fn render(transfers: &[XcmTransfer]) -> String {
+	std::iter::once(header())
+		.chain(render_rows(transfers))
+		.collect::<Vec<_>>()
+		.join("\n")
+}
+
+fn render_rows_colored(transfers: &[XcmTransfer], colored: bool) -> Vec<String> {
+	let mut rows = vec![];
+	for transfer in transfers {
+		let row = match transfer {
+			XcmTransfer::ReceivedTransfer(transfer) => Row {
+				block_number: transfer.block_number,
+				incoming: true,
+				chain: label(&transfer.origin_chain),
+				transfer_type: &transfer.transfer_type,
+				asset: &transfer.asset,
+				amount: transfer.amount,
+				from: "",
+				to: &transfer.beneficiary,
+				failed: transfer.trapped_assets_hash.is_some(),
+			},
+			XcmTransfer::SentTransfer(transfer) => Row {
+				block_number: transfer.block_number,
+				incoming: false,
+				chain: label(&transfer.destination_chain),
+				transfer_type: &transfer.transfer_type,
+				asset: &transfer.asset,
+				amount: transfer.amount,
+				from: &transfer.sender,
+				to: &transfer.beneficiary,
+				failed: failed(transfer.outcome.as_ref()),
+			},
+			XcmTransfer::SentTransferGroup(group) => {
+				rows.extend(
+					group
+						.assets
+						.iter()
+						.map(|asset| render_row(outgoing_asset_row(group, asset), colored)),
+				);
+				continue;
+			},
+			XcmTransfer::RoundTrip(_) |
+			XcmTransfer::StatusUpdate(_) |
+			XcmTransfer::Anomaly(_) |
+			XcmTransfer::FailedMessage(_) => continue,
+		};
+		rows.push(render_row(row, colored));
+	}
+	rows
+}
+
+fn failed(outcome: Option<&ExecutionOutcome>) -> bool {
+	matches!(outcome, Some(ExecutionOutcome::Incomplete { .. } | ExecutionOutcome::Error { .. }))
+}
+
+fn outgoing_asset_row<'a>(
+	group: &'a XcmOutgoingTransferGroup,
+	asset: &'a XcmOutgoingAsset,
+) -> Row<'a> {
+	Row {
+		block_number: group.block_number,
+		incoming: false,
+		chain: label(&group.destination_chain),
+		transfer_type: &asset.transfer_type,
+		asset: &asset.asset,
+		amount: asset.amount,
+		from: &group.sender,
+		to: &group.beneficiary,
+		failed: failed(group.outcome.as_ref()),
+	}
+}
+
+// Incoming and outgoing transfers, and teleports and reserve transfers, are told apart by their
+// color, while failed or trapped transfers are highlighted in red as a whole.
+fn render_row(row: Row, colored: bool) -> String {
+	let (direction, direction_color) = if row.incoming { ("in", GREEN) } else { ("out", BLUE) };
+	let (transfer_type, transfer_type_color) = match row.transfer_type {
+		TransferType::Teleport => ("teleport", MAGENTA),
+		TransferType::Reserve => ("reserve", CYAN),
+	};
+	render_cells(
+		[
+			(row.block_number.to_string(), None),
+			(direction.to_owned(), Some(direction_color)),
+			(row.chain, None),
+			(transfer_type.to_owned(), Some(transfer_type_color)),
+			(row.asset.to_owned(), None),
+			(format_amount(row.amount), None),
+			(shorten_account(row.from), None),
+			(shorten_account(row.to), None),
+		],
+		row.failed.then_some(RED),
+		colored,
+	)
+}
+
+// The colors wrap the padded cells, so they don't count towards their width.
+fn render_cells(
+	cells: [(String, Option<&str>); 8],
+	row_color: Option<&str>,
+	colored: bool,
+) -> String {
+	let line = cells
+		.iter()
+		.zip(COLUMNS)
+		.map(|((cell, color), (name, width))| {
+			let cell = truncate(cell, width);
+			// Amounts are right aligned, so their digits line up
+			let cell = if name == "AMOUNT" {
+				format!("{:>width$}", cell, width = width)
+			} else {
+				format!("{:<width$}", cell, width = width)
+			};
+			match color {
+				Some(color) if colored && row_color.is_none() =>
+					format!("{}{}{}", color, cell, RESET),
+				_ => cell,
+			}
+		})
+		.collect::<Vec<_>>()
+		.join("  ");
+	let line = line.trim_end();
+	match row_color {
+		Some(row_color) if colored => format!("{}{}{}", row_color, line, RESET),
+		_ => line.to_owned(),
+	}
+}
+
+fn truncate(field: &str, width: usize) -> String {
+	if field.chars().count() <= width {
+		return field.to_owned();
+	}
+	field.chars().take(width - 1).chain(std::iter::once('…')).collect()
+}
+
+// Accounts are shortened to their first and last characters, which is enough to tell them apart
+// at a glance.
+fn shorten_account(account: &str) -> String {
+	let chars = account.chars().collect::<Vec<_>>();
+	if chars.len() <= 13 {
+		return account.to_owned();
+	}
+	format!(
+		"{}…{}",
+		chars[..6].iter().collect::<String>(),
+		chars[chars.len() - 6..].iter().collect::<String>()
+	)
+}
+
+// Enums without data are rendered as their name and variants with a value as `Name(value)`, eg
+// `PolkadotParachain(2034)`.
+pub(crate) fn label<T: Serialize>(value: &T) -> String {
+	match serde_json::to_value(value) {
+		Ok(serde_json::Value::String(name)) => name,
+		Ok(serde_json::Value::Object(object)) if object.len() == 1 => object
+			.iter()
+			.map(|(name, value)| match value {
+				serde_json::Value::String(value) => format!("{}({})", name, value),
+				value => format!("{}({})", name, value),
+			})
+			.collect(),
+		Ok(value) => value.to_string(),
+		Err(_) => String::new(),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::incoming_parser::{OriginChain, XcmIncomingTransfer};
+
+	#[test]
+	fn transfers_are_rendered_as_aligned_rows() {
+		let mut transfer = XcmIncomingTransfer {
+			block_number: 9_000_000,
+			origin_chain: OriginChain::PolkadotParachain(2034),
+			message_id: None,
+			beneficiary: "13KsaHFcQKSTd4m73Ub9yVwM1JGCZvipMyTZonHEXEceFYwS".to_owned(),
+			beneficiary_public_key: String::new(),
+			asset: "USDT".to_owned(),
+			asset_id: None,
+			amount: 12.5,
+			exact_amount: None,
+			transfer_type: TransferType::Reserve,
+			fee_swap: None,
+			entities: vec![],
+			dust_lost: None,
+			trapped_assets_hash: None,
+			new_account: false,
+			xc20_address: None,
+		};
+		let table = render(&[XcmTransfer::ReceivedTransfer(transfer.clone())]);
+		let lines = table.lines().collect::<Vec<_>>();
+		assert_eq!(lines.len(), 2);
+		assert!(
+			lines[1].starts_with("9000000     in         PolkadotParachain(2034)   reserve   USDT")
+		);
+		assert_eq!(lines[1].find("USDT"), lines[0].find("ASSET"));
+		// Amounts are right aligned
+		assert_eq!(
+			lines[1].find("12.5").map(|start| start + 4),
+			lines[0].find("AMOUNT").map(|start| start + 6)
+		);
+		assert!(lines[1].ends_with("13KsaH…ceFYwS"));
+
+		let rows = render_rows_colored(&[XcmTransfer::ReceivedTransfer(transfer.clone())], true);
+		assert!(rows[0].starts_with(&format!("9000000     {}in       {}", GREEN, RESET)));
+
+		// Trapped transfers are highlighted as a whole
+		transfer.trapped_assets_hash = Some("0x01".to_owned());
+		let rows = render_rows_colored(&[XcmTransfer::ReceivedTransfer(transfer)], true);
+		assert_eq!(rows[0], format!("{}{}{}", RED, lines[1], RESET));
+	}
+
+	#[test]
+	fn amounts_follow_the_number_locale() {
+		assert_eq!(NumberLocale::Plain.format(1_234_567.89), "1234567.89");
+		assert_eq!(NumberLocale::En.format(1_234_567.89), "1,234,567.89");
+		assert_eq!(NumberLocale::De.format(1_234_567.89), "1.234.567,89");
+		assert_eq!(NumberLocale::Fr.format(234_567.0), "234 567");
+		assert_eq!(NumberLocale::Ch.format(-1_000.5), "-1'000.5");
+		assert_eq!(NumberLocale::En.format(0.25), "0.25");
+	}
+}
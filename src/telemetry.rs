@@ -0,0 +1,72 @@
+use crate::types::BlockNumber;
+use std::{
+	collections::BTreeMap,
+	sync::{
+		Mutex,
+		atomic::{AtomicBool, AtomicU64, Ordering},
+	},
+};
+
+// The `polkadot_xcm` calls seen that no payload generator handled, counted by call name, so
+// maintainers and users know which new calls need support. They're reported in the run summary.
+static UNHANDLED_XCM_CALLS: Mutex<BTreeMap<String, u64>> = Mutex::new(BTreeMap::new());
+// Whether every unhandled call is also logged as soon as it's seen, set once when the CLI starts.
+static LOG_UNHANDLED_XCM_CALLS: AtomicBool = AtomicBool::new(false);
+
+// The times the finalized blocks stopped arriving for longer than the stall window of the
+// subscription, served with the metrics of the API.
+static FINALITY_STALLS: AtomicU64 = AtomicU64::new(0);
+
+pub(crate) fn set_log_unhandled_xcm_calls(log: bool) {
+	LOG_UNHANDLED_XCM_CALLS.store(log, Ordering::Relaxed);
+}
+
+pub(crate) fn record_unhandled_xcm_call(block_number: BlockNumber, call_name: &str) {
+	if LOG_UNHANDLED_XCM_CALLS.load(Ordering::Relaxed) {
+		eprintln!("Unhandled PolkadotXcm.{} call at block {}", call_name, block_number);
+	}
+	if let Ok(mut unhandled_calls) = UNHANDLED_XCM_CALLS.lock() {
+		*unhandled_calls.entry(call_name.to_owned()).or_default() += 1;
+	}
+}
+
+pub(crate) fn unhandled_xcm_calls() -> BTreeMap<String, u64> {
+	UNHANDLED_XCM_CALLS
+		.lock()
+		.map(|unhandled_calls| unhandled_calls.clone())
+		.unwrap_or_default()
+}
+
+pub(crate) fn record_finality_stall() {
+	FINALITY_STALLS.fetch_add(1, Ordering::Relaxed);
+}
+
+// The counters in the Prometheus text format.
+pub(crate) fn render_counters() -> String {
+	format!(
+		"# HELP xcm_indexer_finality_stalls_total Times no finalized block arrived within the stall window\n# TYPE xcm_indexer_finality_stalls_total counter\nxcm_indexer_finality_stalls_total {}\n",
+		FINALITY_STALLS.load(Ordering::Relaxed)
+	)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn record_unhandled_xcm_call_counts_by_name() {
+		record_unhandled_xcm_call(1, "test_call");
+		record_unhandled_xcm_call(2, "test_call");
+		assert_eq!(unhandled_xcm_calls().get("test_call"), Some(&2));
+	}
+
+	#[test]
+	fn finality_stalls_are_counted() {
+		record_finality_stall();
+		let stalls = render_counters()
+			.lines()
+			.find_map(|line| line.strip_prefix("xcm_indexer_finality_stalls_total "))
+			.and_then(|stalls| stalls.parse::<u64>().ok());
+		assert!(stalls.is_some_and(|stalls| stalls >= 1));
+	}
+}
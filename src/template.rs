@@ -0,0 +1,112 @@
+use crate::types::XcmTransfer;
+use serde_json::Value;
+
+// Renders a template for a transfer. Placeholders are written as `{field}` and replaced by the
+// field of the record with that name, nested fields being accessed with dots (eg
+// `{destination_chain.PolkadotParachain}`). `{kind}` is replaced by the kind of record, `{record}`
+// by the whole record as JSON and the extra placeholders by their value. Unknown placeholders are
+// left untouched.
+pub(crate) fn render_template(
+	template: &str,
+	transfer: &XcmTransfer,
+	extra_placeholders: &[(&str, &str)],
+) -> String {
+	let record = serde_json::to_value(transfer).unwrap_or(Value::Null);
+	// The records are serialized as `{ "<Kind>": { ..fields } }`
+	let fields = record.as_object().and_then(|record| record.values().next());
+
+	let mut output = String::new();
+	let mut rest = template;
+	while let Some(start) = rest.find('{') {
+		output.push_str(&rest[..start]);
+		let Some(end) = rest[start..].find('}').map(|end| start + end) else {
+			rest = &rest[start..];
+			break;
+		};
+		let placeholder = &rest[start + 1..end];
+		let field = fields.and_then(|fields| {
+			placeholder.split('.').try_fold(fields, |value, key| value.get(key))
+		});
+		match (placeholder, field) {
+			(_, Some(Value::String(value))) => output.push_str(value),
+			(_, Some(value)) => output.push_str(&value.to_string()),
+			("kind", None) => output.push_str(transfer.kind()),
+			("record", None) =>
+				output.push_str(&serde_json::to_string_pretty(&record).unwrap_or_default()),
+			(placeholder, None) =>
+				match extra_placeholders.iter().find(|(name, _)| *name == placeholder) {
+					Some((_, value)) => output.push_str(value),
+					None => output.push_str(&rest[start..=end]),
+				},
+		}
+		rest = &rest[end + 1..];
+	}
+	output.push_str(rest);
+	output
+}
+
+// Renders a line per transfer.
+pub(crate) fn render_transfers(template: &str, transfers: &[XcmTransfer]) -> String {
+	transfers
+		.iter()
+		.map(|transfer| render_template(template, transfer, &[]))
+		.collect::<Vec<_>>()
+		.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		incoming_parser::{OriginChain, XcmIncomingTransfer},
+		types::TransferType,
+	};
+
+	const BENEFICIARY: &str = "13KsaHFcQKSTd4m73Ub9yVwM1JGCZvipMyTZonHEXEceFYwS";
+
+	fn received(amount: f64) -> XcmTransfer {
+		XcmTransfer::ReceivedTransfer(XcmIncomingTransfer {
+			block_number: 8_935_124,
+			origin_chain: OriginChain::PolkadotParachain(2034),
+			message_id: None,
+			beneficiary: BENEFICIARY.to_owned(),
+			beneficiary_public_key:
+				"0x66dd21797b3ab4b27ff723196324a33f7a80683bda78e0fcebf8e7cebb09a358".to_owned(),
+			asset: "DOT".to_owned(),
+			asset_id: None,
+			amount,
+			exact_amount: None,
+			transfer_type: TransferType::Reserve,
+			fee_swap: None,
+			entities: vec![],
+			dust_lost: None,
+			trapped_assets_hash: None,
+			new_account: false,
+			xc20_address: None,
+		})
+	}
+
+	#[test]
+	fn render_template_replaces_placeholders() {
+		assert_eq!(
+			render_template(
+				"{reason}: {amount} {asset} to {beneficiary} from {origin_chain.PolkadotParachain} at block {block_number} ({kind}) {unknown}",
+				&received(100.0),
+				&[("reason", "Large transfer")]
+			),
+			format!(
+				"Large transfer: 100.0 DOT to {} from 2034 at block 8935124 (received) {{unknown}}",
+				BENEFICIARY
+			)
+		);
+		assert_eq!(render_template("no placeholders {", &received(1.0), &[]), "no placeholders {");
+	}
+
+	#[test]
+	fn render_transfers_renders_a_line_per_transfer() {
+		assert_eq!(
+			render_transfers("{kind} {amount} {asset}", &[received(1.0), received(2.5)]),
+			"received 1.0 DOT\nreceived 2.5 DOT"
+		);
+	}
+}
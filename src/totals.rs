@@ -0,0 +1,188 @@
+use crate::{
+	analytics::TransferDirection,
+	types::{BlockNumber, XcmTransfer},
+};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+
+// The amount of transfers of a flow and their total amount.
+#[derive(Debug, Default, Clone, PartialEq, Serialize)]
+pub(crate) struct Total {
+	pub(crate) transfers: u64,
+	pub(crate) amount: f64,
+}
+
+impl Total {
+	fn add(&mut self, amount: f64) {
+		self.transfers += 1;
+		self.amount += amount;
+	}
+}
+
+#[derive(Debug, Default)]
+struct AssetTotals {
+	total: Total,
+	// Keyed by the origin or destination chain, as rendered in the debug output, eg
+	// `PolkadotParachain(2034)`
+	counterparties: BTreeMap<String, Total>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub(crate) struct CounterpartyTotal {
+	pub(crate) counterparty: String,
+	#[serde(flatten)]
+	pub(crate) total: Total,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub(crate) struct AssetTotal {
+	pub(crate) asset: String,
+	pub(crate) direction: TransferDirection,
+	#[serde(flatten)]
+	pub(crate) total: Total,
+	pub(crate) counterparties: Vec<CounterpartyTotal>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub(crate) struct TotalsSnapshot {
+	// The range of blocks observed, absent until a block is
+	pub(crate) from_block: Option<BlockNumber>,
+	pub(crate) to_block: Option<BlockNumber>,
+	pub(crate) assets: Vec<AssetTotal>,
+}
+
+// Running totals of the transfers received and sent per asset, and per counterparty chain, updated
+// as every block is processed, so aggregate queries are answered without scanning the transfers.
+// They're kept in memory, so they start from scratch when the indexer restarts.
+#[derive(Debug, Default)]
+pub(crate) struct TransferTotals {
+	from_block: Option<BlockNumber>,
+	to_block: Option<BlockNumber>,
+	assets: HashMap<(String, TransferDirection), AssetTotals>,
+}
+
+impl TransferTotals {
+	pub(crate) fn observe(&mut self, block_number: BlockNumber, transfers: &[XcmTransfer]) {
+		// Blocks may be observed out of order by the workers
+		self.from_block = Some(self.from_block.map_or(block_number, |from| from.min(block_number)));
+		self.to_block = Some(self.to_block.map_or(block_number, |to| to.max(block_number)));
+		for transfer in transfers {
+			let (asset, direction, counterparty, amount) = match transfer {
+				XcmTransfer::ReceivedTransfer(incoming) => (
+					&incoming.asset,
+					TransferDirection::Received,
+					format!("{:?}", incoming.origin_chain),
+					incoming.amount,
+				),
+				XcmTransfer::SentTransfer(outgoing) => (
+					&outgoing.asset,
+					TransferDirection::Sent,
+					format!("{:?}", outgoing.destination_chain),
+					outgoing.amount,
+				),
+				_ => continue,
+			};
+			let totals = self.assets.entry((asset.clone(), direction)).or_default();
+			totals.total.add(amount);
+			totals.counterparties.entry(counterparty).or_default().add(amount);
+		}
+	}
+
+	// The totals of every asset, or of a single one, sorted by asset and direction.
+	pub(crate) fn snapshot(&self, asset: Option<&str>) -> TotalsSnapshot {
+		let mut assets: Vec<AssetTotal> = self
+			.assets
+			.iter()
+			.filter(|((symbol, _), _)| asset.is_none_or(|asset| asset.eq_ignore_ascii_case(symbol)))
+			.map(|((symbol, direction), totals)| AssetTotal {
+				asset: symbol.clone(),
+				direction: direction.clone(),
+				total: totals.total.clone(),
+				counterparties: totals
+					.counterparties
+					.iter()
+					.map(|(counterparty, total)| CounterpartyTotal {
+						counterparty: counterparty.clone(),
+						total: total.clone(),
+					})
+					.collect(),
+			})
+			.collect();
+		assets.sort_by(|a, b| {
+			a.asset.cmp(&b.asset).then(
+				(a.direction == TransferDirection::Sent)
+					.cmp(&(b.direction == TransferDirection::Sent)),
+			)
+		});
+		TotalsSnapshot { from_block: self.from_block, to_block: self.to_block, assets }
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		incoming_parser::{OriginChain, XcmIncomingTransfer},
+		types::TransferType,
+	};
+
+	fn received(origin_chain: OriginChain, asset: &str, amount: f64) -> XcmTransfer {
+		XcmTransfer::ReceivedTransfer(XcmIncomingTransfer {
+			block_number: 8_935_124,
+			origin_chain,
+			message_id: None,
+			beneficiary: "13KsaHFcQKSTd4m73Ub9yVwM1JGCZvipMyTZonHEXEceFYwS".to_owned(),
+			beneficiary_public_key:
+				"0x66dd21797b3ab4b27ff723196324a33f7a80683bda78e0fcebf8e7cebb09a358".to_owned(),
+			asset: asset.to_owned(),
+			asset_id: None,
+			amount,
+			exact_amount: None,
+			transfer_type: TransferType::Reserve,
+			fee_swap: None,
+			entities: vec![],
+			dust_lost: None,
+			trapped_assets_hash: None,
+			new_account: false,
+			xc20_address: None,
+		})
+	}
+
+	#[test]
+	fn totals_are_updated_per_asset_and_counterparty() {
+		let mut totals = TransferTotals::default();
+		totals.observe(12, &[received(OriginChain::PolkadotParachain(2034), "USDT", 10.0)]);
+		totals.observe(
+			10,
+			&[
+				received(OriginChain::PolkadotParachain(2034), "USDT", 5.0),
+				received(OriginChain::Polkadot, "DOT", 1.5),
+			],
+		);
+		totals.observe(11, &[]);
+
+		let snapshot = totals.snapshot(None);
+		assert_eq!((snapshot.from_block, snapshot.to_block), (Some(10), Some(12)));
+		assert_eq!(
+			snapshot.assets.iter().map(|total| total.asset.as_str()).collect::<Vec<_>>(),
+			vec!["DOT", "USDT"]
+		);
+
+		let snapshot = totals.snapshot(Some("usdt"));
+		assert_eq!(
+			snapshot.assets,
+			vec![AssetTotal {
+				asset: "USDT".to_owned(),
+				direction: TransferDirection::Received,
+				total: Total { transfers: 2, amount: 15.0 },
+				counterparties: vec![CounterpartyTotal {
+					counterparty: "PolkadotParachain(2034)".to_owned(),
+					total: Total { transfers: 2, amount: 15.0 },
+				}],
+			}]
+		);
+		let json = serde_json::to_value(&snapshot).unwrap();
+		assert_eq!(json["assets"][0]["transfers"], 2);
+		assert_eq!(json["assets"][0]["counterparties"][0]["amount"], 15.0);
+	}
+}
@@ -1,34 +1,357 @@
-use serde::Serialize;
+use crate::{
+	asset_hub::runtime_types::{
+		frame_support::traits::messages::ProcessMessageError,
+		staging_xcm::v4::{junction::Junction, junctions::Junctions, location::Location},
+	},
+	chain_spec::{Chain, ChainSpec},
+};
+use serde::{Deserialize, Serialize};
+use std::{num::NonZeroU32, time::Duration};
 use subxt::PolkadotConfig;
 
-pub(crate) const ASSET_HUB_RPC_ENDPOINT: &str = "wss://polkadot-asset-hub-rpc.polkadot.io";
-
-// As DOT is the native currency, it doesn't have metadata as other assets but it's part of the
-// chainspec. While we can query this value to the node via an RPC call, it's not worthy for this
-// minimal indexer as we can ensure that this value won't change unless the chain is completely
-// shutted down and restarted with a new chainspec.
-pub(crate) const DOT_DECIMALS: u8 = 10;
+#[derive(Debug, Clone)]
+pub(crate) struct ConnectionSettings {
+	pub(crate) connection_timeout: Duration,
+	pub(crate) request_timeout: Duration,
+	pub(crate) ping_interval: Duration,
+	pub(crate) max_rps: Option<NonZeroU32>,
+}
 
-#[derive(Debug, Serialize, PartialEq)]
-pub(crate) enum TransferType {
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+#[cfg_attr(feature = "typescript", derive(ts_rs::TS))]
+pub enum TransferType {
 	Teleport,
 	Reserve,
 }
 
+// Identifies the asset of a transfer unambiguously, as several assets may share a name: the native
+// token, an asset of pallet_assets by its id, or a foreign asset by its location as seen from
+// AssetHub.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+#[cfg_attr(feature = "typescript", derive(ts_rs::TS))]
+pub enum AssetIdentifier {
+	Native,
+	Asset(u32),
+	ForeignAsset(String),
+}
+
+impl AssetIdentifier {
+	pub(crate) fn foreign_asset(location: &Location) -> Self {
+		Self::ForeignAsset(crate::locations::encode_location(location))
+	}
+
+	// The asset at a location as seen from AssetHub, eg `parents=1` for the native token or
+	// `parents=0/PalletInstance(50)/GeneralIndex(1984)` for an asset of pallet_assets.
+	pub(crate) fn from_location(location: &Location) -> Self {
+		match (location.parents, &location.interior) {
+			(1, Junctions::Here) => Self::Native,
+			(
+				0,
+				Junctions::X2(
+					[
+						Junction::PalletInstance(Chain::ASSETS_PALLET_INDEX),
+						Junction::GeneralIndex(asset_id),
+					],
+				),
+			) if u32::try_from(*asset_id).is_ok() => Self::Asset(*asset_id as u32),
+			_ => Self::foreign_asset(location),
+		}
+	}
+
+	// Whether this is the asset designated by a CLI argument or a configuration file, given by
+	// its location in its canonical string form.
+	pub(crate) fn is_at(&self, location: &str) -> bool {
+		crate::locations::parse_location(location)
+			.is_ok_and(|location| *self == Self::from_location(&location))
+	}
+}
+
+// Why a message failed, as told by the events of the chain processing it, so delivery problems can
+// be debugged from the records alone.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+#[cfg_attr(feature = "typescript", derive(ts_rs::TS))]
+pub enum MessageError {
+	// The queue discarded the message before its execution (`MessageQueue::ProcessingFailed`), eg
+	// as it couldn't be decoded or its XCM version isn't supported
+	BadFormat,
+	Corrupt,
+	Unsupported,
+	StackLimitReached,
+	Yield,
+	// The message needs more weight than a block can give, so the queue keeps it until it's
+	// executed manually. The weight is unknown if told by `MessageQueue::OverweightEnqueued`
+	Overweight {
+		#[serde(skip_serializing_if = "Option::is_none")]
+		#[cfg_attr(feature = "typescript", ts(as = "Option<f64>", optional))]
+		ref_time: Option<u64>,
+		#[serde(skip_serializing_if = "Option::is_none")]
+		#[cfg_attr(feature = "typescript", ts(as = "Option<f64>", optional))]
+		proof_size: Option<u64>,
+	},
+	// The execution stopped with an XCM error, eg `Barrier` or `WeightLimitReached`, as reported
+	// by `PolkadotXcm::Attempted`
+	Xcm(String),
+	// The execution failed, but the chain doesn't report the XCM error of received messages
+	// (`MessageQueue::Processed` unsuccessful)
+	ExecutionFailed,
+}
+
+impl From<&ProcessMessageError> for MessageError {
+	fn from(error: &ProcessMessageError) -> Self {
+		match error {
+			ProcessMessageError::BadFormat => Self::BadFormat,
+			ProcessMessageError::Corrupt => Self::Corrupt,
+			ProcessMessageError::Unsupported => Self::Unsupported,
+			ProcessMessageError::Overweight(weight) => Self::Overweight {
+				ref_time: Some(weight.ref_time),
+				proof_size: Some(weight.proof_size),
+			},
+			ProcessMessageError::Yield => Self::Yield,
+			ProcessMessageError::StackLimitReached => Self::StackLimitReached,
+		}
+	}
+}
+
 #[derive(Debug, Serialize, PartialEq)]
 pub(crate) struct AssetMetadataValues {
 	pub(crate) asset_name: String,
 	pub(crate) decimals: u8,
 }
 
-pub(crate) type BlockHash =
+pub type BlockHash =
 	<<PolkadotConfig as subxt::config::Config>::Hasher as subxt::config::Hasher>::Output;
 
-pub(crate) type BlockNumber =
+pub type BlockNumber =
 	<<PolkadotConfig as subxt::config::Config>::Header as subxt::config::Header>::Number;
 
-#[derive(Debug, Serialize, PartialEq)]
-pub(crate) enum XcmTransfer {
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+#[cfg_attr(feature = "typescript", derive(ts_rs::TS))]
+pub enum XcmTransfer {
 	ReceivedTransfer(crate::incoming_parser::XcmIncomingTransfer),
 	SentTransfer(crate::outgoing_parser::XcmOutgoingTransfer),
+	SentTransferGroup(crate::outgoing_parser::XcmOutgoingTransferGroup),
+	RoundTrip(crate::analytics::RoundTrip),
+	StatusUpdate(crate::analytics::TransferStatusUpdate),
+	Anomaly(crate::analytics::VolumeAnomaly),
+	FailedMessage(crate::incoming_parser::XcmFailedMessage),
+}
+
+impl XcmTransfer {
+	// The kind of record, used by the sinks to route each record so consumers can subscribe to the
+	// records they're interested in.
+	pub(crate) fn kind(&self) -> &'static str {
+		match self {
+			Self::ReceivedTransfer(_) => "received",
+			Self::SentTransfer(_) => "sent",
+			Self::SentTransferGroup(_) => "sent_group",
+			Self::RoundTrip(_) => "round_trip",
+			Self::StatusUpdate(_) => "status_update",
+			Self::Anomaly(_) => "anomaly",
+			Self::FailedMessage(_) => "failed_message",
+		}
+	}
+}
+
+// The transfers found in a block, output under the block with the per-block layout so consumers
+// can deduplicate by block and tell blocks without transfers apart from unprocessed ones.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+#[cfg_attr(feature = "typescript", derive(ts_rs::TS))]
+pub struct BlockTransfers {
+	#[cfg_attr(feature = "typescript", ts(as = "String"))]
+	pub block_hash: BlockHash,
+	pub block_number: BlockNumber,
+	// Milliseconds since the Unix epoch, as set by the `Timestamp` pallet
+	#[serde(skip_serializing_if = "Option::is_none")]
+	#[cfg_attr(feature = "typescript", ts(as = "Option<f64>", optional))]
+	pub timestamp: Option<u64>,
+	pub transfers: Vec<XcmTransfer>,
+}
+
+// Marks a processed block without transfers, so consumers can verify no block was skipped.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+#[cfg_attr(feature = "typescript", derive(ts_rs::TS))]
+pub enum BlockMarker {
+	EmptyBlock {
+		#[cfg_attr(feature = "typescript", ts(as = "String"))]
+		block_hash: BlockHash,
+		block_number: BlockNumber,
+	},
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		analytics::{
+			RoundTrip, TransferDirection, TransferStatus, TransferStatusUpdate, VolumeAnomaly,
+			VolumeDeviation,
+		},
+		controllers::{Multisig, SenderControllers},
+		entities::{EntityKind, EntityRole, EntityTag},
+		incoming_parser::{FeeSwap, OriginChain, XcmFailedMessage, XcmIncomingTransfer},
+		outgoing_parser::{
+			DestinationChain, ExecutionOutcome, TransferOrigin, WeightLimit, XcmOutgoingTransfer,
+		},
+	};
+
+	fn outgoing() -> XcmOutgoingTransfer {
+		XcmOutgoingTransfer {
+			block_number: 8_000_000,
+			transfer_group_id: "8000000-2".to_owned(),
+			origin: Some(TransferOrigin::Governance),
+			destination_chain: DestinationChain::Ethereum { chain_id: 1 },
+			sender: "13UVJyLnbVp9RBZYFwFGyDvVd1y27Tt8tkntv6Q7JVPhFsTB".to_owned(),
+			sender_public_key: None,
+			beneficiary: "0x1234567890AbcdEF1234567890aBcdef12345678".to_owned(),
+			beneficiary_public_key: "0x1234567890abcdef1234567890abcdef12345678".to_owned(),
+			asset: "DOT".to_owned(),
+			asset_id: None,
+			amount: 0.1 + 0.2,
+			exact_amount: None,
+			transfer_type: TransferType::Reserve,
+			fee_asset_item: 0,
+			pays_fees: true,
+			weight_limit: WeightLimit::Limited { ref_time: 1, proof_size: 2 },
+			burned_amount: Some(1.0 / 3.0),
+			outcome: Some(ExecutionOutcome::Incomplete {
+				ref_time: 1,
+				proof_size: 2,
+				error: "FailedToTransactAsset".to_owned(),
+			}),
+			message_id: Some(format!("0x{}", "ab".repeat(32))),
+			trapped_assets_hash: None,
+			local_fee_dot: Some(0.0161),
+			nonce: Some(42),
+			tip_dot: Some(0.001),
+			controllers: Some(SenderControllers {
+				proxy_delegates: vec![],
+				multisig: Some(Multisig { threshold: 2, signatories: vec!["a".to_owned()] }),
+			}),
+			raw_xcm: None,
+			entities: vec![EntityTag {
+				role: EntityRole::Sender,
+				name: "Treasury".to_owned(),
+				kind: EntityKind::Treasury,
+			}],
+			xc20_address: None,
+		}
+	}
+
+	fn incoming() -> XcmIncomingTransfer {
+		XcmIncomingTransfer {
+			block_number: 8_000_010,
+			origin_chain: OriginChain::PolkadotParachain(2004),
+			message_id: None,
+			beneficiary: "13UVJyLnbVp9RBZYFwFGyDvVd1y27Tt8tkntv6Q7JVPhFsTB".to_owned(),
+			beneficiary_public_key: format!("0x{}", "6d".repeat(32)),
+			asset: "USDT".to_owned(),
+			asset_id: Some(AssetIdentifier::Asset(1984)),
+			amount: 12.345678,
+			exact_amount: None,
+			transfer_type: TransferType::Teleport,
+			fee_swap: Some(FeeSwap {
+				asset_in: "USDT".to_owned(),
+				amount_in: 0.01,
+				asset_out: "DOT".to_owned(),
+				amount_out: 0.002,
+			}),
+			entities: vec![],
+			dust_lost: None,
+			trapped_assets_hash: None,
+			new_account: true,
+			xc20_address: Some("0xFFFFFFFF0000000000000000000000000000000A".to_owned()),
+		}
+	}
+
+	#[test]
+	fn assets_are_identified_by_their_location() {
+		assert!(AssetIdentifier::Native.is_at("parents=1"));
+		assert!(
+			AssetIdentifier::Asset(1984).is_at("parents=0/PalletInstance(50)/GeneralIndex(1984)")
+		);
+		assert!(
+			AssetIdentifier::ForeignAsset("parents=1/Parachain(3370)".to_owned())
+				.is_at("parents=1 / Parachain(3370)")
+		);
+		assert!(!AssetIdentifier::Asset(1984).is_at("parents=1/Parachain(3370)"));
+		assert!(!AssetIdentifier::Native.is_at("DOT"));
+	}
+
+	#[test]
+	fn message_errors_from_the_message_queue() {
+		use crate::asset_hub::runtime_types::sp_weights::weight_v2::Weight;
+		assert_eq!(MessageError::from(&ProcessMessageError::BadFormat), MessageError::BadFormat);
+		assert_eq!(
+			MessageError::from(&ProcessMessageError::Overweight(Weight {
+				ref_time: 1_000,
+				proof_size: 10
+			})),
+			MessageError::Overweight { ref_time: Some(1_000), proof_size: Some(10) }
+		);
+		// The weight is only given when known
+		assert_eq!(
+			serde_json::to_string(&MessageError::Overweight { ref_time: None, proof_size: None })
+				.unwrap(),
+			r#"{"Overweight":{}}"#
+		);
+		assert_eq!(
+			serde_json::to_string(&MessageError::Xcm("Barrier".to_owned())).unwrap(),
+			r#"{"Xcm":"Barrier"}"#
+		);
+	}
+
+	#[test]
+	fn transfers_round_trip_through_json() {
+		let transfers = vec![
+			XcmTransfer::ReceivedTransfer(incoming()),
+			XcmTransfer::SentTransfer(outgoing()),
+			XcmTransfer::RoundTrip(RoundTrip {
+				outgoing: outgoing(),
+				incoming: incoming(),
+				blocks_elapsed: 10,
+			}),
+			XcmTransfer::StatusUpdate(TransferStatusUpdate {
+				transfer_group_id: "8000000-2".to_owned(),
+				message_id: None,
+				status: TransferStatus::Failed {
+					reason: "Barrier".to_owned(),
+					error: Some(MessageError::Xcm("Barrier".to_owned())),
+				},
+			}),
+			XcmTransfer::Anomaly(VolumeAnomaly {
+				block_number: 8_000_010,
+				direction: TransferDirection::Sent,
+				asset: "USDT".to_owned(),
+				counterparty: "PolkadotParachain(2034)".to_owned(),
+				deviation: VolumeDeviation::Spike,
+				hourly_volume: 1_000_000.0,
+				baseline: 1234.5,
+			}),
+			XcmTransfer::FailedMessage(XcmFailedMessage {
+				block_number: 8_000_010,
+				origin_chain: OriginChain::PolkadotParachain(2034),
+				message_id: format!("0x{}", "ab".repeat(32)),
+				error: MessageError::Overweight { ref_time: None, proof_size: None },
+				trapped_assets_hash: None,
+			}),
+		];
+		let mut transfers_with_groups =
+			crate::outgoing_parser::group_sent_transfers(transfers.clone());
+		transfers_with_groups.extend(transfers);
+		let block = BlockTransfers {
+			block_hash: BlockHash::repeat_byte(1),
+			block_number: 8_000_010,
+			timestamp: Some(1_700_000_000_000),
+			transfers: transfers_with_groups,
+		};
+
+		let json = serde_json::to_string(&block).unwrap();
+		assert_eq!(serde_json::from_str::<BlockTransfers>(&json).unwrap(), block);
+
+		let marker =
+			BlockMarker::EmptyBlock { block_hash: BlockHash::repeat_byte(2), block_number: 1 };
+		let json = serde_json::to_string(&marker).unwrap();
+		assert_eq!(serde_json::from_str::<BlockMarker>(&json).unwrap(), marker);
+	}
 }
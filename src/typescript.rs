@@ -0,0 +1,74 @@
+use crate::{
+	analytics::{
+		RoundTrip, TransferDirection, TransferStatus, TransferStatusUpdate, VolumeAnomaly,
+		VolumeDeviation,
+	},
+	controllers::{Multisig, SenderControllers},
+	entities::{EntityKind, EntityRole, EntityTag},
+	incoming_parser::{FeeSwap, OriginChain, XcmFailedMessage, XcmIncomingTransfer},
+	outgoing_parser::{
+		DestinationChain, ExecutionOutcome, TransferOrigin, WeightLimit, XcmOutgoingAsset,
+		XcmOutgoingTransfer, XcmOutgoingTransferGroup,
+	},
+	raw_xcm::{ForwardedMessage, RawXcm},
+	types::{
+		AssetIdentifier, BlockMarker, BlockTransfers, MessageError, TransferType, XcmTransfer,
+	},
+};
+use ts_rs::TS;
+
+// The TypeScript definitions of the JSON records, derived from the Rust types so they can't drift
+// from the output. Amounts and 64 bits integers are numbers, as that's how `JSON.parse` reads them.
+pub(crate) fn definitions() -> String {
+	[
+		BlockTransfers::decl(),
+		BlockMarker::decl(),
+		XcmTransfer::decl(),
+		XcmIncomingTransfer::decl(),
+		OriginChain::decl(),
+		FeeSwap::decl(),
+		XcmOutgoingTransfer::decl(),
+		XcmOutgoingTransferGroup::decl(),
+		XcmOutgoingAsset::decl(),
+		TransferOrigin::decl(),
+		DestinationChain::decl(),
+		WeightLimit::decl(),
+		ExecutionOutcome::decl(),
+		TransferType::decl(),
+		AssetIdentifier::decl(),
+		SenderControllers::decl(),
+		RawXcm::decl(),
+		ForwardedMessage::decl(),
+		Multisig::decl(),
+		EntityTag::decl(),
+		EntityRole::decl(),
+		EntityKind::decl(),
+		RoundTrip::decl(),
+		TransferStatusUpdate::decl(),
+		TransferStatus::decl(),
+		VolumeAnomaly::decl(),
+		TransferDirection::decl(),
+		VolumeDeviation::decl(),
+		XcmFailedMessage::decl(),
+		MessageError::decl(),
+	]
+	.map(|declaration| format!("export {}", declaration))
+	.join("\n\n")
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn definitions_match_the_json_output() {
+		let definitions = definitions();
+		assert!(definitions.contains("export type XcmTransfer = "));
+		assert!(definitions.contains("\"ReceivedTransfer\": XcmIncomingTransfer"));
+		// Fields skipped when empty are optional
+		assert!(definitions.contains("message_id?: string"));
+		assert!(definitions.contains("entities?: Array<EntityTag>"));
+		assert!(definitions.contains("block_hash: string"));
+		assert!(!definitions.contains("bigint"));
+	}
+}
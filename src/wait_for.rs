@@ -0,0 +1,74 @@
+use crate::{incoming_parser::XcmIncomingTransfer, types::XcmTransfer};
+
+// The incoming transfer awaited by `wait-for`. Every criterion given must hold, so a transfer of
+// at least some amount of an asset to an account can be awaited.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub(crate) struct AwaitedTransfer {
+	pub(crate) beneficiary_public_key: Option<String>,
+	pub(crate) asset: Option<String>,
+	pub(crate) min_amount: Option<f64>,
+}
+
+impl AwaitedTransfer {
+	pub(crate) fn matches(&self, transfer: &XcmIncomingTransfer) -> bool {
+		self.beneficiary_public_key
+			.as_ref()
+			.is_none_or(|public_key| *public_key == transfer.beneficiary_public_key) &&
+			self.asset
+				.as_ref()
+				.is_none_or(|asset| asset.eq_ignore_ascii_case(&transfer.asset)) &&
+			self.min_amount.is_none_or(|min_amount| transfer.amount >= min_amount)
+	}
+
+	// The first incoming transfer of a block matching, if any.
+	pub(crate) fn find(&self, transfers: Vec<XcmTransfer>) -> Option<XcmTransfer> {
+		transfers.into_iter().find(
+			|transfer| matches!(transfer, XcmTransfer::ReceivedTransfer(transfer) if self.matches(transfer)),
+		)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{incoming_parser::OriginChain, types::TransferType};
+
+	#[test]
+	fn every_criterion_given_must_hold() {
+		let transfer = XcmIncomingTransfer {
+			block_number: 10,
+			origin_chain: OriginChain::PolkadotParachain(2034),
+			message_id: None,
+			beneficiary: String::new(),
+			beneficiary_public_key: "0x01".to_owned(),
+			asset: "USDT".to_owned(),
+			asset_id: None,
+			amount: 5.0,
+			exact_amount: None,
+			transfer_type: TransferType::Reserve,
+			fee_swap: None,
+			entities: vec![],
+			dust_lost: None,
+			trapped_assets_hash: None,
+			new_account: false,
+			xc20_address: None,
+		};
+		assert!(AwaitedTransfer::default().matches(&transfer));
+
+		let awaited = AwaitedTransfer {
+			beneficiary_public_key: Some("0x01".to_owned()),
+			asset: Some("usdt".to_owned()),
+			min_amount: Some(5.0),
+		};
+		assert!(awaited.matches(&transfer));
+		assert!(!AwaitedTransfer { min_amount: Some(5.1), ..awaited.clone() }.matches(&transfer));
+		assert!(
+			!AwaitedTransfer { beneficiary_public_key: Some("0x02".to_owned()), ..awaited.clone() }
+				.matches(&transfer)
+		);
+		assert_eq!(
+			awaited.find(vec![XcmTransfer::ReceivedTransfer(transfer.clone())]),
+			Some(XcmTransfer::ReceivedTransfer(transfer))
+		);
+	}
+}
@@ -0,0 +1,171 @@
+use crate::{rules::Condition, sink::TransferSink, types::XcmTransfer};
+use serde::Deserialize;
+use std::{fs::OpenOptions, io::Write, path::PathBuf};
+
+// A named list of watched accounts and assets, giving a team a feed of its own with the records
+// touching them. Each list is delivered to its own file or webhook, independently of the routes
+// and of the other lists, so several teams can share an indexer without seeing each other's feed.
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct WatchList {
+	pub(crate) name: String,
+	// The senders or beneficiaries watched, given by their address or public key
+	#[serde(default)]
+	pub(crate) accounts: Vec<String>,
+	#[serde(default)]
+	pub(crate) assets: Vec<String>,
+	pub(crate) sink: WatchListSink,
+}
+
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum WatchListSink {
+	// A file the records are appended to, one JSON record per line
+	File(PathBuf),
+	// A URL the records of every block are posted to as JSON
+	Webhook(String),
+}
+
+// The body posted to the webhooks, naming the list so a single endpoint can serve several lists.
+#[cfg(feature = "webhooks")]
+#[derive(serde::Serialize)]
+struct WebhookPayload<'a> {
+	watch_list: &'a str,
+	transfers: &'a [XcmTransfer],
+}
+
+impl WatchList {
+	pub(crate) fn validate(&self) -> Result<(), String> {
+		if self.accounts.is_empty() && self.assets.is_empty() {
+			return Err(format!("The watch list {} doesn't watch any account nor asset", self.name));
+		}
+		if matches!(self.sink, WatchListSink::Webhook(_)) && !cfg!(feature = "webhooks") {
+			return Err(format!(
+				"The watch list {} delivers to a webhook, which requires the webhooks feature",
+				self.name
+			));
+		}
+		Ok(())
+	}
+
+	// Whether a record touches one of the watched accounts and one of the watched assets. Lists
+	// without accounts (or assets) watch every account (or asset).
+	pub(crate) fn matches(&self, transfer: &XcmTransfer) -> bool {
+		let condition = |condition: Condition| condition.matches(transfer);
+		(self.accounts.is_empty() ||
+			self.accounts.iter().any(|account| {
+				condition(Condition { account: Some(account.clone()), ..Default::default() })
+			})) && (self.assets.is_empty() ||
+			self.assets.iter().any(|asset| {
+				condition(Condition { asset: Some(asset.clone()), ..Default::default() })
+			}))
+	}
+}
+
+impl TransferSink for WatchList {
+	fn name(&self) -> String {
+		format!("watch_list:{}", self.name)
+	}
+
+	async fn deliver(&self, transfers: &[XcmTransfer]) -> Result<(), String> {
+		match &self.sink {
+			WatchListSink::File(path) => {
+				let mut lines = String::new();
+				for transfer in transfers {
+					let json = serde_json::to_string(transfer)
+						.map_err(|e| format!("Failed to serialize a transfer: {}", e))?;
+					lines.push_str(&json);
+					lines.push('\n');
+				}
+				// A single write per batch, so the lines of concurrent blocks don't interleave
+				OpenOptions::new()
+					.create(true)
+					.append(true)
+					.open(path)
+					.and_then(|mut file| file.write_all(lines.as_bytes()))
+					.map_err(|e| format!("Failed to write to {}: {}", path.display(), e))
+			},
+			#[cfg(feature = "webhooks")]
+			WatchListSink::Webhook(url) => webhook_client()
+				.post(url)
+				.json(&WebhookPayload { watch_list: &self.name, transfers })
+				.send()
+				.await
+				.and_then(reqwest::Response::error_for_status)
+				.map(|_| ())
+				.map_err(|e| format!("Failed to post to the webhook: {}", e)),
+			// Rejected when the rules are loaded
+			#[cfg(not(feature = "webhooks"))]
+			WatchListSink::Webhook(_) => Ok(()),
+		}
+	}
+}
+
+// The client is shared by every list and block, so the connections to the webhooks are reused.
+#[cfg(feature = "webhooks")]
+fn webhook_client() -> &'static reqwest::Client {
+	static CLIENT: std::sync::OnceLock<reqwest::Client> = std::sync::OnceLock::new();
+	CLIENT.get_or_init(reqwest::Client::new)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		incoming_parser::{OriginChain, XcmIncomingTransfer},
+		types::TransferType,
+	};
+
+	const ACCOUNT: &str = "13KsaHFcQKSTd4m73Ub9yVwM1JGCZvipMyTZonHEXEceFYwS";
+
+	fn received(asset: &str, beneficiary: &str) -> XcmTransfer {
+		XcmTransfer::ReceivedTransfer(XcmIncomingTransfer {
+			block_number: 8_935_124,
+			origin_chain: OriginChain::PolkadotParachain(2034),
+			message_id: None,
+			beneficiary: beneficiary.to_owned(),
+			beneficiary_public_key:
+				"0x66dd21797b3ab4b27ff723196324a33f7a80683bda78e0fcebf8e7cebb09a358".to_owned(),
+			asset: asset.to_owned(),
+			asset_id: None,
+			amount: 10.0,
+			exact_amount: None,
+			transfer_type: TransferType::Teleport,
+			fee_swap: None,
+			entities: vec![],
+			dust_lost: None,
+			trapped_assets_hash: None,
+			new_account: false,
+			xc20_address: None,
+		})
+	}
+
+	#[tokio::test]
+	async fn watch_lists_deliver_the_records_touching_them() {
+		let path = std::env::temp_dir()
+			.join(format!("xcm_indexer_watch_list_{}.jsonl", std::process::id()));
+		let _ = std::fs::remove_file(&path);
+		let watch_list: WatchList = serde_json::from_str(&format!(
+			r#"{{ "name": "Treasury", "accounts": ["{}"], "assets": ["DOT", "USDT"],
+			"sink": {{ "file": {:?} }} }}"#,
+			ACCOUNT, path
+		))
+		.unwrap();
+		assert!(watch_list.validate().is_ok());
+
+		let watched = received("DOT", ACCOUNT);
+		assert!(watch_list.matches(&watched));
+		assert!(!watch_list.matches(&received("DOT", "other")));
+		assert!(!watch_list.matches(&received("KSM", ACCOUNT)));
+
+		watch_list.deliver(&[watched.clone(), watched.clone()]).await.unwrap();
+		let content = std::fs::read_to_string(&path).unwrap();
+		let _ = std::fs::remove_file(&path);
+		let lines = content.lines().collect::<Vec<_>>();
+		assert_eq!(lines.len(), 2);
+		assert_eq!(serde_json::from_str::<XcmTransfer>(lines[0]).unwrap(), watched);
+
+		let empty = WatchList { accounts: vec![], assets: vec![], ..watch_list };
+		assert!(empty.validate().is_err());
+	}
+}
@@ -0,0 +1,110 @@
+use crate::types::BlockNumber;
+use std::{
+	sync::{Arc, RwLock},
+	time::Duration,
+};
+use subxt::{OnlineClient, PolkadotConfig};
+
+// The client of the endpoint the subscription follows, shared by its workers so the watchdog can
+// move them to another endpoint when it fails over.
+#[derive(Clone)]
+pub(crate) struct SharedClient(Arc<RwLock<OnlineClient<PolkadotConfig>>>);
+
+impl SharedClient {
+	pub(crate) fn new(api: OnlineClient<PolkadotConfig>) -> Self {
+		Self(Arc::new(RwLock::new(api)))
+	}
+
+	// Clients are cheap to clone, so the lock isn't held while the client is used.
+	pub(crate) fn get(&self) -> OnlineClient<PolkadotConfig> {
+		match self.0.read() {
+			Ok(api) => api.clone(),
+			Err(poisoned) => poisoned.into_inner().clone(),
+		}
+	}
+
+	pub(crate) fn set(&self, api: OnlineClient<PolkadotConfig>) {
+		match self.0.write() {
+			Ok(mut current) => *current = api,
+			Err(poisoned) => *poisoned.into_inner() = api,
+		}
+	}
+}
+
+// The endpoints the subscription may follow: the main one and the failover ones, taken in turn
+// every time the finality stalls. With a single endpoint, failing over subscribes to it again, as
+// the subscription itself may be the one stuck.
+pub(crate) struct FailoverEndpoints {
+	urls: Vec<String>,
+	current: usize,
+}
+
+impl FailoverEndpoints {
+	pub(crate) fn new(main_url: &str, failover_urls: &[String]) -> Self {
+		let mut urls = vec![main_url.to_owned()];
+		urls.extend(failover_urls.iter().filter(|url| *url != main_url).cloned());
+		Self { urls, current: 0 }
+	}
+
+	pub(crate) fn current(&self) -> &str {
+		&self.urls[self.current]
+	}
+
+	// Moves to the next endpoint, returning its URL.
+	pub(crate) fn advance(&mut self) -> &str {
+		self.current = (self.current + 1) % self.urls.len();
+		self.current()
+	}
+}
+
+pub(crate) fn stall_warning(
+	window: Duration,
+	last_block: Option<BlockNumber>,
+	url: &str,
+) -> String {
+	match last_block {
+		Some(block_number) => format!(
+			"No finalized block received from {} for {} seconds, since block {}",
+			url,
+			window.as_secs(),
+			block_number
+		),
+		None => format!(
+			"No finalized block received from {} for {} seconds since the subscription started",
+			url,
+			window.as_secs()
+		),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn failover_endpoints_are_taken_in_turn() {
+		let mut endpoints = FailoverEndpoints::new(
+			"wss://main",
+			&["wss://backup-1".to_owned(), "wss://main".to_owned(), "wss://backup-2".to_owned()],
+		);
+		assert_eq!(endpoints.current(), "wss://main");
+		assert_eq!(endpoints.advance(), "wss://backup-1");
+		assert_eq!(endpoints.advance(), "wss://backup-2");
+		assert_eq!(endpoints.advance(), "wss://main");
+
+		let mut endpoints = FailoverEndpoints::new("wss://main", &[]);
+		assert_eq!(endpoints.advance(), "wss://main");
+	}
+
+	#[test]
+	fn stall_warnings_tell_the_last_block() {
+		assert_eq!(
+			stall_warning(Duration::from_secs(60), Some(9_000_000), "wss://main"),
+			"No finalized block received from wss://main for 60 seconds, since block 9000000"
+		);
+		assert!(
+			stall_warning(Duration::from_secs(60), None, "wss://main")
+				.ends_with("since the subscription started")
+		);
+	}
+}
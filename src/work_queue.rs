@@ -0,0 +1,55 @@
+use tokio::sync::{Mutex, mpsc::Receiver};
+
+// Runs a worker of a subscription: takes the next block queued for the workers and processes it,
+// until the queue is closed. The queue is only locked while taking a block, so every worker
+// processes its own block concurrently with the others.
+pub(crate) async fn run_worker<T, F, Fut>(queue: &Mutex<Receiver<T>>, mut process: F)
+where
+	F: FnMut(T) -> Fut,
+	Fut: Future<Output = ()>,
+{
+	loop {
+		// Received into a local so the guard is dropped before the block is processed
+		let next = queue.lock().await.recv().await;
+		let Some(block) = next else {
+			break;
+		};
+		process(block).await;
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::{sync::Arc, time::Duration};
+	use tokio::sync::Barrier;
+
+	#[tokio::test]
+	async fn workers_process_blocks_concurrently() {
+		let (sender, receiver) = tokio::sync::mpsc::channel(2);
+		let queue = Arc::new(Mutex::new(receiver));
+		// Both blocks must be in process at the same time for the barrier to be passed
+		let barrier = Arc::new(Barrier::new(2));
+		let workers = (0..2)
+			.map(|_| {
+				let (queue, barrier) = (queue.clone(), barrier.clone());
+				tokio::spawn(async move {
+					run_worker(&queue, |_: u32| {
+						let barrier = barrier.clone();
+						async move {
+							barrier.wait().await;
+						}
+					})
+					.await
+				})
+			})
+			.collect::<Vec<_>>();
+		sender.send(1).await.unwrap();
+		sender.send(2).await.unwrap();
+		drop(sender);
+
+		tokio::time::timeout(Duration::from_secs(5), futures::future::join_all(workers))
+			.await
+			.expect("The blocks weren't processed concurrently");
+	}
+}
@@ -0,0 +1,127 @@
+use crate::{
+	asset_hub::runtime_types::xcm::v3::junction::{Junction, NetworkId},
+	chain_spec::{Chain, ChainSpec},
+	incoming_parser::OriginChain,
+	outgoing_parser::DestinationChain,
+};
+use subxt::ext::codec::Encode;
+
+const MOONBEAM_PARA_ID: u32 = 2004;
+const MOONRIVER_PARA_ID: u32 = 2023;
+// Junctions are encoded as the variant index of `Junctions`, which is the number of junctions,
+// followed by each junction.
+const MAX_JUNCTIONS: u8 = 8;
+
+// Moonbeam-based chains expose the assets received through XCM as ERC-20 contracts (XC-20s) whose
+// address is derived from the location of the asset as seen from them, so the same transfer can be
+// reconciled from the EVM side.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum MoonbeamChain {
+	Moonbeam,
+	Moonriver,
+}
+
+impl MoonbeamChain {
+	pub(crate) fn from_destination(destination_chain: &DestinationChain) -> Option<Self> {
+		match destination_chain {
+			DestinationChain::PolkadotParachain(MOONBEAM_PARA_ID) => Some(Self::Moonbeam),
+			DestinationChain::KusamaParachain(MOONRIVER_PARA_ID) => Some(Self::Moonriver),
+			_ => None,
+		}
+	}
+
+	pub(crate) fn from_origin(origin_chain: &OriginChain) -> Option<Self> {
+		match origin_chain {
+			OriginChain::PolkadotParachain(MOONBEAM_PARA_ID) => Some(Self::Moonbeam),
+			_ => None,
+		}
+	}
+
+	// Returns the XC-20 address of an asset given its location as seen from AssetHub: its parents
+	// and its SCALE encoded interior. Moonbeam computes the asset id as the first 16 bytes of the
+	// blake2_256 hash of the SCALE encoded V3 location (read as a little endian u128), and the
+	// address is that id prefixed by 0xFFFFFFFF.
+	pub(crate) fn xc20_address(&self, parents: u8, encoded_interior: &[u8]) -> Option<String> {
+		let (&junctions_count, junctions) = encoded_interior.split_first()?;
+		let relay_junction = Junction::GlobalConsensus(NetworkId::Polkadot).encode();
+		let asset_hub_junction = Junction::Parachain(crate::chain_spec::para_id()).encode();
+
+		// Reanchor the location to the Moonbeam-based chain
+		let (parents, prefix) = match (self, parents) {
+			(Self::Moonbeam, 0) => (1, vec![asset_hub_junction]),
+			(Self::Moonbeam, parents) => (parents, vec![]),
+			(Self::Moonriver, 0) => (2, vec![relay_junction, asset_hub_junction]),
+			(Self::Moonriver, 1) => (2, vec![relay_junction]),
+			// Assets from other consensus would need to be reanchored to Kusama
+			(Self::Moonriver, _) => return None,
+		};
+		let junctions_count = junctions_count + prefix.len() as u8;
+		if junctions_count > MAX_JUNCTIONS {
+			return None;
+		}
+
+		let mut encoded_location = vec![parents, junctions_count];
+		prefix.iter().for_each(|junction| encoded_location.extend(junction));
+		encoded_location.extend(junctions);
+
+		let hash = sp_core::hashing::blake2_256(&encoded_location);
+		let asset_id = u128::from_le_bytes(hash[..16].try_into().ok()?);
+		let mut address = [0xff; 20];
+		address[4..].copy_from_slice(&asset_id.to_be_bytes());
+		Some(crate::helpers::to_eip55_address(&address))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::asset_hub::runtime_types::staging_xcm::v4::{
+		junction::Junction as V4Junction, junctions::Junctions as V4Junctions,
+	};
+
+	#[test]
+	fn moonbeam_chain_from_counterparty() {
+		assert_eq!(
+			MoonbeamChain::from_destination(&DestinationChain::PolkadotParachain(2004)),
+			Some(MoonbeamChain::Moonbeam)
+		);
+		assert_eq!(
+			MoonbeamChain::from_destination(&DestinationChain::KusamaParachain(2023)),
+			Some(MoonbeamChain::Moonriver)
+		);
+		assert_eq!(
+			MoonbeamChain::from_destination(&DestinationChain::PolkadotParachain(2034)),
+			None
+		);
+		assert_eq!(
+			MoonbeamChain::from_origin(&OriginChain::PolkadotParachain(2004)),
+			Some(MoonbeamChain::Moonbeam)
+		);
+		assert_eq!(MoonbeamChain::from_origin(&OriginChain::Polkadot), None);
+	}
+
+	#[test]
+	fn xc20_address_for_dot() {
+		assert_eq!(
+			MoonbeamChain::Moonbeam.xc20_address(1, &V4Junctions::Here.encode()),
+			Some("0xFfFFfFff1FcaCBd218EDc0EbA20Fc2308C778080".to_owned())
+		);
+		assert_eq!(
+			MoonbeamChain::Moonriver.xc20_address(1, &V4Junctions::Here.encode()),
+			Some("0xfffFFfffb5267866B2A08047ad20Df82207D0F39".to_owned())
+		);
+	}
+
+	#[test]
+	fn xc20_address_for_asset_hub_asset() {
+		let interior = V4Junctions::X2([
+			V4Junction::PalletInstance(Chain::ASSETS_PALLET_INDEX),
+			V4Junction::GeneralIndex(1984),
+		]);
+		assert_eq!(
+			MoonbeamChain::Moonbeam.xc20_address(0, &interior.encode()),
+			Some("0xFFFFFFfFea09FB06d082fd1275CD48b191cbCD1d".to_owned())
+		);
+		assert_eq!(MoonbeamChain::Moonriver.xc20_address(2, &interior.encode()), None);
+	}
+}
@@ -0,0 +1,93 @@
+use crate::{
+	Error,
+	asset_hub::polkadot_xcm::events::{
+		NotifyTargetSendFail, SupportedVersionChanged, VersionChangeNotified,
+		VersionNotifyRequested, VersionNotifyStarted, VersionNotifyUnrequested,
+	},
+	outgoing_parser::DestinationChain,
+	types::BlockNumber,
+};
+use serde::Serialize;
+use subxt::{OnlineClient, PolkadotConfig, blocks::Block, events::EventDetails};
+
+// The XCM version negotiation with a counterparty reported by pallet_xcm. Counterparties migrating
+// to a new XCM version may send or expect messages the indexer fails to parse, so these events help
+// correlating parsing failures with the migrations.
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub(crate) struct XcmVersionEvent {
+	pub(crate) block_number: BlockNumber,
+	pub(crate) counterparty: DestinationChain,
+	pub(crate) event: XcmVersionChange,
+}
+
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub(crate) enum XcmVersionChange {
+	// The counterparty notified the version it supports
+	SupportedVersionChanged { version: u32 },
+	// AssetHub notified the counterparty about its version
+	VersionChangeNotified { version: u32 },
+	// AssetHub started notifying the counterparty about its version changes
+	VersionNotifyStarted,
+	// AssetHub requested the counterparty to notify its version changes, or stopped doing so
+	VersionNotifyRequested,
+	VersionNotifyUnrequested,
+	// AssetHub failed to notify the counterparty about its version
+	NotifyTargetSendFail { error: String },
+}
+
+pub(crate) async fn get_xcm_version_events_at_block(
+	block: &Block<PolkadotConfig, OnlineClient<PolkadotConfig>>,
+) -> Result<Vec<XcmVersionEvent>, Error> {
+	let events = block.events().await?;
+	Ok(events
+		.iter()
+		.flatten()
+		.filter_map(|event| decode_xcm_version_event(block.number(), &event))
+		.collect())
+}
+
+fn decode_xcm_version_event(
+	block_number: BlockNumber,
+	event: &EventDetails<PolkadotConfig>,
+) -> Option<XcmVersionEvent> {
+	if event.pallet_name() != "PolkadotXcm" {
+		return None;
+	}
+	let (counterparty, change) = match event.variant_name() {
+		"SupportedVersionChanged" => {
+			let event = event.as_event::<SupportedVersionChanged>().ok()??;
+			(
+				DestinationChain::from(&event.location),
+				XcmVersionChange::SupportedVersionChanged { version: event.version },
+			)
+		},
+		"VersionChangeNotified" => {
+			let event = event.as_event::<VersionChangeNotified>().ok()??;
+			(
+				DestinationChain::from(&event.destination),
+				XcmVersionChange::VersionChangeNotified { version: event.result },
+			)
+		},
+		"VersionNotifyStarted" => {
+			let event = event.as_event::<VersionNotifyStarted>().ok()??;
+			(DestinationChain::from(&event.destination), XcmVersionChange::VersionNotifyStarted)
+		},
+		"VersionNotifyRequested" => {
+			let event = event.as_event::<VersionNotifyRequested>().ok()??;
+			(DestinationChain::from(&event.destination), XcmVersionChange::VersionNotifyRequested)
+		},
+		"VersionNotifyUnrequested" => {
+			let event = event.as_event::<VersionNotifyUnrequested>().ok()??;
+			(DestinationChain::from(&event.destination), XcmVersionChange::VersionNotifyUnrequested)
+		},
+		"NotifyTargetSendFail" => {
+			let event = event.as_event::<NotifyTargetSendFail>().ok()??;
+			(
+				DestinationChain::from(&event.location),
+				XcmVersionChange::NotifyTargetSendFail { error: format!("{:?}", event.error) },
+			)
+		},
+		_ => return None,
+	};
+	Some(XcmVersionEvent { block_number, counterparty, event: change })
+}
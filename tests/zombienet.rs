@@ -0,0 +1,233 @@
+// End-to-end tests running the indexer against the local network described in
+// `zombienet/network.toml`: known transfers are submitted to the network, and the records output by
+// the CLI are asserted. They're ignored by default, as they need the network to be spawned first:
+//
+// zombienet spawn --provider native zombienet/network.toml
+// cargo test --test zombienet -- --ignored --test-threads 1
+//
+// The endpoints of the relay chain and AssetHub can be changed with `ZOMBIENET_RELAY_RPC_URL` and
+// `ZOMBIENET_ASSET_HUB_RPC_URL`.
+use std::process::{Output, Stdio};
+use subxt::{OnlineClient, PolkadotConfig, dynamic::Value, tx::DynamicPayload};
+use subxt_signer::sr25519::{Keypair, dev};
+use tokio::process::Command;
+use xcm_minimal_indexer::{DestinationChain, OriginChain, TransferType, XcmTransfer};
+
+const RELAY_RPC_URL: &str = "ws://127.0.0.1:9944";
+const ASSET_HUB_RPC_URL: &str = "ws://127.0.0.1:9946";
+const ASSET_HUB_PARA_ID: u32 = 1000;
+const PENPAL_PARA_ID: u32 = 2000;
+// The native token of Westend has 12 decimals
+const ONE_TOKEN: u128 = 1_000_000_000_000;
+
+fn relay_rpc_url() -> String {
+	std::env::var("ZOMBIENET_RELAY_RPC_URL").unwrap_or_else(|_| RELAY_RPC_URL.to_owned())
+}
+
+fn asset_hub_rpc_url() -> String {
+	std::env::var("ZOMBIENET_ASSET_HUB_RPC_URL").unwrap_or_else(|_| ASSET_HUB_RPC_URL.to_owned())
+}
+
+fn public_key(keypair: &Keypair) -> String {
+	format!("0x{}", hex::encode(keypair.public_key().0))
+}
+
+// Runs the CLI with the dev profile against the AssetHub of the network.
+fn indexer(args: &[&str]) -> Command {
+	let mut command = Command::new(env!("CARGO_BIN_EXE_xcm_minimal_indexer"));
+	command
+		.args(["--dev", "--rpc-url", &asset_hub_rpc_url()])
+		.args(args)
+		.stdout(Stdio::piped())
+		.stderr(Stdio::piped());
+	command
+}
+
+fn output_transfers(output: &Output) -> Vec<XcmTransfer> {
+	assert!(
+		output.status.success(),
+		"The indexer failed: {}",
+		String::from_utf8_lossy(&output.stderr)
+	);
+	serde_json::from_slice(&output.stdout).expect("The output should be a list of transfers")
+}
+
+// The locations, assets and weight limit of the `polkadot_xcm` calls, in XCM v4.
+fn location(parents: u8, junctions: Vec<Value>) -> Value {
+	let interior = match junctions.len() {
+		0 => Value::unnamed_variant("Here", []),
+		_ => Value::unnamed_variant("X1", [Value::unnamed_composite(junctions)]),
+	};
+	Value::named_composite([("parents", Value::u128(parents.into())), ("interior", interior)])
+}
+
+fn parachain(para_id: u32) -> Value {
+	Value::unnamed_variant("Parachain", [Value::u128(para_id.into())])
+}
+
+fn account(keypair: &Keypair) -> Value {
+	Value::named_variant(
+		"AccountId32",
+		[
+			("network", Value::unnamed_variant("None", [])),
+			("id", Value::from_bytes(keypair.public_key().0)),
+		],
+	)
+}
+
+fn versioned(value: Value) -> Value {
+	Value::unnamed_variant("V4", [value])
+}
+
+fn native_assets(parents: u8, amount: u128) -> Value {
+	let asset = Value::named_composite([
+		("id", Value::unnamed_composite([location(parents, vec![])])),
+		("fun", Value::unnamed_variant("Fungible", [Value::u128(amount)])),
+	]);
+	versioned(Value::unnamed_composite([Value::unnamed_composite([asset])]))
+}
+
+fn xcm_call(
+	pallet: &str,
+	call: &str,
+	dest: Value,
+	beneficiary: &Keypair,
+	assets: Value,
+) -> DynamicPayload {
+	subxt::dynamic::tx(
+		pallet,
+		call,
+		vec![
+			versioned(dest),
+			versioned(location(0, vec![account(beneficiary)])),
+			assets,
+			Value::u128(0),
+			Value::unnamed_variant("Unlimited", []),
+		],
+	)
+}
+
+// Submits a call, waiting for it to be finalized successfully. Returns the hash of its block.
+async fn submit(url: &str, call: DynamicPayload, signer: &Keypair) -> String {
+	let api = OnlineClient::<PolkadotConfig>::from_url(url)
+		.await
+		.expect("The network should be running");
+	let events = api
+		.tx()
+		.sign_and_submit_then_watch_default(&call, signer)
+		.await
+		.expect("The call should be submitted")
+		.wait_for_finalized_success()
+		.await
+		.expect("The call should succeed");
+	format!("{:?}", events.block_hash())
+}
+
+#[tokio::test]
+#[ignore = "requires the zombienet network to be running"]
+async fn teleport_from_the_relay_chain_is_indexed() {
+	let (alice, bob) = (dev::alice(), dev::bob());
+	// The indexer follows the finalized blocks from the moment it starts, so it's started first
+	let wait_for = indexer(&[
+		"wait-for",
+		"--account",
+		&public_key(&bob),
+		"--asset",
+		"WND",
+		"--amount",
+		"0.9",
+		"--timeout",
+		"180",
+	])
+	.spawn()
+	.expect("The indexer should start");
+
+	submit(
+		&relay_rpc_url(),
+		xcm_call(
+			"XcmPallet",
+			"limited_teleport_assets",
+			location(0, vec![parachain(ASSET_HUB_PARA_ID)]),
+			&bob,
+			native_assets(0, ONE_TOKEN),
+		),
+		&alice,
+	)
+	.await;
+
+	let output = wait_for.wait_with_output().await.expect("The indexer should finish");
+	let transfers = output_transfers(&output);
+	let [XcmTransfer::ReceivedTransfer(transfer)] = transfers.as_slice() else {
+		panic!("Expected a single received transfer, got {:?}", transfers);
+	};
+	assert_eq!(transfer.origin_chain, OriginChain::Polkadot);
+	assert_eq!(transfer.transfer_type, TransferType::Teleport);
+	assert_eq!(transfer.beneficiary_public_key, public_key(&bob));
+	// Part of the amount teleported pays the execution in AssetHub
+	assert!(transfer.amount > 0.9 && transfer.amount <= 1.0);
+}
+
+#[tokio::test]
+#[ignore = "requires the zombienet network to be running"]
+async fn teleport_to_the_relay_chain_is_indexed() {
+	let (alice, bob) = (dev::alice(), dev::bob());
+	let block_hash = submit(
+		&asset_hub_rpc_url(),
+		xcm_call(
+			"PolkadotXcm",
+			"limited_teleport_assets",
+			location(1, vec![]),
+			&bob,
+			native_assets(1, ONE_TOKEN),
+		),
+		&alice,
+	)
+	.await;
+
+	let output = indexer(&["get-transfers-at", "--block-hash", &block_hash])
+		.output()
+		.await
+		.expect("The indexer should run");
+	let transfers = output_transfers(&output);
+	let [XcmTransfer::SentTransfer(transfer)] = transfers.as_slice() else {
+		panic!("Expected a single sent transfer, got {:?}", transfers);
+	};
+	assert_eq!(transfer.destination_chain, DestinationChain::Polkadot);
+	assert_eq!(transfer.transfer_type, TransferType::Teleport);
+	assert_eq!(transfer.sender_public_key, Some(public_key(&alice)));
+	assert_eq!(transfer.beneficiary_public_key, public_key(&bob));
+	assert_eq!(transfer.asset, "WND");
+	assert_eq!(transfer.amount, 1.0);
+}
+
+#[tokio::test]
+#[ignore = "requires the zombienet network to be running"]
+async fn transfer_to_the_test_parachain_is_indexed() {
+	let (alice, bob) = (dev::alice(), dev::bob());
+	// `transfer_assets` lets the runtime pick how the native token reaches the sibling
+	let block_hash = submit(
+		&asset_hub_rpc_url(),
+		xcm_call(
+			"PolkadotXcm",
+			"transfer_assets",
+			location(1, vec![parachain(PENPAL_PARA_ID)]),
+			&bob,
+			native_assets(1, ONE_TOKEN),
+		),
+		&alice,
+	)
+	.await;
+
+	let output = indexer(&["get-transfers-at", "--block-hash", &block_hash])
+		.output()
+		.await
+		.expect("The indexer should run");
+	let transfers = output_transfers(&output);
+	let [XcmTransfer::SentTransfer(transfer)] = transfers.as_slice() else {
+		panic!("Expected a single sent transfer, got {:?}", transfers);
+	};
+	assert_eq!(transfer.destination_chain, DestinationChain::PolkadotParachain(PENPAL_PARA_ID));
+	assert_eq!(transfer.sender_public_key, Some(public_key(&alice)));
+	assert_eq!(transfer.asset, "WND");
+	assert_eq!(transfer.amount, 1.0);
+}